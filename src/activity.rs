@@ -0,0 +1,311 @@
+//! Editor activity tracking from WakaTime-compatible heartbeats.
+//!
+//! Editor plugins for WakaTime send a "heartbeat" whenever you type or
+//! switch files. Pointing them at the local endpoint (`api_url =
+//! http://localhost:7879/api/v1` in `~/.wakatime.cfg`) lets the `activity`
+//! command turn those heartbeats into log entries: consecutive heartbeats
+//! for the same project form one stretch of activity, and a stretch ends
+//! after a gap longer than the idle timeout.
+//!
+//! Time that is already being tracked by a running session is not logged a
+//! second time; it is reported instead, so the editor's view and manual
+//! tracking can be compared without double counting.
+//!
+//! A new listener started with `--take-over` takes the port and the open
+//! stretches over from a running one (`POST /handover`), so upgrading
+//! doesn't lose activity that hasn't been logged yet. Each listener writes a
+//! fresh secret to the data directory when it starts (see
+//! [`new_handover_secret`]) and only hands over to a request that carries it,
+//! so other programs and web pages can't take its stretches.
+
+use crate::config::{self, ActivityConfig};
+use crate::http::{Request, Response};
+use crate::log::{self, Entry};
+use crate::session;
+use crate::storage;
+use chrono::{DateTime, Local, Offset};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+/// The header a new listener sends the handover secret in.
+const HANDOVER_HEADER: &str = "X-Handover-Secret";
+
+/// Where the listener on `port` keeps the secret it hands over for.
+fn handover_path(port: u16) -> PathBuf {
+    config::data_dir().join(format!("activity-{}.handover", port))
+}
+
+/// Creates the secret the listener on `port` hands over for, replacing
+/// the one of an earlier listener.
+pub fn new_handover_secret(port: u16) -> io::Result<String> {
+    let secret = log::new_id();
+    let path = handover_path(port);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    log::replace_file(&path, secret.as_bytes())?;
+    Ok(secret)
+}
+
+/// The headers to ask the listener on `port` to hand over with.
+pub fn handover_headers(port: u16) -> io::Result<Vec<(&'static str, String)>> {
+    let secret = std::fs::read_to_string(handover_path(port))?;
+    Ok(vec![(HANDOVER_HEADER, secret.trim().to_string())])
+}
+
+/// The fields of a WakaTime heartbeat used here; the rest are ignored.
+#[derive(Debug, Deserialize)]
+pub struct Heartbeat {
+    /// Unix timestamp with fractional seconds.
+    pub time: f64,
+    pub project: Option<String>,
+    pub branch: Option<String>,
+}
+
+/// A stretch of uninterrupted activity on one project.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Stretch {
+    start: DateTime<Local>,
+    last: DateTime<Local>,
+    branch: Option<String>,
+}
+
+/// Collects heartbeats into stretches of activity per project.
+pub struct Tracker {
+    config: ActivityConfig,
+    open: HashMap<String, Stretch>,
+    started: DateTime<Local>,
+    /// When a stretch was last written to the log, for `/healthz`.
+    last_logged: Option<DateTime<Local>>,
+    /// Whether the open stretches were handed over to another listener.
+    handed_over: bool,
+}
+
+impl Tracker {
+    pub fn new(config: ActivityConfig) -> Self {
+        Tracker { config, open: HashMap::new(), started: Local::now(), last_logged: None, handed_over: false }
+    }
+
+    /// Replaces the settings, e.g. after the config file was edited. Open
+    /// stretches are kept and closed under the new settings.
+    pub fn set_config(&mut self, config: ActivityConfig) {
+        self.config = config;
+    }
+
+    /// Liveness report served at `/healthz`.
+    pub fn health(&self) -> serde_json::Value {
+        json!({
+            "status": "ok",
+            "started": self.started.to_rfc3339(),
+            "uptime_secs": (Local::now() - self.started).num_seconds(),
+            "open_stretches": self.open.len(),
+            "last_logged": self.last_logged.map(|t| t.to_rfc3339()),
+        })
+    }
+
+    /// Records a heartbeat, closing the project's previous stretch if the
+    /// gap since its last heartbeat exceeds the idle timeout.
+    pub fn heartbeat(&mut self, heartbeat: Heartbeat) {
+        let Some(time) = DateTime::from_timestamp(heartbeat.time as i64, 0).map(|t| t.with_timezone(&Local)) else {
+            return;
+        };
+        let project = heartbeat.project.filter(|p| !p.is_empty()).unwrap_or_else(|| "Unknown".to_string());
+        let timeout = chrono::TimeDelta::minutes(self.config.timeout_minutes as i64);
+
+        match self.open.get_mut(&project) {
+            Some(stretch) if time >= stretch.last && time - stretch.last <= timeout => {
+                stretch.last = time;
+                stretch.branch = heartbeat.branch.or(stretch.branch.take());
+            },
+            // Late heartbeats from a plugin's offline queue that fall inside the stretch
+            Some(stretch) if time >= stretch.start && time < stretch.last => {},
+            _ => {
+                if let Some(previous) = self.open.remove(&project) {
+                    self.close(&project, previous);
+                }
+                self.open.insert(project, Stretch { start: time, last: time, branch: heartbeat.branch });
+            },
+        }
+    }
+
+    /// Closes every stretch that has been idle for longer than the timeout.
+    pub fn close_idle(&mut self) {
+        let timeout = chrono::TimeDelta::minutes(self.config.timeout_minutes as i64);
+        let now = Local::now();
+        let idle: Vec<String> = self
+            .open
+            .iter()
+            .filter(|(_, stretch)| now - stretch.last > timeout)
+            .map(|(project, _)| project.clone())
+            .collect();
+        for project in idle {
+            if let Some(stretch) = self.open.remove(&project) {
+                self.close(&project, stretch);
+            }
+        }
+    }
+
+    /// Gives up the open stretches for another listener to [`adopt`], as
+    /// JSON. Heartbeats should no longer be taken after this.
+    ///
+    /// [`adopt`]: Tracker::adopt
+    pub fn hand_over(&mut self) -> serde_json::Value {
+        self.handed_over = true;
+        json!({ "open": std::mem::take(&mut self.open) })
+    }
+
+    /// Whether [`hand_over`](Tracker::hand_over) was called.
+    pub fn handed_over(&self) -> bool {
+        self.handed_over
+    }
+
+    /// Takes over the open stretches another listener handed over,
+    /// returning how many there were.
+    pub fn adopt(&mut self, state: &str) -> Result<usize, String> {
+        #[derive(Deserialize)]
+        struct State {
+            open: HashMap<String, Stretch>,
+        }
+        let state: State = serde_json::from_str(state).map_err(|e| format!("unexpected handover: {}", e))?;
+        let count = state.open.len();
+        self.open.extend(state.open);
+        Ok(count)
+    }
+
+    /// Closes all open stretches, e.g. on shutdown.
+    pub fn close_all(&mut self) {
+        for (project, stretch) in std::mem::take(&mut self.open) {
+            self.close(&project, stretch);
+        }
+    }
+
+    /// Logs a finished stretch unless it is too short or already tracked.
+    fn close(&mut self, project: &str, stretch: Stretch) {
+        let duration_secs = (stretch.last - stretch.start).num_seconds().max(0) as u64;
+        if duration_secs < self.config.min_minutes * 60 {
+            return;
+        }
+
+        let code = self.config.projects.get(project).cloned().unwrap_or_else(|| project.to_string());
+        let task = match &stretch.branch {
+            Some(branch) => format!("{}: {} ({})", self.config.task, project, branch),
+            None => format!("{}: {}", self.config.task, project),
+        };
+
+        if let Some(running) = session::all().unwrap_or_default().into_iter().find(|s| s.started <= stretch.start) {
+            println!(
+                "{} of activity on '{}' is already covered by the running session '{}'; not logged.",
+                crate::duration::format_hms(duration_secs, false),
+                project,
+                running.task
+            );
+            return;
+        }
+
+        let entry = Entry {
+            date: stretch.last.date_naive(),
+            time: stretch.last.time(),
+            offset: Some(stretch.last.offset().fix()),
+            code,
+            task,
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: log::new_id(),
+        };
+        match storage::open().and_then(|mut storage| storage.append(&entry)) {
+            Ok(()) => {
+                self.last_logged = Some(Local::now());
+                println!(
+                    "Logged {} on '{}' ({}).",
+                    crate::duration::format_hms(duration_secs, false),
+                    entry.task,
+                    entry.code
+                );
+            },
+            Err(e) => eprintln!("Failed to log activity on '{}': {}", project, e),
+        }
+    }
+}
+
+/// Handles one request to the heartbeat endpoint. `secret` is the one a
+/// handover has to carry (see [`new_handover_secret`]).
+pub fn handle(tracker: &mut Tracker, request: Request, secret: &str) -> Response {
+    if request.method == "GET" && request.path == "/healthz" {
+        return Response::json(200, &tracker.health());
+    }
+    if request.method == "POST" && request.path == "/handover" {
+        // Web pages send an Origin; a listener taking over doesn't
+        if request.header("Origin").is_some() {
+            return Response::text(403, "Handovers aren't accepted from web pages");
+        }
+        if request.header(HANDOVER_HEADER) != Some(secret) {
+            return Response::text(401, "Missing or wrong handover secret");
+        }
+        return Response::json(200, &tracker.hand_over());
+    }
+    let bulk = request.path.ends_with("/heartbeats.bulk");
+    if request.method != "POST" || !(bulk || request.path.ends_with("/heartbeats")) {
+        return Response::text(404, "Not found");
+    }
+
+    if bulk {
+        let Ok(heartbeats) = serde_json::from_slice::<Vec<Heartbeat>>(&request.body) else {
+            return Response::text(400, "Expected a JSON array of heartbeats");
+        };
+        let responses: Vec<_> = heartbeats.iter().map(|_| json!([{ "data": {} }, 201])).collect();
+        for heartbeat in heartbeats {
+            tracker.heartbeat(heartbeat);
+        }
+        Response::json(202, &json!({ "responses": responses }))
+    } else {
+        let Ok(heartbeat) = serde_json::from_slice::<Heartbeat>(&request.body) else {
+            return Response::text(400, "Expected a JSON heartbeat");
+        };
+        tracker.heartbeat(heartbeat);
+        Response::json(201, &json!({ "data": {} }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_open_stretches_over() {
+        let mut old = Tracker::new(ActivityConfig::default());
+        old.heartbeat(Heartbeat { time: 1_714_980_600.0, project: Some("timer".to_string()), branch: Some("main".to_string()) });
+        old.heartbeat(Heartbeat { time: 1_714_980_900.0, project: Some("timer".to_string()), branch: None });
+        let state = old.hand_over().to_string();
+        assert!(old.handed_over());
+        assert!(old.open.is_empty());
+
+        let mut new = Tracker::new(ActivityConfig::default());
+        assert_eq!(new.adopt(&state), Ok(1));
+        let stretch = &new.open["timer"];
+        assert_eq!((stretch.last - stretch.start).num_seconds(), 300);
+        assert_eq!(stretch.branch.as_deref(), Some("main"));
+        assert!(new.adopt("{}").is_err());
+    }
+
+    #[test]
+    fn hands_over_only_with_the_secret() {
+        let mut tracker = Tracker::new(ActivityConfig::default());
+        let handover = |headers: &[(&str, &str)]| Request {
+            method: "POST".to_string(),
+            path: "/handover".to_string(),
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            ..Default::default()
+        };
+        assert_eq!(handle(&mut tracker, handover(&[]), "s3cret").status, 401);
+        assert_eq!(handle(&mut tracker, handover(&[(HANDOVER_HEADER, "guess")]), "s3cret").status, 401);
+        let from_page = handover(&[(HANDOVER_HEADER, "s3cret"), ("Origin", "https://example.com")]);
+        assert_eq!(handle(&mut tracker, from_page, "s3cret").status, 403);
+        assert!(!tracker.handed_over());
+        assert_eq!(handle(&mut tracker, handover(&[(HANDOVER_HEADER, "s3cret")]), "s3cret").status, 200);
+        assert!(tracker.handed_over());
+    }
+}
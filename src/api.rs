@@ -0,0 +1,220 @@
+//! The local REST API of `serve`, for editors, launcher scripts (Raycast,
+//! Alfred) and browser extensions.
+//!
+//! - `GET /status`: the running sessions, as `status --format json`.
+//! - `POST /start`: starts a session from a JSON body with `task` and
+//!   optionally `code` and `session`, logging the running one first.
+//! - `POST /stop`: stops and logs the default session, or the one named by
+//!   `session` in an optional JSON body, and returns its entry.
+//! - `GET /entries?since=&until=`: logged entries, by day as for `diff`.
+//! - `POST /handover`: stops serving, for a new server started with
+//!   `serve --take-over` to take the port over.
+//!
+//! The server only listens on the loopback interface. Browsers send an
+//! `Origin` header with requests made by web pages, and those are refused
+//! unless their origin is in `[serve] origins`, so a page you visit can't
+//! start or stop the timer. With `[serve] token` set, every request needs
+//! `Authorization: Bearer <token>`.
+
+use crate::config::ServeConfig;
+use crate::dates;
+use crate::http::{Request, Response};
+use crate::log::{DateRange, Entry};
+use crate::session;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// What a request asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Status,
+    Start { task: String, code: String, session: Option<String> },
+    Stop { session: Option<String> },
+    Entries(DateRange),
+    HandOver,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StartBody {
+    task: String,
+    #[serde(default)]
+    code: String,
+    session: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StopBody {
+    session: Option<String>,
+}
+
+/// A JSON error response, `{"error": message}`.
+pub fn error(status: u16, message: impl Into<String>) -> Response {
+    Response::json(status, &json!({ "error": message.into() }))
+}
+
+/// Whether `host`, a `Host` header, names the loopback interface the
+/// server listens on.
+fn is_loopback(host: &str) -> bool {
+    let name = host.rsplit_once(':').filter(|(_, port)| port.bytes().all(|b| b.is_ascii_digit())).map_or(host, |(name, _)| name);
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1"
+}
+
+/// Checks the host, origin and token of `request` and works out what it
+/// asks for, or the response refusing it.
+pub fn route(request: &Request, config: &ServeConfig) -> Result<Call, Response> {
+    // A web page whose name was rebound to 127.0.0.1 still sends its own
+    // name as the host, and is refused here
+    if !request.header("Host").is_some_and(is_loopback) {
+        return Err(error(403, "Only requests to localhost or 127.0.0.1 are answered"));
+    }
+    if let Some(origin) = request.header("Origin")
+        && !config.origins.iter().any(|allowed| allowed == origin)
+    {
+        return Err(error(403, format!("Origin '{}' is not in [serve] origins", origin)));
+    }
+    if request.method == "OPTIONS" {
+        // A preflight from an allowed origin
+        return Err(Response::text(204, "")
+            .with_header("Access-Control-Allow-Methods", "GET, POST")
+            .with_header("Access-Control-Allow-Headers", "Authorization, Content-Type"));
+    }
+    if let Some(token) = &config.token
+        && request.header("Authorization") != Some(&format!("Bearer {}", token))
+    {
+        return Err(error(401, "Missing or wrong token"));
+    }
+
+    match (request.method.as_str(), request.path.trim_end_matches('/')) {
+        ("GET", "/status") => Ok(Call::Status),
+        ("POST", "/start") => {
+            let start: StartBody = serde_json::from_slice(&request.body)
+                .map_err(|e| error(400, format!("Expected a JSON object with task, code and session: {}", e)))?;
+            if start.task.trim().is_empty() {
+                return Err(error(400, "The task can't be empty"));
+            }
+            let session = start.session.as_deref().map(session::parse_name).transpose().map_err(|e| error(400, e))?;
+            Ok(Call::Start { task: start.task, code: start.code, session })
+        },
+        ("POST", "/stop") => {
+            let stop: StopBody = match request.body.iter().all(u8::is_ascii_whitespace) {
+                true => StopBody::default(),
+                false => serde_json::from_slice(&request.body)
+                    .map_err(|e| error(400, format!("Expected nothing or a JSON object with session: {}", e)))?,
+            };
+            let session = stop.session.as_deref().map(session::parse_name).transpose().map_err(|e| error(400, e))?;
+            Ok(Call::Stop { session })
+        },
+        ("GET", "/entries") => {
+            let day = |name, parse: fn(&str) -> Result<_, _>| request.param(name).map(|value| parse(&value)).transpose().map_err(|e| error(400, e));
+            Ok(Call::Entries(DateRange { since: day("since", dates::parse_since)?, until: day("until", dates::parse_until)? }))
+        },
+        ("POST", "/handover") => Ok(Call::HandOver),
+        (_, "/status" | "/start" | "/stop" | "/entries" | "/handover") => Err(error(405, "Method not allowed")),
+        _ => Err(error(404, "Not found")),
+    }
+}
+
+/// Lets the browser hand `response` to the page or extension at the
+/// request's origin, which [`route`] has already checked.
+pub fn allow_origin(response: Response, request: &Request) -> Response {
+    match request.header("Origin") {
+        Some(origin) if response.status != 403 => response.with_header("Access-Control-Allow-Origin", origin),
+        _ => response,
+    }
+}
+
+/// An entry as the API returns it.
+pub fn entry_json(entry: &Entry) -> Value {
+    json!({
+        "id": entry.id,
+        "task": entry.task,
+        "code": entry.code,
+        "start": entry.start().format("%Y-%m-%dT%H:%M:%S").to_string(),
+        "end": entry.end().format("%Y-%m-%dT%H:%M:%S").to_string(),
+        "duration_secs": entry.duration_secs,
+        "git": entry.git,
+        "invoice": entry.invoice,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, target: &str, body: &str) -> Request {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers: vec![("Host".to_string(), "127.0.0.1:7878".to_string())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn routes_requests() {
+        let config = ServeConfig::default();
+        let route = |request| route(&request, &config).map_err(|r| r.status);
+        assert_eq!(route(request("GET", "/status", "")), Ok(Call::Status));
+        assert_eq!(
+            route(request("POST", "/start", r#"{"task": "Review", "code": "ACME"}"#)),
+            Ok(Call::Start { task: "Review".to_string(), code: "ACME".to_string(), session: None })
+        );
+        assert_eq!(route(request("POST", "/stop", "")), Ok(Call::Stop { session: None }));
+        assert_eq!(
+            route(request("POST", "/stop/", r#"{"session": "builds"}"#)),
+            Ok(Call::Stop { session: Some("builds".to_string()) })
+        );
+        let Ok(Call::Entries(range)) = route(request("GET", "/entries?since=2024-05-06", "")) else { panic!() };
+        assert_eq!(range.since, Some("2024-05-06".parse().unwrap()));
+        assert_eq!(range.until, None);
+
+        assert_eq!(route(request("POST", "/start", r#"{"task": ""}"#)), Err(400));
+        assert_eq!(route(request("POST", "/start", "task=Review")), Err(400));
+        assert_eq!(route(request("POST", "/stop", r#"{"session": "../log"}"#)), Err(400));
+        assert_eq!(route(request("GET", "/entries?since=soon", "")), Err(400));
+        assert_eq!(route(request("POST", "/handover", "")), Ok(Call::HandOver));
+        assert_eq!(route(request("GET", "/stop", "")), Err(405));
+        assert_eq!(route(request("GET", "/", "")), Err(404));
+    }
+
+    #[test]
+    fn refuses_other_origins_and_wrong_tokens() {
+        let mut config = ServeConfig { origins: vec!["chrome-extension://abc".to_string()], ..Default::default() };
+        let from = |origin: &str| {
+            let mut request = request("POST", "/stop", "");
+            request.headers.push(("origin".to_string(), origin.to_string()));
+            request
+        };
+        assert_eq!(route(&from("https://example.com"), &config).map_err(|r| r.status), Err(403));
+        let allowed = from("chrome-extension://abc");
+        assert!(route(&allowed, &config).is_ok());
+        let response = allow_origin(Response::text(200, ""), &allowed);
+        assert_eq!(response.headers, vec![("Access-Control-Allow-Origin", "chrome-extension://abc".to_string())]);
+
+        config.token = Some("secret".to_string());
+        let mut request = request("GET", "/status", "");
+        assert_eq!(route(&request, &config).map_err(|r| r.status), Err(401));
+        request.headers.push(("Authorization".to_string(), "Bearer secret".to_string()));
+        assert_eq!(route(&request, &config).map_err(|r| r.status), Ok(Call::Status));
+    }
+
+    #[test]
+    fn refuses_other_hosts() {
+        let config = ServeConfig::default();
+        let to = |host: Option<&str>| {
+            let mut request = request("GET", "/status", "");
+            request.headers.retain(|(name, _)| name != "Host");
+            request.headers.extend(host.map(|host| ("host".to_string(), host.to_string())));
+            route(&request, &config).map_err(|r| r.status)
+        };
+        assert_eq!(to(Some("localhost:7878")), Ok(Call::Status));
+        assert_eq!(to(Some("127.0.0.1")), Ok(Call::Status));
+        assert_eq!(to(Some("attacker.example:7878")), Err(403));
+        assert_eq!(to(Some("localhost.attacker.example")), Err(403));
+        assert_eq!(to(None), Err(403));
+    }
+}
@@ -0,0 +1,152 @@
+//! Moving old entries out of the log into compressed yearly archives.
+//!
+//! Archived entries live next to the log in `archive/<log>-<year>.csv.zst`,
+//! zstd-compressed CSV in the same layout as the log. Most commands only
+//! read the (small) current log; reports, stats and exports read the
+//! archives too when given `--include-archived`.
+
+use crate::log::{self, Entry};
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Compression level for archives: slow to write, which happens rarely,
+/// but small and still fast to read.
+const LEVEL: i32 = 19;
+
+/// Directory holding the archives of the log at `log_path`.
+pub fn archive_dir(log_path: &Path) -> PathBuf {
+    log_path.with_file_name("archive")
+}
+
+/// The archive of one year, e.g. `archive/time_log-2023.csv.zst`.
+fn shard_path(log_path: &Path, year: i32) -> PathBuf {
+    let stem = log_path.file_stem().unwrap_or_default().to_string_lossy();
+    archive_dir(log_path).join(format!("{}-{}.csv.zst", stem, year))
+}
+
+/// Existing archives of the log, oldest first.
+pub fn shards(log_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let stem = log_path.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}-", stem);
+    let mut shards: Vec<PathBuf> = match std::fs::read_dir(archive_dir(log_path)) {
+        Ok(dir) => dir
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".csv.zst"))
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    shards.sort();
+    Ok(shards)
+}
+
+/// Reads the entries of one archive, or any other zstd-compressed log. A
+/// missing archive is empty.
+pub fn read_shard(path: &Path) -> io::Result<Vec<Entry>> {
+    let compressed = match std::fs::read(path) {
+        Ok(compressed) => compressed,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let content = zstd::decode_all(compressed.as_slice())?;
+    let content = String::from_utf8(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    log::parse_entries(&content, path)
+}
+
+/// Reads the archived entries followed by the ones in the log.
+pub fn read_all(log_path: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for shard in shards(log_path)? {
+        entries.extend(read_shard(&shard)?);
+    }
+    entries.extend(log::read_entries(log_path)?);
+    Ok(entries)
+}
+
+/// Moves every entry dated before `before` from the log into the archive of
+/// its year, adding to archives that already exist. Returns the number of
+/// entries moved and the archives written.
+///
+/// Archives are written before the log is rewritten, so an interruption can
+/// at worst leave entries in both places, never lose them.
+pub fn archive(log_path: &Path, before: NaiveDate) -> io::Result<(usize, Vec<PathBuf>)> {
+    let lock = log::lock(log_path)?;
+    let (old, kept): (Vec<Entry>, Vec<Entry>) =
+        log::read_entries(log_path)?.into_iter().partition(|e| e.date < before);
+    if old.is_empty() {
+        return Ok((0, Vec::new()));
+    }
+
+    let mut years: BTreeMap<i32, Vec<Entry>> = BTreeMap::new();
+    for entry in old.iter() {
+        years.entry(entry.date.year()).or_default().push(entry.clone());
+    }
+
+    std::fs::create_dir_all(archive_dir(log_path))?;
+    let mut written = Vec::new();
+    for (year, entries) in years {
+        let path = shard_path(log_path, year);
+        let mut archived = read_shard(&path)?;
+        archived.extend(entries);
+        archived.sort_by_key(|e| e.end());
+        let compressed = zstd::encode_all(log::to_csv(&archived).as_bytes(), LEVEL)?;
+        log::replace_file(&path, &compressed)?;
+        written.push(path);
+    }
+
+    lock.write_entries(&kept)?;
+    Ok((old.len(), written))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    fn entry(date: &str, task: &str) -> Entry {
+        Entry {
+            date: date.parse().unwrap(),
+            time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            offset: None,
+            code: "X".to_string(),
+            task: task.to_string(),
+            duration_secs: 600,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn moves_old_entries_into_yearly_archives() {
+        let dir = std::env::temp_dir().join(format!("simple_timer_archive_{}", std::process::id()));
+        let log_path = dir.join("time_log.csv");
+        std::fs::create_dir_all(&dir).unwrap();
+        let entries = vec![
+            entry("2022-06-01", "a"),
+            entry("2023-02-01", "b"),
+            entry("2023-12-31", "c"),
+            entry("2024-01-01", "d"),
+        ];
+        log::write_entries(&log_path, &entries).unwrap();
+
+        let (moved, written) = archive(&log_path, "2023-06-01".parse().unwrap()).unwrap();
+        assert_eq!(moved, 2);
+        assert_eq!(written, vec![shard_path(&log_path, 2022), shard_path(&log_path, 2023)]);
+        archive(&log_path, "2024-01-01".parse().unwrap()).unwrap();
+
+        let remaining = log::read_entries(&log_path).unwrap();
+        let archived_2023 = read_shard(&shard_path(&log_path, 2023)).unwrap();
+        let all = read_all(&log_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(remaining.iter().map(|e| e.task.as_str()).collect::<Vec<_>>(), vec!["d"]);
+        assert_eq!(archived_2023.iter().map(|e| e.task.as_str()).collect::<Vec<_>>(), vec!["b", "c"]);
+        assert_eq!(all.iter().map(|e| e.task.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c", "d"]);
+    }
+}
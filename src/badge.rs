@@ -0,0 +1,29 @@
+//! Badge readers (NFC, RFID, barcode) used as a punch clock.
+//!
+//! Most USB readers either show up as a serial device that prints one badge
+//! ID per line, or act as a keyboard that types the ID followed by Enter.
+//! Both are read line by line here: the serial device directly, the
+//! keyboard kind through stdin.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Calls `on_scan` with every badge ID read from `device` (`-` for stdin)
+/// until the device is closed.
+pub fn listen(device: &Path, mut on_scan: impl FnMut(&str)) -> io::Result<()> {
+    let reader: Box<dyn BufRead> = if device == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(device)?))
+    };
+
+    for line in reader.lines() {
+        let id = line?;
+        let id = id.trim();
+        if !id.is_empty() {
+            on_scan(id);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! The `bench` command: times the work every command does on the log, using
+//! the actual log, to show where time goes as it grows.
+
+use crate::billing;
+use crate::config;
+use crate::log::{self, DateRange};
+use crate::report::{self, ReportOptions};
+use simple_timer::error::{Context, Error};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Above this, reading the log noticeably delays every command.
+const SLOW: Duration = Duration::from_millis(250);
+
+/// Times each stage `runs` times and prints the median of each.
+pub fn run(runs: usize) -> Result<(), Error> {
+    let path = log::log_path().context("Failed to find the log")?;
+    let failed = format!("Failed to read '{}'", path.display());
+    let size = std::fs::metadata(&path).context(&failed)?.len();
+    let config = config::load();
+
+    let stages = ["read file", "parse CSV", "build entries", "report totals", "billing", "suggestions"];
+    let mut timings: Vec<Vec<Duration>> = vec![Vec::new(); stages.len()];
+    let mut count = 0;
+    for _ in 0..runs.max(1) {
+        let start = Instant::now();
+        let content = std::fs::read_to_string(&path).context(&failed)?;
+        timings[0].push(start.elapsed());
+
+        let start = Instant::now();
+        black_box(log::parse_csv(&content));
+        let csv = start.elapsed();
+        timings[1].push(csv);
+
+        // Building entries includes parsing the CSV again, so count only the rest
+        let start = Instant::now();
+        let entries = log::parse_entries(&content, &path).context(&failed)?;
+        timings[2].push(start.elapsed().saturating_sub(csv));
+        count = entries.len();
+
+        let start = Instant::now();
+        black_box(report::totals(&entries, &DateRange::default()));
+        timings[3].push(start.elapsed());
+
+        let start = Instant::now();
+        black_box(billing::compute(&entries, &ReportOptions::default(), &config));
+        timings[4].push(start.elapsed());
+
+        // Recent tasks, as offered by the prompt and shell completions
+        let start = Instant::now();
+        black_box(log::recent_values(&entries, |e| &e.task));
+        timings[5].push(start.elapsed());
+    }
+
+    let medians: Vec<Duration> = timings
+        .iter_mut()
+        .map(|times| {
+            times.sort();
+            times[times.len() / 2]
+        })
+        .collect();
+    let total: Duration = medians.iter().sum();
+
+    println!("{} ({} KiB, {} entries), median of {} run(s)", path.display(), size / 1024, count, runs.max(1));
+    for (stage, time) in stages.iter().zip(&medians) {
+        let share = if total.is_zero() { 0.0 } else { time.as_secs_f64() / total.as_secs_f64() * 100.0 };
+        println!("  {:<14} {:>9.2} ms {:>5.1}%", stage, time.as_secs_f64() * 1000.0, share);
+    }
+    println!("  {:<14} {:>9.2} ms", "total", total.as_secs_f64() * 1000.0);
+
+    let reading = medians[0] + medians[1] + medians[2];
+    if reading > SLOW {
+        println!(
+            "\nEvery command reads the whole log, which takes {:.0} ms. To speed things up, move older entries into compressed archives with `archive --before <DATE>`; reports still include them.",
+            reading.as_secs_f64() * 1000.0
+        );
+    }
+    Ok(())
+}
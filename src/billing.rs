@@ -0,0 +1,178 @@
+//! Billing reports: billable amounts per client with tax.
+
+use crate::config::Config;
+use crate::duration;
+use crate::log::Entry;
+use crate::report::ReportOptions;
+use std::collections::BTreeMap;
+
+/// Label used for codes that are not mapped to a client.
+pub const NO_CLIENT: &str = "(no client)";
+
+/// Billable time for one code.
+#[derive(Debug)]
+pub struct BillLine {
+    pub code: String,
+    /// Billed duration in seconds (after display rounding, if any).
+    pub secs: u64,
+    pub rate: f64,
+    pub net: f64,
+}
+
+/// Billable totals for one client.
+#[derive(Debug)]
+pub struct ClientBill {
+    pub client: String,
+    pub lines: Vec<BillLine>,
+    pub net: f64,
+    /// Tax rate in percent.
+    pub tax_rate: f64,
+    pub tax: f64,
+    pub gross: f64,
+}
+
+/// Rounds a monetary amount to cents.
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Groups the selected entries by client (see [`Config::client`]) and code
+/// and prices them.
+///
+/// Each code's time is summed before rounding, line amounts are rounded to
+/// cents and tax is computed on the client's net total, matching how an
+/// invoice for that client would be calculated.
+pub fn compute<'a>(entries: impl IntoIterator<Item = &'a Entry>, options: &ReportOptions, config: &'a Config) -> Vec<ClientBill> {
+    let billing = &config.billing;
+    // client -> code -> seconds
+    let mut totals: BTreeMap<&str, BTreeMap<&str, u64>> = BTreeMap::new();
+    for entry in entries.into_iter().filter(|e| options.range.includes(e)) {
+        let client = config.client(&entry.code).unwrap_or(NO_CLIENT);
+        *totals.entry(client).or_default().entry(entry.code.as_str()).or_default() += entry.duration_secs;
+    }
+
+    totals
+        .into_iter()
+        .map(|(client, codes)| {
+            let lines: Vec<BillLine> = codes
+                .into_iter()
+                .map(|(code, secs)| {
+                    let secs = options.rounded(secs);
+                    let rate = billing.rate(code);
+                    BillLine { code: code.to_string(), secs, rate, net: cents(secs as f64 / 3600.0 * rate) }
+                })
+                .collect();
+            let net = cents(lines.iter().map(|l| l.net).sum());
+            let tax_rate = billing.tax_rate(client);
+            let tax = cents(net * tax_rate / 100.0);
+            ClientBill { client: client.to_string(), lines, net, tax_rate, tax, gross: cents(net + tax) }
+        })
+        .collect()
+}
+
+/// Prints net, tax and gross amounts per client followed by overall totals.
+pub fn print_billing_report(entries: &[Entry], options: &ReportOptions, config: &Config) {
+    let bills = compute(entries, options, config);
+    if bills.is_empty() {
+        println!("No entries found.");
+        return;
+    }
+
+    let currency = &config.billing.currency;
+    for bill in &bills {
+        println!("{}", bill.client);
+        for line in &bill.lines {
+            println!(
+                "  {:<24} {:>7}h x {:>8.2} = {:>10.2} {}",
+                line.code,
+                duration::format_decimal_hours(line.secs),
+                line.rate,
+                line.net,
+                currency
+            );
+        }
+        println!("  {:<46} {:>10.2} {}", "Net", bill.net, currency);
+        println!("  {:<46} {:>10.2} {}", format!("Tax ({}%)", bill.tax_rate), bill.tax, currency);
+        println!("  {:<46} {:>10.2} {}", "Gross", bill.gross, currency);
+        println!();
+    }
+
+    let net: f64 = bills.iter().map(|b| b.net).sum();
+    let tax: f64 = bills.iter().map(|b| b.tax).sum();
+    println!("{:<48} {:>10.2} {}", "Total net", net, currency);
+    println!("{:<48} {:>10.2} {}", "Total tax", tax, currency);
+    println!("{:<48} {:>10.2} {}", "Total gross", cents(net + tax), currency);
+
+    if let Some(increment) = options.round_display {
+        println!("\nBilled durations rounded to {} minute(s).", increment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BillingConfig;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn entry(code: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            time: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: "Work".to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn prices_codes_per_client_with_tax() {
+        let mut config = Config { billing: BillingConfig { default_rate: 50.0, default_tax: 20.0, ..Default::default() }, ..Default::default() };
+        config.billing.rates.insert("ACME-DEV".to_string(), 100.0);
+        config.billing.clients.insert("ACME-DEV".to_string(), "Acme".to_string());
+        config.billing.clients.insert("ACME-PM".to_string(), "Acme".to_string());
+        config.billing.tax.insert("Acme".to_string(), 10.0);
+        let entries = vec![entry("ACME-DEV", 5400), entry("ACME-PM", 1800), entry("ACME-DEV", 1800), entry("Misc", 3600)];
+
+        let bills = compute(&entries, &ReportOptions::default(), &config);
+
+        assert_eq!(bills.len(), 2);
+        // "(no client)" sorts first
+        assert_eq!(bills[0].client, NO_CLIENT);
+        assert_eq!(bills[0].gross, 60.0);
+        let acme = &bills[1];
+        assert_eq!(acme.client, "Acme");
+        assert_eq!(acme.lines.len(), 2);
+        assert_eq!(acme.lines[0].secs, 7200);
+        assert_eq!(acme.net, 225.0);
+        assert_eq!(acme.tax, 22.5);
+        assert_eq!(acme.gross, 247.5);
+    }
+
+    #[test]
+    fn bills_rounded_durations() {
+        let config = Config { billing: BillingConfig { default_rate: 60.0, ..Default::default() }, ..Default::default() };
+        let options = ReportOptions { round_display: Some(15), round_mode: crate::duration::RoundMode::Up, ..Default::default() };
+        let bills = compute(&[entry("X", 61)], &options, &config);
+        assert_eq!(bills[0].lines[0].secs, 900);
+        assert_eq!(bills[0].net, 15.0);
+    }
+
+    #[test]
+    fn hierarchical_codes_bill_to_their_client() {
+        let mut config = Config { billing: BillingConfig { default_rate: 50.0, ..Default::default() }, ..Default::default() };
+        config.billing.rates.insert("ACME".to_string(), 120.0);
+        config.billing.rates.insert("ACME/SUPPORT".to_string(), 80.0);
+        let entries = vec![entry("ACME/ONBOARDING", 3600), entry("ACME/SUPPORT", 3600), entry("Misc", 3600)];
+
+        let bills = compute(&entries, &ReportOptions::default(), &config);
+
+        assert_eq!(bills.len(), 2);
+        assert_eq!(bills[1].client, "ACME");
+        assert_eq!(bills[1].net, 200.0);
+        assert_eq!(bills[0].client, NO_CLIENT);
+    }
+}
@@ -0,0 +1,297 @@
+//! Weekly or monthly time budgets per code.
+//!
+//! Budgets are set in `[budgets]`, e.g. `ACME = "40h/week"`. A budget for a
+//! client part also covers its engagements, so `ACME` counts time on
+//! `ACME/ONBOARDING` too. Weeks start on Monday.
+//!
+//! Reaching a share of a budget, 80% and 100% by default, sends an alert
+//! once per period through the configured channels (see [`crate::notify`]).
+//! The thresholds already alerted are kept in `budget_alerts.toml` in the
+//! data directory.
+
+use crate::config;
+use crate::duration;
+use crate::log::{self, DateRange, Entry};
+use crate::recover;
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The period a budget is renewed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The days of the period that includes `today`.
+    pub fn range(self, today: NaiveDate) -> DateRange {
+        let (first, last) = match self {
+            Period::Week => {
+                let monday = today.week(Weekday::Mon).first_day();
+                (monday, monday + Days::new(6))
+            },
+            Period::Month => {
+                let first = today.with_day(1).expect("Every month has a first day");
+                (first, first + Months::new(1) - Days::new(1))
+            },
+        };
+        DateRange { since: Some(first), until: Some(last) }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+}
+
+/// A budget of time per period, written as e.g. `40h/week` or `120h/month`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Budget {
+    pub secs: u64,
+    pub period: Period,
+}
+
+impl FromStr for Budget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let invalid = || format!("expected a budget such as 40h/week or 120h/month, got '{}'", value);
+        let (amount, period) = value.split_once('/').ok_or_else(invalid)?;
+        let period = match period.trim() {
+            "week" => Period::Week,
+            "month" => Period::Month,
+            _ => return Err(invalid()),
+        };
+        let secs = duration::parse_span(amount.trim()).map_err(|_| invalid())?;
+        Ok(Budget { secs, period })
+    }
+}
+
+impl TryFrom<String> for Budget {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        value.parse()
+    }
+}
+
+impl From<Budget> for String {
+    fn from(budget: Budget) -> String {
+        format!("{}/{}", duration::format_span(budget.secs), budget.period.name())
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", duration::format_hms(self.secs, false), self.period.name())
+    }
+}
+
+/// Whether time on `code` counts towards the budget of `key`: the code
+/// itself or, for a hierarchical code, its client part.
+pub fn covers(key: &str, code: &str) -> bool {
+    code == key || log::code_parts(code).0 == key
+}
+
+/// How much of a budget has been used in its current period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    /// The code (or client part) the budget is set for.
+    pub key: String,
+    pub budget: Budget,
+    pub used_secs: u64,
+}
+
+impl Usage {
+    pub fn is_over(&self) -> bool {
+        self.used_secs > self.budget.secs
+    }
+
+    /// The share of the budget used, in percent.
+    pub fn percent(&self) -> u64 {
+        (self.used_secs * 100).checked_div(self.budget.secs).unwrap_or(100)
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (left, word) = if self.is_over() {
+            (self.used_secs - self.budget.secs, "over")
+        } else {
+            (self.budget.secs - self.used_secs, "left")
+        };
+        write!(
+            f,
+            "{}: {} of {} this {}, {} {}",
+            self.key,
+            duration::format_hms(self.used_secs, false),
+            duration::format_hms(self.budget.secs, false),
+            self.budget.period.name(),
+            duration::format_hms(left, false),
+            word
+        )
+    }
+}
+
+/// The usage of every budget in `budgets` as of `today`, from `entries`.
+/// `extra` adds time not logged yet, such as a running session's.
+pub fn usage(budgets: &BTreeMap<String, Budget>, entries: &[Entry], today: NaiveDate, extra: &[(&str, u64)]) -> Vec<Usage> {
+    budgets
+        .iter()
+        .map(|(key, budget)| {
+            let range = budget.period.range(today);
+            let logged: u64 = entries
+                .iter()
+                .filter(|e| range.includes(e) && covers(key, &e.code))
+                .map(|e| e.duration_secs)
+                .sum();
+            let running: u64 = extra.iter().filter(|(code, _)| covers(key, code)).map(|(_, secs)| secs).sum();
+            Usage { key: key.clone(), budget: *budget, used_secs: logged + running }
+        })
+        .collect()
+}
+
+/// The highest threshold alerted for a budget in its current period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alerted {
+    /// First day of the period.
+    pub since: NaiveDate,
+    pub percent: u64,
+}
+
+/// Thresholds alerted per budget key.
+pub type AlertState = BTreeMap<String, Alerted>;
+
+/// The highest of `thresholds` (percentages) that `usage` has reached
+/// without an alert yet in the period that includes `today`, if any.
+/// `alerted` is the last alert for the budget, which may be for an earlier
+/// period.
+pub fn threshold_reached(usage: &Usage, thresholds: &[u64], today: NaiveDate, alerted: Option<&Alerted>) -> Option<u64> {
+    let since = usage.budget.period.range(today).since?;
+    let already = alerted.filter(|a| a.since == since).map_or(0, |a| a.percent);
+    let percent = usage.percent();
+    thresholds.iter().copied().filter(|t| *t > already && *t <= percent).max()
+}
+
+/// Location of the alert state file.
+pub fn alerts_path() -> PathBuf {
+    config::data_dir().join("budget_alerts.toml")
+}
+
+/// Reads the alert state from `path`; a missing file has no alerts, and a
+/// corrupt one is quarantined and has none either.
+pub fn read_alerts(path: &Path) -> io::Result<AlertState> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(AlertState::new()),
+        Err(e) => return Err(e),
+    };
+    if let Some(state) = std::str::from_utf8(&content).ok().and_then(|c| toml::from_str(c).ok()) {
+        return Ok(state);
+    }
+    // Losing it at worst repeats an alert
+    let moved = recover::quarantine(path, false)?;
+    eprintln!("Warning: the budget alert state was corrupt and has been moved to '{}'.", moved.display());
+    Ok(AlertState::new())
+}
+
+/// Writes the alert state to `path`.
+pub fn write_alerts(path: &Path, state: &AlertState) -> io::Result<()> {
+    let content = toml::to_string(state).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    fn entry(date: &str, code: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: date.parse().unwrap(),
+            time: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: "Work".to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn parses_budgets() {
+        assert_eq!("40h/week".parse(), Ok(Budget { secs: 40 * 3600, period: Period::Week }));
+        assert_eq!("90m / month".parse(), Ok(Budget { secs: 90 * 60, period: Period::Month }));
+        for bad in ["40h", "40h/year", "lots/week"] {
+            assert!(bad.parse::<Budget>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn counts_this_period_and_engagements() {
+        let budgets = BTreeMap::from([
+            ("ACME".to_string(), "2h/week".parse().unwrap()),
+            ("OPS".to_string(), "10h/month".parse().unwrap()),
+        ]);
+        let entries = vec![
+            entry("2024-05-06", "ACME", 3600),
+            entry("2024-05-07", "ACME/ONBOARDING", 3600),
+            // The week before
+            entry("2024-05-05", "ACME", 3600),
+            entry("2024-05-01", "OPS", 3600),
+            entry("2024-04-30", "OPS", 3600),
+        ];
+        let today = "2024-05-08".parse().unwrap();
+
+        let usage = usage(&budgets, &entries, today, &[("ACME/SUPPORT", 60)]);
+        assert_eq!(usage[0].used_secs, 2 * 3600 + 60);
+        assert!(usage[0].is_over());
+        assert_eq!(usage[1].used_secs, 3600);
+        assert_eq!(usage[1].to_string(), "OPS: 1h 00m of 10h 00m this month, 9h 00m left");
+        assert!(!covers("ACME", "ACMEX"));
+    }
+
+    #[test]
+    fn alerts_each_threshold_once_per_period() {
+        let usage = Usage { key: "ACME".to_string(), budget: "10h/week".parse().unwrap(), used_secs: 9 * 3600 };
+        let today: NaiveDate = "2024-05-08".parse().unwrap();
+        let thresholds = [80, 100];
+        assert_eq!(usage.percent(), 90);
+        assert_eq!(threshold_reached(&usage, &thresholds, today, None), Some(80));
+
+        let alerted = Alerted { since: "2024-05-06".parse().unwrap(), percent: 80 };
+        assert_eq!(threshold_reached(&usage, &thresholds, today, Some(&alerted)), None);
+        let over = Usage { used_secs: 12 * 3600, ..usage.clone() };
+        assert_eq!(threshold_reached(&over, &thresholds, today, Some(&alerted)), Some(100));
+        // An alert from last week doesn't count
+        let last_week = Alerted { since: "2024-04-29".parse().unwrap(), percent: 100 };
+        assert_eq!(threshold_reached(&usage, &thresholds, today, Some(&last_week)), Some(80));
+    }
+
+    #[test]
+    fn keeps_alert_state() {
+        let path = std::env::temp_dir().join(format!("simple_timer_budget_alerts_{}.toml", std::process::id()));
+        assert_eq!(read_alerts(&path).unwrap(), AlertState::new());
+        let state = AlertState::from([(
+            "ACME/ONBOARDING".to_string(),
+            Alerted { since: "2024-05-06".parse().unwrap(), percent: 80 },
+        )]);
+        write_alerts(&path, &state).unwrap();
+        assert_eq!(read_alerts(&path).unwrap(), state);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+//! Shell completions, including task names and codes from the log.
+//!
+//! Completions are generated on the fly: the registration script printed by
+//! the `completions` command calls back into the binary (with `COMPLETE`
+//! set) whenever the shell needs candidates, so newly logged tasks and codes
+//! are offered straight away.
+
+use crate::config;
+use crate::log;
+use crate::session;
+use crate::storage;
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, EnvCompleter, Fish, Powershell, Zsh};
+use std::io::{self, Write};
+
+/// Environment variable that switches the binary into completion mode.
+pub const VAR: &str = "COMPLETE";
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Writes the script that registers completions with `shell`.
+pub fn write_registration(shell: Shell, out: &mut dyn Write) -> io::Result<()> {
+    let completer: &dyn EnvCompleter = match shell {
+        Shell::Bash => &Bash,
+        Shell::Zsh => &Zsh,
+        Shell::Fish => &Fish,
+        Shell::Powershell => &Powershell,
+    };
+    let exe = std::env::current_exe()?;
+    // Complete the name the binary was invoked as, e.g. when installed as `timer`
+    let bin = std::env::args_os()
+        .next()
+        .and_then(|arg| std::path::Path::new(&arg).file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string());
+    completer.write_registration(VAR, env!("CARGO_PKG_NAME"), &bin, &exe.to_string_lossy(), out)
+}
+
+/// Distinct values of a log column, most recently used first.
+fn recent(value: impl Fn(&log::Entry) -> &str) -> Vec<CompletionCandidate> {
+    let entries = storage::open().and_then(|storage| storage.query(&log::DateRange::default())).unwrap_or_default();
+    log::recent_values(&entries, value)
+        .into_iter()
+        .enumerate()
+        // Keep the shell from re-sorting alphabetically where it supports that
+        .map(|(i, v)| CompletionCandidate::new(v).display_order(Some(i)))
+        .collect()
+}
+
+/// Task names used before.
+pub fn tasks() -> Vec<CompletionCandidate> {
+    recent(|e| &e.task)
+}
+
+/// Codes used before, followed by the code aliases from the config file.
+pub fn codes() -> Vec<CompletionCandidate> {
+    let mut candidates = recent(|e| &e.code);
+    let offset = candidates.len();
+    candidates.extend(config::load().aliases.into_iter().enumerate().map(|(i, (alias, code))| {
+        CompletionCandidate::new(alias).help(Some(code.into())).display_order(Some(offset + i))
+    }));
+    candidates
+}
+
+/// Presets defined in the config file.
+pub fn presets() -> Vec<CompletionCandidate> {
+    config::load().presets.into_keys().map(CompletionCandidate::new).collect()
+}
+
+/// Profiles defined in the config file.
+pub fn profiles() -> Vec<CompletionCandidate> {
+    config::load().profiles.into_keys().map(CompletionCandidate::new).collect()
+}
+
+/// Named sessions that are running, with their tasks.
+pub fn sessions() -> Vec<CompletionCandidate> {
+    session::all()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|s| Some(CompletionCandidate::new(s.name?).help(Some(s.task.into()))))
+        .collect()
+}
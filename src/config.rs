@@ -0,0 +1,1263 @@
+//! Optional TOML configuration file.
+//!
+//! The file lives at `<config dir>/simpletimer/config.toml` (for example
+//! `~/.config/simpletimer/config.toml` on Linux). Every section is optional;
+//! a missing file behaves like an empty one.
+//!
+//! Profiles (`[profiles.<name>]`) keep separate logs, e.g. for work and
+//! personal projects. A profile's table can contain any of the settings
+//! below and overrides them while the profile is active.
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Profile chosen on the command line, set once at startup.
+static SELECTED_PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Whether `--portable` was given, set once at startup.
+static PORTABLE: OnceLock<()> = OnceLock::new();
+
+/// Name of the file next to the executable that turns on portable mode.
+pub const PORTABLE_FLAG: &str = "portable.flag";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub activity: ActivityConfig,
+    pub alerts: AlertsConfig,
+    /// Short names for long codes, e.g. `acme = "ACME-INTERNAL-0042"`.
+    pub aliases: BTreeMap<String, String>,
+    pub asana: AsanaConfig,
+    /// Maps a badge ID read by the `badges` command to a preset.
+    pub badges: BTreeMap<String, BadgeConfig>,
+    pub billing: BillingConfig,
+    pub breaks: BreaksConfig,
+    /// Time budget per code, e.g. `ACME = "40h/week"`.
+    pub budgets: BTreeMap<String, crate::budget::Budget>,
+    pub channels: ChannelsConfig,
+    pub clockify: ClockifyConfig,
+    /// Profile used when none is selected with `--profile` or `SIMPLETIMER_PROFILE`.
+    pub default_profile: Option<String>,
+    pub display: DisplayConfig,
+    pub git: GitConfig,
+    pub gsheets: GoogleSheetsConfig,
+    pub hardware: HardwareConfig,
+    pub harvest: HarvestConfig,
+    pub hooks: HooksConfig,
+    /// Maps a hotkey read by the `hotkeys` command to its action, e.g.
+    /// `"ctrl+alt+p" = "pause"`.
+    pub hotkeys: BTreeMap<String, crate::hotkey::Action>,
+    pub infer: InferConfig,
+    pub invoice: InvoiceConfig,
+    pub jira: JiraConfig,
+    pub labels: LabelsConfig,
+    pub linear: LinearConfig,
+    /// Language of durations and days written out on the command line, e.g.
+    /// `de` for `--ago "1 Std 30 Min"`. Defaults to the one of `LANG`.
+    pub locale: Option<crate::locale::Language>,
+    /// Location of the log file; `~/` is expanded to the home directory.
+    pub log: Option<PathBuf>,
+    /// Sessions are stopped and logged once they have run this long, e.g.
+    /// `max = "8h"`, unless started with `--max`.
+    #[serde(deserialize_with = "crate::duration::deserialize_span", serialize_with = "crate::duration::serialize_span")]
+    pub max: Option<u64>,
+    /// Texts shown when starting and stopping, by ID, replacing the built-in
+    /// ones in every language; see [`crate::messages`].
+    pub messages: BTreeMap<String, String>,
+    pub notion: NotionConfig,
+    /// How precisely sessions are logged, e.g. `precision = "15m"`; see
+    /// [`crate::duration::Precision`].
+    pub precision: crate::duration::Precision,
+    /// Named task/code combinations, e.g. `[presets.standup]`.
+    pub presets: BTreeMap<String, Preset>,
+    /// Settings that override the ones above while a profile is active.
+    pub profiles: BTreeMap<String, toml::Table>,
+    /// Projects grouping codes under clients, e.g. `[projects.Website]`.
+    pub projects: BTreeMap<String, ProjectConfig>,
+    pub redmine: RedmineConfig,
+    pub report: ReportConfig,
+    pub serve: ServeConfig,
+    pub slack: SlackConfig,
+    /// Where entries are kept: `csv` (the log file) or `sqlite`.
+    pub storage: StorageBackend,
+    /// Time zone sessions are logged and reported in, e.g. `UTC`, `+05:30`
+    /// or `Europe/Berlin`; see [`crate::timezone`]. Defaults to the system's.
+    pub timezone: Option<String>,
+    pub wakatime: WakaTimeConfig,
+    pub youtrack: YouTrackConfig,
+}
+
+impl Config {
+    /// Expands a code alias to the full code; other codes are returned unchanged.
+    pub fn expand_code(&self, code: &str) -> String {
+        self.aliases.get(code).cloned().unwrap_or_else(|| code.to_string())
+    }
+
+    /// The project a code belongs to: the one listing the code, or else its
+    /// client part (see [`log::code_parts`](crate::log::code_parts)).
+    pub fn project(&self, code: &str) -> Option<&str> {
+        let (client, _) = crate::log::code_parts(code);
+        let listing = |code: &str| self.projects.iter().find(|(_, project)| project.codes.iter().any(|c| c == code));
+        listing(code).or_else(|| listing(client)).map(|(name, _)| name.as_str())
+    }
+
+    /// The client a code belongs to: its project's client, or else the one
+    /// it is billed to (see [`BillingConfig::client`]).
+    pub fn client<'a>(&'a self, code: &'a str) -> Option<&'a str> {
+        let project = self.project(code).and_then(|name| self.projects[name].client.as_deref());
+        project.or_else(|| self.billing.client(code))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Csv,
+    /// An SQLite database next to where the log would be, with the
+    /// extension `.sqlite`. Needs a build with the `sqlite` feature.
+    Sqlite,
+}
+
+/// A named task and code that can be started with `--preset`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    pub task: Option<String>,
+    pub code: Option<String>,
+}
+
+/// A project: codes belonging together, for a client (`[projects.<name>]`).
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub client: Option<String>,
+    pub codes: Vec<String>,
+}
+
+/// Turning editor heartbeats into entries with the `activity` command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActivityConfig {
+    /// Local port the heartbeat endpoint listens on.
+    pub port: u16,
+    /// A gap longer than this between heartbeats ends a stretch of activity.
+    pub timeout_minutes: u64,
+    /// Stretches shorter than this are not logged.
+    pub min_minutes: u64,
+    /// Task name prefix for logged activity, followed by the project.
+    pub task: String,
+    /// Maps an editor project name to the code it is logged under.
+    /// Unmapped projects are logged with the project name as the code.
+    pub projects: BTreeMap<String, String>,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        ActivityConfig {
+            port: 7879,
+            timeout_minutes: 15,
+            min_minutes: 1,
+            task: "Coding".to_string(),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+/// Reminders of how long a session has been running, with today's total
+/// and the budget status, and alerts when budgets fill up. A timer running
+/// in a terminal also rings the terminal bell.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Alert each time the session has run this much longer, e.g. `"60m"`.
+    #[serde(deserialize_with = "crate::duration::deserialize_span", serialize_with = "crate::duration::serialize_span")]
+    pub every: Option<u64>,
+    /// Alert once the session has run this long, e.g. `["1h", "2h", "4h"]`.
+    #[serde(deserialize_with = "crate::duration::deserialize_spans", serialize_with = "crate::duration::serialize_spans")]
+    pub at: Vec<u64>,
+    /// Alert through `[channels]` once a budget is this full, in percent.
+    pub budgets: Vec<u64>,
+    /// `every` and `at` for sessions on a code or, for a hierarchical code,
+    /// its client part, instead of the ones above, e.g. `[alerts.codes.ACME]`.
+    pub codes: BTreeMap<String, MilestonesConfig>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        AlertsConfig { every: None, at: Vec::new(), budgets: vec![80, 100], codes: BTreeMap::new() }
+    }
+}
+
+/// When to alert for sessions on one code.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MilestonesConfig {
+    #[serde(deserialize_with = "crate::duration::deserialize_span", serialize_with = "crate::duration::serialize_span")]
+    pub every: Option<u64>,
+    #[serde(deserialize_with = "crate::duration::deserialize_spans", serialize_with = "crate::duration::serialize_spans")]
+    pub at: Vec<u64>,
+}
+
+impl AlertsConfig {
+    /// The longest elapsed time an alert is due at for a session on `code`
+    /// after `before` seconds, up to and including `now` seconds, if any.
+    pub fn crossed(&self, code: &str, before: u64, now: u64) -> Option<u64> {
+        let (every, at) = match self.codes.get(code).or_else(|| self.codes.get(crate::log::code_parts(code).0)) {
+            Some(milestones) => (milestones.every, &milestones.at),
+            None => (self.every, &self.at),
+        };
+        let every = every.and_then(|every| crossed_every(every, before, now));
+        let at = at.iter().copied().filter(|at| before < *at && *at <= now).max();
+        every.max(at)
+    }
+
+    /// Whether any session can have milestones.
+    pub fn has_milestones(&self) -> bool {
+        self.every.is_some() || !self.at.is_empty() || self.codes.values().any(|m| m.every.is_some() || !m.at.is_empty())
+    }
+}
+
+/// The last multiple of `every` after `before` and up to `now`, if any.
+fn crossed_every(every: u64, before: u64, now: u64) -> Option<u64> {
+    (every > 0 && now / every > before / every).then(|| now / every * every)
+}
+
+/// Where alerts that may come while no terminal is watched, such as budget
+/// alerts, are sent.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelsConfig {
+    /// Show a desktop notification.
+    pub desktop: bool,
+    /// Slack incoming webhook URL; `SLACK_WEBHOOK_URL` overrides it.
+    pub slack: Option<String>,
+    pub telegram: TelegramConfig,
+    /// URL that receives each alert as JSON in a POST request.
+    pub webhook: Option<String>,
+}
+
+impl Default for ChannelsConfig {
+    fn default() -> Self {
+        ChannelsConfig { desktop: true, slack: None, telegram: TelegramConfig::default(), webhook: None }
+    }
+}
+
+/// A Telegram bot that messages a chat.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// Bot token from @BotFather; `TELEGRAM_BOT_TOKEN` overrides it.
+    pub token: Option<String>,
+    /// Chat the bot writes to, e.g. your user ID.
+    pub chat_id: Option<String>,
+}
+
+/// Your Slack status while a session runs.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlackConfig {
+    /// User token (`xoxp-...`) with the `users.profile:write` scope; turns
+    /// the status on. `SLACK_TOKEN` overrides it.
+    pub token: Option<String>,
+    /// Status emoji, e.g. `:dart:`.
+    pub emoji: String,
+    /// Status text; `{task}` and `{code}` are replaced with the session's.
+    pub text: String,
+}
+
+impl Default for SlackConfig {
+    fn default() -> Self {
+        SlackConfig { token: None, emoji: ":dart:".to_string(), text: "Focusing: {task}".to_string() }
+    }
+}
+
+/// What is run or told when a session starts or stops.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Shell command run when a session starts, with the session in `ST_*`
+    /// environment variables.
+    pub on_start: Option<String>,
+    /// Shell command run when a session stops, with the session and its
+    /// entry in `ST_*` environment variables.
+    pub on_stop: Option<String>,
+    /// URLs that receive each start and stop as JSON in a POST request, e.g.
+    /// a Home Assistant webhook; `keyring:` references are allowed.
+    pub webhooks: Vec<String>,
+}
+
+/// Reminders to take a break, based on the time tracked today.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreaksConfig {
+    /// Remind after each stretch of this much tracked time today, counting
+    /// earlier sessions, e.g. `"50m"`. No reminders if unset.
+    #[serde(deserialize_with = "crate::duration::deserialize_span", serialize_with = "crate::duration::serialize_span")]
+    pub every: Option<u64>,
+    /// Text of the reminder.
+    pub message: String,
+}
+
+impl Default for BreaksConfig {
+    fn default() -> Self {
+        BreaksConfig { every: None, message: "Time for a break: stand up, stretch and rest your eyes.".to_string() }
+    }
+}
+
+impl BreaksConfig {
+    /// Whether a break is due now that the time tracked today went from
+    /// `before` to `now` seconds.
+    pub fn due(&self, before: u64, now: u64) -> bool {
+        self.every.and_then(|every| crossed_every(every, before, now)).is_some()
+    }
+}
+
+/// What fields are called in prompts, help text, the log's header row and
+/// reports, to match an organisation's vocabulary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelsConfig {
+    /// Name for codes, e.g. `Project`, `Cost Center` or `Matter`.
+    pub code: String,
+}
+
+impl Default for LabelsConfig {
+    fn default() -> Self {
+        LabelsConfig { code: "Code".to_string() }
+    }
+}
+
+impl LabelsConfig {
+    /// The code label for use within a sentence, e.g. `cost center`.
+    pub fn code_lower(&self) -> String {
+        self.code.to_lowercase()
+    }
+}
+
+/// How times of day and durations are shown (`[display]`).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// `24h` (`14:05`) or `12h` (`2:05 PM`) for the times sessions ran.
+    pub clock: Clock,
+    /// `hms` (`1h 05m`), `clock` (`01:05`) or `decimal` (`1.08h`) for the
+    /// live timer and summaries. Unset, the timer counts like a clock and
+    /// summaries use `hms`.
+    pub durations: Option<crate::duration::DurationFormat>,
+    /// The live timer turns from green to yellow after this long, e.g. `1h`.
+    #[serde(deserialize_with = "crate::duration::deserialize_span", serialize_with = "crate::duration::serialize_span")]
+    pub yellow: Option<u64>,
+    /// And from yellow to red after this long, e.g. `2h`.
+    #[serde(deserialize_with = "crate::duration::deserialize_span", serialize_with = "crate::duration::serialize_span")]
+    pub red: Option<u64>,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig { clock: Clock::default(), durations: None, yellow: Some(3600), red: Some(2 * 3600) }
+    }
+}
+
+/// The clock times of day are shown on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Clock {
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFour,
+    #[serde(rename = "12h")]
+    Twelve,
+}
+
+impl DisplayConfig {
+    /// The `chrono` format of a time of day, e.g. `%H:%M` or `%-I:%M %p`.
+    pub fn time_format(&self, with_seconds: bool) -> &'static str {
+        match (self.clock, with_seconds) {
+            (Clock::TwentyFour, false) => "%H:%M",
+            (Clock::TwentyFour, true) => "%H:%M:%S",
+            (Clock::Twelve, false) => "%-I:%M %p",
+            (Clock::Twelve, true) => "%-I:%M:%S %p",
+        }
+    }
+
+    /// `secs` in the configured format for summaries; see
+    /// [`DurationFormat::format`](crate::duration::DurationFormat::format).
+    pub fn duration(&self, secs: u64, with_seconds: bool) -> String {
+        self.durations.unwrap_or_default().format(secs, with_seconds)
+    }
+
+    /// `secs` as the live timer shows them, e.g. `01:05:09`.
+    pub fn elapsed(&self, secs: u64) -> String {
+        self.durations.unwrap_or(crate::duration::DurationFormat::Clock).format(secs, true)
+    }
+
+    /// The color of the live timer once a session has run `secs`.
+    pub fn elapsed_color(&self, secs: u64) -> console::Color {
+        let reached = |threshold: Option<u64>| threshold.is_some_and(|threshold| secs >= threshold);
+        if reached(self.red) {
+            console::Color::Red
+        } else if reached(self.yellow) {
+            console::Color::Yellow
+        } else {
+            console::Color::Green
+        }
+    }
+}
+
+/// The `[display]` formats from the config file, or the defaults if it
+/// can't be read.
+pub fn display() -> DisplayConfig {
+    try_load().map(|(config, _)| config.display).unwrap_or_default()
+}
+
+/// The labels from the config file, or the defaults if it can't be read,
+/// for places that must work with a broken config, such as `--help`.
+pub fn labels() -> LabelsConfig {
+    try_load().map(|(config, _)| config.labels).unwrap_or_default()
+}
+
+/// The language durations and days can be written in: `locale` from the
+/// config file, else that of the environment, else English. Like
+/// [`labels`], this works with a broken config.
+pub fn language() -> crate::locale::Language {
+    try_load()
+        .ok()
+        .and_then(|(config, _)| config.locale)
+        .or_else(crate::locale::Language::from_env)
+        .unwrap_or(crate::locale::Language::English)
+}
+
+/// The `timezone` from the config file, or none if it can't be read.
+pub fn timezone() -> Option<String> {
+    try_load().ok().and_then(|(config, _)| config.timezone)
+}
+
+/// The `[messages]` from the config file, or none if it can't be read.
+pub fn messages() -> BTreeMap<String, String> {
+    try_load().map(|(config, _)| config.messages).unwrap_or_default()
+}
+
+/// The local HTTP API started by `serve`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServeConfig {
+    pub port: u16,
+    /// Web origins allowed to call the API, e.g. a browser extension's
+    /// `chrome-extension://<id>`. Requests from other web pages are refused.
+    pub origins: Vec<String>,
+    /// Token every request must send as `Authorization: Bearer <token>`.
+    pub token: Option<String>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig { port: 7878, origins: Vec::new(), token: None }
+    }
+}
+
+/// Git-aware defaults for sessions started inside a repository.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitConfig {
+    /// Use the branch and repository as defaults whenever no task is given.
+    /// When off, they are only used with `--git`.
+    pub auto: bool,
+    /// Maps a repository name to the code its sessions are logged under.
+    /// Unmapped repositories use the repository name as the code.
+    pub codes: BTreeMap<String, String>,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        GitConfig { auto: true, codes: BTreeMap::new() }
+    }
+}
+
+/// Guessing the task from the current directory and shell history when
+/// `start` is given none; see [`crate::infer`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InferConfig {
+    /// Offer a guessed task (confirmed before starting). Off by default.
+    pub enabled: bool,
+    /// Add the command that dominates recent shell history to the task.
+    pub history: bool,
+    /// How many of the latest history entries are looked at.
+    pub lines: usize,
+    /// Commands that say nothing about the task, by program or program and
+    /// subcommand.
+    pub ignore: Vec<String>,
+    /// Maps a directory name to the code its sessions are logged under.
+    pub codes: BTreeMap<String, String>,
+}
+
+impl Default for InferConfig {
+    fn default() -> Self {
+        let ignore = ["cd", "ls", "ll", "pwd", "clear", "exit", "history", "man", "cat", "less", "git status", "git diff", "git log"];
+        InferConfig {
+            enabled: false,
+            history: true,
+            lines: 50,
+            ignore: ignore.into_iter().map(str::to_string).collect(),
+            codes: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BadgeConfig {
+    /// Preset started when the badge is scanned.
+    pub preset: String,
+}
+
+/// GPIO buttons used by the `buttons` command.
+#[cfg_attr(not(feature = "hardware"), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HardwareConfig {
+    /// Presses within this many milliseconds of the previous one are ignored.
+    pub debounce_ms: u64,
+    pub buttons: Vec<ButtonConfig>,
+}
+
+impl Default for HardwareConfig {
+    fn default() -> Self {
+        HardwareConfig { debounce_ms: 250, buttons: Vec::new() }
+    }
+}
+
+#[cfg_attr(not(feature = "hardware"), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ButtonConfig {
+    /// BCM GPIO number the button connects to ground.
+    pub pin: u8,
+    pub action: ButtonAction,
+    /// Preset to start; required for `switch`, optional for `toggle`.
+    pub preset: Option<String>,
+}
+
+#[cfg_attr(not(feature = "hardware"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonAction {
+    /// Stop the running session, or start the preset if none is running.
+    Toggle,
+    /// Start the preset, stopping the running session first.
+    Switch,
+}
+
+/// Hourly rates, clients and tax used by billing reports.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BillingConfig {
+    /// Currency label printed next to amounts.
+    pub currency: String,
+    /// Hourly rate for codes without an entry in `rates`.
+    pub default_rate: f64,
+    /// Hourly rate per code.
+    pub rates: BTreeMap<String, f64>,
+    /// Maps a code to the client it is billed to.
+    pub clients: BTreeMap<String, String>,
+    /// Tax rate in percent for clients without an entry in `tax`.
+    pub default_tax: f64,
+    /// Tax (e.g. VAT) rate in percent per client.
+    pub tax: BTreeMap<String, f64>,
+}
+
+impl Default for BillingConfig {
+    fn default() -> Self {
+        BillingConfig {
+            currency: "EUR".to_string(),
+            default_rate: 0.0,
+            rates: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            default_tax: 0.0,
+            tax: BTreeMap::new(),
+        }
+    }
+}
+
+impl BillingConfig {
+    /// The hourly rate for a code, or for the client part of a hierarchical
+    /// code (see [`log::code_parts`](crate::log::code_parts)) without its own.
+    pub fn rate(&self, code: &str) -> f64 {
+        let (client, _) = crate::log::code_parts(code);
+        self.rates.get(code).or_else(|| self.rates.get(client)).copied().unwrap_or(self.default_rate)
+    }
+
+    /// The client a code is billed to: the one configured for the code or
+    /// its client part, else the client part of a hierarchical code.
+    pub fn client<'a>(&'a self, code: &'a str) -> Option<&'a str> {
+        let (client, engagement) = crate::log::code_parts(code);
+        let configured = self.clients.get(code).or_else(|| self.clients.get(client)).map(String::as_str);
+        configured.or(engagement.map(|_| client))
+    }
+
+    pub fn tax_rate(&self, client: &str) -> f64 {
+        self.tax.get(client).copied().unwrap_or(self.default_tax)
+    }
+}
+
+/// Defaults for the `report` command.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportConfig {
+    /// Layout used without `--layout`, e.g. `payroll` in a profile for an employer.
+    pub layout: crate::report::Layout,
+}
+
+/// Numbering of generated invoices.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvoiceConfig {
+    /// Text placed before the sequence number, e.g. `INV-`.
+    pub prefix: String,
+    /// Minimum number of digits; the number is zero-padded to this width.
+    pub digits: usize,
+    /// Number of the first invoice when the ledger is empty.
+    pub start: u64,
+}
+
+impl Default for InvoiceConfig {
+    fn default() -> Self {
+        InvoiceConfig { prefix: "INV-".to_string(), digits: 4, start: 1 }
+    }
+}
+
+/// Connection settings and project mapping for pushing to Harvest.
+// Kept without the `integrations` feature so the same config file works in every build
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HarvestConfig {
+    pub account_id: Option<String>,
+    /// Personal access token.
+    pub token: Option<String>,
+    /// Overrides the API base URL (defaults to `https://api.harvestapp.com/v2`).
+    pub api_url: Option<String>,
+    /// Maps a code to the Harvest project and task its time is logged against.
+    pub projects: BTreeMap<String, HarvestProject>,
+}
+
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HarvestProject {
+    pub project_id: u64,
+    pub task_id: u64,
+}
+
+/// Connection settings for pushing worklogs to Jira.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JiraConfig {
+    /// Base URL of the Jira site, e.g. `https://example.atlassian.net`.
+    pub url: Option<String>,
+    /// Account email; required for Jira Cloud API tokens.
+    pub email: Option<String>,
+    /// API token (Jira Cloud) or personal access token (Jira Server/Data Center).
+    pub token: Option<String>,
+}
+
+/// Credentials for commenting tracked time on Linear issues.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinearConfig {
+    /// Personal API key.
+    pub api_key: Option<String>,
+    /// Overrides the GraphQL endpoint (defaults to `https://api.linear.app/graphql`).
+    pub api_url: Option<String>,
+    /// Maps a code to the issue (e.g. `ENG-123`) its time is commented on.
+    /// Unmapped codes are used as the issue identifier.
+    pub issues: BTreeMap<String, String>,
+}
+
+/// Credentials for recording tracked time on Asana tasks.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsanaConfig {
+    /// Personal access token.
+    pub token: Option<String>,
+    /// Overrides the API base URL (defaults to `https://app.asana.com/api/1.0`).
+    pub api_url: Option<String>,
+    pub mode: AsanaMode,
+    /// Maps a code to the ID of the task its time is recorded on.
+    /// Unmapped codes are used as the task ID.
+    pub tasks: BTreeMap<String, String>,
+}
+
+/// How tracked time is recorded on an Asana task.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AsanaMode {
+    /// A native time tracking entry.
+    #[default]
+    Time,
+    /// A comment on the task, for workspaces without time tracking.
+    Comment,
+}
+
+/// Appending entries to a Notion database.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotionConfig {
+    /// Internal integration secret; the database must be shared with the integration.
+    pub token: Option<String>,
+    /// ID of the database rows are added to (from its URL).
+    pub database_id: Option<String>,
+    /// Overrides the API base URL (defaults to `https://api.notion.com/v1`).
+    pub api_url: Option<String>,
+    pub columns: NotionColumns,
+}
+
+/// Names of the database properties each field is written to. An empty
+/// name leaves the field out.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotionColumns {
+    /// Title property holding the task.
+    pub task: String,
+    /// Date property holding the start and end of the session.
+    pub date: String,
+    pub code: String,
+    pub code_type: NotionCodeType,
+    /// Number property holding the duration in hours.
+    pub hours: String,
+}
+
+impl Default for NotionColumns {
+    fn default() -> Self {
+        NotionColumns {
+            task: "Name".to_string(),
+            date: "Date".to_string(),
+            code: "Code".to_string(),
+            code_type: NotionCodeType::Select,
+            hours: "Hours".to_string(),
+        }
+    }
+}
+
+/// Type of the Notion property the code is written to.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotionCodeType {
+    #[default]
+    Select,
+    Text,
+}
+
+/// Appending entries to a Google Sheets spreadsheet.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GoogleSheetsConfig {
+    /// ID of the spreadsheet (from its URL).
+    pub spreadsheet_id: Option<String>,
+    /// Sheet (tab) the rows are appended to.
+    pub sheet: String,
+    /// Service account key file (JSON).
+    pub credentials: Option<PathBuf>,
+    /// Overrides the API base URL (defaults to `https://sheets.googleapis.com/v4`).
+    pub api_url: Option<String>,
+}
+
+impl Default for GoogleSheetsConfig {
+    fn default() -> Self {
+        GoogleSheetsConfig { spreadsheet_id: None, sheet: "Sheet1".to_string(), credentials: None, api_url: None }
+    }
+}
+
+/// Credentials for pushing time entries to Redmine.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedmineConfig {
+    /// Base URL of the Redmine instance, e.g. `https://redmine.example.com`.
+    pub url: Option<String>,
+    /// API access key from the account page.
+    pub api_key: Option<String>,
+    /// Time entry activity; Redmine's default activity is used if unset.
+    pub activity_id: Option<u64>,
+}
+
+/// Credentials for pushing work items to YouTrack.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct YouTrackConfig {
+    /// Base URL of the YouTrack instance, e.g. `https://example.youtrack.cloud`.
+    pub url: Option<String>,
+    /// Permanent token.
+    pub token: Option<String>,
+}
+
+/// Credentials for sending entries to a WakaTime-compatible server.
+#[cfg_attr(not(feature = "integrations"), allow(dead_code))]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WakaTimeConfig {
+    /// API base URL; defaults to wakatime.com. Point it at e.g. a Wakapi server.
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+    /// Heartbeat category the entries are shown under.
+    pub category: String,
+}
+
+impl Default for WakaTimeConfig {
+    fn default() -> Self {
+        WakaTimeConfig { api_url: None, api_key: None, category: "coding".to_string() }
+    }
+}
+
+/// Settings for exporting to Clockify.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockifyConfig {
+    /// Email of the Clockify user the time entries belong to.
+    pub email: Option<String>,
+    /// Maps a code to the Clockify project (and optionally client/task) it belongs to.
+    pub projects: BTreeMap<String, ClockifyProject>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockifyProject {
+    pub project: String,
+    pub client: Option<String>,
+    pub task: Option<String>,
+}
+
+/// A base directory from an XDG variable if it is set to an absolute path,
+/// otherwise the platform default. Honouring the variables on every
+/// platform lets macOS and Windows users opt into the XDG layout too.
+fn base_dir(xdg_var: &str, default: Option<PathBuf>) -> Option<PathBuf> {
+    std::env::var_os(xdg_var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or(default)
+}
+
+/// `path` with symlinks resolved. On Windows, without the `\\?\` prefix
+/// `canonicalize` adds, which `cmd` and many other programs don't take.
+fn resolve_links(path: &Path) -> std::io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+    if cfg!(windows)
+        && let Some(plain) = path.to_str().and_then(|p| p.strip_prefix(r"\\?\"))
+        && plain.chars().nth(1) == Some(':')
+    {
+        return Ok(PathBuf::from(plain));
+    }
+    Ok(path)
+}
+
+/// Turns on portable mode for the rest of the run; see [`portable_dir`].
+pub fn select_portable() {
+    let _ = PORTABLE.set(());
+}
+
+/// In portable mode, the directory holding the config file, log and all
+/// other state: `simpletimer` beside the executable. Portable mode is on
+/// with `--portable`, or when a [`PORTABLE_FLAG`] file is beside the
+/// executable, so the timer can run from a USB stick without touching the
+/// home directory.
+pub fn portable_dir() -> Option<PathBuf> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let exe = resolve_links(&std::env::current_exe().ok()?).ok()?;
+        let beside = exe.parent()?;
+        (PORTABLE.get().is_some() || beside.join(PORTABLE_FLAG).exists()).then(|| beside.join("simpletimer"))
+    })
+    .clone()
+}
+
+/// Location of the configuration file (`$XDG_CONFIG_HOME/simpletimer/config.toml`,
+/// or `config.toml` in the [`portable_dir`]).
+pub fn config_path() -> PathBuf {
+    if let Some(dir) = portable_dir() {
+        return dir.join("config.toml");
+    }
+    let mut path = base_dir("XDG_CONFIG_HOME", dirs::config_dir()).expect("Could not find config directory");
+    path.push("simpletimer");
+    path.push("config.toml");
+    path
+}
+
+/// Directory for the log and other data files, such as the invoice ledger
+/// (`$XDG_DATA_HOME/simpletimer`, or the [`portable_dir`]).
+///
+/// Each profile has its own folder, so its running session, invoices and
+/// sync ledgers stay separate from the other profiles'.
+pub fn data_dir() -> PathBuf {
+    let mut path = match portable_dir() {
+        Some(dir) => dir,
+        None => base_dir("XDG_DATA_HOME", dirs::data_dir()).expect("Could not find data directory").join("simpletimer"),
+    };
+    if let Some(profile) = profile() {
+        path.push("profiles");
+        path.push(profile);
+    }
+    path
+}
+
+/// Selects the profile given on the command line for the rest of the run.
+pub fn select_profile(name: String) {
+    SELECTED_PROFILE.set(name).expect("Profile selected twice");
+}
+
+/// The active profile: the one selected on the command line, else
+/// `SIMPLETIMER_PROFILE`, else `default_profile` from the config file.
+pub fn profile() -> Option<String> {
+    active_profile(&read_table())
+}
+
+fn active_profile(table: &toml::Table) -> Option<String> {
+    SELECTED_PROFILE
+        .get()
+        .cloned()
+        .or_else(|| std::env::var("SIMPLETIMER_PROFILE").ok().filter(|p| !p.is_empty()))
+        .or_else(|| table.get("default_profile")?.as_str().map(str::to_string))
+}
+
+/// Reads the configuration file as a raw table, exiting with a message if
+/// it can't be read or parsed.
+pub fn read_table() -> toml::Table {
+    try_read_table().unwrap_or_else(|e| Error::Other(e).exit())
+}
+
+fn try_read_table() -> Result<toml::Table, String> {
+    let path = config_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(toml::Table::new()),
+        Err(e) => return Err(format!("Failed to read config file '{}': {}", path.display(), e)),
+    };
+    content.parse().map_err(|e| format!("Invalid config file '{}': {}", path.display(), e))
+}
+
+/// Recursively overlays `overrides` onto `base`.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            },
+        }
+    }
+}
+
+/// Loads the configuration file with the active profile's settings applied,
+/// exiting with a message if it is invalid or the profile is not defined.
+pub fn load() -> Config {
+    match try_load() {
+        Ok((config, _)) => config,
+        Err(LoadError::UnknownProfile(message)) => Error::Invalid(message).exit(),
+        Err(LoadError::Invalid(message)) => Error::Other(message).exit(),
+    }
+}
+
+enum LoadError {
+    Invalid(String),
+    UnknownProfile(String),
+}
+
+/// Loads the configuration along with the effective settings as a table.
+fn try_load() -> Result<(Config, toml::Table), LoadError> {
+    let mut table = try_read_table().map_err(LoadError::Invalid)?;
+    if let Some(profile) = active_profile(&table) {
+        let overrides = table
+            .get("profiles")
+            .and_then(|profiles| profiles.get(&profile))
+            .and_then(|overrides| overrides.as_table())
+            .cloned();
+        let Some(overrides) = overrides else {
+            return Err(LoadError::UnknownProfile(format!(
+                "Unknown profile '{}'. Define it as [profiles.{}] in the config file.",
+                profile, profile
+            )));
+        };
+        merge(&mut table, overrides);
+    }
+    match table.clone().try_into() {
+        Ok(config) => Ok((config, table)),
+        Err(e) => Err(LoadError::Invalid(format!("Invalid config file '{}': {}", config_path().display(), e))),
+    }
+}
+
+/// Picks up edits to the config file in long-running commands, so settings
+/// can be tuned without restarting them.
+pub struct Watcher {
+    modified: Option<std::time::SystemTime>,
+    table: toml::Table,
+}
+
+impl Watcher {
+    /// Starts watching from the config as it is now.
+    pub fn new() -> Self {
+        Watcher { modified: modified(), table: try_load().map(|(_, table)| table).unwrap_or_default() }
+    }
+
+    /// Reloads the config if the file changed since the last call, returning
+    /// it with the dotted keys of the settings that changed. An invalid edit
+    /// is reported once and ignored until the file is fixed.
+    pub fn poll(&mut self) -> Option<(Config, Vec<String>)> {
+        let modified = modified();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let (config, table) = match try_load() {
+            Ok(loaded) => loaded,
+            Err(LoadError::Invalid(message) | LoadError::UnknownProfile(message)) => {
+                eprintln!("{}\nKeeping the previous settings until the file is fixed.", message);
+                return None;
+            },
+        };
+        let mut changed = Vec::new();
+        changed_keys(&self.table, &table, "", &mut changed);
+        self.table = table;
+        if changed.is_empty() { None } else { Some((config, changed)) }
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn modified() -> Option<std::time::SystemTime> {
+    std::fs::metadata(config_path()).and_then(|m| m.modified()).ok()
+}
+
+/// Collects the dotted keys whose values differ between two tables.
+fn changed_keys(old: &toml::Table, new: &toml::Table, prefix: &str, changed: &mut Vec<String>) {
+    let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (old.get(key), new.get(key)) {
+            (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) => changed_keys(old, new, &path, changed),
+            (old, new) if old != new => changed.push(path),
+            _ => {},
+        }
+    }
+}
+
+/// The value of a setting by dotted key (e.g. `billing.currency`) as the
+/// timer uses it: from the config file with the active profile applied, or
+/// else its default. Strings are returned without quotes and tables as
+/// TOML. Returns `None` for a setting without a value, such as `max` when
+/// sessions have no limit, and an error for a key that isn't a setting.
+pub fn get(key: &str) -> Result<Option<String>, String> {
+    lookup(&load(), key)
+}
+
+fn lookup(config: &Config, key: &str) -> Result<Option<String>, String> {
+    let config = toml::Value::try_from(config).map_err(|e| format!("Failed to read the settings: {}", e))?;
+    let unknown = || format!("'{}' is not a setting.", key);
+    let mut value = &config;
+    for part in key.split('.') {
+        value = match value {
+            toml::Value::Table(table) => match table.get(part) {
+                Some(value) => value,
+                None if is_setting(key) => return Ok(None),
+                None => return Err(unknown()),
+            },
+            _ => return Err(unknown()),
+        };
+    }
+    Ok(Some(match value {
+        toml::Value::String(text) => text.clone(),
+        toml::Value::Table(table) => toml::to_string(table).unwrap_or_default().trim_end().to_string(),
+        other => other.to_string(),
+    }))
+}
+
+/// Whether `key` names a setting, even one without a value: a config with
+/// only that key set may have a value of the wrong type, but no unknown key.
+fn is_setting(key: &str) -> bool {
+    let mut document = toml_edit::DocumentMut::new();
+    if insert(&mut document, key, toml_edit::Value::from("")).is_err() {
+        return false;
+    }
+    match toml::from_str::<Config>(&document.to_string()) {
+        Ok(_) => true,
+        Err(e) => !e.message().starts_with("unknown field"),
+    }
+}
+
+/// Sets a setting in the config file by dotted key, keeping the rest of the
+/// file (including comments) as it is.
+///
+/// The value is read as TOML (a number, boolean, array, ...) and otherwise
+/// as a string. The file is only written if the result is a valid config,
+/// so unknown keys and values of the wrong type are rejected.
+pub fn set(key: &str, value: &str) -> Result<(), String> {
+    let path = config_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read config file '{}': {}", path.display(), e)),
+    };
+    let document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| format!("Invalid config file '{}': {}", path.display(), e))?;
+
+    // `true` or `80` as TOML, but `https://...` or `INV-` as a string
+    let candidates = value.parse::<toml_edit::Value>().ok().into_iter().chain([toml_edit::Value::from(value)]);
+    let mut first_error = None;
+    for candidate in candidates {
+        let mut updated = document.clone();
+        insert(&mut updated, key, candidate)?;
+        let content = updated.to_string();
+        match toml::from_str::<Config>(&content) {
+            Ok(_) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                return std::fs::write(&path, content)
+                    .map_err(|e| format!("Failed to write config file '{}': {}", path.display(), e));
+            },
+            Err(e) => {
+                first_error.get_or_insert_with(|| e.message().to_string());
+            },
+        }
+    }
+    Err(format!("Can't set '{}': {}", key, first_error.unwrap_or_default()))
+}
+
+/// Puts `value` at a dotted key, creating the tables on the way.
+fn insert(document: &mut toml_edit::DocumentMut, key: &str, value: toml_edit::Value) -> Result<(), String> {
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(format!("'{}' is not a valid key", key));
+    }
+    let mut item = document.as_item_mut();
+    for (i, part) in parts.iter().enumerate() {
+        if item.is_none() {
+            // A `[section]` rather than an inline table at the top of the file,
+            // whose header is left out if it only holds other sections
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            *item = toml_edit::Item::Table(table);
+        } else if !item.is_table_like() {
+            return Err(format!("'{}' is not a table", parts[..i].join(".")));
+        }
+        item = &mut item[part];
+    }
+    *item = toml_edit::value(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gets_settings_with_their_defaults() {
+        let config: Config = toml::from_str("max = '8h'\n[billing]\ncurrency = 'USD'").unwrap();
+        assert_eq!(lookup(&config, "billing.currency"), Ok(Some("USD".to_string())));
+        assert_eq!(lookup(&config, "max"), Ok(Some("8h".to_string())));
+        assert_eq!(lookup(&config, "alerts.budgets"), Ok(Some("[80, 100]".to_string())));
+        assert_eq!(lookup(&Config::default(), "max"), Ok(None));
+        assert_eq!(lookup(&config, "billing.rates.ACME"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        assert!(lookup(&Config::default(), "billing.curency").is_err());
+        assert!(lookup(&Config::default(), "nonsense").is_err());
+        assert!(lookup(&Config::default(), "billing.currency.code").is_err());
+    }
+
+    #[test]
+    fn reports_changed_keys() {
+        let old: toml::Table = "a = 1\nb = 2\n[billing]\ncurrency = 'EUR'\nrates = { X = 10 }".parse().unwrap();
+        let new: toml::Table = "a = 1\nc = 3\n[billing]\ncurrency = 'EUR'\nrates = { X = 20 }".parse().unwrap();
+        let mut changed = Vec::new();
+        changed_keys(&old, &new, "", &mut changed);
+        assert_eq!(changed, vec!["b", "billing.rates.X", "c"]);
+    }
+
+    #[test]
+    fn alerts_fire_once_per_threshold() {
+        let alerts: AlertsConfig = toml::from_str("every = '60m'\nat = ['90m', '4h']\n[codes.ACME]\nat = ['30m']").unwrap();
+        assert_eq!(alerts.crossed("OPS", 0, 59 * 60), None);
+        assert_eq!(alerts.crossed("OPS", 59 * 60, 60 * 60), Some(3600));
+        assert_eq!(alerts.crossed("OPS", 60 * 60, 61 * 60), None);
+        assert_eq!(alerts.crossed("OPS", 89 * 60, 90 * 60), Some(90 * 60));
+        assert!(alerts.has_milestones());
+        assert!(!AlertsConfig::default().has_milestones());
+        assert_eq!(AlertsConfig::default().crossed("OPS", 0, 99_999), None);
+        assert!(toml::from_str::<AlertsConfig>("at = ['soon']").is_err());
+
+        // Codes with their own milestones, and their subcodes, only get those
+        assert_eq!(alerts.crossed("ACME", 29 * 60, 30 * 60), Some(30 * 60));
+        assert_eq!(alerts.crossed("ACME/web", 29 * 60, 30 * 60), Some(30 * 60));
+        assert_eq!(alerts.crossed("ACME", 59 * 60, 60 * 60), None);
+    }
+
+    #[test]
+    fn breaks_are_due_after_each_stretch() {
+        let breaks: BreaksConfig = toml::from_str("every = '50m'").unwrap();
+        assert!(!breaks.due(0, 49 * 60));
+        assert!(breaks.due(49 * 60, 50 * 60));
+        assert!(!breaks.due(50 * 60, 99 * 60));
+        assert!(breaks.due(99 * 60, 100 * 60));
+        assert!(!BreaksConfig::default().due(0, 99_999));
+    }
+
+    #[test]
+    fn reads_display_formats() {
+        let display: DisplayConfig = toml::from_str("clock = '12h'\ndurations = 'clock'").unwrap();
+        let time = chrono::NaiveTime::from_hms_opt(14, 5, 9).unwrap();
+        assert_eq!(time.format(display.time_format(false)).to_string(), "2:05 PM");
+        assert_eq!(time.format(DisplayConfig::default().time_format(true)).to_string(), "14:05:09");
+        assert_eq!(display.duration(3909, false), "01:05");
+        assert_eq!(DisplayConfig::default().duration(3909, false), "1h 05m");
+        assert_eq!(DisplayConfig::default().elapsed(3909), "01:05:09");
+        assert!(toml::from_str::<DisplayConfig>("clock = '13h'").is_err());
+
+        let defaults = DisplayConfig::default();
+        assert_eq!(defaults.elapsed_color(59 * 60), console::Color::Green);
+        assert_eq!(defaults.elapsed_color(3600), console::Color::Yellow);
+        assert_eq!(defaults.elapsed_color(2 * 3600), console::Color::Red);
+        let red_only: DisplayConfig = toml::from_str("red = '8h'").unwrap();
+        assert_eq!(red_only.elapsed_color(7 * 3600), console::Color::Yellow);
+        assert_eq!(red_only.elapsed_color(8 * 3600), console::Color::Red);
+    }
+
+    #[test]
+    fn places_codes_in_projects_and_clients() {
+        let config: Config = toml::from_str(
+            "[projects.Website]\nclient = 'ACME'\ncodes = ['FEAT-42', 'GLOBEX']\n[projects.Internal]\ncodes = ['OPS']\n[billing.clients]\nOPS = 'Us'",
+        )
+        .unwrap();
+        assert_eq!((config.project("FEAT-42"), config.client("FEAT-42")), (Some("Website"), Some("ACME")));
+        assert_eq!((config.project("GLOBEX/SUPPORT"), config.client("GLOBEX/SUPPORT")), (Some("Website"), Some("ACME")));
+        assert_eq!((config.project("OPS"), config.client("OPS")), (Some("Internal"), Some("Us")));
+        assert_eq!((config.project("ACME/ONBOARDING"), config.client("ACME/ONBOARDING")), (None, Some("ACME")));
+        assert_eq!((config.project("MISC"), config.client("MISC")), (None, None));
+    }
+
+    #[test]
+    fn merges_profile_over_root() {
+        let mut base: toml::Table = "log = 'a.csv'\n[billing]\ncurrency = 'EUR'\ndefault_rate = 50".parse().unwrap();
+        merge(&mut base, "[billing]\ndefault_rate = 0".parse().unwrap());
+        let config: Config = base.try_into().unwrap();
+        assert_eq!(config.billing.currency, "EUR");
+        assert_eq!(config.billing.default_rate, 0.0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Days and periods typed on the command line.
+//!
+//! A day is `YYYY-MM-DD`, `today`, `yesterday`, a weekday such as `monday`
+//! or `last-<weekday>`. `--since` and `--until` also take a period, such as
+//! `last-month` or `2024-05`, and use its first or last day, so
+//! `--since last-month --until last-month` selects all of last month.
+//! Days written out in the configured language (see [`crate::locale`])
+//! work as well.
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Weekday};
+
+/// Parses a day: `YYYY-MM-DD`, `today`, `yesterday`, a weekday (e.g.
+/// `monday`, the most recent Monday, today included) or `last-<weekday>`
+/// (e.g. `last-sunday`, the most recent Sunday before today).
+pub fn parse_day(value: &str) -> Result<NaiveDate, String> {
+    parse_day_from(value, Local::now().date_naive())
+}
+
+pub(crate) fn parse_day_from(value: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let lower = value.trim().to_lowercase();
+    match lower.as_str() {
+        "today" => return Ok(today),
+        "yesterday" => return Ok(today - Days::new(1)),
+        _ => {},
+    }
+    if let Ok(weekday) = lower.parse::<Weekday>() {
+        let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+        return Ok(today - Days::new(back.into()));
+    }
+    if let Some(weekday) = lower.strip_prefix("last-").and_then(|day| day.parse::<Weekday>().ok()) {
+        let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+        return Ok(today - Days::new(if back == 0 { 7 } else { back.into() }));
+    }
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("expected YYYY-MM-DD, today, yesterday, a weekday or last-<weekday>, got '{}'", value))
+}
+
+/// Parses a period into its first and last day: a day as accepted by
+/// [`parse_day`], `this-` or `last-` followed by `week`, `month` or
+/// `year` (a space works too, as in `last month`), a month such as
+/// `2024-05` or a year such as `2024`. Weeks start on Monday.
+pub fn parse_period_from(value: &str, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), String> {
+    if let Ok(day) = parse_day_from(value, today) {
+        return Ok((day, day));
+    }
+    let lower = value.trim().to_lowercase().replace(' ', "-");
+    named_period(&lower, today)
+        .or_else(|| numbered_period(&lower))
+        .ok_or_else(|| format!("expected YYYY-MM-DD, a day such as yesterday or monday, or a period such as last-month or 2024-05, got '{}'", value))
+}
+
+/// Parses a month into its first and last day: `YYYY-MM`, `this-month` or
+/// `last-month`.
+pub fn parse_month(value: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    parse_month_from(value, Local::now().date_naive())
+}
+
+fn parse_month_from(value: &str, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), String> {
+    let lower = value.trim().to_lowercase().replace(' ', "-");
+    let month = match lower.ends_with("-month") {
+        true => named_period(&lower, today),
+        false => numbered_period(&lower).filter(|_| lower.contains('-')),
+    };
+    month.ok_or_else(|| format!("expected YYYY-MM, this-month or last-month, got '{}'", value))
+}
+
+/// `this-week`, `last-month` and the like.
+fn named_period(value: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let (which, unit) = value.split_once('-')?;
+    let last = match which {
+        "this" => false,
+        "last" => true,
+        _ => return None,
+    };
+    match unit {
+        "week" => {
+            let monday = today.week(Weekday::Mon).first_day() - Days::new(if last { 7 } else { 0 });
+            Some((monday, monday + Days::new(6)))
+        },
+        "month" => {
+            let first = today.with_day(1)?;
+            let first = if last { first.checked_sub_months(Months::new(1))? } else { first };
+            Some((first, first.checked_add_months(Months::new(1))?.pred_opt()?))
+        },
+        "year" => year(today.year() - i32::from(last)),
+        _ => None,
+    }
+}
+
+/// `2024-05` or `2024`.
+fn numbered_period(value: &str) -> Option<(NaiveDate, NaiveDate)> {
+    if !value.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return None;
+    }
+    match value.split_once('-') {
+        Some((year, month)) if month.len() == 2 => {
+            let first = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+            Some((first, first.checked_add_months(Months::new(1))?.pred_opt()?))
+        },
+        None if value.len() == 4 => year(value.parse().ok()?),
+        _ => None,
+    }
+}
+
+fn year(year: i32) -> Option<(NaiveDate, NaiveDate)> {
+    Some((NaiveDate::from_ymd_opt(year, 1, 1)?, NaiveDate::from_ymd_opt(year, 12, 31)?))
+}
+
+/// Parses a period, or a day written out in the configured language.
+fn parse_bounds(value: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let today = Local::now().date_naive();
+    parse_period_from(value, today)
+        .or_else(|e| crate::config::language().parse_day(value, today).map(|day| (day, day)).map_err(|_| e))
+}
+
+/// Parses `--since`: a day, or the first day of a period.
+pub fn parse_since(value: &str) -> Result<NaiveDate, String> {
+    parse_bounds(value).map(|(first, _)| first)
+}
+
+/// Parses `--until`: a day, or the last day of a period.
+pub fn parse_until(value: &str) -> Result<NaiveDate, String> {
+    parse_bounds(value).map(|(_, last)| last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_relative_days() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 5, 8).unwrap();
+        let day = |value| parse_day_from(value, today).map(|d| d.to_string());
+        assert_eq!(day("today"), Ok("2024-05-08".to_string()));
+        assert_eq!(day("Yesterday"), Ok("2024-05-07".to_string()));
+        assert_eq!(day("last-sunday"), Ok("2024-05-05".to_string()));
+        assert_eq!(day("last-mon"), Ok("2024-05-06".to_string()));
+        assert_eq!(day("last-wednesday"), Ok("2024-05-01".to_string()));
+        assert_eq!(day("monday"), Ok("2024-05-06".to_string()));
+        assert_eq!(day("wednesday"), Ok("2024-05-08".to_string()));
+        assert_eq!(day("2024-01-31"), Ok("2024-01-31".to_string()));
+        assert!(day("next-friday").is_err());
+    }
+
+    #[test]
+    fn parses_periods() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 13).unwrap();
+        let period = |value| parse_period_from(value, today).map(|(first, last)| format!("{} {}", first, last));
+        assert_eq!(period("yesterday"), Ok("2024-03-12 2024-03-12".to_string()));
+        assert_eq!(period("this-week"), Ok("2024-03-11 2024-03-17".to_string()));
+        assert_eq!(period("last week"), Ok("2024-03-04 2024-03-10".to_string()));
+        assert_eq!(period("Last Month"), Ok("2024-02-01 2024-02-29".to_string()));
+        assert_eq!(period("this-month"), Ok("2024-03-01 2024-03-31".to_string()));
+        assert_eq!(period("last-year"), Ok("2023-01-01 2023-12-31".to_string()));
+        assert_eq!(period("2023-12"), Ok("2023-12-01 2023-12-31".to_string()));
+        assert_eq!(period("2022"), Ok("2022-01-01 2022-12-31".to_string()));
+        assert!(period("2023-13").is_err());
+        assert!(period("next-month").is_err());
+
+        let month = |value| parse_month_from(value, today).map(|(first, last)| format!("{} {}", first, last));
+        assert_eq!(month("2024-05"), Ok("2024-05-01 2024-05-31".to_string()));
+        assert_eq!(month("last month"), Ok("2024-02-01 2024-02-29".to_string()));
+        assert!(month("2024").is_err());
+        assert!(month("last-week").is_err());
+    }
+}
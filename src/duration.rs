@@ -0,0 +1,268 @@
+//! Helpers for formatting and rounding durations.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How a duration is rounded to a multiple of the rounding increment.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RoundMode {
+    /// Round to the closest increment (halves round up).
+    #[default]
+    Nearest,
+    /// Always round up to the next increment.
+    Up,
+    /// Always round down to the previous increment.
+    Down,
+}
+
+/// Rounds `secs` to a multiple of `increment_minutes` using `mode`.
+///
+/// An increment of zero leaves the duration untouched.
+pub fn round_secs(secs: u64, increment_minutes: u64, mode: RoundMode) -> u64 {
+    let increment = increment_minutes * 60;
+    if increment == 0 {
+        return secs;
+    }
+    let units = match mode {
+        RoundMode::Nearest => (secs + increment / 2) / increment,
+        RoundMode::Up => secs.div_ceil(increment),
+        RoundMode::Down => secs / increment,
+    };
+    units * increment
+}
+
+/// How precisely sessions are logged (`precision` in the config file):
+/// `seconds`, `minutes`, or rounded to an increment such as `15m`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Precision {
+    /// The exact duration.
+    #[default]
+    Seconds,
+    /// Rounded to this many minutes; `minutes` is an increment of one.
+    Rounded(u64),
+}
+
+impl Precision {
+    /// The duration logged for a session of `secs`: rounded to the nearest
+    /// increment, but never below one, so short sessions aren't logged as
+    /// nothing.
+    pub fn apply(self, secs: u64) -> u64 {
+        match self {
+            Precision::Seconds => secs,
+            Precision::Rounded(_) if secs == 0 => 0,
+            Precision::Rounded(minutes) => round_secs(secs, minutes, RoundMode::Nearest).max(minutes * 60),
+        }
+    }
+}
+
+impl std::str::FromStr for Precision {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "seconds" => Ok(Precision::Seconds),
+            "minutes" => Ok(Precision::Rounded(1)),
+            span => match parse_span(span) {
+                Ok(secs) if secs > 0 && secs % 60 == 0 => Ok(Precision::Rounded(secs / 60)),
+                _ => Err(format!("expected seconds, minutes or an increment of whole minutes such as 15m, got '{}'", value)),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Precision {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        value.parse()
+    }
+}
+
+impl From<Precision> for String {
+    fn from(precision: Precision) -> String {
+        match precision {
+            Precision::Seconds => "seconds".to_string(),
+            Precision::Rounded(1) => "minutes".to_string(),
+            Precision::Rounded(minutes) => format_span(minutes * 60),
+        }
+    }
+}
+
+/// Formats a duration as `1h 05m`, or `1h 05m 09s` when `with_seconds` is set.
+pub fn format_hms(secs: u64, with_seconds: bool) -> String {
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    if with_seconds {
+        format!("{}h {:02}m {:02}s", hours, minutes, secs % 60)
+    } else {
+        format!("{}h {:02}m", hours, minutes)
+    }
+}
+
+/// How durations are shown while timing and in summaries (`[display]`
+/// `durations`); reports keep their own columns.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationFormat {
+    /// `1h 05m`, as [`format_hms`].
+    #[default]
+    Hms,
+    /// `01:05`, like a clock.
+    Clock,
+    /// `1.08h`, as [`format_decimal_hours`].
+    Decimal,
+}
+
+impl DurationFormat {
+    /// Formats `secs`, with the seconds when `with_seconds` is set, e.g.
+    /// `01:05:09`. Decimal hours have no seconds.
+    pub fn format(self, secs: u64, with_seconds: bool) -> String {
+        match self {
+            DurationFormat::Hms => format_hms(secs, with_seconds),
+            DurationFormat::Clock if with_seconds => format!("{:02}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60),
+            DurationFormat::Clock => format!("{:02}:{:02}", secs / 3600, (secs % 3600) / 60),
+            DurationFormat::Decimal => format!("{}h", format_decimal_hours(secs)),
+        }
+    }
+}
+
+/// Formats a duration as decimal hours with two places, e.g. `1.25`.
+pub fn format_decimal_hours(secs: u64) -> String {
+    format!("{:.2}", secs as f64 / 3600.0)
+}
+
+/// A bar of `width` characters filled as far as `secs` is towards
+/// `target_secs`, with the percentage, e.g. `[#####-----]  50%`.
+pub fn progress_bar(secs: u64, target_secs: u64, width: usize) -> String {
+    let done = if target_secs == 0 { 1.0 } else { (secs as f64 / target_secs as f64).min(1.0) };
+    let filled = (done * width as f64).round() as usize;
+    format!("[{}{}] {:>3}%", "#".repeat(filled), "-".repeat(width - filled), (done * 100.0).floor() as u64)
+}
+
+/// Parses a short duration such as `25m`, `1h30m` or `90s` into seconds.
+pub fn parse_span(value: &str) -> Result<u64, String> {
+    let invalid = || format!("expected a duration such as 25m, 1h30m or 90s, got '{}'", value);
+    let mut secs: u64 = 0;
+    let mut digits = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let amount: u64 = digits.parse().map_err(|_| invalid())?;
+        secs = amount.checked_mul(unit).and_then(|s| s.checked_add(secs)).ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() || value.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok(secs)
+}
+
+/// Reads an optional duration from the config file, written as for
+/// [`parse_span`], e.g. `max = "8h"`.
+pub fn deserialize_span<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let value: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    value.map(|value| parse_span(&value).map_err(serde::de::Error::custom)).transpose()
+}
+
+/// Reads a list of durations from the config file, e.g. `at = ["4h", "8h"]`.
+pub fn deserialize_spans<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u64>, D::Error> {
+    let values: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+    values.iter().map(|value| parse_span(value).map_err(serde::de::Error::custom)).collect()
+}
+
+/// Formats seconds as [`parse_span`] reads them, e.g. `1h30m`.
+pub fn format_span(secs: u64) -> String {
+    let parts = [(secs / 3600, 'h'), (secs % 3600 / 60, 'm'), (secs % 60, 's')];
+    let span: String = parts.iter().filter(|(amount, _)| *amount > 0).map(|(amount, unit)| format!("{}{}", amount, unit)).collect();
+    if span.is_empty() { "0s".to_string() } else { span }
+}
+
+/// Writes an optional duration as [`deserialize_span`] reads it.
+pub fn serialize_span<S: serde::Serializer>(secs: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&secs.map(format_span), serializer)
+}
+
+/// Writes a list of durations as [`deserialize_spans`] reads it.
+pub fn serialize_spans<S: serde::Serializer>(secs: &[u64], serializer: S) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&secs.iter().copied().map(format_span).collect::<Vec<_>>(), serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_to_the_increment() {
+        assert_eq!(round_secs(7 * 60 + 29, 15, RoundMode::Nearest), 0);
+        assert_eq!(round_secs(7 * 60 + 30, 15, RoundMode::Nearest), 15 * 60);
+        assert_eq!(round_secs(61, 15, RoundMode::Up), 15 * 60);
+        assert_eq!(round_secs(15 * 60, 15, RoundMode::Up), 15 * 60);
+        assert_eq!(round_secs(29 * 60, 15, RoundMode::Down), 15 * 60);
+    }
+
+    #[test]
+    fn zero_increment_leaves_duration_untouched() {
+        assert_eq!(round_secs(1234, 0, RoundMode::Up), 1234);
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_hms(3909, false), "1h 05m");
+        assert_eq!(format_hms(3909, true), "1h 05m 09s");
+        assert_eq!(format_hms(0, true), "0h 00m 00s");
+        assert_eq!(format_decimal_hours(4500), "1.25");
+    }
+
+    #[test]
+    fn formats_durations_as_configured() {
+        assert_eq!(DurationFormat::Hms.format(3909, false), "1h 05m");
+        assert_eq!(DurationFormat::Clock.format(3909, false), "01:05");
+        assert_eq!(DurationFormat::Clock.format(3909, true), "01:05:09");
+        assert_eq!(DurationFormat::Clock.format(100 * 3600, false), "100:00");
+        assert_eq!(DurationFormat::Decimal.format(4500, true), "1.25h");
+    }
+
+    #[test]
+    fn logs_with_the_configured_precision() {
+        assert_eq!(Precision::default().apply(59), 59);
+        let minutes: Precision = "minutes".parse().unwrap();
+        assert_eq!(minutes.apply(89), 60);
+        assert_eq!(minutes.apply(90), 120);
+        assert_eq!(minutes.apply(20), 60);
+        assert_eq!(minutes.apply(0), 0);
+        let quarters: Precision = "15m".parse().unwrap();
+        assert_eq!(quarters.apply(22 * 60), 15 * 60);
+        assert_eq!(quarters.apply(23 * 60), 30 * 60);
+        for bad in ["hours", "90s", "0m"] {
+            assert!(bad.parse::<Precision>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn draws_progress_bars() {
+        assert_eq!(progress_bar(0, 3600, 10), "[----------]   0%");
+        assert_eq!(progress_bar(1800, 3600, 10), "[#####-----]  50%");
+        assert_eq!(progress_bar(3599, 3600, 4), "[####]  99%");
+        assert_eq!(progress_bar(7200, 3600, 4), "[####] 100%");
+        assert_eq!(progress_bar(5, 0, 4), "[####] 100%");
+    }
+
+    #[test]
+    fn parses_short_durations() {
+        assert_eq!(parse_span("25m"), Ok(25 * 60));
+        assert_eq!(parse_span("1h30m"), Ok(90 * 60));
+        assert_eq!(parse_span("90s"), Ok(90));
+        for bad in ["", "25", "m", "1x", "1.5h", "99999999999999999999h"] {
+            assert!(parse_span(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Reshaping logged entries after the fact: `split` carves one entry in
+//! two, e.g. when a session turns out to have covered two codes, and
+//! `merge` collapses fragments of the same task into one.
+
+use crate::duration;
+use crate::log::{self, Entry};
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Where to split an entry: after a duration from its start, such as
+/// `45m`, or at a time of day, such as `10:45`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitPoint {
+    After(u64),
+    At(NaiveTime),
+}
+
+impl FromStr for SplitPoint {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        duration::parse_span(value)
+            .map(SplitPoint::After)
+            .or_else(|_| log::parse_clock(value).map(SplitPoint::At))
+            .map_err(|_| format!("expected a duration such as 45m or a time such as 10:45, got '{}'", value))
+    }
+}
+
+impl SplitPoint {
+    /// How long into `entry` the point is. It has to fall inside it. A time
+    /// of day is in the local time zone, wherever the entry was logged.
+    pub fn secs_into(self, entry: &Entry) -> Result<u64, String> {
+        let local = entry.to_local();
+        let (start, end) = (local.start(), local.end());
+        let secs = match self {
+            SplitPoint::After(secs) => secs,
+            // On the day the entry started, or the next if it ran past midnight
+            SplitPoint::At(time) => {
+                let at = [start.date(), end.date()].map(|day| day.and_time(time)).into_iter().find(|at| *at > start && *at < end);
+                at.map_or(0, |at| (at - start).num_seconds() as u64)
+            },
+        };
+        if secs == 0 || secs >= entry.duration_secs {
+            return Err(format!(
+                "the split has to fall inside the entry, which ran from {} to {}",
+                start.format("%H:%M:%S"),
+                end.format("%H:%M:%S")
+            ));
+        }
+        Ok(secs)
+    }
+}
+
+/// Splits `entry` `secs` into it. The first part keeps the entry's ID; the
+/// second part gets a new one, and `task` and `code` if given. Both stay in
+/// the zone the entry was logged in.
+pub fn split(entry: &Entry, secs: u64, task: Option<String>, code: Option<String>) -> (Entry, Entry) {
+    let boundary: NaiveDateTime = entry.start() + TimeDelta::seconds(secs as i64);
+    let first = Entry {
+        date: boundary.date(),
+        time: boundary.time(),
+        offset: entry.offset,
+        duration_secs: secs,
+        ..entry.clone()
+    };
+    let second = Entry {
+        task: task.unwrap_or_else(|| entry.task.clone()),
+        code: code.unwrap_or_else(|| entry.code.clone()),
+        duration_secs: entry.duration_secs - secs,
+        id: log::new_id(),
+        ..entry.clone()
+    };
+    (first, second)
+}
+
+/// Merges `entries` into one, with the task, code and ID of the earliest,
+/// ending when the last one ended and lasting as long as all of them.
+/// They must share their task and code.
+pub fn merge(entries: &[&Entry]) -> Result<Entry, String> {
+    let (Some(first), Some(last)) = (entries.iter().min_by_key(|e| e.start()), entries.iter().max_by_key(|e| e.end())) else {
+        return Err("nothing to merge".to_string());
+    };
+    if let Some(other) = entries.iter().find(|e| e.task != first.task || e.code != first.code) {
+        return Err(format!(
+            "'{}' ({}) and '{}' ({}) are different tasks; make them the same with `review` first",
+            first.task, first.code, other.task, other.code
+        ));
+    }
+    Ok(Entry {
+        date: last.date,
+        time: last.time,
+        offset: last.offset,
+        duration_secs: entries.iter().map(|e| e.duration_secs).sum(),
+        ..(*first).clone()
+    })
+}
+
+/// The entries worth merging: those of the same task and code on the same
+/// day, in groups of at least two, earliest first. Invoiced entries are
+/// left alone.
+pub fn fragments(entries: &[Entry]) -> Vec<Vec<&Entry>> {
+    let mut groups: BTreeMap<_, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| !e.is_locked()) {
+        groups.entry((entry.start().date(), &entry.code, &entry.task)).or_default().push(entry);
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_by_key(|e| e.start());
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry {
+        Entry {
+            date: "2024-05-06".parse().unwrap(),
+            time: "11:00:00".parse().unwrap(),
+            offset: None,
+            code: "ACME".to_string(),
+            task: "Debugging".to_string(),
+            duration_secs: 2 * 3600,
+            invoice: None,
+            git: None,
+            id: "a1".to_string(),
+        }
+    }
+
+    #[test]
+    fn splits_after_a_duration_or_at_a_time() {
+        let entry = entry();
+        assert_eq!("45m".parse::<SplitPoint>().unwrap().secs_into(&entry), Ok(45 * 60));
+        assert_eq!("10:30".parse::<SplitPoint>().unwrap().secs_into(&entry), Ok(90 * 60));
+        assert!("2h".parse::<SplitPoint>().unwrap().secs_into(&entry).is_err());
+        assert!("12:00".parse::<SplitPoint>().unwrap().secs_into(&entry).is_err());
+        assert!("soon".parse::<SplitPoint>().is_err());
+
+        let (first, second) = split(&entry, 45 * 60, Some("Review".to_string()), None);
+        assert_eq!((first.id.as_str(), first.task.as_str(), first.duration_secs), ("a1", "Debugging", 45 * 60));
+        assert_eq!(first.end(), "2024-05-06T09:45:00".parse().unwrap());
+        assert_eq!((second.task.as_str(), second.code.as_str(), second.duration_secs), ("Review", "ACME", 75 * 60));
+        assert_eq!((second.start(), second.end()), (first.end(), entry.end()));
+        assert_ne!(second.id, entry.id);
+    }
+
+    #[test]
+    fn splits_entries_logged_in_another_zone() {
+        let abroad = Entry { offset: Some("+05:30".parse().unwrap()), ..entry() };
+        let local_time = |secs: i64| (abroad.start_utc() + TimeDelta::seconds(secs)).with_timezone(&chrono::Local).time();
+        assert_eq!(SplitPoint::At(local_time(45 * 60)).secs_into(&abroad), Ok(45 * 60));
+        assert!(SplitPoint::At(local_time(3 * 3600)).secs_into(&abroad).is_err());
+
+        let (first, second) = split(&abroad, 45 * 60, None, None);
+        assert_eq!((first.offset, second.offset), (abroad.offset, abroad.offset));
+        assert_eq!(first.end(), "2024-05-06T09:45:00".parse().unwrap());
+        assert_eq!(first.start_utc(), abroad.start_utc());
+        assert_eq!(second.end_utc(), abroad.end_utc());
+    }
+
+    #[test]
+    fn merges_fragments_of_a_task() {
+        let at = |time: &str, secs: u64, task: &str| Entry { time: time.parse().unwrap(), duration_secs: secs, task: task.to_string(), id: time.to_string(), ..entry() };
+        let entries = vec![at("10:00:00", 600, "Debugging"), at("09:00:00", 300, "Debugging"), at("11:00:00", 60, "Email")];
+
+        let groups = fragments(&entries);
+        assert_eq!(groups.len(), 1);
+        let merged = merge(&groups[0]).unwrap();
+        assert_eq!((merged.id.as_str(), merged.time, merged.duration_secs), ("09:00:00", "10:00:00".parse().unwrap(), 900));
+        assert!(merge(&[&entries[0], &entries[2]]).is_err());
+
+        let mut invoiced = entries.clone();
+        invoiced[0].invoice = Some("2024-001".to_string());
+        assert!(fragments(&invoiced).is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+//! Errors that end a command, and the exit code each ends it with:
+//!
+//! | Code | Meaning                                                        |
+//! |------|----------------------------------------------------------------|
+//! | 0    | Success                                                        |
+//! | 1    | Any other failure, such as a push that was refused             |
+//! | 2    | Invalid input, e.g. a missing task when nothing can be asked   |
+//! | 3    | A file couldn't be read or written, e.g. in a read-only home   |
+//! | 4    | A session is already running and was kept                      |
+
+use crate::config;
+use std::io;
+
+/// Why a command failed.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The command line or the input given was invalid.
+    #[error("{0}")]
+    Invalid(String),
+    /// Reading or writing state failed; `context` says what was being done.
+    #[error("{context}: {source}{}", hint(.source))]
+    Io { context: String, source: io::Error },
+    /// A session is already running, and was kept rather than replaced.
+    #[error("{0}")]
+    Running(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Error::Io { context: context.into(), source }
+    }
+
+    /// Prints the error and ends the process with its exit code.
+    pub fn exit(self) -> ! {
+        eprintln!("{}", self);
+        std::process::exit(self.exit_code())
+    }
+
+    /// The exit code of the process when the command fails with this.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) => 1,
+            Error::Invalid(_) => 2,
+            Error::Io { .. } => 3,
+            Error::Running(_) => 4,
+        }
+    }
+}
+
+/// Where to look when a file can't be written.
+fn hint(source: &io::Error) -> String {
+    match source.kind() {
+        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => format!(
+            ". Check that '{}' can be written, or set XDG_DATA_HOME to keep the state elsewhere",
+            config::data_dir().display()
+        ),
+        _ => String::new(),
+    }
+}
+
+/// Says what was being done when an I/O operation failed.
+pub trait Context<T> {
+    fn context(self, context: &str) -> Result<T, Error>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context(self, context: &str) -> Result<T, Error> {
+        self.map_err(|source| Error::io(context, source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_say_what_failed() {
+        let failed: io::Result<()> = Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        let error = failed.context("Failed to read the log").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to read the log: no such file");
+        assert_eq!(error.exit_code(), 3);
+
+        let denied = Error::io("Failed to save the session", io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(denied.to_string().contains("can be written"), "{}", denied);
+        assert_eq!(Error::Running("'Review' is still running.".to_string()).exit_code(), 4);
+    }
+}
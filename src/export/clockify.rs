@@ -0,0 +1,38 @@
+//! Export in Clockify's bulk time-entry import format.
+//!
+//! The resulting CSV can be uploaded in Clockify under
+//! *Settings > Import > Time entries*. Codes are mapped to Clockify
+//! projects via the `[clockify.projects]` table in the config file; codes
+//! without a mapping are exported with the code as the project name.
+
+use crate::config::ClockifyConfig;
+use crate::log::{csv_record, Entry};
+
+const HEADERS: &str = "Project,Client,Description,Task,Email,Tags,Billable,Start Date,Start Time,Duration (h)";
+
+/// Renders the entries as a Clockify import CSV.
+pub fn export<'a>(entries: impl IntoIterator<Item = &'a Entry>, config: &ClockifyConfig, email: &str) -> String {
+    let mut out = format!("{}\n", HEADERS);
+    for entry in entries {
+        let mapping = config.projects.get(&entry.code);
+        let project = mapping.map(|m| m.project.as_str()).unwrap_or(&entry.code);
+        let client = mapping.and_then(|m| m.client.as_deref()).unwrap_or("");
+        let task = mapping.and_then(|m| m.task.as_deref()).unwrap_or("");
+        let start = entry.start();
+        let secs = entry.duration_secs;
+
+        out.push_str(&csv_record([
+            project,
+            client,
+            &entry.task,
+            task,
+            email,
+            &entry.code,
+            "Yes",
+            &start.format("%Y-%m-%d").to_string(),
+            &start.format("%H:%M:%S").to_string(),
+            &format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60),
+        ]));
+    }
+    out
+}
@@ -0,0 +1,76 @@
+//! Export as an iCalendar (`.ics`) file with one event per session.
+//!
+//! Importing or subscribing to the file in a calendar app overlays tracked
+//! time on the calendar, which makes untracked gaps easy to spot.
+
+use crate::duration;
+use crate::log::Entry;
+use chrono::Utc;
+
+const TIMESTAMP: &str = "%Y%m%dT%H%M%SZ";
+
+/// Renders the entries as an iCalendar document.
+pub fn export<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> String {
+    let stamp = Utc::now().format(TIMESTAMP).to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//SimpleTimer//SimpleTimer//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Tracked time".to_string(),
+    ];
+    let label = crate::config::labels().code;
+    for entry in entries {
+        let start = entry.start_utc().format(TIMESTAMP).to_string();
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            // Stable across exports so calendars update events instead of duplicating them
+            format!("UID:{}-{}-{}@simpletimer", start, entry.duration_secs, uid_part(&entry.code)),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", start),
+            format!("DTEND:{}", entry.end_utc().format(TIMESTAMP)),
+            format!("SUMMARY:{}", escape(&entry.task)),
+            format!(
+                "DESCRIPTION:{}",
+                escape(&format!("{}: {}\nDuration: {}", label, entry.code, duration::format_hms(entry.duration_secs, true)))
+            ),
+            format!("CATEGORIES:{}", escape(&entry.code)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// Escapes text values as required by RFC 5545. Line breaks become `\n`
+/// and other control characters, which are not allowed, are dropped.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\r', '\n'], "\\n")
+        .replace(|c: char| c.is_control() && c != '\t', "")
+}
+
+/// Keeps only characters that are safe in a UID.
+fn uid_part(text: &str) -> String {
+    text.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+/// Folds a content line to at most 75 octets per line and terminates it with CRLF.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
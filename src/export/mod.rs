@@ -0,0 +1,24 @@
+//! Exporters converting the time log into other tools' formats.
+
+pub mod clockify;
+pub mod ics;
+pub mod org;
+pub mod site;
+pub mod timeclock;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Writes exported content to `out`, or to stdout when no path is given.
+pub fn write_output(out: Option<&Path>, content: &str) -> io::Result<()> {
+    match out {
+        Some(path) => {
+            let mut file = File::create(path)?;
+            file.write_all(content.as_bytes())?;
+            eprintln!("Exported to '{}'.", path.display());
+            Ok(())
+        },
+        None => io::stdout().write_all(content.as_bytes()),
+    }
+}
@@ -0,0 +1,65 @@
+//! Export as Emacs org-mode `CLOCK:` entries.
+//!
+//! Entries are grouped under a heading per code and a subheading per task,
+//! each with a `:LOGBOOK:` drawer holding its clock lines, most recent first
+//! as org-mode itself records them:
+//!
+//! ```text
+//! * FEAT-42
+//! ** Work on feature X
+//!    :LOGBOOK:
+//!    CLOCK: [2024-05-01 Wed 08:52]--[2024-05-01 Wed 10:00] =>  1:08
+//!    :END:
+//! ```
+
+use crate::log::Entry;
+use chrono::{NaiveDateTime, Timelike};
+use std::collections::BTreeMap;
+
+const TIMESTAMP: &str = "[%Y-%m-%d %a %H:%M]";
+
+/// Renders the entries as an org-mode outline with clock drawers.
+pub fn export<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> String {
+    // code -> task -> entries
+    let mut outline: BTreeMap<&str, BTreeMap<&str, Vec<&Entry>>> = BTreeMap::new();
+    for entry in entries {
+        outline.entry(&entry.code).or_default().entry(&entry.task).or_default().push(entry);
+    }
+
+    let mut out = String::new();
+    for (code, tasks) in outline {
+        out.push_str(&format!("* {}\n", heading(code)));
+        for (task, mut entries) in tasks {
+            entries.sort_by_key(|e| std::cmp::Reverse(e.start()));
+            out.push_str(&format!("** {}\n   :LOGBOOK:\n", heading(task)));
+            for entry in entries {
+                out.push_str(&format!("   {}\n", clock_line(entry)));
+            }
+            out.push_str("   :END:\n");
+        }
+    }
+    out
+}
+
+/// Org clocks have minute resolution; seconds are dropped like org does.
+fn to_minute(time: NaiveDateTime) -> NaiveDateTime {
+    time.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(time)
+}
+
+fn clock_line(entry: &Entry) -> String {
+    let start = to_minute(entry.start());
+    let end = to_minute(entry.end());
+    let minutes = (end - start).num_minutes().max(0);
+    format!(
+        "CLOCK: {}--{} => {:>2}:{:02}",
+        start.format(TIMESTAMP),
+        end.format(TIMESTAMP),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Keeps headings on one line so they don't break the outline.
+fn heading(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,134 @@
+//! Static HTML report site for sharing a code's time with a client.
+//!
+//! Produces a self-contained `index.html` (no scripts, no external assets)
+//! with a summary, a bar chart of hours per week and per task, and a table
+//! of entries for every week. The folder can be served by any static web
+//! server, e.g. behind basic auth.
+
+use crate::duration;
+use crate::log::Entry;
+use chrono::{Local, NaiveDate, Weekday};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 960px; margin: 2rem auto; padding: 0 1rem; color: #222; }
+h1 { margin-bottom: 0.2rem; }
+.muted { color: #777; }
+.summary { display: flex; gap: 2rem; margin: 1.5rem 0; }
+.summary div { background: #f4f6f8; padding: 0.8rem 1.2rem; border-radius: 6px; }
+.summary strong { display: block; font-size: 1.6rem; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 1.5rem; }
+th, td { text-align: left; padding: 0.35rem 0.5rem; border-bottom: 1px solid #e3e6e9; }
+td.num, th.num { text-align: right; font-variant-numeric: tabular-nums; }
+.chart .row { display: flex; align-items: center; margin: 0.2rem 0; }
+.chart .label { width: 12rem; flex-shrink: 0; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+.chart .bar { background: #3b82f6; height: 1.1rem; border-radius: 3px; margin-right: 0.5rem; }
+";
+
+/// Writes the site for `code` into `out_dir`, creating the folder if needed.
+pub fn export<'a>(entries: impl IntoIterator<Item = &'a Entry>, code: &str, out_dir: &Path) -> io::Result<()> {
+    let mut entries: Vec<&Entry> = entries.into_iter().filter(|e| e.code == code).collect();
+    entries.sort_by_key(|e| e.start());
+
+    fs::create_dir_all(out_dir)?;
+    fs::write(out_dir.join("index.html"), render(&entries, code))
+}
+
+/// Monday of the week a date falls in.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date.week(Weekday::Mon).first_day()
+}
+
+/// Escapes text for inclusion in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a horizontal bar chart of `(label, seconds)` rows.
+fn chart(rows: &[(String, u64)]) -> String {
+    let max = rows.iter().map(|(_, secs)| *secs).max().unwrap_or(0).max(1);
+    let mut html = String::from("<div class=\"chart\">\n");
+    for (label, secs) in rows {
+        html.push_str(&format!(
+            "<div class=\"row\"><span class=\"label\">{}</span><span class=\"bar\" style=\"width: {:.1}%\"></span>{}h</div>\n",
+            escape(label),
+            *secs as f64 / max as f64 * 60.0,
+            duration::format_decimal_hours(*secs)
+        ));
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+fn render(entries: &[&Entry], code: &str) -> String {
+    let total: u64 = entries.iter().map(|e| e.duration_secs).sum();
+
+    let mut weeks: BTreeMap<NaiveDate, Vec<&Entry>> = BTreeMap::new();
+    let mut tasks: BTreeMap<&str, u64> = BTreeMap::new();
+    for entry in entries {
+        weeks.entry(week_start(entry.date)).or_default().push(entry);
+        *tasks.entry(entry.task.as_str()).or_default() += entry.duration_secs;
+    }
+
+    let period = match (entries.first(), entries.last()) {
+        (Some(first), Some(last)) => format!("{} to {}", first.date, last.date),
+        _ => "No entries".to_string(),
+    };
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{code} time report</title>\n<style>{style}</style>\n</head>\n<body>\n\
+         <h1>{code}</h1>\n<p class=\"muted\">{period} &middot; generated {generated}</p>\n\
+         <div class=\"summary\"><div><strong>{hours}</strong>hours</div><div><strong>{sessions}</strong>sessions</div><div><strong>{weeks}</strong>weeks</div></div>\n",
+        code = escape(code),
+        style = STYLE,
+        period = period,
+        generated = Local::now().format("%Y-%m-%d %H:%M"),
+        hours = duration::format_decimal_hours(total),
+        sessions = entries.len(),
+        weeks = weeks.len(),
+    );
+
+    let week_rows: Vec<(String, u64)> = weeks
+        .iter()
+        .map(|(start, entries)| (format!("Week of {}", start), entries.iter().map(|e| e.duration_secs).sum()))
+        .collect();
+    html.push_str("<h2>Hours per week</h2>\n");
+    html.push_str(&chart(&week_rows));
+
+    let mut task_rows: Vec<(String, u64)> = tasks.iter().map(|(task, secs)| (task.to_string(), *secs)).collect();
+    task_rows.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+    html.push_str("<h2>Hours per task</h2>\n");
+    html.push_str(&chart(&task_rows));
+
+    // Most recent week first
+    for (start, week_entries) in weeks.iter().rev() {
+        let week_total: u64 = week_entries.iter().map(|e| e.duration_secs).sum();
+        html.push_str(&format!(
+            "<h2>Week of {}</h2>\n<table>\n<tr><th>Date</th><th>Task</th><th class=\"num\">Duration</th><th class=\"num\">Hours</th></tr>\n",
+            start
+        ));
+        for entry in week_entries {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>\n",
+                entry.date,
+                escape(&entry.task),
+                duration::format_hms(entry.duration_secs, false),
+                duration::format_decimal_hours(entry.duration_secs)
+            ));
+        }
+        html.push_str(&format!(
+            "<tr><th>Total</th><th></th><th class=\"num\">{}</th><th class=\"num\">{}</th></tr>\n</table>\n",
+            duration::format_hms(week_total, false),
+            duration::format_decimal_hours(week_total)
+        ));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
@@ -0,0 +1,39 @@
+//! Export as timeclock entries for hledger/ledger.
+//!
+//! Each session becomes a check-in/check-out pair, with the code as the
+//! account and the task as the description:
+//!
+//! ```text
+//! i 2024-05-01 08:52:48 FEAT-42  Work on feature X
+//! o 2024-05-01 10:00:00
+//! ```
+
+use crate::log::Entry;
+
+const TIMESTAMP: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The code as an account name: whitespace (including line breaks) is
+/// collapsed to single spaces, since two spaces would end the name.
+pub fn account(code: &str) -> String {
+    let account = code.split_whitespace().collect::<Vec<_>>().join(" ");
+    if account.is_empty() { "(no code)".to_string() } else { account }
+}
+
+/// Renders the entries as a timeclock file, in chronological order.
+pub fn export<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> String {
+    let mut entries: Vec<&Entry> = entries.into_iter().collect();
+    entries.sort_by_key(|e| e.start());
+
+    let mut out = String::new();
+    for entry in entries {
+        // Two spaces end the account name, which may itself contain single spaces
+        out.push_str(&format!(
+            "i {} {}  {}\n",
+            entry.start().format(TIMESTAMP),
+            account(&entry.code),
+            entry.task.replace(['\r', '\n'], " ")
+        ));
+        out.push_str(&format!("o {}\n", entry.end().format(TIMESTAMP)));
+    }
+    out
+}
@@ -0,0 +1,44 @@
+//! Default task and code from the git repository the timer is started in.
+//!
+//! Work often maps 1:1 to branches, so inside a repository the branch is
+//! used as the task name and the repository (or the code it is mapped to in
+//! `[git]`) as the code.
+
+use std::path::Path;
+use std::process::Command;
+
+/// The repository and branch of the working directory.
+#[derive(Debug, Clone)]
+pub struct GitInfo {
+    /// Name of the repository's top-level folder.
+    pub repo: String,
+    /// Current branch, or the abbreviated commit on a detached HEAD.
+    pub branch: String,
+}
+
+impl GitInfo {
+    /// The value recorded in the log's `Git` column, e.g. `simpletimer@main`.
+    pub fn label(&self) -> String {
+        format!("{}@{}", self.repo, self.branch)
+    }
+}
+
+/// Runs a git command in the working directory and returns its trimmed output.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// The repository and branch of the working directory, or `None` outside a
+/// repository or when git is not installed.
+pub fn detect() -> Option<GitInfo> {
+    let top_level = git(&["rev-parse", "--show-toplevel"])?;
+    let repo = Path::new(&top_level).file_name()?.to_string_lossy().into_owned();
+    let branch = git(&["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .or_else(|| git(&["rev-parse", "--short", "HEAD"]))?;
+    Some(GitInfo { repo, branch })
+}
@@ -0,0 +1,38 @@
+//! GPIO push buttons for a desk "punch clock" on a Raspberry Pi.
+//!
+//! Each button configured under `[[hardware.buttons]]` is wired between its
+//! GPIO pin and ground; the pin's internal pull-up keeps it high until the
+//! button is pressed.
+
+use crate::config::{ButtonConfig, HardwareConfig};
+use rppal::gpio::{Gpio, InputPin};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the pins are sampled.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Watches the configured buttons forever, calling `on_press` for every press.
+pub fn listen(config: &HardwareConfig, mut on_press: impl FnMut(&ButtonConfig)) -> rppal::gpio::Result<()> {
+    let gpio = Gpio::new()?;
+    let mut pins: Vec<(&ButtonConfig, InputPin, bool, Option<Instant>)> = Vec::new();
+    for button in &config.buttons {
+        let pin = gpio.get(button.pin)?.into_input_pullup();
+        let pressed = pin.is_low();
+        pins.push((button, pin, pressed, None));
+    }
+
+    let debounce = Duration::from_millis(config.debounce_ms);
+    loop {
+        for (button, pin, was_pressed, last_press) in pins.iter_mut() {
+            let pressed = pin.is_low();
+            // React to the moment the button goes down, ignoring contact bounce
+            if pressed && !*was_pressed && last_press.is_none_or(|t| t.elapsed() >= debounce) {
+                *last_press = Some(Instant::now());
+                on_press(button);
+            }
+            *was_pressed = pressed;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
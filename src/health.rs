@@ -0,0 +1,146 @@
+//! The `health` (or `doctor`) command: a quick check of the log, the
+//! running session, the activity listener and the push backlog, and with
+//! `--self-test` of how entries are written and read back.
+
+use crate::config;
+use crate::duration;
+use crate::http;
+use crate::log;
+use crate::recover;
+use crate::serialization;
+use crate::session;
+use crate::storage;
+use chrono::{DateTime, Local};
+use std::time::Duration;
+
+/// Prints one line of the report.
+fn line(component: &str, healthy: bool, detail: impl std::fmt::Display) {
+    println!("{:<10} {:<5} {}", component, if healthy { "ok" } else { "FAIL" }, detail);
+}
+
+/// Prints the health report, returning whether everything is healthy.
+pub fn report() -> bool {
+    let config = config::load();
+    let mut healthy = true;
+    line("config", true, config::config_path().display());
+
+    let entries = match storage::open().map(|storage| (storage.location(), storage.query(&log::DateRange::default()))) {
+        Ok((location, Ok(entries))) => {
+            let written = std::fs::metadata(&location)
+                .and_then(|m| m.modified())
+                .map(|t| format!(", last written {}", DateTime::<Local>::from(t).format("%Y-%m-%d %H:%M:%S")))
+                .unwrap_or_default();
+            line("log", true, format!("{} ({} entries{})", location, entries.len(), written));
+            entries
+        },
+        Ok((location, Err(e))) => {
+            healthy = false;
+            line("log", false, format!("{}: {}", location, e));
+            Vec::new()
+        },
+        Err(e) => {
+            healthy = false;
+            line("log", false, e);
+            Vec::new()
+        },
+    };
+
+    match session::all() {
+        Ok(sessions) if sessions.is_empty() => line("session", true, "none running"),
+        Ok(sessions) => {
+            for running in sessions {
+                let name = running.name.as_ref().map(|name| format!("{}: ", name)).unwrap_or_default();
+                let elapsed = duration::format_hms(running.elapsed_secs(), true);
+                line("session", true, format!("{}'{}' ({}) running for {}", name, running.task, running.code, elapsed));
+            }
+        },
+        Err(e) => {
+            healthy = false;
+            line("session", false, e);
+        },
+    }
+
+    // Reading the sessions above quarantined any corrupt state
+    if let Ok(quarantined) = recover::list() {
+        let unrecovered = quarantined.iter().filter(|q| !q.restored && q.session().is_some()).count();
+        if unrecovered > 0 {
+            healthy = false;
+            line("quarantine", false, format!("{} session(s) to recover: run `timer recover`", unrecovered));
+        } else if !quarantined.is_empty() {
+            line("quarantine", true, format!("{} backup(s) in {}", quarantined.len(), recover::quarantine_dir().display()));
+        }
+    }
+
+    // The activity listener is optional, so not running is not a failure
+    let addr = ([127, 0, 0, 1], config.activity.port).into();
+    match http::get(addr, "/healthz", Duration::from_secs(1)) {
+        Ok((200, body)) => {
+            let status: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+            let uptime = status["uptime_secs"].as_u64().unwrap_or(0);
+            let last_logged = status["last_logged"].as_str().unwrap_or("never");
+            line("activity", true, format!("up {} on port {}, last logged {}", duration::format_hms(uptime, true), addr.port(), last_logged));
+        },
+        Ok((status, _)) => {
+            healthy = false;
+            line("activity", false, format!("port {} answered HTTP {}", addr.port(), status));
+        },
+        Err(_) => line("activity", true, format!("not running (port {})", addr.port())),
+    }
+
+    healthy &= push_backlog(&entries);
+    healthy
+}
+
+/// Reports how many entries each push target that has been used still has
+/// to receive.
+#[cfg(feature = "integrations")]
+fn push_backlog(entries: &[log::Entry]) -> bool {
+    let Ok(ledgers) = std::fs::read_dir(config::data_dir().join("sync")) else {
+        return true;
+    };
+    let mut targets: Vec<String> = ledgers
+        .filter_map(|ledger| ledger.ok()?.path().file_stem()?.to_str().map(str::to_string))
+        .collect();
+    targets.sort();
+
+    let mut healthy = true;
+    for target in targets {
+        match crate::push::read_synced(&target) {
+            Ok(synced) => {
+                let pending = entries.iter().filter(|e| !crate::push::is_synced(&synced, e)).count();
+                line(&target, true, format!("{} pushed, {} not pushed yet", synced.len(), pending));
+            },
+            Err(e) => {
+                healthy = false;
+                line(&target, false, e);
+            },
+        }
+    }
+    healthy
+}
+
+#[cfg(not(feature = "integrations"))]
+fn push_backlog(_entries: &[log::Entry]) -> bool {
+    true
+}
+
+/// Runs the serialization round-trip checks on sample and random entries,
+/// returning whether they all pass.
+pub fn self_test() -> bool {
+    let seed = Local::now().timestamp_micros() as u64;
+    let mut entries = serialization::samples();
+    entries.extend(serialization::random_entries(seed, 500));
+
+    println!();
+    let mut passed = true;
+    for (name, result) in serialization::check_all(&entries) {
+        match result {
+            Ok(()) => line(name, true, format!("{} entries round-trip", entries.len())),
+            Err(e) => {
+                passed = false;
+                line(name, false, format!("{} (seed {})", e, seed));
+            },
+        }
+    }
+    passed
+}
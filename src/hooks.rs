@@ -0,0 +1,158 @@
+//! Running commands and telling other tools when a session starts or stops
+//! (`[hooks]`).
+//!
+//! `on_start` and `on_stop` are shell commands, run with the session in
+//! environment variables: `ST_EVENT`, `ST_SESSION`, `ST_TASK`, `ST_CODE`,
+//! `ST_STARTED` and `ST_GIT`, and for a stop `ST_ENDED`,
+//! `ST_DURATION_SECS` and `ST_ENTRY_ID`. Variables without a value, such as
+//! `ST_SESSION` for the default session, are empty.
+//!
+//! Each URL in `webhooks` receives a JSON object by POST, e.g. to set an
+//! office light red in Home Assistant while tracking:
+//!
+//! ```json
+//! {"event": "start", "session": null, "task": "Review", "code": "ACME",
+//!  "started": "2024-05-06T09:30:00+02:00", "git": null}
+//! ```
+//!
+//! A stop also has `ended`, `duration_secs` and `id`, the logged entry's.
+//! Failing hooks are reported on stderr and never stop the timer.
+
+use crate::config::HooksConfig;
+use crate::log::Entry;
+use crate::notify;
+use crate::secret;
+use crate::session::Session;
+use chrono::{Local, SecondsFormat};
+use serde_json::{Value, json};
+use std::io;
+use std::process::{Command, Stdio};
+
+/// A session starting or stopping.
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    Start(&'a Session),
+    /// The session and the entry it was logged as.
+    Stop(&'a Session, &'a Entry),
+}
+
+/// The JSON webhooks receive for `event`.
+pub fn payload(event: Event) -> Value {
+    let (name, session) = match event {
+        Event::Start(session) => ("start", session),
+        Event::Stop(session, _) => ("stop", session),
+    };
+    let mut payload = json!({
+        "event": name,
+        "session": session.name,
+        "task": session.task,
+        "code": session.code,
+        "started": session.started.to_rfc3339_opts(SecondsFormat::Secs, false),
+        "git": session.git,
+    });
+    if let Event::Stop(_, entry) = event {
+        payload["ended"] = entry.end_utc().with_timezone(&Local).to_rfc3339_opts(SecondsFormat::Secs, false).into();
+        payload["duration_secs"] = entry.duration_secs.into();
+        payload["id"] = entry.id.clone().into();
+    }
+    payload
+}
+
+/// The environment variables hook commands get for `event`.
+pub fn environment(event: Event) -> Vec<(&'static str, String)> {
+    let payload = payload(event);
+    let text = |key: &str| match &payload[key] {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    let mut variables = vec![
+        ("ST_EVENT", text("event")),
+        ("ST_SESSION", text("session")),
+        ("ST_TASK", text("task")),
+        ("ST_CODE", text("code")),
+        ("ST_STARTED", text("started")),
+        ("ST_GIT", text("git")),
+    ];
+    if let Event::Stop(..) = event {
+        variables.extend([("ST_ENDED", text("ended")), ("ST_DURATION_SECS", text("duration_secs")), ("ST_ENTRY_ID", text("id"))]);
+    }
+    variables
+}
+
+/// Runs `command` with the shell, writing its output to stderr so it can't
+/// mix with the output of commands such as `status --format json`.
+fn run(command: &str, variables: &[(&str, String)]) -> Result<(), String> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let status = Command::new(shell)
+        .args([flag, command])
+        .envs(variables.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(io::stderr())
+        .status()
+        .map_err(|e| e.to_string())?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("exited with {}", status)),
+    }
+}
+
+/// Runs the hooks for `event`.
+pub fn fire(config: &HooksConfig, event: Event) {
+    let command = match event {
+        Event::Start(_) => &config.on_start,
+        Event::Stop(..) => &config.on_stop,
+    };
+    if let Some(command) = command.as_deref().filter(|c| !c.trim().is_empty())
+        && let Err(e) = run(command, &environment(event))
+    {
+        eprintln!("Hook '{}' failed: {}", command, e);
+    }
+    if config.webhooks.is_empty() {
+        return;
+    }
+    let payload = payload(event);
+    for url in &config.webhooks {
+        if let Err(e) = secret::resolve(url).and_then(|url| notify::post(&url, &payload)) {
+            eprintln!("Webhook '{}' failed: {}", url, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn describes_starts_and_stops() {
+        let mut session = Session::new(Some("builds".to_string()), "Review".to_string(), "ACME".to_string(), None, false);
+        session.started = DateTime::parse_from_rfc3339("2024-05-06T09:30:00+02:00").unwrap().with_timezone(&Local);
+        let start = payload(Event::Start(&session));
+        assert_eq!(start["event"], "start");
+        assert_eq!(start["session"], "builds");
+        assert_eq!(start["task"], "Review");
+        assert_eq!(start["started"], session.started.to_rfc3339_opts(SecondsFormat::Secs, false));
+        assert!(start.get("duration_secs").is_none());
+
+        let entry = session.to_entry(session.started + chrono::Duration::minutes(30));
+        let stop = payload(Event::Stop(&session, &entry));
+        assert_eq!(stop["event"], "stop");
+        assert_eq!(stop["duration_secs"], 1800);
+        assert_eq!(stop["id"], entry.id.as_str());
+        assert_eq!(stop["ended"], (session.started + chrono::Duration::minutes(30)).to_rfc3339_opts(SecondsFormat::Secs, false));
+
+        let variables = environment(Event::Stop(&session, &entry));
+        let variable = |name| variables.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str());
+        assert_eq!(variable("ST_TASK"), Some("Review"));
+        assert_eq!(variable("ST_SESSION"), Some("builds"));
+        assert_eq!(variable("ST_GIT"), Some(""));
+        assert_eq!(variable("ST_DURATION_SECS"), Some("1800"));
+        assert_eq!(environment(Event::Start(&session)).len(), 6);
+
+        // An entry logged in another zone still ends at the same instant
+        let ended = entry.end_utc().with_timezone(&"+05:30".parse::<chrono::FixedOffset>().unwrap());
+        let abroad = Entry { date: ended.date_naive(), time: ended.time(), offset: Some(*ended.offset()), ..entry.clone() };
+        assert_eq!(payload(Event::Stop(&session, &abroad))["ended"], stop["ended"]);
+    }
+}
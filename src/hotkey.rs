@@ -0,0 +1,240 @@
+//! Global hotkeys read straight from the keyboards (`hotkeys`).
+//!
+//! Keys are read from the Linux input devices (`/dev/input/event*`), so
+//! they work whichever window has focus, under X11, Wayland or on a text
+//! console. Reading them needs access to the devices, usually by being in
+//! the `input` group.
+//!
+//! Hotkeys are written like `ctrl+alt+p`: any of `ctrl`, `alt`, `shift` and
+//! `super`, then one key. A hotkey only fires when exactly its modifiers are
+//! held, so `ctrl+alt+p` doesn't also fire for `ctrl+alt+shift+p`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What a hotkey does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Stop and log the running session, or resume the last task if none runs.
+    Pause,
+    /// Stop and log the running session.
+    Stop,
+}
+
+/// Modifier keys, as bits.
+const CTRL: u8 = 1;
+const ALT: u8 = 2;
+const SHIFT: u8 = 4;
+const SUPER: u8 = 8;
+
+/// Linux key codes (`input-event-codes.h`) by name.
+const KEYS: &[(&str, u16)] = &[
+    ("esc", 1), ("1", 2), ("2", 3), ("3", 4), ("4", 5), ("5", 6), ("6", 7), ("7", 8), ("8", 9), ("9", 10),
+    ("0", 11), ("minus", 12), ("equal", 13), ("backspace", 14), ("tab", 15), ("q", 16), ("w", 17),
+    ("e", 18), ("r", 19), ("t", 20), ("y", 21), ("u", 22), ("i", 23), ("o", 24), ("p", 25), ("enter", 28),
+    ("a", 30), ("s", 31), ("d", 32), ("f", 33), ("g", 34), ("h", 35), ("j", 36), ("k", 37), ("l", 38),
+    ("z", 44), ("x", 45), ("c", 46), ("v", 47), ("b", 48), ("n", 49), ("m", 50), ("space", 57),
+    ("f1", 59), ("f2", 60), ("f3", 61), ("f4", 62), ("f5", 63), ("f6", 64), ("f7", 65), ("f8", 66),
+    ("f9", 67), ("f10", 68), ("scrolllock", 70), ("f11", 87), ("f12", 88), ("home", 102), ("up", 103),
+    ("pageup", 104), ("left", 105), ("right", 106), ("end", 107), ("down", 108), ("pagedown", 109),
+    ("insert", 110), ("delete", 111), ("pause", 119),
+];
+
+/// Modifier key codes: left and right Ctrl, Shift, Alt and Super.
+const MODIFIER_KEYS: &[(u16, u8)] =
+    &[(29, CTRL), (97, CTRL), (42, SHIFT), (54, SHIFT), (56, ALT), (100, ALT), (125, SUPER), (126, SUPER)];
+
+/// A key with the modifiers that must be held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    modifiers: u8,
+    key: u16,
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let mut modifiers = 0;
+        let mut key = None;
+        for part in value.split('+').map(|p| p.trim().to_lowercase()) {
+            let modifier = match part.as_str() {
+                "ctrl" | "control" => CTRL,
+                "alt" => ALT,
+                "shift" => SHIFT,
+                "super" | "meta" | "win" | "cmd" => SUPER,
+                name => {
+                    let code = KEYS.iter().find(|(known, _)| *known == name).map(|(_, code)| *code);
+                    let code = code.ok_or_else(|| format!("unknown key '{}' in hotkey '{}'", name, value))?;
+                    if key.replace(code).is_some() {
+                        return Err(format!("hotkey '{}' has more than one key besides modifiers", value));
+                    }
+                    continue;
+                },
+            };
+            modifiers |= modifier;
+        }
+        let key = key.ok_or_else(|| format!("hotkey '{}' needs a key besides modifiers", value))?;
+        Ok(Hotkey { modifiers, key })
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (bit, name) in [(CTRL, "ctrl"), (ALT, "alt"), (SHIFT, "shift"), (SUPER, "super")] {
+            if self.modifiers & bit != 0 {
+                write!(f, "{}+", name)?;
+            }
+        }
+        let name = KEYS.iter().find(|(_, code)| *code == self.key).map_or("?", |(name, _)| name);
+        write!(f, "{}", name)
+    }
+}
+
+/// Keeps track of the held modifiers across key events.
+#[derive(Debug, Default)]
+pub struct Keyboard {
+    held: u8,
+}
+
+impl Keyboard {
+    /// Feeds one key event (`value` 1 for a press, 0 for a release, 2 for a
+    /// repeat) and returns the key pressed with the modifiers held, if it
+    /// was a press of a key other than a modifier.
+    pub fn key_event(&mut self, code: u16, value: i32) -> Option<Hotkey> {
+        if let Some((_, bit)) = MODIFIER_KEYS.iter().find(|(key, _)| *key == code) {
+            match value {
+                0 => self.held &= !bit,
+                _ => self.held |= bit,
+            }
+            return None;
+        }
+        (value == 1).then_some(Hotkey { modifiers: self.held, key: code })
+    }
+}
+
+/// Event type of key presses and releases.
+const EV_KEY: u16 = 1;
+
+/// Size of a `struct input_event`: a `timeval`, then type, code and value.
+const EVENT_SIZE: usize = 2 * size_of::<usize>() + 8;
+
+/// Decodes a `struct input_event` into its key code and value, if it is a
+/// key event.
+pub fn decode(event: &[u8; EVENT_SIZE]) -> Option<(u16, i32)> {
+    let rest = &event[EVENT_SIZE - 8..];
+    let kind = u16::from_ne_bytes([rest[0], rest[1]]);
+    let code = u16::from_ne_bytes([rest[2], rest[3]]);
+    let value = i32::from_ne_bytes([rest[4], rest[5], rest[6], rest[7]]);
+    (kind == EV_KEY).then_some((code, value))
+}
+
+/// The event devices of keyboards, from the content of
+/// `/proc/bus/input/devices`: devices with a `kbd` handler that report
+/// letter keys (rather than only media or power buttons).
+pub fn keyboards(devices: &str) -> Vec<PathBuf> {
+    devices
+        .split("\n\n")
+        .filter_map(|device| {
+            let handlers = device.lines().find_map(|l| l.strip_prefix("H: Handlers="))?;
+            let mut handlers = handlers.split_whitespace();
+            if !handlers.clone().any(|h| h == "kbd") {
+                return None;
+            }
+            let event = handlers.find(|h| h.starts_with("event"))?;
+            // The lowest bits of the key bitmap hold Esc to the letter keys
+            let keys = device.lines().find_map(|l| l.strip_prefix("B: KEY="))?;
+            let lowest = u64::from_str_radix(keys.split_whitespace().last()?, 16).ok()?;
+            (lowest & 0xfffffffe == 0xfffffffe).then(|| Path::new("/dev/input").join(event))
+        })
+        .collect()
+}
+
+/// The keyboards of this machine; none if they can't be listed.
+pub fn find_keyboards() -> Vec<PathBuf> {
+    std::fs::read_to_string("/proc/bus/input/devices").map(|d| keyboards(&d)).unwrap_or_default()
+}
+
+/// Reads key events from `device` until it fails, calling `on_key` with
+/// each key pressed together with the modifiers held on that device.
+pub fn listen(device: &Path, mut on_key: impl FnMut(Hotkey)) -> io::Result<()> {
+    let mut file = File::open(device)?;
+    let mut keyboard = Keyboard::default();
+    let mut event = [0; EVENT_SIZE];
+    loop {
+        file.read_exact(&mut event)?;
+        if let Some((code, value)) = decode(&event)
+            && let Some(hotkey) = keyboard.key_event(code, value)
+        {
+            on_key(hotkey);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: u16, code: u16, value: i32) -> [u8; EVENT_SIZE] {
+        let mut event = [0; EVENT_SIZE];
+        event[EVENT_SIZE - 8..EVENT_SIZE - 6].copy_from_slice(&kind.to_ne_bytes());
+        event[EVENT_SIZE - 6..EVENT_SIZE - 4].copy_from_slice(&code.to_ne_bytes());
+        event[EVENT_SIZE - 4..].copy_from_slice(&value.to_ne_bytes());
+        event
+    }
+
+    #[test]
+    fn parses_hotkeys() {
+        let hotkey: Hotkey = "Ctrl+Alt+P".parse().unwrap();
+        assert_eq!(hotkey, Hotkey { modifiers: CTRL | ALT, key: 25 });
+        assert_eq!(hotkey.to_string(), "ctrl+alt+p");
+        assert_eq!("pause".parse::<Hotkey>().unwrap().to_string(), "pause");
+        for bad in ["ctrl+alt", "ctrl+hyper+p", "ctrl+p+q", ""] {
+            assert!(bad.parse::<Hotkey>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn matches_exactly_the_held_modifiers() {
+        let hotkey: Hotkey = "ctrl+alt+p".parse().unwrap();
+        let mut keyboard = Keyboard::default();
+        let mut feed = |kind, code, value| decode(&event(kind, code, value)).and_then(|(c, v)| keyboard.key_event(c, v));
+
+        assert_eq!(feed(EV_KEY, 25, 1).map(|k| k == hotkey), Some(false));
+        assert_eq!(feed(EV_KEY, 29, 1), None);
+        assert_eq!(feed(EV_KEY, 100, 1), None);
+        // Synchronisation events are ignored
+        assert_eq!(feed(0, 0, 0), None);
+        assert_eq!(feed(EV_KEY, 25, 1), Some(hotkey));
+        // Repeats and releases don't fire again
+        assert_eq!(feed(EV_KEY, 25, 2), None);
+        assert_eq!(feed(EV_KEY, 25, 0), None);
+        assert_eq!(feed(EV_KEY, 42, 1), None);
+        assert_ne!(feed(EV_KEY, 25, 1), Some(hotkey));
+        feed(EV_KEY, 42, 0);
+        feed(EV_KEY, 100, 0);
+        assert_ne!(feed(EV_KEY, 25, 1), Some(hotkey));
+    }
+
+    #[test]
+    fn finds_keyboards() {
+        let devices = "I: Bus=0011 Vendor=0001 Product=0001 Version=ab41\n\
+            N: Name=\"AT Translated Set 2 keyboard\"\n\
+            H: Handlers=sysrq kbd leds event3 \n\
+            B: KEY=402000000 3803078f800d001 feffffdfffefffff fffffffffffffffe\n\
+            \n\
+            I: Bus=0019 Vendor=0000 Product=0001 Version=0000\n\
+            N: Name=\"Power Button\"\n\
+            H: Handlers=kbd event0 \n\
+            B: KEY=10000000000000 0\n\
+            \n\
+            N: Name=\"Mouse\"\n\
+            H: Handlers=mouse0 event5 \n";
+        assert_eq!(keyboards(devices), vec![PathBuf::from("/dev/input/event3")]);
+    }
+}
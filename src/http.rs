@@ -0,0 +1,279 @@
+//! A deliberately tiny HTTP/1.1 server for local endpoints.
+//!
+//! Requests are handled one at a time on the calling thread, which is all
+//! the local listeners need. Connections are closed after each response.
+//!
+//! A new listener can take over the port of a running one, e.g. after an
+//! upgrade, with [`bind_or_take_over`]: it asks the old one to hand over
+//! with `POST /handover`, gets back whatever state the old one had in
+//! flight, and listens as soon as the old one has let go of the port.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+/// Requests with larger bodies are rejected.
+const MAX_BODY: usize = 4 * 1024 * 1024;
+
+/// Requests whose request line and headers together are larger are rejected.
+const MAX_HEAD: u64 = 64 * 1024;
+
+/// How long a client may take to send its request or read the response
+/// before the connection is dropped, so one stalled client can't block the
+/// listener.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string.
+    pub path: String,
+    /// Query string without the `?`, still percent-encoded.
+    pub query: String,
+    /// Header names and values, in the order received.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the header `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// The decoded value of the query parameter `name`.
+    pub fn param(&self, name: &str) -> Option<String> {
+        self.query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key) == name).then(|| decode(value))
+        })
+    }
+}
+
+/// Decodes a percent-encoded query component, with `+` for spaces.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if let Some(byte) = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) => {
+                decoded.push(byte);
+                i += 2;
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+    /// Headers besides Content-Type, Content-Length and Connection.
+    pub headers: Vec<(&'static str, String)>,
+}
+
+impl Response {
+    pub fn json(status: u16, body: &serde_json::Value) -> Self {
+        Response { status, content_type: "application/json", body: body.to_string(), headers: Vec::new() }
+    }
+
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Response { status, content_type: "text/plain; charset=utf-8", body: body.into(), headers: Vec::new() }
+    }
+
+    /// Adds a header to the response.
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// Accepts connections forever, answering each request with `handler`.
+pub fn serve(listener: TcpListener, handler: impl FnMut(Request) -> Response) -> io::Result<()> {
+    serve_until(listener, handler, || false)
+}
+
+/// Like [`serve`], but stops listening once `done` says so after a request
+/// has been answered, e.g. after handing over to another listener.
+pub fn serve_until(listener: TcpListener, mut handler: impl FnMut(Request) -> Response, mut done: impl FnMut() -> bool) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Connection failed: {}", e);
+                continue;
+            }
+        };
+        let timeouts = stream
+            .set_read_timeout(Some(CONNECTION_TIMEOUT))
+            .and_then(|()| stream.set_write_timeout(Some(CONNECTION_TIMEOUT)));
+        if let Err(e) = timeouts {
+            eprintln!("Connection failed: {}", e);
+            continue;
+        }
+        let response = match read_request(&mut stream) {
+            Ok(request) => handler(request),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Response::text(400, e.to_string()),
+            Err(e) => {
+                eprintln!("Failed to read request: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = write_response(&mut stream, &response) {
+            eprintln!("Failed to send response: {}", e);
+        }
+        if done() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// How long to wait for a listener that handed over to let go of its port.
+const HANDOVER_WAIT: Duration = Duration::from_secs(5);
+
+/// Listens on `port` of the loopback interface. If the port is taken and
+/// `take_over` is given, asks the listener there to hand over, sending the
+/// `take_over` headers (e.g. a token), and listens once it has let go,
+/// returning the state it handed over.
+pub fn bind_or_take_over(port: u16, take_over: Option<&[(&str, String)]>) -> io::Result<(TcpListener, Option<String>)> {
+    let error = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => return Ok((listener, None)),
+        Err(e) => e,
+    };
+    let Some(headers) = take_over.filter(|_| error.kind() == io::ErrorKind::AddrInUse) else {
+        return Err(error);
+    };
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let (status, state) = send(addr, "POST", "/handover", headers, Duration::from_secs(10))?;
+    if status != 200 {
+        return Err(io::Error::other(format!("the listener on port {} refused to hand over: HTTP {} {}", port, status, state.trim())));
+    }
+    let waited = std::time::Instant::now();
+    loop {
+        match TcpListener::bind(addr) {
+            Ok(listener) => return Ok((listener, Some(state))),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse && waited.elapsed() < HANDOVER_WAIT => {
+                std::thread::sleep(Duration::from_millis(20));
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEAD);
+    let mut read_line = |line: &mut String| {
+        let read = head.read_line(line)?;
+        if head.limit() == 0 && !line.ends_with('\n') {
+            return Err(invalid("request headers too large"));
+        }
+        Ok(read)
+    };
+
+    let mut request_line = String::new();
+    read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = Vec::new();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().map_err(|_| invalid("invalid Content-Length"))?;
+        }
+        headers.push((name.to_string(), value.to_string()));
+    }
+    if content_length > MAX_BODY {
+        return Err(invalid("request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let extra: String = response.headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len(),
+        extra,
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// Sends a GET request to a local endpoint, returning the status and body.
+pub fn get(addr: SocketAddr, path: &str, timeout: Duration) -> io::Result<(u16, String)> {
+    send(addr, "GET", path, &[], timeout)
+}
+
+/// Sends a request without a body to a local endpoint, returning the status
+/// and body.
+fn send(addr: SocketAddr, method: &str, path: &str, headers: &[(&str, String)], timeout: Duration) -> io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    let headers: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n", method, path, addr, headers)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed response"))?;
+    Ok((status, body.to_string()))
+}
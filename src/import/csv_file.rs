@@ -0,0 +1,318 @@
+//! Import of CSV time sheets from other tools, such as Toggl or Clockify
+//! reports, spreadsheets kept by hand or another SimpleTimer log.
+//!
+//! Columns are found by their header names, ignoring case:
+//!
+//! | Field    | Headers |
+//! |----------|---------|
+//! | start    | `Start`, or `Start Date` (or `Date`) and `Start Time` |
+//! | end      | `End` or `Stop`, or `End Date` (or `Date`) and `End Time` (or `Time`) |
+//! | duration | `Duration` or `Duration (h)`, as `h:mm[:ss]` or decimal hours; or `Hours`, `Minutes` and `Seconds` |
+//! | code     | `Code`, `Project` or `Tags` |
+//! | task     | `Description` or `Task` |
+//!
+//! A row needs two of a start, an end and a duration. Dates are
+//! `YYYY-MM-DD`, times are 24-hour or have AM/PM, and combined date-times
+//! may also be RFC 3339 with an offset. An end time earlier than a start
+//! time on the same date is taken to be after midnight.
+
+use super::{read_text, Imported};
+use crate::log::{self, Entry};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use std::io;
+use std::path::Path;
+
+/// Where each field is found in a file, by column index.
+struct Columns {
+    start: Option<usize>,
+    start_date: Option<usize>,
+    start_time: Option<usize>,
+    end: Option<usize>,
+    end_date: Option<usize>,
+    end_time: Option<usize>,
+    duration: Option<usize>,
+    hours: Option<usize>,
+    minutes: Option<usize>,
+    seconds: Option<usize>,
+    code: Option<usize>,
+    task: Option<usize>,
+}
+
+impl Columns {
+    fn find(headers: &csv::StringRecord) -> Self {
+        let column = |names: &[&str]| {
+            names.iter().find_map(|name| {
+                // Spreadsheets often start the file with a byte order mark
+                headers.iter().position(|h| h.trim_start_matches('\u{feff}').trim().eq_ignore_ascii_case(name))
+            })
+        };
+        Columns {
+            start: column(&["start"]),
+            start_date: column(&["start date", "date"]),
+            start_time: column(&["start time"]),
+            end: column(&["end", "stop"]),
+            end_date: column(&["end date", "date", "start date"]),
+            end_time: column(&["end time", "time"]),
+            duration: column(&["duration", "duration (h)"]),
+            hours: column(&["hours"]),
+            minutes: column(&["minutes"]),
+            seconds: column(&["seconds"]),
+            code: column(&["code", "project", "tags"]),
+            task: column(&["description", "task"]),
+        }
+    }
+
+    /// Whether any column says when or how long.
+    fn has_times(&self) -> bool {
+        [self.start, self.start_time, self.end, self.end_time, self.duration, self.hours, self.minutes]
+            .iter()
+            .any(Option::is_some)
+    }
+}
+
+/// Reads every row of a CSV time sheet.
+pub fn import(path: &Path) -> io::Result<Imported> {
+    parse(&read_text(path)?)
+}
+
+/// Parses the content of a CSV time sheet. Fails only if the header row
+/// has no columns to take times from; rows that can't be read are
+/// reported in [`Imported::errors`].
+pub fn parse(content: &str) -> io::Result<Imported> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(content.as_bytes());
+    let mut records = reader.records();
+    let headers = match records.next() {
+        Some(headers) => headers.map_err(|e| invalid(e.to_string()))?,
+        None => return Ok(Imported::default()),
+    };
+    let columns = Columns::find(&headers);
+    if !columns.has_times() {
+        return Err(invalid(
+            "no columns with times found (expected headers such as Start Date, Start Time and Duration)".to_string(),
+        ));
+    }
+
+    let mut imported = Imported::default();
+    for (i, record) in records.enumerate() {
+        let row = match record {
+            // Spreadsheets pad the end of a sheet with empty rows
+            Ok(record) if record.iter().all(|field| field.trim().is_empty()) => Ok(None),
+            Ok(record) => to_entry(&columns, &record).map(Some),
+            Err(e) => Err(e.to_string()),
+        };
+        // Numbered as in a spreadsheet, where the header is row 1
+        imported.add(|| format!("row {}", i + 2), row);
+    }
+    Ok(imported)
+}
+
+fn to_entry(columns: &Columns, row: &csv::StringRecord) -> Result<Entry, String> {
+    let field = |column: Option<usize>| column.and_then(|i| row.get(i)).map(str::trim).filter(|s| !s.is_empty());
+    let at = |combined: Option<usize>, date: Option<usize>, time: Option<usize>| match field(combined) {
+        Some(value) => parse_date_time(value).map(Some),
+        None => match (field(date), field(time)) {
+            (Some(date), Some(time)) => Ok(Some(parse_date(date)?.and_time(parse_time(time)?))),
+            _ => Ok(None),
+        },
+    };
+    let start = at(columns.start, columns.start_date, columns.start_time)?;
+    let mut end = at(columns.end, columns.end_date, columns.end_time)?;
+    if let (Some(start), Some(stop)) = (start, end)
+        && stop < start
+        && columns.end.is_none()
+        && columns.end_date == columns.start_date
+    {
+        end = stop.checked_add_signed(TimeDelta::days(1));
+    }
+
+    let duration = match field(columns.duration) {
+        Some(value) => Some(parse_duration(value)?),
+        None if [columns.hours, columns.minutes, columns.seconds].iter().any(|c| field(*c).is_some()) => {
+            let part = |column: Option<usize>, unit: f64| field(column).map_or(Ok(0.0), |v| number(v).map(|n| n * unit));
+            Some(seconds(part(columns.hours, 3600.0)? + part(columns.minutes, 60.0)? + part(columns.seconds, 1.0)?)?)
+        },
+        None => None,
+    };
+
+    let (end, duration_secs) = match (start, end, duration) {
+        (Some(start), Some(end), _) => {
+            let secs = u64::try_from((end - start).num_seconds()).map_err(|_| "the row ends before it starts")?;
+            (end, secs)
+        },
+        (None, Some(end), Some(duration)) => (end, duration),
+        (Some(start), None, Some(duration)) => {
+            let end = i64::try_from(duration)
+                .ok()
+                .and_then(TimeDelta::try_seconds)
+                .and_then(|d| start.checked_add_signed(d))
+                .ok_or("the duration is out of range")?;
+            (end, duration)
+        },
+        _ => return Err("needs two of a start, an end and a duration".to_string()),
+    };
+    let end = end.with_nanosecond(0).unwrap_or(end);
+
+    let code = field(columns.code);
+    Ok(Entry {
+        date: end.date(),
+        time: end.time(),
+        offset: None,
+        code: code.unwrap_or("NA").to_string(),
+        task: field(columns.task).or(code).unwrap_or("Unnamed Task").to_string(),
+        duration_secs,
+        invoice: None,
+        git: None,
+        id: log::new_id(),
+    })
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("unrecognised date '{}' (expected YYYY-MM-DD)", value))
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    ["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("unrecognised time '{}'", value))
+}
+
+fn parse_date_time(value: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Local).naive_local());
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("unrecognised date and time '{}'", value))
+}
+
+/// Parses `h:mm[:ss]` or decimal hours into seconds.
+fn parse_duration(value: &str) -> Result<u64, String> {
+    if !value.contains(':') {
+        return seconds(number(value)? * 3600.0);
+    }
+    let invalid = || format!("unrecognised duration '{}'", value);
+    let part = |part: &str| part.trim().parse::<u64>().map_err(|_| invalid());
+    let (hours, minutes, secs) = match value.split(':').collect::<Vec<_>>().as_slice() {
+        [hours, minutes] => (part(hours)?, part(minutes)?, 0),
+        [hours, minutes, secs] => (part(hours)?, part(minutes)?, part(secs)?),
+        _ => return Err(invalid()),
+    };
+    hours
+        .checked_mul(3600)
+        .and_then(|h| minutes.checked_mul(60)?.checked_add(h))
+        .and_then(|s| s.checked_add(secs))
+        .ok_or_else(invalid)
+}
+
+/// Parses a non-negative number, such as decimal hours.
+fn number(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .ok_or_else(|| format!("'{}' is not a number", value))
+}
+
+fn seconds(secs: f64) -> Result<u64, String> {
+    // Beyond this, the duration can't be added to a date anyway
+    if secs >= i64::MAX as f64 / 1000.0 {
+        return Err("the duration is out of range".to_string());
+    }
+    Ok(secs.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClockifyConfig;
+
+    fn summary(imported: &Imported) -> Vec<(String, String, String, u64)> {
+        imported
+            .entries
+            .iter()
+            .map(|e| (e.start().format("%Y-%m-%d %H:%M:%S").to_string(), e.code.clone(), e.task.clone(), e.duration_secs))
+            .collect()
+    }
+
+    #[test]
+    fn reads_toggl_style_report() {
+        let content = "\u{feff}Project,Description,Start date,Start time,End date,End time,Duration\n\
+                       ACME,\"Design, v2\",2024-05-01,09:00:00,2024-05-01,10:30:00,01:30:00\n";
+        let imported = parse(content).unwrap();
+        assert_eq!(imported.errors, vec![]);
+        assert_eq!(summary(&imported), vec![("2024-05-01 09:00:00".into(), "ACME".into(), "Design, v2".into(), 5400)]);
+    }
+
+    #[test]
+    fn reads_hand_kept_sheet_and_reports_bad_rows() {
+        let content = "Date,Start Time,End Time,Project,Task\n\
+                       2024-05-01,22:00,01:30,ACME,Late release\n\
+                       2024-13-01,09:00,10:00,ACME,Bad date\n\
+                       ,,,,\n\
+                       2024-05-02,9:15 AM,11:45 AM,,Email\n\
+                       2024-05-03,10:00,,ACME,No end\n";
+        let imported = parse(content).unwrap();
+        assert_eq!(
+            summary(&imported),
+            vec![
+                ("2024-05-01 22:00:00".into(), "ACME".into(), "Late release".into(), 12_600),
+                ("2024-05-02 09:15:00".into(), "NA".into(), "Email".into(), 9000),
+            ]
+        );
+        let errors: Vec<String> = imported.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "row 3: unrecognised date '2024-13-01' (expected YYYY-MM-DD)",
+                "row 6: needs two of a start, an end and a duration",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_clockify_export_and_own_log() {
+        let mut entry = Entry {
+            date: "2024-05-01".parse().unwrap(),
+            time: "10:30:00".parse().unwrap(),
+            offset: None,
+            code: "ACME".to_string(),
+            task: "Design \"v2\"".to_string(),
+            duration_secs: 5405,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        };
+        let clockify = crate::export::clockify::export([&entry], &ClockifyConfig::default(), "me@example.com");
+        let own_log = log::to_csv(std::slice::from_ref(&entry));
+        for content in [clockify, own_log] {
+            let imported = parse(&content).unwrap();
+            assert_eq!(imported.errors, vec![]);
+            entry.id = imported.entries[0].id.clone();
+            assert_eq!(imported.entries, vec![entry.clone()]);
+        }
+    }
+
+    #[test]
+    fn reads_date_times_and_decimal_hours() {
+        let content = "Start,Hours,Code\n2024-05-01T09:00:00,1.25,X\n2024-05-01 09:00,-1,X\n2024-05-01 09:00,1e300,X\n";
+        let imported = parse(content).unwrap();
+        assert_eq!(summary(&imported), vec![("2024-05-01 09:00:00".into(), "X".into(), "X".into(), 4500)]);
+        assert_eq!(imported.errors.len(), 2);
+    }
+
+    #[test]
+    fn rejects_durations_reaching_before_any_date() {
+        let imported = parse("End,Duration\n2024-05-01 10:00,2000000000000:00\n").unwrap();
+        assert!(imported.entries.is_empty());
+        assert_eq!(imported.errors[0].message, "duration is out of range");
+    }
+
+    #[test]
+    fn rejects_sheet_without_times() {
+        assert!(parse("Name,Email\nMe,me@example.com\n").is_err());
+        assert!(parse("").unwrap().entries.is_empty());
+    }
+}
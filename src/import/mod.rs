@@ -0,0 +1,188 @@
+//! Importers bringing history from other time trackers into the log.
+//!
+//! Sources are often hand-edited or half-written, so importers never give up
+//! on a whole file because of one bad row: they read what they can and
+//! report the rest as [`RowError`]s.
+
+pub mod csv_file;
+pub mod timew;
+pub mod watson;
+
+use crate::log::{DateRange, Entry};
+use crate::storage::Storage;
+use chrono::TimeDelta;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// A line, frame or row of an import source that could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// Where in the source, e.g. `2024-05.data line 12` or `frame 3`.
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// What an importer read from its source.
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub entries: Vec<Entry>,
+    /// Rows that were skipped because they could not be read, in source order.
+    pub errors: Vec<RowError>,
+}
+
+impl Imported {
+    /// Records the outcome of reading one row. `Ok(None)` is a row that is
+    /// left out on purpose, such as a session that is still running.
+    fn add(&mut self, location: impl FnOnce() -> String, row: Result<Option<Entry>, String>) {
+        match row.and_then(|entry| entry.map(check).transpose()) {
+            Ok(Some(entry)) => self.entries.push(entry),
+            Ok(None) => {},
+            Err(message) => self.errors.push(RowError { location: location(), message }),
+        }
+    }
+}
+
+/// Reads a source file as text, replacing bytes that aren't valid UTF-8.
+fn read_text(path: &Path) -> io::Result<String> {
+    Ok(String::from_utf8_lossy(&std::fs::read(path)?).into_owned())
+}
+
+/// Rejects entries whose start can't be represented, so nothing later
+/// overflows working with them.
+fn check(entry: Entry) -> Result<Entry, String> {
+    i64::try_from(entry.duration_secs)
+        .ok()
+        .and_then(TimeDelta::try_seconds)
+        .and_then(|duration| entry.end().checked_sub_signed(duration))
+        .map(|_| entry)
+        .ok_or_else(|| "duration is out of range".to_string())
+}
+
+/// Appends `imported` entries to the log, skipping any whose start and end
+/// (to the second, as instants, whatever zone they were logged in) match an
+/// entry already in the log (or its archives) or earlier in the batch.
+///
+/// Returns the number of entries added and the number skipped as duplicates.
+pub fn append_new(storage: &mut dyn Storage, mut imported: Vec<Entry>, dry_run: bool) -> io::Result<(usize, usize)> {
+    let existing = storage.history(&DateRange::default())?;
+    let mut seen: HashSet<_> = existing.iter().map(|e| (e.start_utc(), e.end_utc())).collect();
+
+    imported.sort_by_key(|e| e.start_utc());
+    let total = imported.len();
+    imported.retain(|entry| seen.insert((entry.start_utc(), entry.end_utc())));
+    if !dry_run {
+        storage.append_all(&imported)?;
+    }
+    Ok((imported.len(), total - imported.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::Memory;
+    use chrono::NaiveTime;
+    use proptest::prelude::*;
+
+    fn entry(time: &str, task: &str) -> Entry {
+        Entry {
+            date: "2024-03-01".parse().unwrap(),
+            time: time.parse::<NaiveTime>().unwrap(),
+            offset: None,
+            code: "X".to_string(),
+            task: task.to_string(),
+            duration_secs: 600,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn skips_entries_already_logged_or_repeated() {
+        let mut storage = Memory::new(vec![entry("10:00:00", "logged")]);
+        let imported = vec![entry("11:00:00", "new"), entry("10:00:00", "same span"), entry("11:00:00", "repeat")];
+
+        assert_eq!(append_new(&mut storage, imported.clone(), true).unwrap(), (1, 2));
+        assert_eq!(storage.entries.len(), 1);
+
+        assert_eq!(append_new(&mut storage, imported, false).unwrap(), (1, 2));
+        assert_eq!(storage.entries.iter().map(|e| e.task.as_str()).collect::<Vec<_>>(), vec!["logged", "new"]);
+    }
+
+    #[test]
+    fn skips_entries_logged_in_another_zone() {
+        let mut abroad = entry("17:30:00", "logged abroad");
+        abroad.offset = Some("+05:30".parse().unwrap());
+        let mut storage = Memory::new(vec![abroad.clone()]);
+
+        // The same span as Watson or Timewarrior give it back, in UTC
+        let end = abroad.end_utc();
+        let again = Entry { date: end.date_naive(), time: end.time(), offset: Some(chrono::FixedOffset::east_opt(0).unwrap()), ..entry("00:00:00", "again") };
+        assert_eq!(append_new(&mut storage, vec![again], false).unwrap(), (0, 1));
+    }
+
+    /// Every imported entry must be safe to work with.
+    fn check_entries(imported: &Imported) {
+        for entry in &imported.entries {
+            let _ = (entry.start_utc(), entry.end_utc());
+        }
+    }
+
+    /// Field values near the edges of what the importers parse.
+    fn field() -> impl Strategy<Value = String> {
+        prop_oneof![
+            any::<String>(),
+            "[0-9:. -]{0,20}",
+            "[0-9]{1,4}-[0-9]{1,2}-[0-9]{1,2}[ T][0-9]{1,2}:[0-9]{2}",
+            Just("262143-12-31".to_string()),
+            Just("23:59:60".to_string()),
+            Just("18446744073709551615:59:59".to_string()),
+            Just("1e308".to_string()),
+            Just("NaN".to_string()),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn csv_survives_arbitrary_input(content in any::<String>()) {
+            if let Ok(imported) = csv_file::parse(&content) {
+                check_entries(&imported);
+            }
+        }
+
+        #[test]
+        fn csv_survives_malformed_rows(rows in proptest::collection::vec(proptest::collection::vec(field(), 0..8), 0..10)) {
+            let mut content = "Start Date,Start Time,End Date,End Time,Duration,Hours,Project,Description\n".to_string();
+            for row in rows {
+                content.push_str(&crate::log::csv_record(row));
+            }
+            check_entries(&csv_file::parse(&content).unwrap());
+        }
+
+        #[test]
+        fn timew_survives_arbitrary_input(content in any::<String>(), times in proptest::collection::vec(field(), 0..6)) {
+            check_entries(&timew::parse(&content));
+            let line = format!("inc {} - {} # tag\n", times.first().cloned().unwrap_or_default(), times.last().cloned().unwrap_or_default());
+            check_entries(&timew::parse(&line));
+        }
+
+        #[test]
+        fn watson_survives_arbitrary_input(content in any::<String>(), start in any::<i64>(), stop in any::<i64>()) {
+            if let Ok(imported) = watson::parse(&content) {
+                check_entries(&imported);
+            }
+            let frames = format!("[[{}, {}, \"p\", \"id\", [\"t\"], 0], [1, 2], \"x\"]", start, stop);
+            let imported = watson::parse(&frames).unwrap();
+            check_entries(&imported);
+            prop_assert_eq!(imported.entries.len() + imported.errors.len(), 3);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Conversion between the log and Timewarrior's interval data.
+//!
+//! Timewarrior stores intervals in monthly files such as
+//! `~/.timewarrior/data/2024-05.data`, one per line:
+//!
+//! ```text
+//! inc 20240501T085248Z - 20240501T100000Z # FEAT-42 "Work on feature X"
+//! ```
+//!
+//! Exported intervals are tagged with the code followed by the task. When
+//! importing, the first tag becomes the code and the task is taken from the
+//! annotation if there is one, otherwise from the remaining tags.
+//! `timew export` JSON is understood as well.
+
+use super::{read_text, Imported};
+use crate::log::{self, Entry};
+use chrono::{Local, NaiveDateTime, Offset};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const TIMESTAMP: &str = "%Y%m%dT%H%M%SZ";
+
+/// One Timewarrior interval, as found in data files or `timew export` JSON.
+#[derive(Debug, Deserialize)]
+struct Interval {
+    start: String,
+    end: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    annotation: Option<String>,
+}
+
+/// Renders the entries as Timewarrior data file lines.
+pub fn export<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&format!(
+            "inc {} - {} # {} {}\n",
+            entry.start_utc().format(TIMESTAMP),
+            entry.end_utc().format(TIMESTAMP),
+            quote_tag(&one_line(&entry.code)),
+            quote_tag(&one_line(&entry.task))
+        ));
+    }
+    out
+}
+
+/// Reads intervals from a data file, a data directory (every `*.data` file)
+/// or a `timew export` JSON file, skipping intervals that are still open.
+pub fn import(path: &Path) -> io::Result<Imported> {
+    let mut imported = Imported::default();
+    let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    if path.is_dir() {
+        let mut files: Vec<_> = fs::read_dir(path)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "data"))
+            .collect();
+        files.sort();
+        for file in files {
+            read_data(&mut imported, Some(&name(&file)), &read_text(&file)?);
+        }
+    } else {
+        let content = read_text(path)?;
+        if content.trim_start().starts_with('[') {
+            let intervals: Vec<serde_json::Value> =
+                serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            for (i, interval) in intervals.into_iter().enumerate() {
+                let row = serde_json::from_value(interval).map_err(|e| e.to_string()).and_then(to_entry);
+                imported.add(|| format!("interval {}", i + 1), row);
+            }
+        } else {
+            read_data(&mut imported, Some(&name(path)), &content);
+        }
+    }
+    Ok(imported)
+}
+
+/// Data files hold one interval per line, so line breaks become spaces.
+fn one_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/// Parses the lines of a data file into entries, skipping open intervals.
+pub fn parse(content: &str) -> Imported {
+    let mut imported = Imported::default();
+    read_data(&mut imported, None, content);
+    imported
+}
+
+/// Reads the lines of a data file; `file` names it in error locations.
+fn read_data(imported: &mut Imported, file: Option<&str>, content: &str) {
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = parse_line(line).and_then(to_entry);
+        imported.add(
+            || match file {
+                Some(file) => format!("{} line {}", file, i + 1),
+                None => format!("line {}", i + 1),
+            },
+            row,
+        );
+    }
+}
+
+/// Quotes a tag if Timewarrior would otherwise split or misread it.
+fn quote_tag(tag: &str) -> String {
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == '"' || c == '#') {
+        format!("\"{}\"", tag.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        tag.to_string()
+    }
+}
+
+/// Splits the tag section of a data line into tags, honouring quotes.
+fn split_tags(text: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut current = String::new();
+    // Set once a tag has been quoted, so an empty quoted tag ("") is kept
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_quotes => current.extend(chars.next()),
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            },
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() || quoted {
+                    tags.push(std::mem::take(&mut current));
+                    quoted = false;
+                }
+            },
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() || quoted {
+        tags.push(current);
+    }
+    tags
+}
+
+/// Splits at the first ` # ` that is not inside a quoted tag.
+fn split_section(text: &str) -> Option<(&str, &str)> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ' ' if !in_quotes && text[i..].starts_with(" # ") => return Some((&text[..i], &text[i + 3..])),
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Parses an `inc <start> [- <end>] [# tags [# annotation]]` line.
+fn parse_line(line: &str) -> Result<Interval, String> {
+    let rest = line.trim().strip_prefix("inc ").ok_or("not an interval (expected `inc <start> - <end>`)")?;
+    let (times, tags) = split_section(rest).unwrap_or((rest, ""));
+    let (tags, annotation) = match split_section(tags) {
+        Some((tags, annotation)) => (tags, split_tags(annotation).into_iter().next()),
+        None => (tags, None),
+    };
+    let mut times = times.split(" - ");
+    Ok(Interval {
+        start: times.next().unwrap_or_default().trim().to_string(),
+        end: times.next().map(|t| t.trim().to_string()),
+        tags: split_tags(tags),
+        annotation,
+    })
+}
+
+/// Converts an interval, or gives `None` for one that is still open.
+fn to_entry(interval: Interval) -> Result<Option<Entry>, String> {
+    let parse = |s: &str| {
+        NaiveDateTime::parse_from_str(s, TIMESTAMP)
+            .map(|t| t.and_utc().with_timezone(&Local))
+            .map_err(|_| format!("invalid time '{}'", s))
+    };
+    let start = parse(&interval.start)?;
+    let Some(end) = interval.end.as_deref() else {
+        return Ok(None);
+    };
+    let end = parse(end)?;
+    let duration_secs =
+        u64::try_from((end - start).num_seconds()).map_err(|_| "the interval ends before it starts".to_string())?;
+
+    let mut tags = interval.tags.into_iter();
+    let code = tags.next();
+    let rest: Vec<String> = tags.collect();
+    let task = match (interval.annotation.filter(|a| !a.is_empty()), rest.is_empty()) {
+        (Some(annotation), _) => annotation,
+        (None, false) => rest.join(" "),
+        (None, true) => code.clone().unwrap_or_else(|| "Unnamed Task".to_string()),
+    };
+
+    Ok(Some(Entry {
+        date: end.date_naive(),
+        time: end.time(),
+        offset: Some(end.offset().fix()),
+        code: code.unwrap_or_else(|| "NA".to_string()),
+        task,
+        duration_secs,
+        invoice: None,
+        git: None,
+        id: log::new_id(),
+    }))
+}
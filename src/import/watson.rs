@@ -0,0 +1,57 @@
+//! Import of Watson frames.
+//!
+//! Watson keeps its history in a JSON `frames` file (by default
+//! `~/.config/watson/frames`), an array of
+//! `[start, stop, project, id, tags, updated_at]` with Unix timestamps.
+//! The project becomes the code and the tags, joined with commas, the task
+//! (the project name is used when a frame has no tags).
+
+use super::{read_text, Imported};
+use crate::log::{self, Entry};
+use chrono::{DateTime, Local, Offset};
+use serde::Deserialize;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+struct Frame(i64, i64, String, String, #[serde(default)] Vec<String>, #[serde(default)] Option<i64>);
+
+/// Reads every frame from a Watson `frames` file.
+pub fn import(path: &Path) -> io::Result<Imported> {
+    parse(&read_text(path)?)
+}
+
+/// Parses the content of a `frames` file. Fails only if it isn't a JSON
+/// array; frames that can't be read are reported in [`Imported::errors`].
+pub fn parse(content: &str) -> io::Result<Imported> {
+    let frames: Vec<serde_json::Value> =
+        serde_json::from_str(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut imported = Imported::default();
+    for (i, frame) in frames.into_iter().enumerate() {
+        let row = serde_json::from_value(frame).map_err(|e| e.to_string()).and_then(to_entry);
+        imported.add(|| format!("frame {}", i + 1), row.map(Some));
+    }
+    Ok(imported)
+}
+
+fn to_entry(Frame(start, stop, project, _id, tags, _updated_at): Frame) -> Result<Entry, String> {
+    let time = |t: i64| {
+        DateTime::from_timestamp(t, 0).map(|t| t.with_timezone(&Local)).ok_or_else(|| format!("invalid timestamp {}", t))
+    };
+    let (start, stop_time) = (time(start)?, time(stop)?);
+    let duration_secs =
+        u64::try_from((stop_time - start).num_seconds()).map_err(|_| "the frame stops before it starts".to_string())?;
+    let task = if tags.is_empty() { project.clone() } else { tags.join(", ") };
+
+    Ok(Entry {
+        date: stop_time.date_naive(),
+        time: stop_time.time(),
+        offset: Some(stop_time.offset().fix()),
+        code: project,
+        task,
+        duration_secs,
+        invoice: None,
+        git: None,
+        id: log::new_id(),
+    })
+}
@@ -0,0 +1,181 @@
+//! Guessing the task from the working directory and shell history.
+//!
+//! When `[infer] enabled` is set and `start` is given no task (and git
+//! doesn't provide one), the task is taken from the name of the current
+//! directory. If recent shell history is dominated by one command, such as
+//! `cargo test`, it is added: `importer: cargo test`. The guess is always
+//! confirmed before the session starts.
+//!
+//! History is read from `$HISTFILE`, or the most recently written of the
+//! bash, zsh and fish history files. Bash writes its history when the shell
+//! exits, unless `PROMPT_COMMAND="history -a"` is set.
+
+use crate::config::InferConfig;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A command needs to appear at least this often to name the task.
+const MIN_REPEATS: usize = 3;
+
+/// A guessed task, and code if the directory is mapped to one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Guess {
+    pub task: String,
+    pub code: Option<String>,
+}
+
+/// The commands in a bash, zsh (plain or extended) or fish history file,
+/// oldest first.
+pub fn parse_history(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            // zsh extended history: `: 1700000000:0;cargo test`
+            if let Some(rest) = line.strip_prefix(": ")
+                && let Some((_, command)) = rest.split_once(';')
+            {
+                return Some(command);
+            }
+            // fish: `- cmd: cargo test`, followed by `  when: ...` lines
+            if let Some(command) = line.strip_prefix("- cmd: ") {
+                return Some(command);
+            }
+            // bash timestamps (`#1700000000`) and fish metadata
+            if line.starts_with('#') || line.starts_with("  ") {
+                return None;
+            }
+            Some(line)
+        })
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The program of a command line, with its subcommand if it has one:
+/// `cargo test --lib` is `cargo test`, `ls -la` is `ls`.
+fn command_name(command: &str) -> Option<String> {
+    let mut words = command.split_whitespace().skip_while(|word| word.contains('=') || *word == "sudo");
+    let program = words.next()?;
+    match words.next() {
+        Some(sub) if sub.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') && !sub.starts_with('-') => {
+            Some(format!("{} {}", program, sub))
+        },
+        _ => Some(program.to_string()),
+    }
+}
+
+/// The command that the last `lines` commands run most, if any runs at
+/// least [`MIN_REPEATS`] times. Commands in `ignore` (matched by their
+/// program, or program and subcommand) don't count.
+pub fn dominant_command(commands: &[String], lines: usize, ignore: &[String]) -> Option<String> {
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    let recent = &commands[commands.len().saturating_sub(lines)..];
+    for (index, command) in recent.iter().enumerate() {
+        let Some(name) = command_name(command) else { continue };
+        let program = name.split(' ').next().unwrap_or_default();
+        if ignore.iter().any(|ignored| *ignored == name || ignored == program) {
+            continue;
+        }
+        let (count, last) = counts.entry(name).or_default();
+        *count += 1;
+        *last = index;
+    }
+    // The more recent of equally frequent commands
+    let (name, (count, _)) = counts.into_iter().max_by_key(|(_, (count, last))| (*count, *last))?;
+    (count >= MIN_REPEATS).then_some(name)
+}
+
+/// Guesses the task for working in `dir` with the history `commands`.
+/// The home directory and file system root say nothing about the task.
+pub fn guess(dir: &Path, commands: &[String], config: &InferConfig) -> Option<Guess> {
+    if dirs::home_dir().is_some_and(|home| home == dir) {
+        return None;
+    }
+    let name = dir.file_name()?.to_str()?.to_string();
+    let code = config.codes.get(&name).cloned();
+    let task = match config.history.then(|| dominant_command(commands, config.lines, &config.ignore)).flatten() {
+        Some(command) => format!("{}: {}", name, command),
+        None => name,
+    };
+    Some(Guess { task, code })
+}
+
+/// The shell history file to read: `$HISTFILE`, else the most recently
+/// written of the usual bash, zsh and fish locations.
+pub fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("HISTFILE").map(PathBuf::from).filter(|p| p.is_file()) {
+        return Some(path);
+    }
+    let home = dirs::home_dir()?;
+    let fish = dirs::data_dir().map(|dir| dir.join("fish").join("fish_history"));
+    [Some(home.join(".zsh_history")), Some(home.join(".bash_history")), fish]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| Some((std::fs::metadata(&path).ok()?.modified().ok()?, path)))
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Reads the commands from the shell history, or none if there is no
+/// history file. Files need not be valid UTF-8 (zsh escapes some bytes).
+pub fn read_history() -> Vec<String> {
+    history_path()
+        .and_then(|path| std::fs::read(path).ok())
+        .map(|bytes| parse_history(&String::from_utf8_lossy(&bytes)))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_every_history_format() {
+        let bash = "#1700000000\ncargo test\nls -la\n";
+        let zsh = ": 1700000000:0;cargo test\n: 1700000001:0;git status\n";
+        let fish = "- cmd: cargo test\n  when: 1700000000\n- cmd: make\n  when: 1700000001\n  paths:\n    - src\n";
+        assert_eq!(parse_history(bash), commands(&["cargo test", "ls -la"]));
+        assert_eq!(parse_history(zsh), commands(&["cargo test", "git status"]));
+        assert_eq!(parse_history(fish), commands(&["cargo test", "make"]));
+    }
+
+    #[test]
+    fn finds_the_dominant_command() {
+        let ignore = commands(&["ls", "cd", "git status"]);
+        let history = commands(&[
+            "npm run build",
+            "cargo test --lib",
+            "ls",
+            "cargo test importer",
+            "git status",
+            "RUST_LOG=debug cargo test",
+            "cd ..",
+            "ls -la",
+            "ls",
+        ]);
+        assert_eq!(dominant_command(&history, 100, &ignore), Some("cargo test".to_string()));
+        // Too few repeats in the last four commands
+        assert_eq!(dominant_command(&history, 4, &ignore), None);
+        assert_eq!(dominant_command(&history, 100, &[]), Some("ls".to_string()));
+    }
+
+    #[test]
+    fn guesses_from_the_directory() {
+        let mut config = InferConfig::default();
+        config.codes.insert("importer".to_string(), "ACME".to_string());
+        let history = commands(&["make", "make", "make check", "make"]);
+        let dir = Path::new("/work/importer");
+        assert_eq!(
+            guess(dir, &history, &config),
+            Some(Guess { task: "importer: make".to_string(), code: Some("ACME".to_string()) })
+        );
+        config.history = false;
+        assert_eq!(guess(dir, &history, &config).unwrap().task, "importer");
+        assert_eq!(guess(Path::new("/"), &history, &config), None);
+    }
+}
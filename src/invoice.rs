@@ -0,0 +1,304 @@
+//! Invoice generation and the ledger of issued invoices.
+//!
+//! Every generated invoice is recorded in `invoices.csv` in the data
+//! directory. The ledger is also the invoice counter: the next number is
+//! always one more than the highest number recorded, and a ledger row is
+//! only written once the covered entries are marked in the log and the
+//! invoice file itself has been written, so numbers are never reused or
+//! skipped, and entries are never billed twice.
+//!
+//! Entries billed on an invoice are tagged with its number in the log's
+//! `Invoice` column, which locks them: they are excluded from later
+//! invoices and cannot be edited.
+
+use crate::billing::{self, ClientBill};
+use crate::config::{self, Config, InvoiceConfig};
+use crate::duration;
+use crate::log::{self, Entry};
+use crate::report::ReportOptions;
+use crate::storage::Storage;
+use chrono::{Local, NaiveDate};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const LEDGER_HEADERS: &str = "Sequence,Number,Issued,Client,PeriodStart,PeriodEnd,Net,Tax,Gross,Currency,File";
+
+/// One issued invoice, i.e. one row of the ledger.
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub sequence: u64,
+    /// Formatted invoice number, e.g. `INV-0042`.
+    pub number: String,
+    pub issued: NaiveDate,
+    pub client: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub net: f64,
+    pub tax: f64,
+    pub gross: f64,
+    pub currency: String,
+    pub file: PathBuf,
+}
+
+pub fn ledger_path() -> PathBuf {
+    config::data_dir().join("invoices.csv")
+}
+
+fn invoices_dir() -> PathBuf {
+    config::data_dir().join("invoices")
+}
+
+/// Reads all issued invoices from the ledger. A missing ledger is empty.
+pub fn read_ledger(path: &Path) -> io::Result<Vec<LedgerEntry>> {
+    let content = match log::read_text(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let invalid = |row: usize| io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed row {} in invoice ledger '{}'", row, path.display()),
+    );
+    let mut ledger = Vec::new();
+    // Skip the header row
+    for (index, row) in log::parse_csv(&content).into_iter().enumerate().skip(1) {
+        let date = |i: usize| row.get(i).and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok());
+        let amount = |i: usize| row.get(i).and_then(|s| s.parse::<f64>().ok());
+        let parsed = (|| {
+            Some(LedgerEntry {
+                sequence: row.first()?.parse().ok()?,
+                number: row.get(1)?.clone(),
+                issued: date(2)?,
+                client: row.get(3)?.clone(),
+                period_start: date(4)?,
+                period_end: date(5)?,
+                net: amount(6)?,
+                tax: amount(7)?,
+                gross: amount(8)?,
+                currency: row.get(9)?.clone(),
+                file: PathBuf::from(row.get(10)?),
+            })
+        })();
+        // The ledger guards the numbering, so refuse to guess around damage
+        ledger.push(parsed.ok_or_else(|| invalid(index + 1))?);
+    }
+    Ok(ledger)
+}
+
+fn append_ledger(path: &Path, entry: &LedgerEntry) -> io::Result<()> {
+    let needs_header = fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    if needs_header {
+        writeln!(file, "{}", LEDGER_HEADERS)?;
+    }
+    file.write_all(
+        log::csv_record([
+            entry.sequence.to_string(),
+            entry.number.clone(),
+            entry.issued.to_string(),
+            entry.client.clone(),
+            entry.period_start.to_string(),
+            entry.period_end.to_string(),
+            format!("{:.2}", entry.net),
+            format!("{:.2}", entry.tax),
+            format!("{:.2}", entry.gross),
+            entry.currency.clone(),
+            entry.file.display().to_string(),
+        ])
+        .as_bytes(),
+    )
+}
+
+/// Sequence number the next invoice will get.
+fn next_sequence(ledger: &[LedgerEntry], numbering: &InvoiceConfig) -> u64 {
+    ledger
+        .iter()
+        .map(|e| e.sequence + 1)
+        .max()
+        .unwrap_or(numbering.start)
+        .max(numbering.start)
+}
+
+fn format_number(sequence: u64, numbering: &InvoiceConfig) -> String {
+    format!("{}{:0width$}", numbering.prefix, sequence, width = numbering.digits)
+}
+
+/// Generates an invoice for `client` covering the not yet invoiced entries
+/// selected by `options`, writes it to the invoices directory, records it in
+/// the ledger and locks the covered entries in `storage`.
+pub fn create_invoice(
+    storage: &mut dyn Storage,
+    client: &str,
+    options: &ReportOptions,
+    config: &Config,
+) -> io::Result<LedgerEntry> {
+    let numbering = &config.invoice;
+    let ledger_path = ledger_path();
+    // Held until the invoice is recorded, so two invoices can't get one number
+    let _ledger = log::lock(&ledger_path)?;
+    let sequence = next_sequence(&read_ledger(&ledger_path)?, numbering);
+
+    // Mark the entries first: once that is saved they can't be billed again
+    let mut issued = None;
+    storage.update(&mut |entries| {
+        issued = Some(issue(entries, client, options, config, sequence)?);
+        Ok(())
+    })?;
+    let (entry, bill) = issued.expect("Invoice issued");
+
+    let recorded = fs::create_dir_all(invoices_dir())
+        .and_then(|()| fs::write(&entry.file, render(&entry, &bill)))
+        .and_then(|()| append_ledger(&ledger_path, &entry));
+    if let Err(e) = recorded {
+        // Unmark the entries, so the number is free and they can be billed again
+        let unmarked = storage.update(&mut |entries| {
+            for marked in entries.iter_mut().filter(|e| e.invoice.as_deref() == Some(entry.number.as_str())) {
+                marked.invoice = None;
+            }
+            Ok(())
+        });
+        return Err(match unmarked {
+            Ok(()) => e,
+            Err(unmark) => io::Error::new(
+                e.kind(),
+                format!("{}, and the entries marked with {} couldn't be unmarked: {}", e, entry.number, unmark),
+            ),
+        });
+    }
+    Ok(entry)
+}
+
+/// Works out the invoice with `sequence` and marks the covered `entries`
+/// with its number.
+fn issue(
+    entries: &mut [Entry],
+    client: &str,
+    options: &ReportOptions,
+    config: &Config,
+    sequence: u64,
+) -> io::Result<(LedgerEntry, ClientBill)> {
+    let is_covered =
+        |e: &Entry| !e.is_locked() && options.range.includes(e) && config.client(&e.code) == Some(client);
+    let covered: Vec<&Entry> = entries.iter().filter(|e| is_covered(e)).collect();
+    let Some(bill) = billing::compute(covered.iter().copied(), options, config).into_iter().next() else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No uninvoiced entries for client '{}' in the selected period", client),
+        ));
+    };
+
+    // Period defaults to the span of the covered entries
+    let period_start = options.range.since.unwrap_or_else(|| covered.iter().map(|e| e.date).min().unwrap());
+    let period_end = options.range.until.unwrap_or_else(|| covered.iter().map(|e| e.date).max().unwrap());
+
+    let number = format_number(sequence, &config.invoice);
+    let file = invoices_dir().join(format!("{}.txt", number));
+    let entry = LedgerEntry {
+        sequence,
+        number,
+        issued: Local::now().date_naive(),
+        client: client.to_string(),
+        period_start,
+        period_end,
+        net: bill.net,
+        tax: bill.tax,
+        gross: bill.gross,
+        currency: config.billing.currency.clone(),
+        file,
+    };
+
+    for covered in entries.iter_mut().filter(|e| is_covered(e)) {
+        covered.invoice = Some(entry.number.clone());
+    }
+    Ok((entry, bill))
+}
+
+/// Renders the plain-text invoice document.
+fn render(entry: &LedgerEntry, bill: &ClientBill) -> String {
+    let currency = &entry.currency;
+    let mut out = format!(
+        "INVOICE {}\n\nDate:   {}\nClient: {}\nPeriod: {} to {}\n\n",
+        entry.number, entry.issued, entry.client, entry.period_start, entry.period_end
+    );
+    for line in &bill.lines {
+        out.push_str(&format!(
+            "{:<24} {:>7}h x {:>8.2} = {:>10.2} {}\n",
+            line.code,
+            duration::format_decimal_hours(line.secs),
+            line.rate,
+            line.net,
+            currency
+        ));
+    }
+    out.push_str(&format!("\n{:<46} {:>10.2} {}\n", "Net", bill.net, currency));
+    out.push_str(&format!("{:<46} {:>10.2} {}\n", format!("Tax ({}%)", bill.tax_rate), bill.tax, currency));
+    out.push_str(&format!("{:<46} {:>10.2} {}\n", "Total", bill.gross, currency));
+    out
+}
+
+/// Prints the ledger of issued invoices.
+pub fn print_list(ledger: &[LedgerEntry]) {
+    if ledger.is_empty() {
+        println!("No invoices issued yet.");
+        return;
+    }
+    println!("{:<12} {:<10} {:<20} {:<23} {:>12}  File", "Number", "Issued", "Client", "Period", "Gross");
+    for entry in ledger {
+        println!(
+            "{:<12} {:<10} {:<20} {} - {} {:>8.2} {}  {}",
+            entry.number,
+            entry.issued,
+            entry.client,
+            entry.period_start,
+            entry.period_end,
+            entry.gross,
+            entry.currency,
+            entry.file.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::DateRange;
+    use crate::report::{self, Level};
+    use chrono::NaiveTime;
+
+    fn entry(code: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            time: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: "Work".to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn reports_billing_and_invoices_agree_on_clients() {
+        let config: Config = toml::from_str("[projects.Website]\nclient = 'ACME'\ncodes = ['FEAT-42']\n\n[billing]\nclients = { 'GLOBEX/SUPPORT' = 'Globex' }").unwrap();
+        let mut entries = vec![entry("FEAT-42", 600), entry("GLOBEX/SUPPORT", 300), entry("OPS", 60)];
+        let options = ReportOptions::default();
+        let range = DateRange::default();
+
+        let rolled = report::roll_up(&entries, Level::Client, &config);
+        assert_eq!(rolled.iter().map(|e| e.code.as_str()).collect::<Vec<_>>(), vec!["ACME", "Globex", billing::NO_CLIENT]);
+        let tree = report::tree(&entries, &range, &config);
+        assert_eq!(tree.keys().copied().collect::<Vec<_>>(), vec![billing::NO_CLIENT, "ACME", "Globex"]);
+        let clients = report::by_client(&entries, &options, &config);
+        assert_eq!(clients.keys().copied().collect::<Vec<_>>(), vec![billing::NO_CLIENT, "ACME", "Globex"]);
+        let bills = billing::compute(&entries, &options, &config);
+        assert_eq!(bills.iter().map(|b| b.client.as_str()).collect::<Vec<_>>(), vec![billing::NO_CLIENT, "ACME", "Globex"]);
+
+        let (invoice, bill) = issue(&mut entries, "ACME", &options, &config, 1).unwrap();
+        assert_eq!(bill.lines.len(), 1);
+        assert_eq!(entries.iter().map(|e| e.invoice.as_deref()).collect::<Vec<_>>(), vec![Some(invoice.number.as_str()), None, None]);
+    }
+}
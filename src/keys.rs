@@ -0,0 +1,64 @@
+//! Single keys pressed while the timer runs in the foreground.
+//!
+//! Keys are read on their own thread, which waits for the next key with
+//! the terminal in raw mode. The timer can exit during that wait (e.g. when
+//! its session is stopped from another shell), so [`restore`] puts the
+//! terminal back as it was first.
+
+use console::{Key, Term};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+#[cfg(unix)]
+static ORIGINAL: std::sync::OnceLock<libc::termios> = std::sync::OnceLock::new();
+
+/// Reads keys until the process exits. `handle` is called on the reading
+/// thread with each key and may read more input, such as a line, from the
+/// terminal; what it returns is passed on. While it runs, `busy` is set, so
+/// the live display can pause. Returns `None` when not on a terminal.
+pub fn listen<T: Send + 'static>(
+    mut handle: impl FnMut(Key, &Term) -> Option<T> + Send + 'static,
+) -> Option<(Receiver<T>, Arc<AtomicBool>)> {
+    let term = Term::stdout();
+    if !std::io::stdin().is_terminal() || !term.is_term() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+        let mut termios = std::mem::MaybeUninit::uninit();
+        // SAFETY: tcgetattr fills in the struct when it returns 0
+        if unsafe { libc::tcgetattr(std::io::stdin().as_raw_fd(), termios.as_mut_ptr()) } == 0 {
+            let _ = ORIGINAL.set(unsafe { termios.assume_init() });
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let busy = Arc::new(AtomicBool::new(false));
+    let reading = busy.clone();
+    std::thread::spawn(move || {
+        while let Ok(key) = term.read_key() {
+            reading.store(true, Ordering::SeqCst);
+            let message = handle(key, &term);
+            reading.store(false, Ordering::SeqCst);
+            if let Some(message) = message
+                && sender.send(message).is_err()
+            {
+                break;
+            }
+        }
+    });
+    Some((receiver, busy))
+}
+
+/// Puts the terminal back as it was before [`listen`]; call before exiting.
+pub fn restore() {
+    #[cfg(unix)]
+    if let Some(original) = ORIGINAL.get() {
+        use std::os::fd::AsRawFd;
+        // SAFETY: `original` was filled in by tcgetattr
+        unsafe { libc::tcsetattr(std::io::stdin().as_raw_fd(), libc::TCSADRAIN, original) };
+    }
+}
@@ -0,0 +1,130 @@
+//! Laps recorded within a session, kept beside the log.
+//!
+//! While a timer runs, each lap marks the end of a named segment of the
+//! session (see [`crate::session::Lap`]). When the session is logged, its
+//! segments are appended to `laps.csv` in the data directory, one row per
+//! segment (`Id,Lap,Seconds`) keyed by the ID of the logged entry, so the
+//! log itself keeps its columns. Time after the last lap isn't a segment.
+
+use crate::config;
+use crate::log::{self, DateRange, Entry};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Header row of the laps file.
+const HEADERS: &str = "Id,Lap,Seconds";
+
+/// A named part of a logged session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub name: String,
+    pub secs: u64,
+}
+
+/// Location of the laps file.
+pub fn laps_path() -> PathBuf {
+    config::data_dir().join("laps.csv")
+}
+
+/// Appends the segments of the entry with ID `id` to the laps file at `path`.
+pub fn record(path: &Path, id: &str, segments: &[Segment]) -> io::Result<()> {
+    if segments.is_empty() {
+        return Ok(());
+    }
+    let _lock = log::lock(path)?;
+    let mut content = String::new();
+    if std::fs::metadata(path).map_or(true, |m| m.len() == 0) {
+        content.push_str(HEADERS);
+        content.push('\n');
+    }
+    for segment in segments {
+        content.push_str(&log::csv_record([id.to_string(), segment.name.clone(), segment.secs.to_string()]));
+    }
+    OpenOptions::new().append(true).create(true).open(path)?.write_all(content.as_bytes())
+}
+
+/// Reads the segments of every entry from the laps file at `path`, by
+/// entry ID and in the order they were recorded. A missing file has none.
+pub fn read(path: &Path) -> io::Result<HashMap<String, Vec<Segment>>> {
+    let content = match log::read_text(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut laps: HashMap<String, Vec<Segment>> = HashMap::new();
+    for row in log::parse_csv(&content).into_iter().skip(1) {
+        if let [id, name, secs] = row.as_slice()
+            && let Ok(secs) = secs.trim().parse()
+        {
+            laps.entry(id.clone()).or_default().push(Segment { name: name.clone(), secs });
+        }
+    }
+    Ok(laps)
+}
+
+/// How often each lap name was recorded, and its total time, per code and task.
+pub type Totals<'a> = BTreeMap<(&'a str, &'a str), BTreeMap<&'a str, (usize, u64)>>;
+
+/// The laps of the entries in `range`.
+pub fn totals<'a>(entries: &'a [Entry], laps: &'a HashMap<String, Vec<Segment>>, range: &DateRange) -> Totals<'a> {
+    let mut totals = Totals::new();
+    for entry in entries.iter().filter(|e| range.includes(e)) {
+        for segment in laps.get(&entry.id).into_iter().flatten() {
+            let (count, secs) = totals
+                .entry((entry.code.as_str(), entry.task.as_str()))
+                .or_default()
+                .entry(segment.name.as_str())
+                .or_default();
+            *count += 1;
+            *secs += segment.secs;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    fn entry(id: &str, task: &str) -> Entry {
+        Entry {
+            date: "2024-05-06".parse().unwrap(),
+            time: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            offset: None,
+            code: "X".to_string(),
+            task: task.to_string(),
+            duration_secs: 3600,
+            invoice: None,
+            git: None,
+            id: id.to_string(),
+        }
+    }
+
+    fn segment(name: &str, secs: u64) -> Segment {
+        Segment { name: name.to_string(), secs }
+    }
+
+    #[test]
+    fn records_reads_and_totals_laps() {
+        let path = std::env::temp_dir().join(format!("simple_timer_laps_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        record(&path, "a", &[segment("Test run", 300), segment("Fix, \"quoted\"", 600)]).unwrap();
+        record(&path, "b", &[segment("Test run", 120)]).unwrap();
+        record(&path, "c", &[]).unwrap();
+
+        let laps = read(&path).unwrap();
+        assert_eq!(laps["a"], vec![segment("Test run", 300), segment("Fix, \"quoted\"", 600)]);
+        assert!(!laps.contains_key("c"));
+
+        let entries = vec![entry("a", "Debugging"), entry("b", "Debugging"), entry("c", "Other")];
+        let totals = totals(&entries, &laps, &DateRange::default());
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[&("X", "Debugging")]["Test run"], (2, 420));
+
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("csv.lock"));
+    }
+}
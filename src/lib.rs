@@ -0,0 +1,58 @@
+//! Time tracking with a plain CSV log.
+//!
+//! The `simple_timer` binary is a thin command-line front end over this
+//! library. The modules can also be used on their own, e.g. to read the log
+//! from a status bar:
+//!
+//! ```no_run
+//! use simple_timer::{duration, log};
+//!
+//! let entries = log::read_entries(&log::log_path().unwrap()).unwrap();
+//! let today = chrono::Local::now().date_naive();
+//! let secs: u64 = entries.iter().filter(|e| e.date == today).map(|e| e.duration_secs).sum();
+//! println!("{}", duration::format_hms(secs, false));
+//! ```
+
+pub mod activity;
+pub mod api;
+pub mod archive;
+pub mod badge;
+pub mod billing;
+pub mod budget;
+pub mod config;
+pub mod dates;
+pub mod duration;
+pub mod edit;
+pub mod error;
+pub mod export;
+pub mod git;
+#[cfg(feature = "hardware")]
+pub mod hardware;
+pub mod hooks;
+pub mod hotkey;
+pub mod http;
+pub mod import;
+pub mod infer;
+pub mod invoice;
+pub mod laps;
+pub mod locale;
+pub mod log;
+pub mod messages;
+pub mod milestone;
+pub mod notify;
+pub mod onboard;
+#[cfg(feature = "integrations")]
+pub mod push;
+pub mod recover;
+pub mod report;
+pub mod review;
+pub mod secret;
+pub mod serialization;
+pub mod session;
+pub mod slack;
+pub mod snapshot;
+pub mod stats;
+pub mod status;
+pub mod storage;
+pub mod timezone;
+pub mod tray;
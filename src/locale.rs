@@ -0,0 +1,458 @@
+//! Durations and days typed in the user's language.
+//!
+//! Besides the short forms (`1h30m`, `yesterday`), options such as `--ago`
+//! accept durations and days written out in the language set with `locale`
+//! in the config file, or taken from `LC_ALL`, `LC_TIME` or `LANG`:
+//! `1 Std 30 Min`, `1,5 horas` or `1h30` for durations, and `gestern`,
+//! `ayer` or `lundi dernier` for days. English always works as well.
+//!
+//! Dates shown in reports follow the language too, e.g. `Mo 06.05.2024` in
+//! German.
+
+use crate::duration;
+use crate::dates;
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A language durations and days can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Dutch,
+    Portuguese,
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    /// Parses a language code or locale name, e.g. `de` or `de_AT.UTF-8`.
+    fn from_str(value: &str) -> Result<Self, String> {
+        let code = value.split(['_', '-', '.', '@']).next().unwrap_or_default().to_lowercase();
+        match code.as_str() {
+            "en" => Ok(Language::English),
+            "de" => Ok(Language::German),
+            "fr" => Ok(Language::French),
+            "es" => Ok(Language::Spanish),
+            "it" => Ok(Language::Italian),
+            "nl" => Ok(Language::Dutch),
+            "pt" => Ok(Language::Portuguese),
+            _ => Err(format!("unsupported locale '{}'; expected one of en, de, fr, es, it, nl or pt", value)),
+        }
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        value.parse()
+    }
+}
+
+impl From<Language> for String {
+    /// The language code, e.g. `de`.
+    fn from(language: Language) -> String {
+        let code = match language {
+            Language::English => "en",
+            Language::German => "de",
+            Language::French => "fr",
+            Language::Spanish => "es",
+            Language::Italian => "it",
+            Language::Dutch => "nl",
+            Language::Portuguese => "pt",
+        };
+        code.to_string()
+    }
+}
+
+/// The words of a language that durations and days are made of.
+struct Words {
+    hours: &'static [&'static str],
+    minutes: &'static [&'static str],
+    seconds: &'static [&'static str],
+    /// Words that may join the parts of a duration, like `and`.
+    joins: &'static [&'static str],
+    today: &'static [&'static str],
+    yesterday: &'static [&'static str],
+    day_before_yesterday: &'static [&'static str],
+    /// Monday first.
+    weekdays: [&'static [&'static str]; 7],
+    /// Words around a weekday meaning its most recent occurrence, like `last`.
+    last: &'static [&'static str],
+    example_span: &'static str,
+    example_day: &'static str,
+    /// Names of the weekdays as shown, Monday first: full and abbreviated.
+    day_names: [&'static str; 7],
+    short_day_names: [&'static str; 7],
+    /// How dates are written, e.g. `%d.%m.%Y`.
+    date_format: &'static str,
+}
+
+impl Language {
+    /// The language of the `LC_ALL`, `LC_TIME` or `LANG` environment
+    /// variable, whichever is set first, if supported.
+    pub fn from_env() -> Option<Language> {
+        ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.parse().ok())
+    }
+
+    fn words(self) -> &'static Words {
+        match self {
+            Language::English => &ENGLISH,
+            Language::German => &GERMAN,
+            Language::French => &FRENCH,
+            Language::Spanish => &SPANISH,
+            Language::Italian => &ITALIAN,
+            Language::Dutch => &DUTCH,
+            Language::Portuguese => &PORTUGUESE,
+        }
+    }
+
+    /// The words of this language, followed by English as a fallback.
+    fn vocabularies(self) -> impl Iterator<Item = &'static Words> {
+        std::iter::once(self.words()).chain((self != Language::English).then_some(&ENGLISH))
+    }
+
+    /// The name of `weekday` in this language, abbreviated if `short`.
+    pub fn weekday(self, weekday: Weekday, short: bool) -> &'static str {
+        let names = if short { self.words().short_day_names } else { self.words().day_names };
+        names[weekday.num_days_from_monday() as usize]
+    }
+
+    /// How dates are written in this language, as a chrono format such as
+    /// `%d.%m.%Y`.
+    pub fn date_format(self) -> &'static str {
+        self.words().date_format
+    }
+
+    /// Formats `date` with a chrono `format`, writing the weekday names of
+    /// `%A` and `%a` in this language.
+    pub fn format_date(self, date: NaiveDate, format: &str) -> String {
+        let format = format
+            .replace("%A", self.weekday(date.weekday(), false))
+            .replace("%a", self.weekday(date.weekday(), true));
+        date.format(&format).to_string()
+    }
+
+    /// Parses a duration into seconds: a short form such as `1h30m`, or one
+    /// written out, such as `1 Std 30 Min` in German.
+    pub fn parse_span(self, value: &str) -> Result<u64, String> {
+        if let Ok(secs) = duration::parse_span(value) {
+            return Ok(secs);
+        }
+        self.vocabularies().find_map(|words| spoken_span(value, words)).ok_or_else(|| {
+            format!("expected a duration such as 25m, 1h30m or {}, got '{}'", self.words().example_span, value)
+        })
+    }
+
+    /// Parses a day relative to `today`: one accepted by [`dates::parse_day`],
+    /// or written out, such as `gestern` or `letzten Montag` in German.
+    /// A weekday is its most recent occurrence before today.
+    pub fn parse_day(self, value: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+        if let Ok(day) = dates::parse_day_from(value, today) {
+            return Ok(day);
+        }
+        self.vocabularies().find_map(|words| spoken_day(value, words, today)).ok_or_else(|| {
+            format!(
+                "expected YYYY-MM-DD, today, yesterday, last-<weekday> or {}, got '{}'",
+                self.words().example_day,
+                value
+            )
+        })
+    }
+}
+
+/// Splits text into numbers (with `.` or `,` decimals) and words.
+fn tokens(value: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut previous_digit = None;
+    for c in value.trim().to_lowercase().chars() {
+        let digit = c.is_ascii_digit() || ((c == '.' || c == ',') && previous_digit == Some(true));
+        if c.is_whitespace() || (!digit && !c.is_alphabetic()) {
+            previous_digit = None;
+            continue;
+        }
+        match tokens.last_mut() {
+            Some(token) if previous_digit == Some(digit) => token.push(c),
+            _ => tokens.push(c.to_string()),
+        }
+        previous_digit = Some(digit);
+    }
+    tokens
+}
+
+/// A duration written with the units of `words`, e.g. `1 hour and 30
+/// minutes` or `1,5 h`. A number after the hours without a unit counts as
+/// minutes, as in `1h30`.
+fn spoken_span(value: &str, words: &Words) -> Option<u64> {
+    let tokens = tokens(value);
+    let mut tokens = tokens.iter().filter(|t| !words.joins.contains(&t.as_str())).peekable();
+    let mut secs = 0.0;
+    let mut last_unit = None;
+    tokens.peek()?;
+    while let Some(amount) = tokens.next() {
+        let amount: f64 = amount.replace(',', ".").parse().ok()?;
+        let unit = match tokens.next() {
+            Some(word) if words.hours.contains(&word.as_str()) => 3600,
+            Some(word) if words.minutes.contains(&word.as_str()) => 60,
+            Some(word) if words.seconds.contains(&word.as_str()) => 1,
+            None if last_unit == Some(3600) => 60,
+            _ => return None,
+        };
+        if last_unit.is_some_and(|last| last <= unit) {
+            return None;
+        }
+        last_unit = Some(unit);
+        secs += amount * unit as f64;
+    }
+    (secs.is_finite() && secs < u64::MAX as f64).then(|| secs.round() as u64)
+}
+
+/// A day written with the words of `words`, e.g. `day before yesterday` or
+/// `last monday`.
+fn spoken_day(value: &str, words: &Words, today: NaiveDate) -> Option<NaiveDate> {
+    let phrase = value.trim().to_lowercase().replace(['\'', '’', '-'], " ");
+    let phrase: Vec<&str> = phrase.split_whitespace().collect();
+    let matches = |options: &[&str]| options.iter().any(|option| option.split(' ').eq(phrase.iter().copied()));
+    if matches(words.today) {
+        return Some(today);
+    }
+    if matches(words.yesterday) {
+        return today.checked_sub_days(Days::new(1));
+    }
+    if matches(words.day_before_yesterday) {
+        return today.checked_sub_days(Days::new(2));
+    }
+    let name: Vec<&str> = phrase.iter().copied().filter(|word| !words.last.contains(word)).collect();
+    let name = name.join(" ");
+    let index = words.weekdays.iter().position(|names| names.contains(&name.as_str()))?;
+    let weekday = Weekday::try_from(index as u8).ok()?;
+    let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    today.checked_sub_days(Days::new(if back == 0 { 7 } else { back.into() }))
+}
+
+const ENGLISH: Words = Words {
+    hours: &["h", "hr", "hrs", "hour", "hours"],
+    minutes: &["m", "min", "mins", "minute", "minutes"],
+    seconds: &["s", "sec", "secs", "second", "seconds"],
+    joins: &["and"],
+    today: &["today"],
+    yesterday: &["yesterday"],
+    day_before_yesterday: &["day before yesterday", "the day before yesterday"],
+    weekdays: [
+        &["monday", "mon"],
+        &["tuesday", "tue"],
+        &["wednesday", "wed"],
+        &["thursday", "thu"],
+        &["friday", "fri"],
+        &["saturday", "sat"],
+        &["sunday", "sun"],
+    ],
+    last: &["last", "on"],
+    example_span: "1 hour 30 minutes",
+    example_day: "last monday",
+    day_names: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+    short_day_names: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    date_format: "%Y-%m-%d",
+};
+
+const GERMAN: Words = Words {
+    hours: &["h", "std", "stunde", "stunden"],
+    minutes: &["m", "min", "minute", "minuten"],
+    seconds: &["s", "sek", "sekunde", "sekunden"],
+    joins: &["und"],
+    today: &["heute"],
+    yesterday: &["gestern"],
+    day_before_yesterday: &["vorgestern"],
+    weekdays: [
+        &["montag", "mo"],
+        &["dienstag", "di"],
+        &["mittwoch", "mi"],
+        &["donnerstag", "do"],
+        &["freitag", "fr"],
+        &["samstag", "sonnabend", "sa"],
+        &["sonntag", "so"],
+    ],
+    last: &["letzten", "letzter", "letztem", "vergangenen", "am"],
+    example_span: "1 Std 30 Min",
+    example_day: "gestern",
+    day_names: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+    short_day_names: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    date_format: "%d.%m.%Y",
+};
+
+const FRENCH: Words = Words {
+    hours: &["h", "heure", "heures"],
+    minutes: &["m", "min", "minute", "minutes"],
+    seconds: &["s", "sec", "seconde", "secondes"],
+    joins: &["et"],
+    today: &["aujourd hui"],
+    yesterday: &["hier"],
+    day_before_yesterday: &["avant hier"],
+    weekdays: [&["lundi"], &["mardi"], &["mercredi"], &["jeudi"], &["vendredi"], &["samedi"], &["dimanche"]],
+    last: &["dernier"],
+    example_span: "1 heure 30",
+    example_day: "hier",
+    day_names: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+    short_day_names: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+    date_format: "%d/%m/%Y",
+};
+
+const SPANISH: Words = Words {
+    hours: &["h", "hora", "horas"],
+    minutes: &["m", "min", "minuto", "minutos"],
+    seconds: &["s", "seg", "segundo", "segundos"],
+    joins: &["y"],
+    today: &["hoy"],
+    yesterday: &["ayer"],
+    day_before_yesterday: &["anteayer", "antier", "antes de ayer"],
+    weekdays: [
+        &["lunes"],
+        &["martes"],
+        &["miércoles", "miercoles"],
+        &["jueves"],
+        &["viernes"],
+        &["sábado", "sabado"],
+        &["domingo"],
+    ],
+    last: &["el", "pasado"],
+    example_span: "1 hora 30 minutos",
+    example_day: "ayer",
+    day_names: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+    short_day_names: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    date_format: "%d/%m/%Y",
+};
+
+const ITALIAN: Words = Words {
+    hours: &["h", "ora", "ore"],
+    minutes: &["m", "min", "minuto", "minuti"],
+    seconds: &["s", "sec", "secondo", "secondi"],
+    joins: &["e"],
+    today: &["oggi"],
+    yesterday: &["ieri"],
+    day_before_yesterday: &["l altro ieri", "altro ieri", "altroieri", "avantieri"],
+    weekdays: [
+        &["lunedì", "lunedi"],
+        &["martedì", "martedi"],
+        &["mercoledì", "mercoledi"],
+        &["giovedì", "giovedi"],
+        &["venerdì", "venerdi"],
+        &["sabato"],
+        &["domenica"],
+    ],
+    last: &["scorso", "scorsa"],
+    example_span: "1 ora e 30 minuti",
+    example_day: "ieri",
+    day_names: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+    short_day_names: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    date_format: "%d/%m/%Y",
+};
+
+const DUTCH: Words = Words {
+    hours: &["u", "h", "uur", "uren"],
+    minutes: &["m", "min", "minuut", "minuten"],
+    seconds: &["s", "sec", "seconde", "seconden"],
+    joins: &["en"],
+    today: &["vandaag"],
+    yesterday: &["gisteren"],
+    day_before_yesterday: &["eergisteren"],
+    weekdays: [&["maandag"], &["dinsdag"], &["woensdag"], &["donderdag"], &["vrijdag"], &["zaterdag"], &["zondag"]],
+    last: &["vorige", "afgelopen"],
+    example_span: "1 uur 30 min",
+    example_day: "gisteren",
+    day_names: ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+    short_day_names: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+    date_format: "%d-%m-%Y",
+};
+
+const PORTUGUESE: Words = Words {
+    hours: &["h", "hora", "horas"],
+    minutes: &["m", "min", "minuto", "minutos"],
+    seconds: &["s", "seg", "segundo", "segundos"],
+    joins: &["e"],
+    today: &["hoje"],
+    yesterday: &["ontem"],
+    day_before_yesterday: &["anteontem"],
+    weekdays: [
+        &["segunda", "segunda feira"],
+        &["terça", "terca", "terça feira", "terca feira"],
+        &["quarta", "quarta feira"],
+        &["quinta", "quinta feira"],
+        &["sexta", "sexta feira"],
+        &["sábado", "sabado"],
+        &["domingo"],
+    ],
+    last: &["passado", "passada"],
+    example_span: "1 hora e 30 minutos",
+    example_day: "ontem",
+    day_names: ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+    short_day_names: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+    date_format: "%d/%m/%Y",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_locale_names() {
+        assert_eq!("de_AT.UTF-8".parse(), Ok(Language::German));
+        assert_eq!("pt-BR".parse(), Ok(Language::Portuguese));
+        assert!("C".parse::<Language>().is_err());
+    }
+
+    #[test]
+    fn parses_spoken_durations() {
+        let german = Language::German;
+        assert_eq!(german.parse_span("1 Std 30 Min"), Ok(5400));
+        assert_eq!(german.parse_span("1,5 Stunden"), Ok(5400));
+        assert_eq!(german.parse_span("1 Stunde und 5 Minuten"), Ok(3900));
+        assert_eq!(german.parse_span("1h30m"), Ok(5400));
+        // English always works
+        assert_eq!(german.parse_span("2 hours"), Ok(7200));
+        assert_eq!(Language::French.parse_span("1h30"), Ok(5400));
+        assert_eq!(Language::Spanish.parse_span("1 hora y 15 minutos"), Ok(4500));
+        assert_eq!(Language::Dutch.parse_span("1 uur"), Ok(3600));
+        for bad in ["", "Std", "30", "30 Min 1 Std", "1 Std 30 Min 10 Min", "1 Tag"] {
+            assert!(german.parse_span(bad).is_err(), "{}", bad);
+        }
+        assert!(german.parse_span("viel").unwrap_err().contains("1 Std 30 Min"));
+    }
+
+    #[test]
+    fn formats_dates() {
+        let monday: NaiveDate = "2024-05-06".parse().unwrap();
+        let english = Language::English;
+        assert_eq!(english.format_date(monday, &format!("{} %a", english.date_format())), "2024-05-06 Mon");
+        let german = Language::German;
+        assert_eq!(german.format_date(monday, &format!("%a {}", german.date_format())), "Mo 06.05.2024");
+        assert_eq!(Language::French.format_date(monday, "%A %d/%m"), "lundi 06/05");
+    }
+
+    #[test]
+    fn parses_spoken_days() {
+        // A Wednesday
+        let today: NaiveDate = "2024-05-08".parse().unwrap();
+        let day = |language: Language, value| language.parse_day(value, today).map(|d| d.to_string());
+        assert_eq!(day(Language::Spanish, "ayer"), Ok("2024-05-07".to_string()));
+        assert_eq!(day(Language::German, "Vorgestern"), Ok("2024-05-06".to_string()));
+        assert_eq!(day(Language::German, "letzten Montag"), Ok("2024-05-06".to_string()));
+        assert_eq!(day(Language::French, "aujourd'hui"), Ok("2024-05-08".to_string()));
+        assert_eq!(day(Language::French, "mercredi dernier"), Ok("2024-05-01".to_string()));
+        assert_eq!(day(Language::Italian, "l'altro ieri"), Ok("2024-05-06".to_string()));
+        assert_eq!(day(Language::Portuguese, "sexta-feira passada"), Ok("2024-05-03".to_string()));
+        assert_eq!(day(Language::German, "day before yesterday"), Ok("2024-05-06".to_string()));
+        assert_eq!(day(Language::German, "2024-05-01"), Ok("2024-05-01".to_string()));
+        assert!(day(Language::German, "morgen").is_err());
+        assert!(day(Language::German, "Montag Dienstag").is_err());
+    }
+}
@@ -0,0 +1,907 @@
+//! Reading and writing the CSV time log.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeDelta, TimeZone, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Header row written to a freshly created log file, before the code
+/// column is relabelled (see [`headers`]).
+pub const HEADERS: &str = "Date,Time,Code,Task,Hours,Minutes,Seconds,DecimalHours,Invoice,Git,Id,Offset";
+
+/// The header row logs are written with: [`HEADERS`] with the code column
+/// named after `labels.code` from the config file.
+pub fn headers() -> String {
+    let label = crate::config::labels().code;
+    let fields = HEADERS.split(',').map(|name| if name == "Code" { label.as_str() } else { name });
+    csv_record(fields).trim_end().to_string()
+}
+
+/// A single logged session, i.e. one row of the CSV log.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Entry {
+    pub date: NaiveDate,
+    /// Time of day at which the session was stopped and logged.
+    pub time: NaiveTime,
+    /// UTC offset `date` and `time` are in, e.g. `+02:00`. `None` for rows
+    /// logged before offsets were, which are in local time.
+    pub offset: Option<FixedOffset>,
+    pub code: String,
+    pub task: String,
+    /// Total duration of the session in seconds.
+    pub duration_secs: u64,
+    /// Number of the invoice this entry was billed on. Invoiced entries are
+    /// locked: they cannot be invoiced again or edited.
+    pub invoice: Option<String>,
+    /// Repository and branch (`repo@branch`) the session was started in, if
+    /// its task was taken from git.
+    pub git: Option<String>,
+    /// Unique ID (see [`new_id`]) other commands and sync ledgers use to
+    /// refer to this entry. Empty for rows of a log written before IDs
+    /// existed, until the log is next written.
+    pub id: String,
+}
+
+impl Entry {
+    /// When the session was stopped.
+    pub fn end(&self) -> NaiveDateTime {
+        self.date.and_time(self.time)
+    }
+
+    /// When the session was started, derived from the stop time and duration.
+    pub fn start(&self) -> NaiveDateTime {
+        self.end() - TimeDelta::seconds(self.duration_secs as i64)
+    }
+
+    /// The start time as an absolute (UTC) instant.
+    pub fn start_utc(&self) -> DateTime<Utc> {
+        self.end_utc() - TimeDelta::seconds(self.duration_secs as i64)
+    }
+
+    /// The stop time as an absolute (UTC) instant.
+    pub fn end_utc(&self) -> DateTime<Utc> {
+        match self.offset {
+            Some(offset) => (self.end() - offset).and_utc(),
+            None => local_to_utc(self.end()),
+        }
+    }
+
+    /// The day the session was stopped on in the local time zone.
+    pub fn local_date(&self) -> NaiveDate {
+        match self.offset {
+            Some(_) => self.end_utc().with_timezone(&Local).date_naive(),
+            None => self.date,
+        }
+    }
+
+    /// The entry with its times in the local time zone, which is the one
+    /// of `timezone` in the config file if set, to show or report it.
+    /// Entries without an offset are taken to be local already.
+    pub fn to_local(&self) -> Entry {
+        if self.offset.is_none() {
+            return self.clone();
+        }
+        let end = self.end_utc().with_timezone(&Local);
+        Entry { date: end.date_naive(), time: end.time(), offset: Some(end.offset().fix()), ..self.clone() }
+    }
+
+    /// Whether the entry has been invoiced and must not change any more.
+    pub fn is_locked(&self) -> bool {
+        self.invoice.is_some()
+    }
+
+    /// Gives the entry an ID if it doesn't have one yet.
+    pub fn ensure_id(&mut self) {
+        if self.id.is_empty() {
+            self.id = new_id();
+        }
+    }
+}
+
+/// `entries` with their times in the local time zone (see
+/// [`Entry::to_local`]).
+pub fn to_local(entries: Vec<Entry>) -> Vec<Entry> {
+    entries.iter().map(Entry::to_local).collect()
+}
+
+/// A fresh entry ID (a random UUID).
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// The entry whose ID is `id` or starts with it, so the first few
+/// characters of an ID are enough as long as they are unique.
+pub fn find_by_id<'a>(entries: &'a [Entry], id: &str) -> Result<&'a Entry, String> {
+    let id = id.trim();
+    if let Some(entry) = entries.iter().find(|e| e.id == id) {
+        return Ok(entry);
+    }
+    let mut matches = entries.iter().filter(|e| !id.is_empty() && e.id.starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(entry), None) => Ok(entry),
+        (Some(_), Some(_)) => Err(format!("More than one entry has an ID starting with '{}'", id)),
+        (None, _) => Err(format!("No entry has the ID '{}'", id)),
+    }
+}
+
+/// The UTC offset of the local time zone at `local`, e.g. to log a time
+/// typed in; `None` if it was skipped by a DST change.
+pub fn local_offset(local: NaiveDateTime) -> Option<FixedOffset> {
+    Local.from_local_datetime(&local).earliest().map(|t| t.offset().fix())
+}
+
+/// Interprets a logged (local) time as an instant. Times that are ambiguous
+/// or skipped because of a DST change resolve to the earliest match, or are
+/// taken as UTC if they don't exist at all.
+fn local_to_utc(local: NaiveDateTime) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| local.and_utc())
+}
+
+/// Date range used to select entries, shared by reports and exports.
+#[derive(clap::Args, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DateRange {
+    /// Only include entries on or after this day: YYYY-MM-DD, a day such as
+    /// yesterday or monday, or the start of a period such as last-month or 2024-05.
+    #[arg(long, value_name = "DATE", global = true, value_parser = crate::dates::parse_since)]
+    pub since: Option<NaiveDate>,
+
+    /// Only include entries on or before this day: YYYY-MM-DD, a day such as
+    /// yesterday or monday, or the end of a period such as last-month or 2024-05.
+    #[arg(long, value_name = "DATE", global = true, value_parser = crate::dates::parse_until)]
+    pub until: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// Whether an entry falls inside the range, by its local day (see
+    /// [`Entry::local_date`]).
+    pub fn includes(&self, entry: &Entry) -> bool {
+        let date = entry.local_date();
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+}
+
+/// Codes and tags used to select entries, shared by exports. Entries match
+/// any of the codes given and any of the tags given.
+#[derive(clap::Args, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// Only include entries with this code; repeat for several. A client code,
+    /// such as ACME, includes its engagements, such as ACME/ONBOARDING.
+    #[arg(id = "filter_code", long = "code", value_name = "CODE", global = true)]
+    pub code: Vec<String>,
+
+    /// Only include entries whose task has this #tag, e.g. `--tag bug` for
+    /// "Fix login #bug"; repeat for several.
+    #[arg(long, value_name = "TAG", global = true)]
+    pub tag: Vec<String>,
+}
+
+impl Filter {
+    /// Whether an entry matches the filter.
+    pub fn includes(&self, entry: &Entry) -> bool {
+        let code_matches = |code: &String| entry.code == *code || code_parts(&entry.code).0 == code;
+        let tags: Vec<String> = entry
+            .task
+            .split_whitespace()
+            .filter_map(|word| word.strip_prefix('#'))
+            .map(str::to_lowercase)
+            .collect();
+        let tag_matches = |tag: &String| tags.contains(&tag.trim_start_matches('#').to_lowercase());
+        (self.code.is_empty() || self.code.iter().any(code_matches)) && (self.tag.is_empty() || self.tag.iter().any(tag_matches))
+    }
+}
+
+/// Parses a time of day given on the command line: `HH:MM` or `HH:MM:SS`.
+pub fn parse_clock(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(value.trim(), "%H:%M"))
+        .map_err(|_| format!("expected HH:MM or HH:MM:SS, got '{}'", value))
+}
+
+/// Separates the client and engagement parts of a hierarchical code, as in
+/// `ACME/ONBOARDING`.
+pub const CODE_SEPARATOR: char = '/';
+
+/// Splits a code into its client part and, for a hierarchical code, its
+/// engagement part: `ACME/ONBOARDING` gives `("ACME", Some("ONBOARDING"))`.
+pub fn code_parts(code: &str) -> (&str, Option<&str>) {
+    match code.split_once(CODE_SEPARATOR) {
+        Some((client, engagement)) => (client, Some(engagement)),
+        None => (code, None),
+    }
+}
+
+/// Distinct values of a field (e.g. tasks or codes), most recently logged first.
+pub fn recent_values(entries: &[Entry], value: impl Fn(&Entry) -> &str) -> Vec<String> {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.end()));
+    let mut seen = std::collections::HashSet::new();
+    entries
+        .into_iter()
+        .map(value)
+        .filter(|v| !v.is_empty() && seen.insert(*v))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Location of the time log: `log` from the config file, or `time_log.csv`
+/// in the data directory.
+///
+/// Logs written by older versions live in the home directory. As long as
+/// such a log exists and nothing has been written to the new location, it
+/// keeps being used, with a hint to run `onboard`.
+pub fn log_path() -> io::Result<PathBuf> {
+    if let Some(path) = crate::config::load().log {
+        return match path.strip_prefix("~") {
+            Ok(relative) => Ok(home_dir()?.join(relative)),
+            Err(_) => Ok(path),
+        };
+    }
+    let path = default_log_path();
+    // A portable install stays out of the home directory
+    if crate::config::portable_dir().is_some() {
+        return Ok(path);
+    }
+    let legacy = legacy_log_path()?;
+    if !path.exists() && legacy.exists() {
+        static HINT: std::sync::Once = std::sync::Once::new();
+        HINT.call_once(|| {
+            eprintln!(
+                "Using the log at '{}'. Run `onboard` to move it to '{}'.",
+                legacy.display(),
+                path.display()
+            )
+        });
+        return Ok(legacy);
+    }
+    Ok(path)
+}
+
+fn home_dir() -> io::Result<PathBuf> {
+    dirs::home_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not find the home directory"))
+}
+
+/// Where the log is kept unless configured otherwise.
+pub fn default_log_path() -> PathBuf {
+    crate::config::data_dir().join("time_log.csv")
+}
+
+/// Where older versions kept the log: `time_log.csv` in the home directory,
+/// or `time_log_<profile>.csv` for a profile.
+pub fn legacy_log_path() -> io::Result<PathBuf> {
+    let home = home_dir()?;
+    Ok(match crate::config::profile() {
+        Some(profile) => home.join(format!("time_log_{}.csv", profile)),
+        None => home.join("time_log.csv"),
+    })
+}
+
+/// Moves a log from its legacy location to the data directory, returning
+/// the old and new paths, or `None` if there is no legacy log.
+pub fn migrate_legacy() -> io::Result<Option<(PathBuf, PathBuf)>> {
+    let (legacy, path) = (legacy_log_path()?, default_log_path());
+    if !legacy.exists() {
+        return Ok(None);
+    }
+    if path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists; merge the two logs by hand", path.display()),
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Renaming fails across file systems, e.g. a separate /home
+    if std::fs::rename(&legacy, &path).is_err() {
+        std::fs::copy(&legacy, &path)?;
+        std::fs::remove_file(&legacy)?;
+    }
+    Ok(Some((legacy, path)))
+}
+
+/// An advisory lock on the log, held until dropped.
+///
+/// Every change to the log goes through a lock, so two processes stopping
+/// sessions at the same time can't interleave or lose rows. The lock is
+/// taken on a `<log>.lock` file next to the log, because rewrites replace
+/// the log file itself.
+pub struct LogLock {
+    path: PathBuf,
+    _file: File,
+}
+
+/// Locks the log at `path`, waiting while another process holds the lock.
+pub fn lock(path: &Path) -> io::Result<LogLock> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(path.with_file_name(name))?;
+    file.lock()?;
+    Ok(LogLock { path: path.to_path_buf(), _file: file })
+}
+
+impl LogLock {
+    /// Appends a single entry to the log, writing the header row first if needed.
+    ///
+    /// A log written by an older version with fewer columns is upgraded to the
+    /// current layout first, so every row always matches the header.
+    pub fn append_entry(&self, entry: &Entry) -> io::Result<()> {
+        self.append_entries(std::slice::from_ref(entry))
+    }
+
+    /// Appends several entries like [`append_entry`](LogLock::append_entry),
+    /// checking the header once and writing all rows in a single append.
+    pub fn append_entries(&self, entries: &[Entry]) -> io::Result<()> {
+        create_csv_with_headers_if_needed(&self.path)?;
+        self.upgrade_if_needed()?;
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true) // Ensure file is created if it doesn't exist after header check
+            .open(&self.path)?;
+        let mut rows = String::new();
+        for entry in entries {
+            let mut entry = entry.clone();
+            entry.ensure_id();
+            rows.push_str(&format_row(&entry));
+        }
+        file.write_all(rows.as_bytes())
+    }
+
+    /// Replaces the whole log with `entries`, in the current column layout,
+    /// giving entries without an ID one.
+    ///
+    /// Rows of the log that can't be read as entries are kept after them, so
+    /// only `recover` (see [`write_repaired`](LogLock::write_repaired)) drops
+    /// them. The new content is written to a temporary file that then
+    /// replaces the log, so readers (or a crash) never see a half-written log.
+    pub fn write_entries(&self, entries: &[Entry]) -> io::Result<()> {
+        let unreadable = match read_text(&self.path) {
+            Ok(content) => parse_log(&content, &self.path)?.1,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if !unreadable.is_empty() {
+            eprintln!(
+                "Kept {} row(s) that can't be read at the end of '{}'; run `recover` to fix or drop them.",
+                unreadable.len(),
+                self.path.display()
+            );
+        }
+        let mut content = self.content(entries);
+        for row in &unreadable {
+            content.push_str(&csv_record(&row.fields));
+        }
+        replace_file(&self.path, content.as_bytes())
+    }
+
+    /// Replaces the whole log with `entries` like
+    /// [`write_entries`](LogLock::write_entries), but drops the rows that
+    /// can't be read, as `recover` does once it has made a backup.
+    pub fn write_repaired(&self, entries: &[Entry]) -> io::Result<()> {
+        replace_file(&self.path, self.content(entries).as_bytes())
+    }
+
+    /// The log with `entries`, each given an ID if it has none.
+    fn content(&self, entries: &[Entry]) -> String {
+        let mut entries = entries.to_vec();
+        entries.iter_mut().for_each(Entry::ensure_id);
+        to_csv(&entries)
+    }
+
+    /// Rewrites the log in the current layout if its header row is outdated,
+    /// which also gives older rows their IDs.
+    fn upgrade_if_needed(&self) -> io::Result<()> {
+        let content = read_text(&self.path)?;
+        if content.lines().next().is_some_and(|header| header.trim() == headers()) {
+            return Ok(());
+        }
+        let entries = read_entries(&self.path)?;
+        self.write_entries(&entries)
+    }
+}
+
+/// Renders entries as a complete log, header row included.
+pub fn to_csv(entries: &[Entry]) -> String {
+    let mut content = format!("{}\n", headers());
+    for entry in entries {
+        content.push_str(&format_row(entry));
+    }
+    content
+}
+
+/// Replaces a file with `content` by writing a temporary file next to it
+/// and renaming that over the original.
+pub fn replace_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let mut file = File::create(&temp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&temp, path)
+}
+
+/// Appends a single entry to the log under the lock; see [`LogLock::append_entry`].
+pub fn append_entry(path: &Path, entry: &Entry) -> io::Result<()> {
+    lock(path)?.append_entry(entry)
+}
+
+/// Replaces the whole log under the lock; see [`LogLock::write_entries`].
+pub fn write_entries(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    lock(path)?.write_entries(entries)
+}
+
+/// Formats an entry as one CSV row, including the trailing newline.
+fn format_row(entry: &Entry) -> String {
+    let total_minutes = entry.duration_secs / 60;
+    csv_record([
+        entry.date.format("%Y-%m-%d").to_string(),
+        entry.time.format("%H:%M:%S").to_string(),
+        entry.code.clone(),
+        entry.task.clone(),
+        (total_minutes / 60).to_string(),
+        (total_minutes % 60).to_string(),
+        (entry.duration_secs % 60).to_string(),
+        crate::duration::format_decimal_hours(entry.duration_secs),
+        entry.invoice.clone().unwrap_or_default(),
+        entry.git.clone().unwrap_or_default(),
+        entry.id.clone(),
+        entry.offset.map(|offset| offset.to_string()).unwrap_or_default(),
+    ])
+}
+
+/// Reads a text file such as the log, reading bytes that aren't valid UTF-8
+/// as `\u{FFFD}` with a warning rather than failing over them.
+pub fn read_text(path: &Path) -> io::Result<String> {
+    match String::from_utf8(std::fs::read(path)?) {
+        Ok(content) => Ok(content),
+        Err(e) => {
+            eprintln!("Warning: '{}' contains bytes that aren't valid UTF-8; they are read as '\u{FFFD}'.", path.display());
+            Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+        },
+    }
+}
+
+/// Reads every entry from the log. A missing log is treated as empty.
+///
+/// Columns are located by header name, so logs written before the `Seconds`
+/// column existed are still understood. `DecimalHours` is only written for
+/// spreadsheets; the duration is read from the other columns. Entries keep
+/// the date, time and offset they were logged with, so rewrites don't move
+/// them to another zone; see [`Entry::to_local`] to show them. Rows that
+/// cannot be parsed are
+/// reported on stderr and skipped here, though rewrites keep them (see
+/// [`LogLock::write_entries`]).
+pub fn read_entries(path: &Path) -> io::Result<Vec<Entry>> {
+    let content = match read_text(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    parse_entries(&content, path)
+}
+
+/// Parses the content of a log; `path` is only used in messages.
+pub fn parse_entries(content: &str, path: &Path) -> io::Result<Vec<Entry>> {
+    let (entries, unreadable) = parse_log(content, path)?;
+    for row in unreadable {
+        eprintln!("Skipping malformed row on line {} in '{}'", row.line, path.display());
+    }
+    Ok(entries)
+}
+
+/// A row of the log that can't be read as an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unreadable {
+    /// The line the row starts on.
+    pub line: u64,
+    /// Its fields in the columns of [`HEADERS`], followed by any beyond the
+    /// header row, so it can be written back into a log of the current
+    /// layout unchanged.
+    pub fields: Vec<String>,
+}
+
+/// Parses the content of a log into the entries that can be read and the
+/// rows that can't; `path` is only used in messages.
+pub fn parse_log(content: &str, path: &Path) -> io::Result<(Vec<Entry>, Vec<Unreadable>)> {
+    let mut rows = parse_csv_lines(content).into_iter();
+    let headers = match rows.next() {
+        Some((_, headers)) => headers,
+        None => return Ok((Vec::new(), Vec::new())),
+    };
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    // The code column may carry the configured label, or one configured
+    // earlier, so fall back to where it is written: between Time and Task
+    let code = column("Code")
+        .or_else(|| column(&crate::config::labels().code))
+        .or_else(|| column("Time").map(|time| time + 1).filter(|code| column("Task") == Some(code + 1)));
+    let (Some(date), Some(time), Some(code), Some(task), Some(hours), Some(minutes)) = (
+        column("Date"),
+        column("Time"),
+        code,
+        column("Task"),
+        column("Hours"),
+        column("Minutes"),
+    ) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("'{}' does not have the expected header row", path.display()),
+        ));
+    };
+    let seconds = column("Seconds");
+    let invoice = column("Invoice");
+    let git = column("Git");
+    let id = column("Id");
+    let offset = column("Offset");
+    // Where each column of the current layout is, for rows that are kept as they are
+    let layout = [Some(date), Some(time), Some(code), Some(task), Some(hours), Some(minutes), seconds, column("DecimalHours"), invoice, git, id, offset];
+
+    let mut entries = Vec::new();
+    let mut unreadable = Vec::new();
+    for (line, row) in rows {
+        let field = |i: usize| row.get(i).map(|s| s.trim()).unwrap_or("");
+        let number = |i: usize| field(i).parse::<u64>().ok();
+
+        let parsed = (|| {
+            Some(Entry {
+                date: NaiveDate::parse_from_str(field(date), "%Y-%m-%d").ok()?,
+                time: NaiveTime::parse_from_str(field(time), "%H:%M:%S").ok()?,
+                offset: match offset.map(field).unwrap_or("") {
+                    "" => None,
+                    offset => Some(offset.parse().ok()?),
+                },
+                code: row.get(code)?.clone(),
+                task: row.get(task)?.clone(),
+                duration_secs: number(hours)? * 3600
+                    + number(minutes)? * 60
+                    + seconds.and_then(number).unwrap_or(0),
+                invoice: invoice.map(field).filter(|s| !s.is_empty()).map(str::to_string),
+                git: git.map(field).filter(|s| !s.is_empty()).map(str::to_string),
+                id: id.map(field).unwrap_or("").to_string(),
+            })
+        })();
+
+        match parsed {
+            Some(entry) => entries.push(entry),
+            None => {
+                let mut fields: Vec<String> = layout.iter().map(|i| i.and_then(|i| row.get(i)).cloned().unwrap_or_default()).collect();
+                fields.extend(row.iter().enumerate().filter(|(i, _)| !layout.contains(&Some(*i))).map(|(_, field)| field.clone()));
+                unreadable.push(Unreadable { line, fields });
+            },
+        }
+    }
+    Ok((entries, unreadable))
+}
+
+/// Creates the log with its header row if it doesn't exist yet or is empty.
+pub fn create_csv_with_headers_if_needed(path: &Path) -> io::Result<()> {
+    // Check if file exists and is empty, by its size: reading it would fail
+    // on a broken byte and get it recreated
+    let file_exists = path.exists();
+    let file_empty = !file_exists || std::fs::metadata(path).is_ok_and(|m| m.len() == 0);
+
+    // Create file with headers if it doesn't exist or is empty
+    if !file_exists || file_empty {
+        let mut file = File::create(path).map_err(|e| io::Error::new(e.kind(), format!("failed to create '{}': {}", path.display(), e)))?;
+        file.write_all(format!("{}\n", headers()).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Formats one CSV record, including the trailing newline. Fields are
+/// quoted only when they contain a comma, quote or line break.
+pub fn csv_record<I, T>(fields: I) -> String
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields).expect("Failed to format CSV record");
+    let bytes = writer.into_inner().expect("Failed to format CSV record");
+    String::from_utf8(bytes).expect("CSV record is not valid UTF-8")
+}
+
+/// Splits CSV content into rows of unquoted fields.
+///
+/// Rows may have different numbers of fields; blank lines are skipped.
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    parse_csv_lines(content).into_iter().map(|(_, row)| row).collect()
+}
+
+/// Like [`parse_csv`], with the line each row starts on (1-based), which
+/// differs from the row number once a quoted field spans several lines.
+///
+/// Every record is kept: records are read as bytes, so text that isn't
+/// valid UTF-8 can't make one fail, and reading from memory can't fail
+/// otherwise.
+pub fn parse_csv_lines(content: &str) -> Vec<(u64, Vec<String>)> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(content.as_bytes());
+    let mut rows = Vec::new();
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).expect("Reading CSV from memory can't fail") {
+        // The position is where reading started, before any blank lines
+        let (mut line, start) = record.position().map_or((0, 0), |position| (position.line(), position.byte() as usize));
+        line += content[start..].bytes().take_while(|b| matches!(b, b'\r' | b'\n')).filter(|b| *b == b'\n').count() as u64;
+        rows.push((line, record.iter().map(|field| String::from_utf8_lossy(field).into_owned()).collect()));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(code: &str, task: &str, time: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            time: time.parse().unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: task.to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    /// A fresh file path in the temp directory, unique per test.
+    fn temp_log(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("simple_timer_test_{}_{}.csv", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn cleanup(path: &Path) {
+        std::fs::remove_file(path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("csv.lock"));
+    }
+
+    #[test]
+    fn concurrent_appends_keep_every_row() {
+        let path = temp_log("concurrent");
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        append_entry(&path, &entry("X", &format!("Task {} {}", writer, i), "10:00:00", 60)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let read = read_entries(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        cleanup(&path);
+
+        assert_eq!(read.len(), 100);
+        assert_eq!(content.lines().filter(|line| *line == HEADERS).count(), 1);
+    }
+
+    #[test]
+    fn rewrites_leave_no_temp_file() {
+        let path = temp_log("rewrite");
+        write_entries(&path, &[entry("X", "Task", "10:00:00", 60)]).unwrap();
+        write_entries(&path, &[]).unwrap();
+        let leftovers = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .any(|e| e.file_name().to_string_lossy().ends_with("rewrite.csv.tmp"));
+        let read = read_entries(&path).unwrap();
+        cleanup(&path);
+
+        assert!(!leftovers);
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn parses_quoted_fields() {
+        let rows = parse_csv("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\n\"multi\nline\",x\n");
+        assert_eq!(rows, vec![vec!["a", "b, c", "say \"hi\""], vec!["multi\nline", "x"]]);
+    }
+
+    #[test]
+    fn parses_last_row_without_newline() {
+        assert_eq!(parse_csv("a,b\nc,d"), vec![vec!["a", "b"], vec!["c", "d"]]);
+    }
+
+    #[test]
+    fn quotes_fields_only_when_needed() {
+        assert_eq!(csv_record(["a", "b, c", "say \"hi\"", "two\nlines"]), "a,\"b, c\",\"say \"\"hi\"\"\",\"two\nlines\"\n");
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let path = temp_log("round_trip");
+        let mut invoiced = entry("ACME", "Design, \"v2\"\nand review", "17:30:00", 3725);
+        invoiced.invoice = Some("2024-001".to_string());
+        invoiced.git = Some("site@main".to_string());
+        invoiced.id = "kept-as-is".to_string();
+        let entries = vec![entry("Internal", "Email", "09:15:00", 59), invoiced];
+
+        write_entries(&path, &entries).unwrap();
+        let read = read_entries(&path).unwrap();
+        cleanup(&path);
+
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].task, "Email");
+        assert_eq!(read[0].duration_secs, 59);
+        assert_eq!(read[1].task, "Design, \"v2\"\nand review");
+        assert_eq!(read[1].duration_secs, 3725);
+        assert_eq!(read[1].invoice.as_deref(), Some("2024-001"));
+        assert_eq!(read[1].git.as_deref(), Some("site@main"));
+        assert_eq!(read[1].id, "kept-as-is");
+        assert_eq!(read[0].id.len(), 36);
+    }
+
+    #[test]
+    fn keeps_the_zone_entries_were_logged_in() {
+        let mut abroad = entry("ACME", "Design", "17:30:00", 3600);
+        abroad.offset = Some("+05:30".parse().unwrap());
+        abroad.id = "abroad".to_string();
+        assert_eq!(abroad.end_utc().to_rfc3339(), "2024-03-01T12:00:00+00:00");
+        assert!(format_row(&abroad).ends_with(",+05:30\n"));
+
+        let read = parse_entries(&to_csv(std::slice::from_ref(&abroad)), Path::new("abroad.csv")).unwrap();
+        assert_eq!(read, vec![abroad.clone()]);
+
+        let local = abroad.to_local();
+        assert_eq!(local.end_utc(), abroad.end_utc());
+        assert_eq!(local.start_utc(), abroad.start_utc());
+        assert_eq!(local.offset, Some(abroad.end_utc().with_timezone(&Local).offset().fix()));
+        assert_eq!(local.date, abroad.local_date());
+
+        let path = temp_log("abroad");
+        write_entries(&path, std::slice::from_ref(&abroad)).unwrap();
+        append_entry(&path, &entry("ACME", "Build", "18:00:00", 60)).unwrap();
+        lock(&path).unwrap().write_entries(&read_entries(&path).unwrap()).unwrap();
+        assert_eq!(read_entries(&path).unwrap()[0], abroad);
+    }
+
+    #[test]
+    fn rewrites_keep_unreadable_rows() {
+        let path = temp_log("unreadable");
+        std::fs::write(&path, "Date,Time,Code,Task,Hours,Minutes,Seconds,Invoice,Git,Id\n2024-03-01,10:00:00,ACME,Design,1,30,0,2024-001,,a1\n2024-03-02,soon,ACME,Review,x,0,0,2024-002,main,a2\n").unwrap();
+
+        // The first append upgrades the old layout
+        append_entry(&path, &entry("ACME", "Build", "12:00:00", 60)).unwrap();
+        let (entries, unreadable) = parse_log(&std::fs::read_to_string(&path).unwrap(), &path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(unreadable.len(), 1);
+        let fields = &unreadable[0].fields;
+        assert_eq!((fields[1].as_str(), fields[4].as_str(), fields[8].as_str(), fields[9].as_str()), ("soon", "x", "2024-002", "main"));
+
+        // And so does any other rewrite
+        write_entries(&path, &entries[..1]).unwrap();
+        let (entries, kept) = parse_log(&std::fs::read_to_string(&path).unwrap(), &path).unwrap();
+        assert_eq!((entries.len(), &kept[0].fields), (1, fields));
+
+        lock(&path).unwrap().write_repaired(&entries).unwrap();
+        assert!(parse_log(&std::fs::read_to_string(&path).unwrap(), &path).unwrap().1.is_empty());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn keeps_every_csv_record() {
+        let content = "Date,Task\n2024-03-01,\"Two\nlines\"\n\n2024-03-02,\"unclosed\n";
+        let rows = parse_csv_lines(content);
+        assert_eq!(rows.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![1, 2, 5]);
+        assert_eq!(rows[2].1, vec!["2024-03-02", "unclosed\n"]);
+    }
+
+    #[test]
+    fn writes_decimal_hours() {
+        let row = format_row(&entry("ACME", "Design", "17:30:00", 3600 + 45 * 60));
+        assert!(row.starts_with("2024-03-01,17:30:00,ACME,Design,1,45,0,1.75,"), "{}", row);
+    }
+
+    #[test]
+    fn reads_old_layout_and_skips_malformed_rows() {
+        let path = temp_log("old_layout");
+        std::fs::write(&path, "Date,Time,Code,Task,Hours,Minutes\n2024-03-01,10:00:00,\"ACME\",\"Design\",1,30\nnot,a,row\n").unwrap();
+        let read = read_entries(&path).unwrap();
+        cleanup(&path);
+
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].duration_secs, 90 * 60);
+        assert_eq!(read[0].invoice, None);
+        assert_eq!(read[0].id, "");
+    }
+
+    #[test]
+    fn splits_hierarchical_codes() {
+        assert_eq!(code_parts("ACME/ONBOARDING"), ("ACME", Some("ONBOARDING")));
+        assert_eq!(code_parts("ACME/ONBOARDING/WEEK-1"), ("ACME", Some("ONBOARDING/WEEK-1")));
+        assert_eq!(code_parts("FEAT-42"), ("FEAT-42", None));
+    }
+
+    #[test]
+    fn reads_a_relabelled_code_column() {
+        let content = "Date,Time,Matter,Task,Hours,Minutes\n2024-03-01,10:00:00,ACME-1,Design,1,30\n";
+        let read = parse_entries(content, Path::new("relabelled.csv")).unwrap();
+        assert_eq!(read[0].code, "ACME-1");
+        assert!(parse_entries("Date,Time,Task,Matter,Hours,Minutes\n", Path::new("x.csv")).is_err());
+    }
+
+    #[test]
+    fn appending_upgrades_old_layout() {
+        let path = temp_log("upgrade");
+        std::fs::write(&path, "Date,Time,Code,Task,Hours,Minutes\n2024-03-01,10:00:00,\"ACME\",\"Design\",1,30\n").unwrap();
+        append_entry(&path, &entry("ACME", "Review", "11:00:00", 600)).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let read = read_entries(&path).unwrap();
+        cleanup(&path);
+
+        assert_eq!(content.lines().next(), Some(HEADERS));
+        assert_eq!(read.len(), 2);
+        assert!(read.iter().all(|e| !e.id.is_empty()));
+        assert_ne!(read[0].id, read[1].id);
+    }
+
+    #[test]
+    fn missing_log_is_empty() {
+        assert!(read_entries(&temp_log("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn start_is_derived_from_stop_and_duration() {
+        let e = entry("ACME", "Design", "10:00:00", 3600 + 30);
+        assert_eq!(e.start().time(), NaiveTime::from_hms_opt(8, 59, 30).unwrap());
+    }
+
+    #[test]
+    fn filters_by_code_and_tag() {
+        let filter = |code: &[&str], tag: &[&str]| Filter {
+            code: code.iter().map(|c| c.to_string()).collect(),
+            tag: tag.iter().map(|t| t.to_string()).collect(),
+        };
+        let bug = entry("ACME/ONBOARDING", "Fix login #Bug", "10:00:00", 60);
+        let design = entry("GLOBEX", "Design #ui", "11:00:00", 60);
+        assert!(filter(&[], &[]).includes(&bug));
+        assert!(filter(&["ACME"], &[]).includes(&bug));
+        assert!(filter(&["ACME/ONBOARDING", "GLOBEX"], &[]).includes(&design));
+        assert!(!filter(&["ACME/SUPPORT"], &[]).includes(&bug));
+        assert!(filter(&[], &["bug"]).includes(&bug));
+        assert!(filter(&[], &["#ui", "bug"]).includes(&design));
+        assert!(!filter(&["ACME"], &["ui"]).includes(&design));
+        assert!(!filter(&[], &["login"]).includes(&bug));
+    }
+
+    #[test]
+    fn recent_values_are_distinct_newest_first() {
+        let entries = vec![
+            entry("A", "Old", "09:00:00", 60),
+            entry("B", "New", "12:00:00", 60),
+            entry("A", "Mid", "10:00:00", 60),
+            entry("", "Blank", "08:00:00", 60),
+        ];
+        assert_eq!(recent_values(&entries, |e| e.code.as_str()), vec!["B", "A"]);
+        assert_eq!(recent_values(&entries, |e| e.task.as_str()), vec!["New", "Mid", "Old", "Blank"]);
+    }
+
+    #[test]
+    fn finds_entries_by_id_prefix() {
+        let mut entries = vec![entry("X", "a", "10:00:00", 60), entry("X", "b", "11:00:00", 60), entry("X", "c", "12:00:00", 60)];
+        entries[0].id = "abc123".to_string();
+        entries[1].id = "abd456".to_string();
+        entries[2].id = "ab".to_string();
+        assert_eq!(find_by_id(&entries, "abc").unwrap().task, "a");
+        // An exact ID wins over longer IDs it is a prefix of
+        assert_eq!(find_by_id(&entries, "ab").unwrap().task, "c");
+        assert!(find_by_id(&entries, "abx").is_err());
+        assert!(find_by_id(&entries, "").is_err());
+        entries[2].id = "other".to_string();
+        assert!(find_by_id(&entries, "ab").unwrap_err().contains("More than one"));
+    }
+}
@@ -1,188 +1,2846 @@
-use clap::Parser;
-use std::time::Instant;
-
-use std::fs::{OpenOptions, File};
-use std::io::{stdin, stdout, Write as IoWrite, Read}; // Use alias for Write
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::engine::ArgValueCandidates;
 use std::path::PathBuf;
-use dirs;
-use chrono; // Ensure chrono is explicitly used or imported if needed for time formatting
+
+use std::io::{stderr, stdout, IsTerminal, Write as IoWrite}; // Use alias for Write
+
+mod bench;
+mod completions;
+mod keys;
+mod health;
+mod picker;
+mod prompt;
+mod shutdown;
+mod stopwatch;
+
+#[cfg(feature = "hardware")]
+use simple_timer::hardware;
+#[cfg(feature = "integrations")]
+use simple_timer::push;
+use simple_timer::{activity, api, archive, badge, billing, budget, config, dates, duration, edit, export, git, hotkey, http, import, infer, invoice, laps, log, messages, milestone, notify, onboard, recover, report, review, secret, serialization, session, snapshot, stats, status, storage, timezone, tray};
+
+use chrono::{DateTime, Local, TimeDelta};
+use duration::RoundMode;
+use log::DateRange;
+use session::Session;
+use simple_timer::error::{Context, Error};
+
+/// Set by `--output json`: the summary of a stopped session goes to stdout
+/// as JSON, and everything else to stderr.
+static JSON_OUTPUT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Prints `error` and exits with its code (see [`simple_timer::error`]).
+fn exit(error: Error) -> ! {
+    error.exit()
+}
+
+/// Ends the command when a step fails, with the exit code of its error.
+trait OrExit<T> {
+    fn or_exit(self) -> T;
+}
+
+impl<T> OrExit<T> for Result<T, Error> {
+    fn or_exit(self) -> T {
+        self.unwrap_or_else(|e| exit(e))
+    }
+}
+
+/// Opens the configured log, ending the command when it can't be opened.
+fn open_storage() -> Box<dyn storage::Storage> {
+    storage::open()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::Unsupported => Error::Invalid(e.to_string()),
+            _ => Error::io("Failed to open the log", e),
+        })
+        .or_exit()
+}
+
+/// The path of the CSV log, ending the command when there's no home directory.
+fn log_path() -> PathBuf {
+    log::log_path().context("Failed to find the log").or_exit()
+}
+
+/// Prints a line for people: to stdout, or to stderr with `--output json`.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 /// Simple command-line timer that logs time spent on tasks to a CSV file.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// The name of the task being tracked. If omitted, you will be prompted.
-    #[arg(short, long, value_name = "TASK_NAME")]
+    /// Use a profile from the config file (`[profiles.<NAME>]`), with its own
+    /// log and settings. Defaults to SIMPLETIMER_PROFILE or `default_profile`.
+    #[arg(long, global = true, value_name = "NAME", add = ArgValueCandidates::new(completions::profiles))]
+    profile: Option<String>,
+
+    /// Keep the config file, log and all other state in a `simpletimer`
+    /// folder beside the executable, e.g. to run from a USB stick. Also on
+    /// when a `portable.flag` file is beside the executable.
+    #[arg(long, global = true)]
+    portable: bool,
+
+    /// Never ask anything or show the live timer, and fail when the task or
+    /// code is missing instead of asking for it. Also on when stdout isn't a
+    /// terminal, e.g. under cron, `watch` or in CI.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// With `json`, print the summary of a stopped session as a JSON object
+    /// (id, task, code, start, end, duration_secs) on stdout, and the text
+    /// meant for people on stderr.
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    output: Output,
+
+    /// Print no colors, e.g. for screen readers or logs. Also on when
+    /// NO_COLOR is set.
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Log and report in this time zone: `local`, `UTC`, an offset such as
+    /// `+05:30` or a name such as `Europe/Berlin`. Defaults to `timezone`
+    /// from the config file.
+    #[arg(long, global = true, value_name = "ZONE")]
+    timezone: Option<String>,
+
+    #[command(flatten)]
+    track: TrackArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// What to track when starting a session.
+#[derive(Args, Debug)]
+struct TrackArgs {
+    /// The name of the task being tracked. If omitted, the current git branch
+    /// is used inside a repository; otherwise you will be prompted.
+    #[arg(short, long, value_name = "TASK_NAME", add = ArgValueCandidates::new(completions::tasks))]
     task: Option<String>,
 
     /// Optional code to associate with the task entry in the log. If omitted, you will be prompted.
-    #[arg(short, long, value_name = "CODE")]
+    #[arg(short, long, value_name = "CODE", add = ArgValueCandidates::new(completions::codes))]
     code: Option<String>,
+
+    /// Take the task and code from a preset in the config file (`[presets.<NAME>]`).
+    /// --task and --code override the preset's values.
+    #[arg(short, long, value_name = "NAME", add = ArgValueCandidates::new(completions::presets))]
+    preset: Option<String>,
+
+    /// Use the current git branch as the task and the repository as the code,
+    /// even if automatic git defaults are turned off in the config file.
+    #[arg(long, conflicts_with_all = ["task", "preset"])]
+    git: bool,
+
+    /// Track this as a named session that runs alongside the default session
+    /// and other named ones, e.g. `--session builds`.
+    #[arg(long, value_name = "NAME", value_parser = session::parse_name, add = ArgValueCandidates::new(completions::sessions))]
+    session: Option<String>,
+
+    /// Stop and log the session once it has run this long, e.g. `8h`, so a
+    /// forgotten timer doesn't run overnight. Defaults to `max` from the
+    /// config file.
+    #[arg(long, value_name = "DURATION", value_parser = parse_span)]
+    max: Option<u64>,
+}
+
+impl TrackArgs {
+    /// Fills in the task and code from the preset, if one was given.
+    fn resolve_preset(mut self) -> Self {
+        if let Some(name) = self.preset.take() {
+            let mut config = config::load();
+            let Some(preset) = config.presets.remove(&name) else {
+                exit(Error::Invalid(format!("Unknown preset '{}'. Define it as [presets.{}] in the config file.", name, name)));
+            };
+            self.task = self.task.or(preset.task);
+            self.code = self.code.or(preset.code);
+        }
+        self
+    }
+
+    /// Fills in the task and code from the git repository in the working
+    /// directory when no task was given, returning the repository and branch
+    /// to record with the session.
+    fn resolve_git(&mut self) -> Option<String> {
+        let config = config::load().git;
+        if self.task.is_some() || !(self.git || config.auto) {
+            return None;
+        }
+        let Some(info) = git::detect() else {
+            if self.git {
+                exit(Error::Invalid("--git needs to be run inside a git repository.".into()));
+            }
+            return None;
+        };
+        self.task = Some(info.branch.clone());
+        if self.code.is_none() {
+            self.code = Some(config.codes.get(&info.repo).cloned().unwrap_or_else(|| info.repo.clone()));
+        }
+        Some(info.label())
+    }
+}
+
+/// How the summary of a stopped session is printed.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start tracking a task (the same as running without a subcommand). If
+    /// a session is already running, asks whether to switch, stop it or abort.
+    Start {
+        #[command(flatten)]
+        track: TrackArgs,
+
+        /// Record the session and return immediately instead of showing a live
+        /// timer. Stop it later with `stop` or `toggle`.
+        #[arg(long)]
+        detach: bool,
+
+        /// Count the session from this long ago, e.g. `25m`, `1h30m` or
+        /// `"1 Std 30 Min"` (see `locale`), for when you forgot to start the timer. A running session is logged
+        /// as having stopped then.
+        #[arg(long, value_name = "DURATION", value_parser = parse_span, conflicts_with = "at")]
+        ago: Option<u64>,
+
+        /// Count the session from this time today (HH:MM), like --ago.
+        #[arg(long, value_name = "TIME", value_parser = log::parse_clock)]
+        at: Option<chrono::NaiveTime>,
+    },
+
+    /// Start a new session for the task and code of the most recently
+    /// logged entry, without prompting.
+    #[command(alias = "resume")]
+    Again {
+        /// Start it as this named session; the task still comes from the
+        /// latest entry of any session.
+        #[arg(long, value_name = "NAME", value_parser = session::parse_name, add = ArgValueCandidates::new(completions::sessions))]
+        session: Option<String>,
+
+        /// Record the session and return immediately instead of showing a live timer.
+        #[arg(long)]
+        detach: bool,
+    },
+
+    /// Record a lap of the running session, e.g. from another shell than the
+    /// one showing its timer.
+    Lap {
+        /// Name of the lap, e.g. `"Test run"`; numbered if left out.
+        name: Option<String>,
+
+        /// Record it in this named session instead of the default one.
+        #[arg(long, value_parser = session::parse_name, add = ArgValueCandidates::new(completions::sessions))]
+        session: Option<String>,
+    },
+
+    /// Stop the running session and log it.
+    Stop {
+        /// Stop this named session instead of the default one.
+        #[arg(value_parser = session::parse_name, add = ArgValueCandidates::new(completions::sessions))]
+        session: Option<String>,
+    },
+
+    /// A stopwatch with laps (Space) that never writes to the log.
+    Stopwatch,
+
+    /// List the running sessions.
+    Status {
+        /// Print for a status bar or script instead: json, waybar (a custom
+        /// module with `return-type = "json"`), i3blocks, one line of text
+        /// (e.g. for polybar), a colored segment for the tmux status line, or
+        /// a minimal string for a shell prompt. Only the session state files
+        /// are read, never the log.
+        #[arg(long, value_enum)]
+        format: Option<status::Format>,
+    },
+
+    /// Stop the running session, or start a detached one if none is running.
+    /// Never prompts: a missing task or code falls back to the defaults.
+    Toggle {
+        #[command(flatten)]
+        track: TrackArgs,
+    },
+
+    /// Stop the running session (if any) and start a detached one for another task.
+    Switch {
+        #[command(flatten)]
+        track: TrackArgs,
+    },
+
+    /// Serve a local HTTP API to query and control the timer, e.g. from
+    /// editors, launcher scripts and browser extensions (see `[serve]`).
+    Serve {
+        /// Port to listen on. Defaults to `serve.port` from the config file (7878).
+        #[arg(long)]
+        port: Option<u16>,
+        /// If another `serve` holds the port, e.g. one started before an
+        /// upgrade, take it over instead of failing.
+        #[arg(long)]
+        take_over: bool,
+    },
+
+    /// Receive WakaTime-compatible editor heartbeats on a local port and log
+    /// stretches of editor activity as entries (see `[activity]` in the config file).
+    Activity {
+        /// Port to listen on. Defaults to `activity.port` from the config file (7879).
+        #[arg(long)]
+        port: Option<u16>,
+        /// If another `activity` holds the port, e.g. one started before an
+        /// upgrade, take it over with its open stretches instead of failing.
+        #[arg(long)]
+        take_over: bool,
+    },
+
+    /// Read badge IDs from a reader and punch in or out with the preset mapped
+    /// to each badge in `[badges]`. Scanning the badge of the running preset
+    /// stops it; any other badge switches to its preset.
+    Badges {
+        /// Serial device the reader prints badge IDs to, or `-` for readers
+        /// that type the ID like a keyboard.
+        #[arg(long, value_name = "PATH", default_value = "-")]
+        device: PathBuf,
+    },
+
+    /// Listen for the hotkeys in `[hotkeys]` on every keyboard, whichever
+    /// window has focus, and pause or stop the running session (Linux only).
+    Hotkeys {
+        /// Input device to read instead of every keyboard found, e.g.
+        /// `/dev/input/event3`. Can be given more than once.
+        #[arg(long, value_name = "PATH")]
+        device: Vec<PathBuf>,
+    },
+
+    /// Show a system tray icon with the running session and a menu to stop
+    /// it, resume the last task or switch to a recent one (needs `yad`).
+    Tray,
+
+    /// Listen for GPIO button presses and toggle or switch sessions
+    /// (see `[hardware]` in the config file).
+    #[cfg(feature = "hardware")]
+    Buttons,
+
+    /// Check the log, the running session, the activity listener and the push
+    /// backlog. Exits with status 1 if something is broken.
+    #[command(alias = "doctor")]
+    Health {
+        /// Also check that every storage backend and export format writes
+        /// and reads back tricky task names (emoji, line breaks, quotes,
+        /// right-to-left text) without corrupting rows.
+        #[arg(long)]
+        self_test: bool,
+    },
+
+    /// Salvage sessions whose state files were corrupt and have been
+    /// quarantined: log or resume them, repair a damaged log, and delete
+    /// the backups.
+    Recover,
+
+    /// Time reading, parsing and reporting on your log, to see where time
+    /// goes as it grows.
+    Bench {
+        /// Number of runs; the median of each stage is shown.
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
+    },
+
+    /// Read or change settings in the config file.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Keep integration tokens in the OS keyring, for the config file to
+    /// refer to as `keyring:<name>`, e.g. `jira.token = "keyring:jira-token"`.
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+
+    /// Move a log from the home directory, where older versions kept it, to the
+    /// data directory.
+    Migrate,
+
+    /// Guided move of a log kept by older versions in the home directory:
+    /// shows what it holds, backs it up, copies it into the storage of the
+    /// profile and checks that the totals match before removing it.
+    Onboard {
+        /// Where to keep the entries; asked on a terminal, else the
+        /// `storage` setting.
+        #[arg(long, value_enum)]
+        storage: Option<config::StorageBackend>,
+
+        /// Don't ask for confirmation.
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Print a script that sets up tab completion for your shell, including
+    /// task names and codes from the log. For example, add
+    /// `source <(simple_timer completions bash)` to ~/.bashrc.
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+
+    /// Move entries before a date out of the log into compressed yearly
+    /// archives. Reports and exports still include them.
+    Archive {
+        /// Archive entries dated before this day (YYYY-MM-DD).
+        #[arg(long, value_name = "DATE")]
+        before: chrono::NaiveDate,
+    },
+
+    /// List entries added, edited or deleted since a weekly snapshot of the
+    /// log, e.g. as a check before submitting a timesheet.
+    Diff {
+        /// Compare with the snapshot taken on or before this day: YYYY-MM-DD,
+        /// today, yesterday, last-<weekday> or written out in your language, e.g.
+        /// `gestern`. Defaults to this week's snapshot.
+        #[arg(long, value_name = "DAY", value_parser = parse_day)]
+        since: Option<chrono::NaiveDate>,
+    },
+
+    /// Step through each day of a week, flagging gaps, overlaps, days over
+    /// twelve hours and unknown codes, fix entries inline and finish by
+    /// showing the report or pushing the week.
+    Review {
+        /// The week to review; weeks start on Monday.
+        #[arg(long, value_enum, default_value_t = review::Week::This)]
+        week: review::Week,
+    },
+
+    /// Summarise logged time per code and task.
+    Report {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Round displayed durations to this many minutes (e.g. 15 for quarter hours).
+        /// Only the printed report is rounded; the log keeps the precise values.
+        #[arg(long, value_name = "MINUTES")]
+        round_display: Option<u64>,
+
+        /// How displayed durations are rounded when --round-display is given.
+        #[arg(long, value_enum, default_value_t = RoundMode::Nearest, requires = "round_display")]
+        round_mode: RoundMode,
+
+        /// Show billable amounts per client with net, tax and gross totals,
+        /// using the rates from the `[billing]` section of the config file.
+        #[arg(long)]
+        billing: bool,
+
+        /// How time is grouped and rounded. Defaults to `report.layout` from
+        /// the config file (detailed). --round-display overrides the rounding.
+        #[arg(long, value_enum, conflicts_with = "billing")]
+        layout: Option<report::Layout>,
+
+        /// Report codes as they are (engagement), rolled up to their project
+        /// from `[projects]` in the config file (project), or to their client
+        /// (client), e.g. ACME/ONBOARDING to ACME.
+        #[arg(long, value_enum, default_value_t = report::Level::Engagement, conflicts_with = "billing")]
+        level: report::Level,
+
+        /// Instead of the report, show per month and code how rounding changes
+        /// the logged time, using --round-display or the layout's rounding.
+        #[arg(long, conflicts_with = "billing")]
+        rounding_impact: bool,
+
+        /// Instead of the report, list the time per client with a breakdown of
+        /// their projects, as plain text to paste into monthly client emails.
+        #[arg(long, conflicts_with_all = ["billing", "layout", "level", "rounding_impact"])]
+        by_client: bool,
+
+        /// Report on one month: YYYY-MM, this-month or last-month. The same as
+        /// --since and --until on its first and last day.
+        #[arg(long, value_name = "MONTH", value_parser = dates::parse_month, conflicts_with_all = ["since", "until"])]
+        month: Option<(chrono::NaiveDate, chrono::NaiveDate)>,
+
+        /// Include entries moved into yearly archives by `archive`.
+        #[arg(long)]
+        include_archived: bool,
+    },
+
+    /// Show everything about one entry, including its laps: the latest
+    /// entry, or the one with the given ID (or the start of it).
+    Explain {
+        id: Option<String>,
+    },
+
+    /// Split a logged entry in two, e.g. when a session covered two codes.
+    /// The part after the split gets the new task or code.
+    Split {
+        /// The entry's ID, or the start of it.
+        id: String,
+
+        /// Where to split: after a duration from the start, e.g. `45m`, or at
+        /// a time of day, e.g. `10:45`.
+        #[arg(long, value_name = "WHEN")]
+        at: edit::SplitPoint,
+
+        /// Task of the part after the split; the same as before if left out.
+        #[arg(short, long)]
+        task: Option<String>,
+
+        /// Code of the part after the split; the same as before if left out.
+        #[arg(short, long)]
+        code: Option<String>,
+    },
+
+    /// Merge entries of the same task and code into one that lasts as long
+    /// as all of them, e.g. fragments left by interruptions.
+    Merge {
+        /// IDs of the entries to merge, or the start of them.
+        #[arg(required_unless_present = "auto", num_args = 2..)]
+        ids: Vec<String>,
+
+        /// Merge the entries of each task and code on the same day, for the
+        /// days in `--since`/`--until` or all of them.
+        #[arg(long, conflicts_with = "ids")]
+        auto: bool,
+
+        #[command(flatten)]
+        range: DateRange,
+    },
+
+    /// Show total time, the average per day, the longest session, your
+    /// current daily streak and the busiest day.
+    Stats {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Include entries moved into yearly archives by `archive`.
+        #[arg(long)]
+        include_archived: bool,
+    },
+
+    /// Generate an invoice for a client and record it in the invoice ledger.
+    Invoice {
+        /// Client to invoice, as named in `billing.clients` in the config file.
+        /// Entries already billed on an earlier invoice are never included again.
+        #[arg(long)]
+        client: String,
+
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Round each code's billed time to this many minutes.
+        #[arg(long, value_name = "MINUTES")]
+        round_display: Option<u64>,
+
+        /// How billed durations are rounded when --round-display is given.
+        #[arg(long, value_enum, default_value_t = RoundMode::Nearest, requires = "round_display")]
+        round_mode: RoundMode,
+    },
+
+    /// Inspect issued invoices.
+    Invoices {
+        #[command(subcommand)]
+        action: InvoicesAction,
+    },
+
+    /// Push entries that haven't been sent yet to an external service.
+    #[cfg(feature = "integrations")]
+    Push {
+        #[command(subcommand)]
+        target: PushTargetArg,
+
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Show what would be pushed without sending anything.
+        #[arg(long, global = true)]
+        dry_run: bool,
+    },
+
+    /// Import history from another time tracker into the log.
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+
+        /// Show how many entries would be imported without changing the log.
+        #[arg(long, global = true)]
+        dry_run: bool,
+
+        /// Import nothing if any row can't be read, instead of skipping it.
+        #[arg(long, global = true)]
+        strict: bool,
+    },
+
+    /// Export logged time in another tool's format.
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+
+        #[command(flatten)]
+        filter: log::Filter,
+
+        /// Include entries moved into yearly archives by `archive`.
+        #[arg(long, global = true)]
+        include_archived: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print a setting by its dotted key, e.g. `billing.currency`.
+    Get { key: String },
+
+    /// Change a setting, e.g. `config set billing.default_rate 95`. Unknown keys
+    /// and values of the wrong type are rejected; comments in the file are kept.
+    Set { key: String, value: String },
+
+    /// Print the location of the config file.
+    Path,
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretAction {
+    /// Store a secret of the active profile, read from standard input (not
+    /// echoed on a terminal), replacing any stored before.
+    Set {
+        #[arg(value_parser = secret::parse_name)]
+        name: String,
+        /// Also point this setting at the secret, e.g. `jira.token`.
+        #[arg(long)]
+        config: Option<String>,
+    },
+
+    /// Remove a secret of the active profile from the keyring.
+    Delete {
+        #[arg(value_parser = secret::parse_name)]
+        name: String,
+    },
+
+    /// List the settings that refer to the keyring, and whether their
+    /// secret is stored.
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum InvoicesAction {
+    /// List all issued invoices.
+    List,
+}
+
+#[cfg(feature = "integrations")]
+#[derive(Subcommand, clap::ValueEnum, Clone, Copy, Debug)]
+enum PushTargetArg {
+    /// Post entries as Jira worklogs, using the code as the issue key (e.g. PROJ-123).
+    Jira,
+
+    /// Create Harvest time entries, mapping codes to projects and tasks via `[harvest.projects]`.
+    Harvest,
+
+    /// Create Redmine time entries, using the code as the issue ID (e.g. 1234).
+    Redmine,
+
+    /// Add YouTrack work items, using the code as the issue ID (e.g. PROJ-123).
+    Youtrack,
+
+    /// Comment entries on Linear issues, using the code as the issue (e.g. ENG-123)
+    /// or mapping it via `[linear.issues]`.
+    Linear,
+
+    /// Record entries on Asana tasks as time tracking entries or comments, using
+    /// the code as the task ID or mapping it via `[asana.tasks]`.
+    Asana,
+
+    /// Add entries as rows of a Notion database, with the columns from `[notion.columns]`.
+    Notion,
+
+    /// Append entries as rows of a Google Sheets spreadsheet (see `[gsheets]`).
+    Gsheets,
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportSource {
+    /// Timewarrior intervals: a data directory, a `.data` file or `timew export` JSON.
+    /// Defaults to `$TIMEWARRIORDB/data` or `~/.timewarrior/data`.
+    Timew {
+        path: Option<PathBuf>,
+    },
+
+    /// Watson frames (JSON). Defaults to Watson's `frames` file in the config directory.
+    Watson {
+        path: Option<PathBuf>,
+    },
+
+    /// A CSV time sheet from another tool or a spreadsheet, e.g. a Toggl or
+    /// Clockify report. Columns are found by their header names, such as
+    /// Start Date, Start Time, Duration, Project and Description.
+    Csv {
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportFormat {
+    /// CSV in Clockify's bulk time-entry import format.
+    Clockify {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Email of the Clockify user. Defaults to `clockify.email` from the config file.
+        #[arg(long)]
+        email: Option<String>,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Timewarrior data file lines, tagged with the code and the task.
+    Timew {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Timeclock check-in/out pairs for hledger and ledger (code as account, task as description).
+    Timeclock {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Emacs org-mode outline with CLOCK lines, grouped by code and task.
+    Org {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// iCalendar file with one event per session, to overlay tracked time on a calendar.
+    Ics {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Send entries as heartbeats to WakaTime or a compatible server (see
+    /// `[wakatime]` in the config file). Entries already sent are skipped.
+    #[cfg(feature = "integrations")]
+    Wakatime {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Show what would be sent without sending anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Static HTML site (summary, charts and weekly tables) for the one code
+    /// given with --code.
+    Site {
+        #[command(flatten)]
+        range: DateRange,
+
+        /// Folder to write the site to.
+        #[arg(short, long, value_name = "DIR")]
+        out: PathBuf,
+    },
+}
+
+/// The command-line interface, with codes called by `labels.code` from the
+/// config file in the help text.
+fn cli_command() -> clap::Command {
+    let labels = config::labels();
+    // The export filter is defined in the library, which can't offer the candidates
+    let command = Cli::command()
+        .mut_subcommand("export", |export| export.mut_arg("filter_code", |arg| arg.add(ArgValueCandidates::new(completions::codes))));
+    relabel(command, &labels)
+}
+
+/// Renames codes in the help of every `--code` option of `command` and its
+/// subcommands.
+fn relabel(command: clap::Command, labels: &config::LabelsConfig) -> clap::Command {
+    if labels.code == "Code" {
+        return command;
+    }
+    let names: Vec<String> = command.get_subcommands().map(|c| c.get_name().to_string()).collect();
+    let code = command.get_arguments().find(|arg| arg.get_long() == Some("code")).map(|arg| arg.get_id().clone());
+    let mut command = if let Some(code) = code {
+        command.mut_arg(code, |arg| {
+            let help = arg.get_help().map(|help| help.to_string().replace("code", &labels.code_lower()).replace("Code", &labels.code));
+            let arg = arg.value_name(labels.code.to_uppercase().replace(' ', "_"));
+            match help {
+                Some(help) => arg.help(help),
+                None => arg,
+            }
+        })
+    } else {
+        command
+    };
+    for name in names {
+        command = command.mut_subcommand(name, |sub| relabel(sub, labels));
+    }
+    command
 }
 
 fn main() {
+    // The config file is read to build the command line, so this can't wait for parsing
+    if std::env::args_os().skip(1).take_while(|arg| arg != "--").any(|arg| arg == "--portable") {
+        config::select_portable();
+    }
+    // Answer completion requests from the shell before anything else is printed
+    clap_complete::CompleteEnv::with_factory(cli_command)
+        .var(completions::VAR)
+        .complete();
+
     // Parse command-line arguments
-    let cli = Cli::parse();
+    let cli = Cli::from_arg_matches(&cli_command().get_matches()).unwrap_or_else(|e| e.exit());
+    // Task options belong to the default (start) command; `--profile` works with any
+    let track = &cli.track;
+    if cli.command.is_some() && (track.task.is_some() || track.code.is_some() || track.preset.is_some() || track.git || track.max.is_some()) {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "task options go after the subcommand, e.g. `start --task ...`",
+            )
+            .exit();
+    }
+    if let Some(profile) = cli.profile {
+        config::select_profile(profile);
+    }
+    if cli.quiet {
+        prompt::set_quiet();
+    }
+    if cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    if let Some(zone) = cli.timezone.or_else(config::timezone) {
+        match timezone::tz_value(&zone) {
+            // SAFETY: nothing else runs yet that could read the environment
+            Ok(Some(tz)) => unsafe { std::env::set_var("TZ", tz) },
+            Ok(None) => {},
+            Err(e) => exit(Error::Invalid(e)),
+        }
+    }
+    JSON_OUTPUT.store(cli.output == Output::Json, std::sync::atomic::Ordering::Relaxed);
+    // `status --format` runs every few seconds from status bars and prompts,
+    // and only reads the session state, so it never stops or logs anything
+    if !matches!(cli.command, Some(Command::Status { format: Some(_) })) {
+        stop_expired();
+        check_milestones();
+    }
 
-    // Determine the task name: use from args or prompt if missing
-    let task_name = match cli.task {
-        Some(t) => t, // Use task name from argument
+    match cli.command {
+        Some(command) => run_command(command),
         None => {
-            // Prompt user for task name
-            print!("Enter task name: ");
-            stdout().flush().expect("Failed to flush stdout"); // Ensure prompt appears before input
+            if check_running(cli.track.session.as_deref()) {
+                start(cli.track, false, None);
+            }
+        },
+    }
+}
+
+/// Warns when the session `name` is already running and asks whether to
+/// switch to the new one, stop the old one or leave it running. Returns
+/// whether to go on starting. Without a terminal to ask on, the old session
+/// is logged and replaced, as `switch` does.
+fn check_running(name: Option<&str>) -> bool {
+    let Some(running) = session::current(name).context("Failed to read session state").or_exit() else {
+        return true;
+    };
+    let elapsed = config::display().duration(running.elapsed_secs(), true);
+    let id = if running.pid.is_some() { "running-terminal" } else { "running-background" };
+    eprintln!("{}", messages::text(id, &[("task", &running.task), ("code", &running.code), ("duration", &elapsed)]));
+    if !prompt::interactive() {
+        eprintln!("Logging it before starting the new session.");
+        return true;
+    }
+    let choices = ["choice-switch", "choice-stop", "choice-abort"].map(|id| messages::text(id, &[("task", &running.task)]));
+    match prompt::choose(&messages::text("what-now", &[]), &choices) {
+        Some(0) => true,
+        Some(1) => {
+            match session::stop(name).context("Failed to stop the running session").or_exit() {
+                Some(entry) => print_stopped(&entry),
+                None => say!("{}", messages::text("already-stopped", &[])),
+            }
+            false
+        },
+        _ => exit(Error::Running(format!("Kept '{}' running; nothing was started.", running.task))),
+    }
+}
+
+/// Parses a duration option, also written out in the configured language.
+fn parse_span(value: &str) -> Result<u64, String> {
+    config::language().parse_span(value)
+}
 
-            let mut user_task = String::new();
-            stdin().read_line(&mut user_task)
-                 .expect("Failed to read task name from stdin");
+/// Parses a day option, also written out in the configured language.
+fn parse_day(value: &str) -> Result<chrono::NaiveDate, String> {
+    config::language().parse_day(value, Local::now().date_naive())
+}
 
-            user_task.trim().to_string() // Trim whitespace
-        }
+/// When a session started with `--ago` or `--at` began, or `None` to start
+/// it now. Exits with an error for a time in the future.
+fn backdate(ago: Option<u64>, at: Option<chrono::NaiveTime>) -> Option<DateTime<Local>> {
+    let now = Local::now();
+    let started = match (ago, at) {
+        (Some(secs), _) => TimeDelta::try_seconds(secs as i64).and_then(|ago| now.checked_sub_signed(ago)),
+        (None, Some(time)) => now.date_naive().and_time(time).and_local_timezone(Local).earliest(),
+        (None, None) => return None,
     };
+    match started {
+        Some(started) if started <= now => Some(started),
+        Some(started) => {
+            exit(Error::Invalid(format!("{} is still to come; the timer can only start earlier than now.", started.format("%H:%M"))));
+        },
+        None => {
+            exit(Error::Invalid("That start time doesn't exist.".into()));
+        },
+    }
+}
 
-    // Ensure task name is not empty, default to "Unnamed Task" if it is after trimming
-    let task_name = if task_name.is_empty() {
-        println!("Task name cannot be empty, using 'Unnamed Task'.");
-        "Unnamed Task".to_string()
+/// Starts a session, prompting for a missing task name or code. It counts
+/// from `started` when given, see [`backdate`].
+fn start(track: TrackArgs, detach: bool, started: Option<DateTime<Local>>) {
+    let mut track = track.resolve_preset();
+    let git = track.resolve_git();
+
+    // An opt-in guess from the directory and shell history, if confirmed
+    if track.task.is_none()
+        && let Some(guess) = infer_task()
+    {
+        track.task = Some(guess.task);
+        track.code = track.code.or(guess.code);
+    }
+
+    // Offer recent tasks before asking for a new one
+    if track.task.is_none()
+        && let Some((task, code)) = picker::pick_recent()
+    {
+        track.task = Some(task);
+        track.code = track.code.or(Some(code));
+    }
+
+    // Nobody would see the prompts, and a script would hang on them
+    if !prompt::can_ask() && (track.task.is_none() || track.code.is_none()) {
+        let missing = match (&track.task, &track.code) {
+            (None, None) => "--task and --code",
+            (None, Some(_)) => "--task",
+            _ => "--code",
+        };
+        exit(Error::Invalid(format!("Missing {}: nothing is asked with --quiet or when the output isn't a terminal.", missing)));
+    }
+
+    // Previously used values for completion and history in the prompts
+    let entries = if track.task.is_none() || track.code.is_none() {
+        open_storage().query(&DateRange::default()).unwrap_or_default()
     } else {
-        task_name
+        Vec::new()
     };
 
+    // Determine the task name: use from args or prompt if missing
+    let task_name = match track.task {
+        Some(t) => t, // Use task name from argument
+        None => prompt::ask(&messages::text("prompt-task", &[]), log::recent_values(&entries, |e| &e.task)),
+    };
 
     // Determine the code: use from args or prompt if missing
-    let code = match cli.code {
+    let code = match track.code {
         Some(c) => c, // Use code from argument
-        None => {
-            // Prompt user for code
-            print!("Enter code for this task: ");
-            stdout().flush().expect("Failed to flush stdout"); // Ensure prompt appears before input
+        None => prompt::ask(
+            &messages::text("prompt-code", &[("label", &config::labels().code_lower())]),
+            log::recent_values(&entries, |e| &e.code),
+        ),
+    };
 
-            let mut user_code = String::new();
-            stdin().read_line(&mut user_code)
-                 .expect("Failed to read code from stdin");
+    begin_session(track.session, task_name, code, git, detach, started, track.max).or_exit();
+}
 
-            user_code.trim().to_string() // Trim whitespace
-        }
+/// Guesses the task from the current directory and shell history when
+/// `[infer] enabled` is set, and asks whether to start it. Only asks on a
+/// terminal; returns `None` if the guess is declined.
+fn infer_task() -> Option<infer::Guess> {
+    let config = config::load().infer;
+    if !config.enabled || !prompt::interactive() {
+        return None;
+    }
+    let dir = std::env::current_dir().ok()?;
+    let history = if config.history { infer::read_history() } else { Vec::new() };
+    let guess = infer::guess(&dir, &history, &config)?;
+    let code = guess.code.as_ref().map(|code| format!(" ({})", code)).unwrap_or_default();
+    let answer = prompt::ask(&format!("Start '{}'{}? [Y/n]", guess.task, code), Vec::new());
+    (answer.is_empty() || answer.eq_ignore_ascii_case("y")).then_some(guess)
+}
+
+/// Records a new session under `name` (see [`Session::name`]), replacing
+/// defaults for an empty task or code, and either runs the live timer or
+/// returns straight away when `detach` is set. The session counts from
+/// `started` if given, and from now otherwise, and is stopped after `max`
+/// seconds, or the `max` from the config file.
+fn begin_session(
+    name: Option<String>,
+    task_name: String,
+    code: String,
+    git: Option<String>,
+    detach: bool,
+    started: Option<DateTime<Local>>,
+    max: Option<u64>,
+) -> Result<(), Error> {
+    // Ensure task name is not empty, default to "Unnamed Task" if it is after trimming
+    let task_name = if task_name.trim().is_empty() {
+        say!("Task name cannot be empty, using 'Unnamed Task'.");
+        "Unnamed Task".to_string()
+    } else {
+        task_name
     };
 
     // Ensure code is not empty, default to "NA" if it is after trimming
-    let code = if code.is_empty() {
-        println!("Code cannot be empty, using 'NA'.");
+    let code = if code.trim().is_empty() {
+        say!("{} cannot be empty, using 'NA'.", config::labels().code);
         "NA".to_string()
     } else {
-        code
-    };
-
-    println!("Tracking task '{}' with code '{}'. Press Ctrl+C to stop.", task_name, code);
-    let start_time = Instant::now();
-
-    // Clone the final task_name and code to move them into the closure
-    let task_name_clone = task_name.clone();
-    let code_clone = code.clone();
-
-    // Set up Ctrl+C handler
-    ctrlc::set_handler(move || {
-        let duration_secs = start_time.elapsed().as_secs();
-        let total_minutes = duration_secs / 60;
-        let hours = total_minutes / 60;
-        let minutes = total_minutes % 60;
-        let seconds = duration_secs % 60; // Still needed for console output
-
-        // Log to console (keep showing seconds here for immediate feedback)
-        println!("\nStopped. Time spent on task '{}' (Code: {}): {}h {}m {}s", task_name_clone, code_clone, hours, minutes, seconds);
-
-        // Get home directory and create path for time log (CSV)
-        let mut log_path = dirs::home_dir().expect("Could not find home directory");
-        log_path.push("time_log.csv");
-
-        // Create CSV with headers if needed
-        create_csv_with_headers_if_needed(&log_path);
-
-        // Escape task name for CSV (replace quotes with double quotes)
-        let escaped_task_name = task_name_clone.replace("\"", "\"\"");
-        // Escape code for CSV
-        let escaped_code = code_clone.replace("\"", "\"\"");
-
-        // Format as CSV: date, time, code, task name, duration (hours), duration (minutes), duration (seconds)
-        let log_entry = format!(
-            "{},{},\"{}\",\"{}\",{},{},{}\n",
-            chrono::Local::now().format("%Y-%m-%d"),
-            chrono::Local::now().format("%H:%M:%S"), // Keep precise time of logging
-            escaped_code, // Use the escaped code
-            escaped_task_name,
-            hours,
-            minutes,
-            seconds // Include seconds in the log entry
-        );
+        config::load().expand_code(code.trim())
+    };
+
+    // Only one session of a name runs at a time: log the previous one, up to
+    // where the new one starts, before replacing it
+    let stopped = session::stop_at(name.as_deref(), started.unwrap_or_else(Local::now));
+    if let Some(previous) = stopped.context("Failed to stop the running session")? {
+        print_json(&previous);
+        let spent = config::display().duration(previous.duration_secs, true);
+        say!("{}", messages::text("stopped-previous", &[("task", &previous.task), ("duration", &spent)]));
+    }
+
+    let config = config::load();
+    for usage in budget_usage(&config).iter().filter(|u| u.is_over() && budget::covers(&u.key, &code)) {
+        eprintln!("Over budget: {}.", usage);
+    }
+    check_budget_alerts(&config);
 
-        let mut file = OpenOptions::new()
-            .append(true)
-            .create(true) // Ensure file is created if it doesn't exist after header check
-            .open(&log_path)
-            .expect("Failed to open log file");
+    let mut session = Session::new(name, task_name, code, git, !detach);
+    if let Some(started) = started {
+        session.started = started;
+    }
+    session.max_secs = max.or(config.max);
+    session::start(&session).context("Failed to save session state")?;
+
+    if detach {
+        let stop = match &session.name {
+            Some(name) => format!("stop {}", name),
+            None => "stop".to_string(),
+        };
+        let label = config::labels().code_lower();
+        say!("{}", messages::text("tracking-detached", &[("task", &session.task), ("label", &label), ("code", &session.code), ("stop", &stop)]));
+    } else {
+        run_timer(session);
+    }
+    Ok(())
+}
+
+/// Set while Ctrl+C stops the foreground session, which then exits.
+static STOPPING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 
-        // Note: std::io::Write was imported as IoWrite, but file.write_all uses the trait implicitly.
-        file.write_all(log_entry.as_bytes())
-            .expect("Failed to write to log file");
+/// Shows the live timer for a foreground session until it is stopped, either
+/// with Ctrl+C or from another shell.
+fn run_timer(mut session: Session) {
+    // The live timer is only for a terminal: elsewhere its `\r` updates pile up
+    let json = JSON_OUTPUT.load(std::sync::atomic::Ordering::Relaxed);
+    let live = !prompt::quiet() && if json { stderr().is_terminal() } else { stdout().is_terminal() };
+    // Pressing `l` records a lap, after asking for its name
+    let laps = live.then(|| keys::listen(|key, term| match key {
+        console::Key::Char('l' | 'L') => {
+            let at = Local::now();
+            let _ = term.clear_line();
+            let _ = term.write_str("Lap name (Enter to number it): ");
+            Some((at, term.read_line().unwrap_or_default()))
+        },
+        _ => None,
+    })).flatten();
+    // Read once: the live display is redrawn every second
+    let texts = messages::Messages::load();
+    let display = config::display();
+    let hint = if laps.is_some() { texts.text("tracking-laps", &[]) } else { String::new() };
+    let label = config::labels().code_lower();
+    say!("{}", texts.text("tracking", &[("task", &session.task), ("label", &label), ("code", &session.code), ("laps", &hint)]));
 
-        std::process::exit(0);
-    }).expect("Error setting Ctrl+C handler");
+    // Log the session when stopped with Ctrl+C, `kill` or by closing the terminal
+    let name = session.name.clone();
+    shutdown::on_exit(move || {
+        keys::restore();
+        STOPPING.store(true, std::sync::atomic::Ordering::SeqCst);
+        match session::stop(name.as_deref()) {
+            Ok(Some(entry)) => {
+                say!();
+                print_stopped(&entry);
+            },
+            Ok(None) => say!("\n{}", messages::text("already-stopped", &[])),
+            Err(e) => {
+                eprintln!();
+                exit(Error::io("Failed to log the session", e));
+            },
+        }
+    });
 
     // Keep the program running and display elapsed time
+    let config = config::load();
+    let mut previous = session.elapsed_secs();
+    // Breaks count all of today's tracked time, not just this session's
+    let today = Local::now().date_naive();
+    let logged_today: u64 = if config.breaks.every.is_some() {
+        let range = DateRange { since: Some(today), until: Some(today) };
+        open_storage().query(&range).unwrap_or_default().iter().map(|e| e.duration_secs).sum()
+    } else {
+        0
+    };
     loop {
-        let elapsed = start_time.elapsed();
-        let total_seconds = elapsed.as_secs();
+        let current = session::current(session.name.as_deref()).ok().flatten();
+        if !current.as_ref().is_some_and(|current| current.same_run(&session)) {
+            // Ctrl+C removes the state file before logging; let it finish
+            while STOPPING.load(std::sync::atomic::Ordering::SeqCst) {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            keys::restore();
+            say!("\n{}", texts.text("stopped-elsewhere", &[]));
+            std::process::exit(0);
+        }
+        // Laps recorded with `lap` from another shell
+        if let Some(current) = current.filter(|current| current.laps.len() > session.laps.len()) {
+            for (index, lap) in current.laps.iter().enumerate().skip(session.laps.len()) {
+                let previous = index.checked_sub(1).map_or(current.started, |previous| current.laps[previous].at);
+                let secs = (lap.at - previous).num_seconds().max(0) as u64;
+                say!("\rLap {} '{}': {}", index + 1, lap.name, display.duration(secs, true));
+            }
+            session = current;
+        }
 
-        // Calculate hours, minutes, and seconds
-        let hours = total_seconds / 3600;
-        let minutes = (total_seconds % 3600) / 60;
-        let seconds = total_seconds % 60;
+        if session.deadline().is_some_and(|deadline| deadline <= Local::now()) {
+            keys::restore();
+            if let Some(entry) = session::stop(session.name.as_deref()).context("Failed to stop the running session").or_exit() {
+                say!("\x07");
+                report_limit(&session, &entry);
+            }
+            std::process::exit(0);
+        }
 
-        // Format the time string (hh:mm:ss)
-        let time_str = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
+        let total_seconds = session.elapsed_secs();
+        if let Some(reached) = config.alerts.crossed(&session.code, previous, total_seconds) {
+            let message = milestone_message(&config, &session, reached);
+            say!("\x07\n{}", message);
+            notify::send("Timer", &message);
+        }
+        if config.breaks.due(logged_today + previous, logged_today + total_seconds) {
+            say!("\x07\n{}", config.breaks.message);
+            notify::send("Break", &config.breaks.message);
+        }
+        // The running session counts towards budgets too
+        if total_seconds / 60 > previous / 60 {
+            check_budget_alerts(&config);
+        }
+        previous = total_seconds;
 
-        // Print on the same line using carriage return \r
-        // Keep the original tracking message and append elapsed time
-        // Print on the same line using carriage return \r and green color
-        // Keep the original tracking message and append elapsed time in green
-        print!("\r\x1b[32mElapsed: {}\x1b[0m", time_str); // \x1b[32m is ANSI green, \x1b[0m resets color
-        stdout().flush().expect("Failed to flush stdout");
+        // hh:mm:ss unless `[display]` says otherwise, with how far the session is towards its
+        // maximum duration, the time left and when it ends if it has one
+        let mut time_str = display.elapsed(total_seconds);
+        if let (Some(max), Some(deadline)) = (session.max_secs, session.deadline()) {
+            time_str.push_str(&format!(
+                "  {}  {} left, ends {}",
+                duration::progress_bar(total_seconds, max, 20),
+                display.elapsed(max.saturating_sub(total_seconds)),
+                deadline.format(display.time_format(false))
+            ));
+        }
 
-        // Sleep for 1 second
-        std::thread::sleep(std::time::Duration::from_secs(1));
-    }
-}
+        // Print on the same line using carriage return \r, green turning yellow and red as the session
+        // runs long, unless a lap is being named. Colors are left out with `--no-color` or NO_COLOR, and
+        // where the console doesn't take ANSI codes, such as legacy Windows consoles
+        if live && !laps.as_ref().is_some_and(|(_, naming)| naming.load(std::sync::atomic::Ordering::SeqCst)) {
+            let elapsed = console::style(texts.text("elapsed", &[("time", &time_str)])).fg(display.elapsed_color(total_seconds));
+            let (mut out, elapsed): (Box<dyn IoWrite>, _) = if json { (Box::new(stderr()), elapsed.for_stderr()) } else { (Box::new(stdout()), elapsed) };
+            write!(out, "\r{}", elapsed).and_then(|()| out.flush()).context("Failed to write the timer").or_exit();
+        }
 
-fn create_csv_with_headers_if_needed(path: &PathBuf) {
-    // Check if file exists and is empty
-    let file_exists = path.exists();
-    let file_empty = if file_exists {
-        match File::open(path) {
-            Ok(mut file) => {
-                let mut content = String::new();
-                // Check if read results in 0 bytes read, indicating empty or error
-                file.read_to_string(&mut content).unwrap_or(0) == 0 && content.is_empty()
+        // Wait a second, or until a lap is recorded
+        let lap = match &laps {
+            Some((recorded, _)) => recorded.recv_timeout(std::time::Duration::from_secs(1)).ok(),
+            None => {
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                None
             },
-            Err(_) => true, // Treat error opening as if file needs creation/headers
+        };
+        if let Some((at, name)) = lap {
+            // Only while the session still runs, or a stopped one would come back
+            if session::current(session.name.as_deref()).ok().flatten().is_some_and(|current| current.same_run(&session))
+                && let Some((current, secs)) = session::record_lap(session.name.as_deref(), Some(name), at).context("Failed to save session state").or_exit()
+            {
+                session = current;
+                let lap = session.laps.last().expect("A lap was just added");
+                say!("\rLap {} '{}': {}", session.laps.len(), lap.name, display.duration(secs, true));
+            }
         }
-    } else {
-        true // File doesn't exist, needs creation and headers
-    };
+    }
+}
 
-    // Create file with headers if it doesn't exist or is empty
-    if !file_exists || file_empty {
-        match File::create(path) {
-            Ok(mut file) => {
-                // Update headers to include Seconds
-                let headers = "Date,Time,Code,Task,Hours,Minutes,Seconds\n";
-                // Note: std::io::Write was imported as IoWrite, but file.write_all uses the trait implicitly.
-                file.write_all(headers.as_bytes()).expect("Failed to write headers");
-            },
-            Err(e) => {
-                eprintln!("Failed to create CSV file '{}': {}", path.display(), e);
-                // Optionally exit or handle the error differently
-                std::process::exit(1);
+/// Stops and logs the sessions that have reached their maximum duration.
+fn stop_expired() {
+    match session::stop_expired() {
+        Ok(stopped) => {
+            for (session, entry) in stopped {
+                report_limit(&session, &entry);
             }
+        },
+        Err(e) => eprintln!("Failed to stop sessions that reached their limit: {}", e),
+    }
+}
+
+/// Nudges, with a desktop notification, about the milestones detached
+/// sessions reached since the last check; sessions running in a terminal
+/// nudge on their own.
+fn check_milestones() {
+    let config = config::load();
+    if !config.alerts.has_milestones() {
+        return;
+    }
+    let sessions = session::all().unwrap_or_default();
+    let path = milestone::sent_path();
+    let mut sent = milestone::read_sent(&path).unwrap_or_default();
+    let checked = sent.clone();
+    // Forget the sessions that have stopped
+    sent.retain(|key, _| sessions.iter().any(|s| milestone::key(s) == *key));
+    for session in sessions.iter().filter(|s| s.pid.is_none()) {
+        if let Some(reached) = milestone::due(&config.alerts, session, Local::now(), &mut sent) {
+            notify::send("Timer", &milestone_message(&config, session, reached));
         }
     }
+    if sent != checked
+        && let Err(e) = milestone::write_sent(&path, &sent)
+    {
+        eprintln!("Failed to save the milestone state: {}", e);
+    }
+}
+
+/// The nudge for `session` reaching a milestone, with today's total
+/// (running sessions included) and the status of its budgets.
+fn milestone_message(config: &config::Config, session: &Session, reached: u64) -> String {
+    let today = Local::now().date_naive();
+    let range = DateRange { since: Some(today), until: Some(today) };
+    let logged: u64 = open_storage().query(&range).unwrap_or_default().iter().map(|e| e.duration_secs).sum();
+    let running: u64 = session::all().unwrap_or_default().iter().map(Session::elapsed_secs).sum();
+    milestone::message(session, reached, logged + running, &budget_usage(config))
+}
+
+/// Tells the user, also with a desktop notification, that a session was
+/// stopped at its maximum duration.
+fn report_limit(session: &Session, entry: &log::Entry) {
+    let display = config::display();
+    let message = format!(
+        "'{}' ({}) reached its limit of {} and was logged as stopped at {}.",
+        entry.task,
+        entry.code,
+        display.duration(session.max_secs.unwrap_or_default(), false),
+        entry.end().format(display.time_format(false))
+    );
+    eprintln!("{}", message);
+    notify::send("Timer stopped", &message);
+}
+
+/// Stops the running session, or starts a detached one without prompting.
+fn toggle(track: TrackArgs) {
+    match session::stop(track.session.as_deref()).context("Failed to stop the running session").or_exit() {
+        Some(entry) => print_stopped(&entry),
+        None => {
+            let mut track = track.resolve_preset();
+            let git = track.resolve_git();
+            begin_session(track.session, track.task.unwrap_or_default(), track.code.unwrap_or_default(), git, true, None, track.max).or_exit();
+        },
+    }
+}
+
+/// Starts a session for the task and code of the latest entry. A session
+/// already running under `name` is logged first, unless it is that task.
+fn again(name: Option<String>, detach: bool) {
+    let entries = open_storage().query(&DateRange::default()).context("Failed to read the log").or_exit();
+    let Some(last) = entries.into_iter().max_by_key(|e| e.end()) else {
+        exit(Error::Invalid("Nothing has been logged yet. Start a task with `start`.".into()));
+    };
+    let running = session::current(name.as_deref()).context("Failed to read session state").or_exit();
+    if running.is_some_and(|s| s.task == last.task && s.code == last.code) {
+        say!("'{}' ({}) is already running.", last.task, last.code);
+        return;
+    }
+    begin_session(name, last.task, last.code, last.git, detach, None, None).or_exit();
+}
+
+/// Reads the configured hotkeys from `devices` (every keyboard if none)
+/// until Ctrl+C. Each keyboard is read on its own thread; actions run here,
+/// one at a time.
+fn listen_hotkeys(devices: Vec<PathBuf>) {
+    let parse = |config: &config::Config| {
+        config
+            .hotkeys
+            .iter()
+            .map(|(keys, action)| keys.parse::<hotkey::Hotkey>().map(|hotkey| (hotkey, *action)))
+            .collect::<Result<std::collections::HashMap<_, _>, _>>()
+    };
+    let mut hotkeys = parse(&config::load()).unwrap_or_else(|e| {
+        exit(Error::Invalid(format!("Invalid [hotkeys] in the config file: {}.", e)));
+    });
+    if hotkeys.is_empty() {
+        exit(Error::Invalid("No hotkeys configured. Add e.g. `\"ctrl+alt+p\" = \"pause\"` under [hotkeys] in the config file.".into()));
+    }
+    let devices = if devices.is_empty() { hotkey::find_keyboards() } else { devices };
+    if devices.is_empty() {
+        exit(Error::Other("No keyboard found. Pass one with --device, e.g. --device /dev/input/event3.".into()));
+    }
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for device in devices {
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let result = hotkey::listen(&device, |pressed| {
+                let _ = sender.send(pressed);
+            });
+            if let Err(e) = result {
+                let hint = if e.kind() == std::io::ErrorKind::PermissionDenied { " (is your user in the 'input' group?)" } else { "" };
+                eprintln!("Failed to read from '{}': {}{}", device.display(), e, hint);
+            }
+        });
+    }
+    drop(sender);
+    let mut names: Vec<_> = hotkeys.iter().map(|(hotkey, action)| format!("{} ({:?})", hotkey, action).to_lowercase()).collect();
+    names.sort();
+    println!("Listening for {}. Press Ctrl+C to quit.", names.join(", "));
+
+    let mut watcher = config::Watcher::new();
+    // Ends once no keyboard can be read
+    for pressed in receiver {
+        if let Some((reloaded, changed)) = watcher.poll() {
+            match parse(&reloaded) {
+                Ok(parsed) => {
+                    print_reloaded(&changed);
+                    hotkeys = parsed;
+                },
+                Err(e) => eprintln!("Ignoring the changed config file: invalid [hotkeys]: {}.", e),
+            }
+        }
+        let Some(action) = hotkeys.get(&pressed) else { continue };
+        match session::stop(None).context("Failed to stop the running session").or_exit() {
+            Some(entry) => {
+                print_stopped(&entry);
+                let verb = if *action == hotkey::Action::Pause { "Paused" } else { "Stopped" };
+                notify::send(&format!("Timer {}", verb.to_lowercase()), &format!("{} '{}' ({}).", verb, entry.task, entry.code));
+            },
+            None if *action == hotkey::Action::Pause => {
+                let entries = open_storage().query(&DateRange::default()).unwrap_or_default();
+                let Some(last) = entries.into_iter().max_by_key(|e| e.end()) else {
+                    eprintln!("Nothing has been logged yet, so there is nothing to resume.");
+                    continue;
+                };
+                notify::send("Timer resumed", &format!("Resumed '{}' ({}).", last.task, last.code));
+                begin_session(None, last.task, last.code, last.git, true, None, None).or_exit();
+            },
+            None => println!("No session is running."),
+        }
+    }
+    exit(Error::Other("No keyboard can be read anymore.".into()));
+}
+
+/// Serves the local API on `port` (or `serve.port`) until Ctrl+C. The
+/// config file is read for each request, so a changed token or origin
+/// applies straight away.
+fn run_serve(port: Option<u16>, take_over: bool) {
+    let config = config::load().serve;
+    let port = port.unwrap_or(config.port);
+    let token: Vec<(&str, String)> = config.token.iter().map(|token| ("Authorization", format!("Bearer {}", token))).collect();
+    let (listener, handed_over) = http::bind_or_take_over(port, take_over.then_some(&token)).unwrap_or_else(|e| {
+        exit(Error::Other(format!("Failed to listen on port {}: {}", port, e)));
+    });
+    if handed_over.is_some() {
+        println!("Took over from the previous server.");
+    }
+    println!("Serving the API on http://127.0.0.1:{}. Press Ctrl+C to stop.", port);
+    let handing_over = std::cell::Cell::new(false);
+    http::serve_until(
+        listener,
+        |request| {
+            let response = match api::route(&request, &config::load().serve) {
+                Ok(api::Call::HandOver) => {
+                    handing_over.set(true);
+                    http::Response::json(200, &serde_json::json!({}))
+                },
+                Ok(call) => answer(call),
+                Err(refused) => refused,
+            };
+            api::allow_origin(response, &request)
+        },
+        || handing_over.get(),
+    )
+    .context("API server failed")
+    .or_exit();
+    println!("Handed over to a new server.");
+}
+
+/// Carries out an API call.
+fn answer(call: api::Call) -> http::Response {
+    stop_expired();
+    check_milestones();
+    let sessions = |name: Option<&str>| session::all().map(|all| all.into_iter().filter(|s| s.name.as_deref() == name).collect::<Vec<_>>());
+    match call {
+        api::Call::Status => match session::all() {
+            Ok(sessions) => http::Response::json(200, &status::to_json(&sessions, Local::now())),
+            Err(e) => api::error(500, format!("Failed to read session state: {}", e)),
+        },
+        api::Call::Start { task, code, session } => {
+            if let Err(e) = begin_session(session.clone(), task, code, None, true, None, None) {
+                let status = match e {
+                    Error::Invalid(_) => 400,
+                    Error::Running(_) => 409,
+                    Error::Io { .. } | Error::Other(_) => 500,
+                };
+                return api::error(status, e.to_string());
+            }
+            match sessions(session.as_deref()) {
+                Ok(started) => http::Response::json(201, &status::to_json(&started, Local::now())),
+                Err(e) => api::error(500, format!("Failed to read session state: {}", e)),
+            }
+        },
+        api::Call::Stop { session } => match session::stop(session.as_deref()) {
+            Ok(Some(entry)) => {
+                print_stopped(&entry);
+                http::Response::json(200, &api::entry_json(&entry))
+            },
+            Ok(None) => api::error(409, "No session is running"),
+            Err(e) => api::error(500, format!("Failed to log the session: {}", e)),
+        },
+        api::Call::Entries(range) => match open_storage().query(&range) {
+            Ok(entries) => http::Response::json(200, &log::to_local(entries).iter().map(api::entry_json).collect()),
+            Err(e) => api::error(500, format!("Failed to read the log: {}", e)),
+        },
+        api::Call::HandOver => unreachable!("run_serve hands over itself"),
+    }
+}
+
+/// Shows the tray icon until Quit is chosen from its menu, refreshing the
+/// elapsed time every second and the recent tasks every ten.
+fn run_tray() {
+    let mut tray = tray::Tray::spawn().unwrap_or_else(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => exit(Error::Other("The tray icon is shown by yad; install it first, e.g. `sudo apt install yad`.".into())),
+        _ => exit(Error::Other(format!("Failed to show the tray icon: {}", e))),
+    });
+    // Menu actions run this program, with the same profile and data
+    let mut program = vec![std::env::current_exe().map(|p| p.display().to_string()).unwrap_or_else(|_| "simple_timer".to_string())];
+    if config::portable_dir().is_some() {
+        program.push("--portable".to_string());
+    }
+    if let Some(profile) = config::profile() {
+        program.extend(["--profile".to_string(), profile]);
+    }
+    println!("Showing the tray icon. Choose Quit from its menu or press Ctrl+C to quit.");
+
+    let mut recent = Vec::new();
+    for tick in 0u64.. {
+        if !tray.is_open() {
+            break;
+        }
+        stop_expired();
+        check_milestones();
+        if tick % 10 == 0 {
+            let entries = open_storage().query(&DateRange::default()).unwrap_or_default();
+            recent = tray::recent_tasks(&entries, tray::RECENT_TASKS);
+        }
+        let sessions = session::all().unwrap_or_default();
+        if tray.show(&tray::commands(&program, &sessions, &recent, Local::now())).is_err() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Lists the running sessions, one per line.
+fn print_status() {
+    let sessions = session::all().context("Failed to read session state").or_exit();
+    if sessions.is_empty() {
+        println!("{}", messages::text("no-session", &[]));
+    }
+    let width = sessions.iter().filter_map(|s| s.name.as_ref()).map(|n| n.chars().count()).max().unwrap_or(0).max(7);
+    let usage = budget_usage(&config::load());
+    let display = config::display();
+    for running in sessions {
+        println!(
+            "{:<width$}  {}  '{}' ({})",
+            running.name.as_deref().unwrap_or("default"),
+            display.duration(running.elapsed_secs(), true),
+            running.task,
+            running.code,
+            width = width
+        );
+        for usage in usage.iter().filter(|u| budget::covers(&u.key, &running.code)) {
+            println!("{:<width$}  {}", "", usage, width = width);
+        }
+    }
+}
+
+/// How much of each budget in the config file has been used, counting the
+/// running sessions.
+fn budget_usage(config: &config::Config) -> Vec<budget::Usage> {
+    if config.budgets.is_empty() {
+        return Vec::new();
+    }
+    let entries = storage::open().and_then(|storage| storage.query(&DateRange::default())).unwrap_or_else(|e| {
+        eprintln!("Failed to read the log for budgets: {}", e);
+        Vec::new()
+    });
+    let sessions = session::all().unwrap_or_default();
+    let running: Vec<(&str, u64)> = sessions.iter().map(|s| (s.code.as_str(), s.elapsed_secs())).collect();
+    budget::usage(&config.budgets, &entries, Local::now().date_naive(), &running)
+}
+
+/// Prints the logged entry as JSON on stdout with `--output json`.
+fn print_json(entry: &log::Entry) {
+    if JSON_OUTPUT.load(std::sync::atomic::Ordering::Relaxed) {
+        println!("{}", api::entry_json(entry));
+    }
+}
+
+/// Prints the summary shown when a session has been stopped and logged.
+fn print_stopped(entry: &log::Entry) {
+    print_json(entry);
+    // Keep showing seconds here for immediate feedback
+    let spent = config::display().duration(entry.duration_secs, true);
+    let label = config::labels().code;
+    say!("{}", messages::text("stopped", &[("task", &entry.task), ("label", &label), ("code", &entry.code), ("duration", &spent)]));
+    print_day_summary(entry.date);
+    check_budget_alerts(&config::load());
+}
+
+/// Alerts through the configured channels, once per period, for each budget
+/// that has reached one of the thresholds in `[alerts] budgets`.
+fn check_budget_alerts(config: &config::Config) {
+    if config.budgets.is_empty() || config.alerts.budgets.is_empty() {
+        return;
+    }
+    let path = budget::alerts_path();
+    let mut state = match budget::read_alerts(&path) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Failed to read '{}': {}", path.display(), e);
+            return;
+        },
+    };
+    let today = Local::now().date_naive();
+    let mut alerted = false;
+    for usage in budget_usage(config) {
+        let Some(percent) = budget::threshold_reached(&usage, &config.alerts.budgets, today, state.get(&usage.key)) else {
+            continue;
+        };
+        let title = format!("Budget {}% used", percent);
+        let body = usage.to_string();
+        eprintln!("{}: {}.", title, body);
+        let details = serde_json::json!({
+            "event": "budget",
+            "code": usage.key,
+            "percent": percent,
+            "used_secs": usage.used_secs,
+            "budget": usage.budget.to_string(),
+            "budget_secs": usage.budget.secs,
+        });
+        for failure in notify::broadcast(&config.channels, &title, &body, details) {
+            eprintln!("Failed to send the budget alert to {}", failure);
+        }
+        let since = usage.budget.period.range(today).since.expect("Budget periods have a first day");
+        state.insert(usage.key, budget::Alerted { since, percent });
+        alerted = true;
+    }
+    if alerted && let Err(e) = budget::write_alerts(&path, &state) {
+        eprintln!("Failed to write '{}': {}", path.display(), e);
+    }
+}
+
+/// Prints the time logged on `day` per code and in total, which includes
+/// the session just stopped.
+fn print_day_summary(day: chrono::NaiveDate) {
+    let range = DateRange { since: Some(day), until: Some(day) };
+    let entries = match open_storage().query(&range) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read today's entries: {}", e);
+            return;
+        },
+    };
+    let totals = report::totals(&entries, &range);
+    let per_code: Vec<(&str, u64)> = totals.iter().map(|(code, tasks)| (*code, tasks.values().sum())).collect();
+    let total: u64 = per_code.iter().map(|(_, secs)| secs).sum();
+    let language = config::language();
+    let display = config::display();
+    let label = if day == Local::now().date_naive() { messages::text("today", &[]) } else { language.format_date(day, language.date_format()) };
+    say!("{}", messages::text("day-total", &[("day", &label), ("total", &display.duration(total, false))]));
+    for (code, secs) in per_code {
+        say!("  {:<30} {}", code, display.duration(secs, false));
+    }
+}
+
+/// Reports which settings changed when a long-running command reloads the config.
+fn print_reloaded(changed: &[String]) {
+    println!("Reloaded the config file; changed: {}.", changed.join(", "));
+}
+
+/// Runs a subcommand instead of starting the timer.
+fn run_command(command: Command) {
+    match command {
+        Command::Start { track, detach, ago, at } => {
+            let started = backdate(ago, at);
+            if check_running(track.session.as_deref()) {
+                start(track, detach, started);
+            }
+        },
+        Command::Again { session, detach } => again(session, detach),
+        Command::Stop { session } => match session::stop(session.as_deref()).context("Failed to stop the running session").or_exit() {
+            Some(entry) => print_stopped(&entry),
+            None => {
+                match &session {
+                    Some(name) => say!("No session named '{}' is running.", name),
+                    None => say!("{}", messages::text("no-session", &[])),
+                }
+                let named: Vec<String> = session::all().unwrap_or_default().into_iter().filter_map(|s| s.name).collect();
+                if !named.is_empty() {
+                    say!("Running named sessions: {} (stop one with `stop <NAME>`).", named.join(", "));
+                }
+            },
+        },
+        Command::Lap { name, session } => {
+            match session::record_lap(session.as_deref(), name, Local::now()).context("Failed to save session state").or_exit() {
+                Some((running, secs)) => {
+                    let lap = running.laps.last().expect("A lap was just added");
+                    say!("Lap {} '{}' of '{}': {}", running.laps.len(), lap.name, running.task, config::display().duration(secs, true));
+                },
+                None => exit(Error::Invalid(match &session {
+                    Some(name) => format!("No session named '{}' is running.", name),
+                    None => messages::text("no-session", &[]),
+                })),
+            }
+        },
+        Command::Stopwatch => stopwatch::run(),
+        Command::Status { format: None } => print_status(),
+        Command::Status { format: Some(format) } => {
+            let sessions = session::all().context("Failed to read session state").or_exit();
+            let output = status::render(&sessions, format, Local::now());
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+        },
+        Command::Toggle { track } => toggle(track),
+        Command::Switch { track } => start(track, true, None),
+        Command::Serve { port, take_over } => run_serve(port, take_over),
+        Command::Activity { port, take_over } => {
+            let config = config::load().activity;
+            let port = port.unwrap_or(config.port);
+            let headers = match take_over {
+                true => activity::handover_headers(port).unwrap_or_default(),
+                false => Vec::new(),
+            };
+            let (listener, handed_over) = http::bind_or_take_over(port, take_over.then_some(&headers)).unwrap_or_else(|e| {
+                exit(Error::Other(format!("Failed to listen on port {}: {}", port, e)));
+            });
+            let secret = activity::new_handover_secret(port).context("Failed to write the handover secret").or_exit();
+            let tracker = std::sync::Arc::new(std::sync::Mutex::new(activity::Tracker::new(config)));
+            if let Some(state) = handed_over {
+                match tracker.lock().unwrap().adopt(&state) {
+                    Ok(count) => println!("Took over from the previous listener with {} open stretch(es).", count),
+                    Err(e) => eprintln!("Took over from the previous listener, but its stretches were lost: {}", e),
+                }
+            }
+
+            // Close stretches once their idle timeout passes, even if no further heartbeats arrive
+            let idle_tracker = tracker.clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(std::time::Duration::from_secs(30));
+                idle_tracker.lock().unwrap().close_idle();
+            });
+            // Apply edits to the [activity] settings without a restart
+            let watch_tracker = tracker.clone();
+            let mut watcher = config::Watcher::new();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(std::time::Duration::from_secs(2));
+                if let Some((config, changed)) = watcher.poll() {
+                    print_reloaded(&changed);
+                    if changed.iter().any(|key| key == "activity.port") {
+                        eprintln!("The new port takes effect when `activity` is restarted.");
+                    }
+                    watch_tracker.lock().unwrap().set_config(config.activity);
+                }
+            });
+            let exit_tracker = tracker.clone();
+            shutdown::on_exit(move || exit_tracker.lock().unwrap().close_all());
+
+            println!("Listening for heartbeats on http://127.0.0.1:{}/api/v1. Press Ctrl+C to stop.", port);
+            http::serve_until(
+                listener,
+                |request| activity::handle(&mut tracker.lock().unwrap(), request, &secret),
+                || tracker.lock().unwrap().handed_over(),
+            )
+            .context("Heartbeat server failed")
+            .or_exit();
+            println!("Handed over to a new listener.");
+            std::process::exit(0);
+        },
+        Command::Badges { device } => {
+            let config = config::load();
+            // Catch typos up front rather than on the first scan
+            for (id, badge) in &config.badges {
+                if !config.presets.contains_key(&badge.preset) {
+                    exit(Error::Invalid(format!("Badge '{}' uses unknown preset '{}'.", id, badge.preset)));
+                }
+            }
+            println!("Waiting for badges on '{}'. Press Ctrl+C to quit.", device.display());
+            let mut config = config;
+            let mut watcher = config::Watcher::new();
+            let result = badge::listen(&device, |id| {
+                if let Some((reloaded, changed)) = watcher.poll() {
+                    print_reloaded(&changed);
+                    config = reloaded;
+                }
+                let Some(badge) = config.badges.get(id) else {
+                    eprintln!("Unknown badge '{}'.", id);
+                    return;
+                };
+                let track = TrackArgs { task: None, code: None, preset: Some(badge.preset.clone()), git: false, session: None, max: None }
+                    .resolve_preset();
+                let task = track.task.unwrap_or_default();
+                let code = config.expand_code(&track.code.unwrap_or_default());
+
+                let running = session::current(None).context("Failed to read session state").or_exit();
+                if running.is_some_and(|s| s.task == task && s.code == code) {
+                    if let Some(entry) = session::stop(None).context("Failed to stop the running session").or_exit() {
+                        print_stopped(&entry);
+                    }
+                } else {
+                    begin_session(None, task, code, None, true, None, None).or_exit();
+                }
+            });
+            if let Err(e) = result {
+                exit(Error::Other(format!("Failed to read from '{}': {}", device.display(), e)));
+            }
+        },
+        Command::Hotkeys { device } => listen_hotkeys(device),
+        Command::Tray => run_tray(),
+        #[cfg(feature = "hardware")]
+        Command::Buttons => {
+            let config = config::load();
+            if config.hardware.buttons.is_empty() {
+                exit(Error::Invalid("No buttons configured. Add [[hardware.buttons]] entries to the config file.".into()));
+            }
+            println!("Listening on {} button(s). Press Ctrl+C to quit.", config.hardware.buttons.len());
+            let result = hardware::listen(&config.hardware, |button| {
+                let track = TrackArgs { task: None, code: None, preset: button.preset.clone(), git: false, session: None, max: None };
+                match button.action {
+                    config::ButtonAction::Toggle => toggle(track),
+                    config::ButtonAction::Switch => {
+                        let track = track.resolve_preset();
+                        begin_session(None, track.task.unwrap_or_default(), track.code.unwrap_or_default(), None, true, None, None).or_exit();
+                    },
+                }
+            });
+            if let Err(e) = result {
+                exit(Error::Other(format!("GPIO error: {}", e)));
+            }
+        },
+        Command::Recover => run_recover(),
+        Command::Bench { runs } => bench::run(runs).or_exit(),
+        Command::Health { self_test } => {
+            let mut healthy = health::report();
+            if self_test {
+                healthy &= health::self_test();
+            }
+            if !healthy {
+                exit(Error::Other("Some checks failed.".into()));
+            }
+        },
+        Command::Config { action } => match action {
+            ConfigAction::Get { key } => match config::get(&key) {
+                Ok(Some(value)) => println!("{}", value),
+                Ok(None) => exit(Error::Other(format!("'{}' has no value.", key))),
+                Err(e) => exit(Error::Invalid(e)),
+            },
+            ConfigAction::Set { key, value } => config::set(&key, &value).map_err(Error::Invalid).or_exit(),
+            ConfigAction::Path => println!("{}", config::config_path().display()),
+        },
+        Command::Secret { action } => run_secret(action),
+        Command::Migrate => {
+            if let Some(path) = config::load().log {
+                println!("The log location is set in the config file ('{}'); nothing to migrate.", path.display());
+                return;
+            }
+            match log::migrate_legacy() {
+                Ok(Some((from, to))) => println!("Moved '{}' to '{}'.", from.display(), to.display()),
+                Ok(None) => println!("No log to migrate; the log is at '{}'.", log::default_log_path().display()),
+                Err(e) => exit(Error::io("Failed to migrate the log", e)),
+            }
+        },
+        Command::Onboard { storage, yes } => run_onboard(storage, yes),
+        Command::Stats { range, include_archived } => {
+            let entries = logged(&range, include_archived);
+            print_stats(&stats::Stats::new(&entries, Local::now().date_naive()));
+        },
+        Command::Completions { shell } => {
+            completions::write_registration(shell, &mut stdout())
+                .context("Failed to write completion script")
+                .or_exit();
+        },
+        Command::Archive { .. } if config::load().storage != config::StorageBackend::Csv => {
+            println!("Archiving applies to the CSV log only; an SQLite database stays fast as it grows.");
+        },
+        Command::Archive { before } => match archive::archive(&log_path(), before) {
+            Ok((0, _)) => println!("No entries before {} to archive.", before),
+            Ok((moved, written)) => {
+                println!("Archived {} entries:", moved);
+                for path in written {
+                    println!("  {}", path.display());
+                }
+            },
+            Err(e) => exit(Error::io("Failed to archive entries", e)),
+        },
+        Command::Diff { since } => run_diff(since),
+        Command::Review { week } => run_review(week),
+        Command::Report { range, round_display, round_mode, billing, layout, level, rounding_impact, by_client, month, include_archived } => {
+            let config = config::load();
+            let entries = report::roll_up(&logged(&DateRange::default(), include_archived), level, &config);
+            let range = month.map_or(range, |(first, last)| DateRange { since: Some(first), until: Some(last) });
+            let mut options = report::ReportOptions { range, round_display, round_mode };
+            if by_client {
+                report::print_by_client(&entries, &options, &config);
+            } else if billing {
+                billing::print_billing_report(&entries, &options, &config);
+            } else {
+                let layout = layout.unwrap_or(config.report.layout);
+                layout.apply_rounding(&mut options);
+                if rounding_impact {
+                    if options.round_display.is_none() {
+                        exit(Error::Invalid("Nothing is rounded: pass --round-display or a --layout that rounds, such as client.".into()));
+                    }
+                    report::print_rounding_impact(&entries, &options, &config);
+                    return;
+                }
+                report::print_report(&entries, &options, layout, &config);
+                let usage = budget_usage(&config);
+                if !usage.is_empty() {
+                    println!("\nBudgets");
+                    for usage in usage {
+                        println!("  {}", usage);
+                    }
+                }
+                print_laps(&entries, &options.range);
+            }
+        },
+        Command::Explain { id } => explain(id.as_deref()),
+        Command::Split { id, at, task, code } => split_entry(&id, at, task, code),
+        Command::Merge { ids, auto, range } => merge_entries(&ids, auto, &range),
+        Command::Invoice { client, range, round_display, round_mode } => {
+            let config = config::load();
+            let options = report::ReportOptions { range, round_display, round_mode };
+            match invoice::create_invoice(open_storage().as_mut(), &client, &options, &config) {
+                Ok(issued) => println!(
+                    "Invoice {} for '{}': {:.2} {} written to '{}'.",
+                    issued.number, issued.client, issued.gross, issued.currency, issued.file.display()
+                ),
+                Err(e) => {
+                    exit(Error::Other(format!("Failed to create invoice: {}", e)));
+                }
+            }
+        },
+        Command::Invoices { action: InvoicesAction::List } => {
+            let ledger = invoice::read_ledger(&invoice::ledger_path())
+                .context("Failed to read invoice ledger")
+                .or_exit();
+            invoice::print_list(&ledger);
+        },
+        #[cfg(feature = "integrations")]
+        Command::Push { target, range, dry_run } => run_push(target, &range, dry_run),
+        Command::Import { source, dry_run, strict } => run_import(source, dry_run, strict),
+        Command::Export { format, filter, include_archived } => run_export(format, filter, include_archived),
+    }
+}
+
+/// Runs the `diff` command.
+fn run_diff(since: Option<chrono::NaiveDate>) {
+    let storage = open_storage();
+    let log_path = log_path();
+    let since = since.unwrap_or_else(|| chrono::Local::now().date_naive());
+    let Some((taken, path)) = snapshot::find(&log_path, since).context("Failed to read the snapshots").or_exit() else {
+        println!("There are no snapshots of the log yet.");
+        return;
+    };
+    let before = snapshot::read(&path).context(&format!("Failed to read '{}'", path.display())).or_exit();
+    let after = storage.history(&DateRange::default()).context("Failed to read log file").or_exit();
+    let changes = snapshot::diff(&before, &after);
+
+    if taken > since {
+        println!("The oldest snapshot is from {}; comparing with that.", taken);
+    }
+    if changes.is_empty() {
+        println!("No changes since the snapshot of {}.", taken);
+        return;
+    }
+    println!("Changes since the snapshot of {}:", taken);
+    let describe = |e: &log::Entry| {
+        let e = e.to_local();
+        format!("{} {}  {}  '{}'  {}", e.date, e.time.format("%H:%M:%S"), e.code, e.task, duration::format_hms(e.duration_secs, true))
+    };
+    for entry in &changes.added {
+        println!("+ {}", describe(entry));
+    }
+    for (old, new) in &changes.edited {
+        println!("~ {}", describe(new));
+        let field = |name: &str, old: String, new: String| {
+            if old != new {
+                println!("    {}: {} -> {}", name, old, new);
+            }
+        };
+        field("stopped", old.end().to_string(), new.end().to_string());
+        field(&config::labels().code_lower(), old.code.clone(), new.code.clone());
+        field("task", format!("'{}'", old.task), format!("'{}'", new.task));
+        field("duration", duration::format_hms(old.duration_secs, true), duration::format_hms(new.duration_secs, true));
+        field("invoice", old.invoice.clone().unwrap_or_default(), new.invoice.clone().unwrap_or_default());
+        field("git", old.git.clone().unwrap_or_default(), new.git.clone().unwrap_or_default());
+    }
+    for entry in &changes.deleted {
+        println!("- {}", describe(entry));
+    }
+    println!("{} added, {} edited, {} deleted.", changes.added.len(), changes.edited.len(), changes.deleted.len());
+}
+
+/// Prints the laps recorded in `range` per code and task, if there are any.
+fn print_laps(entries: &[log::Entry], range: &DateRange) {
+    let laps = laps::read(&laps::laps_path()).unwrap_or_else(|e| {
+        eprintln!("Failed to read the laps: {}", e);
+        Default::default()
+    });
+    let totals = laps::totals(entries, &laps, range);
+    if totals.is_empty() {
+        return;
+    }
+    println!("\nLaps");
+    for ((code, task), names) in totals {
+        println!("  {} / {}", code, task);
+        for (name, (count, secs)) in names {
+            println!("    {:<30} {:>3}x {:>12}", name, count, duration::format_hms(secs, true));
+        }
+    }
+}
+
+/// Splits the entry with ID `id` at `at`, giving the second part `task` and
+/// `code` if given.
+fn split_entry(id: &str, at: edit::SplitPoint, task: Option<String>, code: Option<String>) {
+    let mut storage = open_storage();
+    let entries = storage.query(&DateRange::default()).context("Failed to read the log").or_exit();
+    let original = log::find_by_id(&entries, id).map_err(|e| Error::Invalid(format!("{}.", e))).or_exit();
+    if original.is_locked() {
+        exit(Error::Invalid("This entry has been invoiced and can't be changed.".to_string()));
+    }
+    let secs = at.secs_into(original).map_err(|e| Error::Invalid(format!("Can't split there: {}.", e))).or_exit();
+    let code = code.map(|code| config::load().expand_code(&code));
+    let (first, second) = edit::split(original, secs, task, code);
+    let display = config::display();
+    let described = [describe_entry(&first, &display), describe_entry(&second, &display)];
+    storage
+        .update(&mut |entries| {
+            let index = entries.iter().position(|e| e == original).ok_or_else(|| std::io::Error::other("The entry was changed elsewhere meanwhile; nothing was saved."))?;
+            entries.splice(index..=index, [first.clone(), second.clone()]);
+            Ok(())
+        })
+        .context("Failed to save the log")
+        .or_exit();
+    println!("Split into:\n  {}\n  {}", described[0], described[1]);
+}
+
+/// Merges the entries with the IDs `ids`, or with `auto` the fragments of
+/// each task in `range`.
+fn merge_entries(ids: &[String], auto: bool, range: &DateRange) {
+    let mut storage = open_storage();
+    let entries = storage.query(&DateRange::default()).context("Failed to read the log").or_exit();
+    let groups = if auto {
+        let in_range: Vec<log::Entry> = entries.iter().filter(|e| range.includes(e)).cloned().collect();
+        edit::fragments(&in_range).into_iter().map(|group| group.into_iter().cloned().collect()).collect()
+    } else {
+        let mut group: Vec<log::Entry> = Vec::new();
+        for id in ids {
+            let entry = log::find_by_id(&entries, id).map_err(|e| Error::Invalid(format!("{}.", e))).or_exit();
+            if entry.is_locked() {
+                exit(Error::Invalid(format!("'{}' has been invoiced and can't be changed.", entry.task)));
+            }
+            if !group.contains(entry) {
+                group.push(entry.clone());
+            }
+        }
+        if group.len() < 2 {
+            exit(Error::Invalid("Give the IDs of at least two entries to merge.".to_string()));
+        }
+        group.sort_by_key(|e| e.start());
+        vec![group]
+    };
+    if groups.is_empty() {
+        println!("Nothing to merge.");
+        return;
+    }
+    let mut merges = Vec::new();
+    for group in groups {
+        let merged = edit::merge(&group.iter().collect::<Vec<_>>()).map_err(|e| Error::Invalid(format!("Can't merge them: {}.", e))).or_exit();
+        merges.push((group, merged));
+    }
+    storage
+        .update(&mut |entries| {
+            for (group, merged) in &merges {
+                for (index, entry) in group.iter().enumerate() {
+                    let position = entries.iter().position(|e| e == entry).ok_or_else(|| std::io::Error::other("An entry was changed elsewhere meanwhile; nothing was saved."))?;
+                    // The merged entry takes the place of the earliest
+                    if index == 0 {
+                        entries[position] = merged.clone();
+                    } else {
+                        entries.remove(position);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .context("Failed to save the log")
+        .or_exit();
+    let display = config::display();
+    for (group, merged) in &merges {
+        println!("Merged {} entries into: {}", group.len(), describe_entry(merged, &display));
+    }
+}
+
+/// Prints the details of the entry with ID `id`, or of the latest entry,
+/// with its laps.
+fn explain(id: Option<&str>) {
+    let entries = open_storage().history(&DateRange::default()).context("Failed to read the log").or_exit();
+    let entries = log::to_local(entries);
+    let entry = match id {
+        Some(id) => log::find_by_id(&entries, id).unwrap_or_else(|e| {
+            exit(Error::Invalid(format!("{}.", e)));
+        }),
+        None => entries.iter().max_by_key(|e| e.end()).unwrap_or_else(|| {
+            exit(Error::Invalid("Nothing has been logged yet.".into()));
+        }),
+    };
+    println!("{:<10} {}", "Task", entry.task);
+    println!("{:<10} {}", config::labels().code, entry.code);
+    let display = config::display();
+    let time = display.time_format(true);
+    println!("{:<10} {} to {}", "Time", entry.start().format(&format!("%Y-%m-%d {}", time)), entry.end().format(time));
+    println!("{:<10} {}", "Duration", display.duration(entry.duration_secs, true));
+    if let Some(git) = &entry.git {
+        println!("{:<10} {}", "Git", git);
+    }
+    println!("{:<10} {}", "Invoice", entry.invoice.as_deref().unwrap_or("not invoiced"));
+    println!("{:<10} {}", "ID", entry.id);
+
+    let laps = laps::read(&laps::laps_path()).unwrap_or_else(|e| {
+        eprintln!("Failed to read the laps: {}", e);
+        Default::default()
+    });
+    let Some(segments) = laps.get(&entry.id) else { return };
+    let share = |secs: u64| (secs * 100).checked_div(entry.duration_secs).unwrap_or(0);
+    println!("\nLaps");
+    for segment in segments {
+        println!("  {:<30} {:>12} {:>4}%", segment.name, duration::format_hms(segment.secs, true), share(segment.secs));
+    }
+    let rest = entry.duration_secs.saturating_sub(segments.iter().map(|s| s.secs).sum());
+    if rest > 0 {
+        println!("  {:<30} {:>12} {:>4}%", "(after the last lap)", duration::format_hms(rest, true), share(rest));
+    }
+}
+
+fn print_stats(stats: &stats::Stats) {
+    if stats.entries == 0 {
+        println!("No entries found.");
+        return;
+    }
+    let days = |count: usize| format!("{} day{}", count, if count == 1 { "" } else { "s" });
+    let display = config::display();
+    println!(
+        "{:<20} {} ({} entries over {})",
+        "Total tracked",
+        display.duration(stats.total_secs, false),
+        stats.entries,
+        days(stats.days)
+    );
+    println!("{:<20} {}", "Average per workday", display.duration(stats.average_secs(), false));
+    if let Some(longest) = &stats.longest {
+        println!(
+            "{:<20} {} on {}: {} {}",
+            "Longest session",
+            display.duration(longest.duration_secs, true),
+            longest.date,
+            longest.code,
+            longest.task
+        );
+    }
+    println!("{:<20} {} (longest: {})", "Current streak", days(stats.current_streak), days(stats.longest_streak));
+    if let Some((day, secs)) = stats.busiest_day {
+        let language = config::language();
+        let day = language.format_date(day, &format!("%a {}", language.date_format()));
+        println!("{:<20} {} ({})", "Busiest day", day, display.duration(secs, false));
+    }
+}
+
+/// Walks through moving a legacy log into the storage of the active
+/// profile; see [`onboard`].
+fn run_onboard(storage: Option<config::StorageBackend>, yes: bool) {
+    let config = config::load();
+    if let Some(path) = &config.log {
+        println!("The log location is set in the config file ('{}'); nothing to onboard.", path.display());
+        return;
+    }
+    let legacy = log::legacy_log_path().context("Failed to find the log").or_exit();
+    let others = onboard::other_legacy_logs();
+    if !legacy.exists() {
+        println!("No log at '{}'; the log is at '{}'.", legacy.display(), log::default_log_path().display());
+        for (profile, path) in &others {
+            match profile {
+                Some(profile) => println!("Run `onboard --profile {}` for '{}'.", profile, path.display()),
+                None => println!("Run `onboard` without a profile for '{}'.", path.display()),
+            }
+        }
+        return;
+    }
+
+    let analysis = std::fs::read_to_string(&legacy).and_then(|content| onboard::analyze(&content, &legacy));
+    let analysis = analysis.context(&format!("Failed to read '{}'", legacy.display())).or_exit();
+    let before = onboard::Totals::of(&analysis.entries);
+    println!("Found '{}':", legacy.display());
+    println!("  Columns: {} ({})", analysis.header.join(","), analysis.variant());
+    match analysis.span() {
+        Some((first, last)) => println!(
+            "  {} entries from {} to {}, {} in total",
+            before.entries(),
+            first,
+            last,
+            duration::format_hms(before.secs(), false)
+        ),
+        None => println!("  No entries"),
+    }
+    if analysis.skipped > 0 {
+        println!("  {} malformed row(s) that will be left out; they stay in the backup", analysis.skipped);
+    }
+
+    let backends: Vec<config::StorageBackend> = [config::StorageBackend::Csv]
+        .into_iter()
+        .chain(cfg!(feature = "sqlite").then_some(config::StorageBackend::Sqlite))
+        .collect();
+    let backend = match storage {
+        Some(backend) => backend,
+        None if backends.len() > 1 && prompt::interactive() => {
+            let items: Vec<String> = backends
+                .iter()
+                .map(|backend| {
+                    let name = match backend {
+                        config::StorageBackend::Csv => "CSV log",
+                        config::StorageBackend::Sqlite => "SQLite database",
+                    };
+                    format!("{}{}", name, if *backend == config.storage { " (current)" } else { "" })
+                })
+                .collect();
+            match prompt::choose("Keep the entries in", &items) {
+                Some(index) => backends[index],
+                None => return,
+            }
+        },
+        None => config.storage,
+    };
+    if !backends.contains(&backend) {
+        exit(Error::Invalid("SQLite storage needs a build with the `sqlite` feature.".into()));
+    }
+    let target = log::default_log_path();
+    let location = match backend {
+        config::StorageBackend::Csv => target.clone(),
+        config::StorageBackend::Sqlite => target.with_extension("sqlite"),
+    };
+    if !yes && !prompt::ask(&format!("Copy them to '{}'? [y/N]", location.display()), Vec::new()).eq_ignore_ascii_case("y") {
+        println!("Nothing changed.");
+        return;
+    }
+
+    let fail = |what: &str, e: &dyn std::fmt::Display| -> ! {
+        exit(Error::Other(format!("Failed to {}: {}", what, e)));
+    };
+    let backup = onboard::backup(&legacy).unwrap_or_else(|e| fail("back up the log", &e));
+    println!("Backed up '{}' to '{}'.", legacy.display(), backup.display());
+    let target_backup = location.exists().then(|| onboard::backup(&location).unwrap_or_else(|e| fail("back up the existing storage", &e)));
+    if let Some(path) = &target_backup {
+        println!("Backed up '{}' to '{}'.", location.display(), path.display());
+    }
+
+    let mut target_storage: Box<dyn storage::Storage> = match backend {
+        config::StorageBackend::Csv => Box::new(storage::log_file::LogFile::new(target.clone())),
+        #[cfg(feature = "sqlite")]
+        config::StorageBackend::Sqlite => {
+            let fresh = !location.exists();
+            let mut database = storage::sqlite::Sqlite::open(&location).unwrap_or_else(|e| fail("open the database", &e));
+            // Carry over the CSV log of the profile too, as switching storage would
+            if fresh && target.exists() {
+                let entries = log::read_entries(&target).and_then(|entries| storage::Storage::append_all(&mut database, &entries).map(|_| entries));
+                let entries = entries.unwrap_or_else(|e| fail("copy the CSV log into the database", &e));
+                println!("Copied {} entries from '{}'.", entries.len(), target.display());
+            }
+            Box::new(database)
+        },
+        #[cfg(not(feature = "sqlite"))]
+        config::StorageBackend::Sqlite => unreachable!("Checked before backing up"),
+    };
+    let existing = target_storage.query(&DateRange::default()).unwrap_or_else(|e| fail("read the existing storage", &e));
+    let added = onboard::new_entries(&existing, &analysis.entries);
+    let expected = onboard::Totals::of(&[existing.clone(), added.clone()].concat());
+    let written = target_storage.append_all(&added).and_then(|_| target_storage.query(&DateRange::default()));
+    let after = written.as_deref().map(onboard::Totals::of).unwrap_or_default();
+    let mismatches = after.mismatches(&expected);
+    if written.is_err() || !mismatches.is_empty() {
+        match written {
+            Err(e) => eprintln!("Failed to copy the entries: {}", e),
+            Ok(_) => eprintln!("Totals don't match after copying, for: {}", mismatches.join(", ")),
+        }
+        drop(target_storage);
+        let restored = match &target_backup {
+            Some(path) => std::fs::copy(path, &location).map(|_| ()),
+            None => std::fs::remove_file(&location),
+        };
+        if let Err(e) = restored {
+            eprintln!("Failed to restore '{}': {}", location.display(), e);
+        }
+        exit(Error::Other(format!("'{}' was left as it was.", legacy.display())));
+    }
+
+    if existing.is_empty() {
+        println!("Copied {} entries; totals match ({}).", added.len(), duration::format_hms(after.secs(), false));
+    } else {
+        println!(
+            "Copied {} entries ({} were already there); totals match ({} entries, {}).",
+            added.len(),
+            analysis.entries.len() - added.len(),
+            after.entries(),
+            duration::format_hms(after.secs(), false)
+        );
+    }
+    if backend != config.storage {
+        let key = match config::profile() {
+            Some(profile) => format!("profiles.{}.storage", profile),
+            None => "storage".to_string(),
+        };
+        let value = clap::ValueEnum::to_possible_value(&backend).map(|v| v.get_name().to_string()).unwrap_or_default();
+        match config::set(&key, &value) {
+            Ok(()) => println!("Set `{} = \"{}\"` in the config file.", key, value),
+            Err(e) => eprintln!("{}; set `{} = \"{}\"` by hand.", e, key, value),
+        }
+    }
+    match std::fs::remove_file(&legacy) {
+        Ok(()) => println!("Removed '{}'; the backup is kept.", legacy.display()),
+        Err(e) => eprintln!("Failed to remove '{}': {}", legacy.display(), e),
+    }
+}
+
+/// Manages the secrets in the keyring.
+fn run_secret(action: SecretAction) {
+    let fail = |e: String| -> ! { exit(Error::Other(e)) };
+    match action {
+        SecretAction::Set { name, config: key } => {
+            let value = read_secret(&name).unwrap_or_else(|e| fail(format!("Failed to read the secret: {}", e)));
+            if value.is_empty() {
+                fail("The secret is empty; nothing was stored.".to_string());
+            }
+            secret::set(&name, &value).unwrap_or_else(|e| fail(format!("Failed to store '{}': {}", name, e)));
+            let reference = format!("{}{}", secret::PREFIX, name);
+            match key {
+                Some(key) => {
+                    config::set(&key, &reference).unwrap_or_else(|e| fail(e));
+                    println!("Stored '{}' in the keyring; {} now refers to it.", name, key);
+                },
+                None => println!("Stored '{}' in the keyring; refer to it as \"{}\" in the config file.", name, reference),
+            }
+        },
+        SecretAction::Delete { name } => match secret::delete(&name) {
+            Ok(true) => println!("Removed '{}' from the keyring.", name),
+            Ok(false) => println!("'{}' is not in the keyring.", name),
+            Err(e) => fail(format!("Failed to remove '{}': {}", name, e)),
+        },
+        SecretAction::List => {
+            let references = secret::references(&config::read_table());
+            if references.is_empty() {
+                println!("No setting refers to the keyring.");
+            }
+            for (key, name) in references {
+                let state = match secret::get(&name) {
+                    Ok(Some(_)) => "stored".to_string(),
+                    Ok(None) => "missing".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                println!("{:<32} {:<20} {}", key, name, state);
+            }
+        },
+    }
+}
+
+/// Reads a secret from standard input: one line, without echoing it, on a
+/// terminal, else everything piped in.
+fn read_secret(name: &str) -> std::io::Result<String> {
+    use std::io::{IsTerminal, Read};
+
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        let mut value = String::new();
+        stdin.lock().read_to_string(&mut value)?;
+        return Ok(value.trim_end_matches(['\r', '\n']).to_string());
+    }
+    print!("Value of '{}': ", name);
+    stdout().flush()?;
+    let echo = |setting: &str| std::process::Command::new("stty").arg(setting).stdin(std::process::Stdio::inherit()).status();
+    let hidden = echo("-echo").is_ok_and(|status| status.success());
+    let mut value = String::new();
+    let read = stdin.read_line(&mut value);
+    if hidden {
+        let _ = echo("echo");
+        println!();
+    }
+    read?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Goes through the quarantine: asks for what couldn't be salvaged from a
+/// session's state and logs or resumes it, repairs a damaged CSV log, then
+/// offers to delete the backups left.
+fn run_recover() {
+    use chrono::TimeZone;
+
+    // Reading the state quarantines whatever is corrupt
+    if let Err(e) = session::all() {
+        eprintln!("Failed to read the running sessions: {}", e);
+    }
+    if let Err(e) = budget::read_alerts(&budget::alerts_path()) {
+        eprintln!("Failed to read the budget alert state: {}", e);
+    }
+    let config = config::load();
+    let labels = config::labels();
+    let interactive = prompt::interactive();
+    let entries = open_storage().query(&DateRange::default()).unwrap_or_default();
+    let mut found = false;
+
+    for file in recover::list().context("Failed to read the quarantine").or_exit() {
+        let Some(name) = file.session().filter(|_| !file.restored) else {
+            continue;
+        };
+        found = true;
+        let content = match std::fs::read(&file.path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Failed to read '{}': {}", file.path.display(), e);
+                continue;
+            },
+        };
+        let mut salvaged = recover::salvage(&content);
+        let which = name.map_or("The running session".to_string(), |name| format!("Session '{}'", name));
+        let lost = || "(lost)".to_string();
+        println!("\n{} was quarantined at {} ('{}'):", which, file.at.format("%Y-%m-%d %H:%M"), file.path.display());
+        println!("  Task: {}", salvaged.task.clone().unwrap_or_else(lost));
+        println!("  {}: {}", labels.code, salvaged.code.clone().unwrap_or_else(lost));
+        println!("  Started: {}", salvaged.started.map_or_else(lost, |t| t.format("%Y-%m-%d %H:%M").to_string()));
+        if !interactive {
+            continue;
+        }
+
+        if salvaged.task.is_none() {
+            salvaged.task = Some(prompt::ask("Task", log::recent_values(&entries, |e| &e.task))).filter(|t| !t.trim().is_empty());
+        }
+        if salvaged.code.is_none() {
+            let code = prompt::ask(&labels.code, log::recent_values(&entries, |e| &e.code));
+            salvaged.code = Some(config.expand_code(&code)).filter(|c| !c.trim().is_empty());
+        }
+        if salvaged.started.is_none() {
+            let date = file.at.date();
+            let started = ask_time(&format!("Started at (on {})", date), date, None);
+            salvaged.started = Local.from_local_datetime(&started).earliest();
+        }
+        let (Some(session), Some(quarantined)) = (salvaged.session(name), Local.from_local_datetime(&file.at).earliest()) else {
+            continue;
+        };
+        let end = session.deadline().map_or(quarantined, |deadline| deadline.min(quarantined));
+        let actions = [format!("Log it, ending at {}", end.format("%H:%M")), "Resume it".to_string(), "Leave it".to_string()];
+        let result = match prompt::choose("Recover it?", &actions) {
+            Some(0) => {
+                let entry = session.to_entry(end);
+                open_storage()
+                    .append(&entry)
+                    .and_then(|()| laps::record(&laps::laps_path(), &entry.id, &session.segments(end)))
+                    .map(|()| print_stopped(&entry))
+            },
+            Some(1) if session::current(name).ok().flatten().is_some() => {
+                eprintln!("{} is running again already; stop it to resume this one.", which);
+                continue;
+            },
+            Some(1) => session::start(&session).map(|()| {
+                println!("Resumed '{}' ({}), running since {}.", session.task, session.code, session.started.format("%Y-%m-%d %H:%M"));
+            }),
+            _ => continue,
+        };
+        if let Err(e) = result.and_then(|()| std::fs::remove_file(&file.path)) {
+            eprintln!("Failed to recover '{}': {}", file.path.display(), e);
+        }
+    }
+
+    if config.storage == config::StorageBackend::Csv {
+        let path = log_path();
+        if let Ok(content) = std::fs::read(&path) {
+            let damage = recover::check_log(&content, &path).context("Failed to read log file").or_exit();
+            if damage.is_damaged() {
+                found = true;
+                let mut problems = Vec::new();
+                if damage.invalid_utf8 {
+                    problems.push("bytes that aren't valid UTF-8".to_string());
+                }
+                if damage.malformed > 0 {
+                    problems.push(format!("{} row(s) that can't be read", damage.malformed));
+                }
+                println!("\nThe log '{}' has {}.", path.display(), problems.join(" and "));
+                let question = format!("Rewrite it with the {} entries that can be read? A copy is kept. [y/N]", damage.entries.len());
+                if interactive && prompt::ask(&question, Vec::new()).eq_ignore_ascii_case("y") {
+                    match recover::back_up(&path).and_then(|backup| log::lock(&path)?.write_repaired(&damage.entries).map(|()| backup)) {
+                        Ok(backup) => println!("Rewrote the log; the original is in '{}'.", backup.display()),
+                        Err(e) => eprintln!("Failed to rewrite the log: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    let quarantined = recover::list().context("Failed to read the quarantine").or_exit();
+    let (sessions, backups): (Vec<_>, Vec<_>) = quarantined.into_iter().partition(|q| !q.restored && q.session().is_some());
+    if !interactive && !sessions.is_empty() {
+        println!("\nRun `timer recover` in a terminal to log or resume them.");
+    }
+    if backups.is_empty() {
+        if !found {
+            println!("Nothing to recover.");
+        }
+        return;
+    }
+    println!("\n{} backup(s) of repaired or unneeded files in '{}'.", backups.len(), recover::quarantine_dir().display());
+    if interactive && prompt::ask("Delete them? [y/N]", Vec::new()).eq_ignore_ascii_case("y") {
+        for backup in backups {
+            if let Err(e) = std::fs::remove_file(&backup.path) {
+                eprintln!("Failed to delete '{}': {}", backup.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Runs the `review` wizard. Without a terminal, the days and what was
+/// flagged on them are only printed.
+fn run_review(week: review::Week) {
+    let config = config::load();
+    let range = week.range(Local::now().date_naive());
+    let monday = range.since.expect("A week has a first day");
+    let mut storage = open_storage();
+    let earlier = storage
+        .history(&DateRange { since: None, until: monday.pred_opt() })
+        .context("Failed to read log file")
+        .or_exit();
+    let known = review::known_codes(&earlier, &config);
+    let language = config::language();
+    let heading = format!("%A {}", language.date_format());
+    let display = config::display();
+
+    let mut flagged = 0;
+    for day in monday.iter_days().take(7) {
+        loop {
+            let entries = log::to_local(storage.query(&range).context("Failed to read log file").or_exit());
+            let day_entries = review::day_entries(&entries, day);
+            let anomalies = review::check_day(day, &day_entries, &known);
+            let total: u64 = day_entries.iter().map(|e| e.duration_secs).sum();
+            println!("\n{}  {}", language.format_date(day, &heading), display.duration(total, false));
+            for entry in &day_entries {
+                println!("  {}", describe_entry(entry, &display));
+            }
+            for anomaly in &anomalies {
+                println!("  ! {}", anomaly);
+            }
+            if !prompt::interactive() {
+                flagged += anomalies.len();
+                break;
+            }
+            let actions = ["Next day", "Edit an entry", "Delete an entry", "Add an entry"].map(String::from);
+            let result = match prompt::choose("Fix anything?", &actions) {
+                Some(0) => {
+                    flagged += anomalies.len();
+                    break;
+                },
+                Some(1) => review_edit(storage.as_mut(), &day_entries),
+                Some(2) => review_delete(storage.as_mut(), &day_entries),
+                Some(3) => review_add(storage.as_mut(), day, &entries),
+                _ => {
+                    println!("Review stopped.");
+                    return;
+                },
+            };
+            if let Err(e) = result {
+                eprintln!("{}", e);
+            }
+        }
+    }
+    println!("\n{} thing(s) left flagged in the week of {}.", flagged, monday);
+    if !prompt::interactive() {
+        return;
+    }
+
+    let actions = vec!["Done".to_string(), "Show the report for the week".to_string()];
+    #[cfg(feature = "integrations")]
+    let targets: Vec<PushTargetArg> = <PushTargetArg as clap::ValueEnum>::value_variants()
+        .iter()
+        .copied()
+        .filter(|t| push_target(*t, &config).is_ok())
+        .collect();
+    #[cfg(feature = "integrations")]
+    let actions: Vec<String> = actions
+        .into_iter()
+        .chain(targets.iter().filter_map(|t| Some(format!("Push the week to {}", clap::ValueEnum::to_possible_value(t)?.get_name()))))
+        .collect();
+    loop {
+        match prompt::choose("Submit the week?", &actions) {
+            Some(1) => {
+                let entries = log::to_local(storage.query(&range).context("Failed to read log file").or_exit());
+                let mut options = report::ReportOptions { range: range.clone(), ..Default::default() };
+                config.report.layout.apply_rounding(&mut options);
+                report::print_report(&entries, &options, config.report.layout, &config);
+            },
+            #[cfg(feature = "integrations")]
+            Some(i) if i >= 2 => {
+                run_push(targets[i - 2], &range, false);
+                return;
+            },
+            _ => return,
+        }
+    }
+}
+
+/// One line describing an entry in the review: its times, code, task and
+/// duration, and the invoice it is locked by.
+fn describe_entry(entry: &log::Entry, display: &config::DisplayConfig) -> String {
+    let entry = entry.to_local();
+    let mut line = format!(
+        "{}-{}  {}  '{}'  {}",
+        entry.start().format(display.time_format(false)),
+        entry.end().format(display.time_format(false)),
+        entry.code,
+        entry.task,
+        display.duration(entry.duration_secs, false)
+    );
+    if let Some(invoice) = &entry.invoice {
+        line.push_str(&format!("  (invoiced on {})", invoice));
+    }
+    line
+}
+
+/// Lets the user pick one of `entries`, or `None` if there are none or
+/// they pressed Esc.
+fn pick_entry<'a>(entries: &'a [log::Entry], prompt: &str) -> Option<&'a log::Entry> {
+    if entries.is_empty() {
+        println!("There are no entries on this day.");
+        return None;
+    }
+    let display = config::display();
+    let items: Vec<String> = entries.iter().map(|entry| describe_entry(entry, &display)).collect();
+    prompt::choose(prompt, &items).map(|i| &entries[i])
+}
+
+/// Replaces `original`, as shown in local time, with `changed`, or removes
+/// it if `changed` is `None`.
+fn replace_entry(storage: &mut dyn storage::Storage, original: &log::Entry, changed: Option<log::Entry>) -> std::io::Result<()> {
+    let mut changed = changed;
+    storage.update(&mut |entries| {
+        let Some(index) = entries.iter().position(|e| e.to_local() == *original) else {
+            return Err(std::io::Error::other("The entry was changed elsewhere meanwhile; nothing was saved."));
+        };
+        match changed.take() {
+            Some(mut entry) => {
+                // Keep the zone it was logged in unless its stop time changed
+                if (entry.date, entry.time) == (original.date, original.time) {
+                    let stored = &entries[index];
+                    (entry.date, entry.time, entry.offset) = (stored.date, stored.time, stored.offset);
+                }
+                entries[index] = entry;
+            },
+            None => {
+                entries.remove(index);
+            },
+        }
+        Ok(())
+    })
+}
+
+/// Asks for a time of day on `date` until a valid one is given; an empty
+/// answer keeps `current`.
+fn ask_time(question: &str, date: chrono::NaiveDate, current: Option<chrono::NaiveDateTime>) -> chrono::NaiveDateTime {
+    loop {
+        let answer = prompt::ask(question, current.map(|t| t.format("%H:%M").to_string()).into_iter().collect());
+        if answer.is_empty()
+            && let Some(current) = current
+        {
+            return current;
+        }
+        match log::parse_clock(&answer) {
+            Ok(time) => return date.and_time(time),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+/// Why an entry added or edited in the review is refused.
+const STOPS_BEFORE_START: &str = "The entry has to stop after it started; nothing was saved.";
+
+/// Changes the task, code, start or end of an entry.
+fn review_edit(storage: &mut dyn storage::Storage, day_entries: &[log::Entry]) -> std::io::Result<()> {
+    let Some(original) = pick_entry(day_entries, "Edit which entry?") else {
+        return Ok(());
+    };
+    if original.is_locked() {
+        println!("This entry has been invoiced and can't be changed.");
+        return Ok(());
+    }
+    let labels = config::labels();
+    let fields = ["Task", &labels.code, "Start", "End"].map(String::from);
+    let mut entry = original.clone();
+    let (mut start, mut end) = (original.start(), original.end());
+    match prompt::choose("Change what?", &fields) {
+        Some(0) => entry.task = prompt::ask("Task", vec![original.task.clone()]),
+        Some(1) => entry.code = config::load().expand_code(&prompt::ask(&labels.code, vec![original.code.clone()])),
+        Some(2) => start = ask_time("Started at (HH:MM)", start.date(), Some(start)),
+        Some(3) => {
+            end = ask_time("Stopped at (HH:MM)", end.date(), Some(end));
+            entry.date = end.date();
+            entry.time = end.time();
+        },
+        _ => return Ok(()),
+    }
+    if (start, end) != (original.start(), original.end()) {
+        if end <= start {
+            println!("{}", STOPS_BEFORE_START);
+            return Ok(());
+        }
+        entry.duration_secs = (end - start).num_seconds() as u64;
+    }
+    if entry.task.is_empty() || entry.code.is_empty() {
+        println!("The task and {} can't be empty; nothing was changed.", labels.code_lower());
+        return Ok(());
+    }
+    replace_entry(storage, original, Some(entry))
+}
+
+/// Removes an entry after confirmation.
+fn review_delete(storage: &mut dyn storage::Storage, day_entries: &[log::Entry]) -> std::io::Result<()> {
+    let Some(original) = pick_entry(day_entries, "Delete which entry?") else {
+        return Ok(());
+    };
+    if original.is_locked() {
+        println!("This entry has been invoiced and can't be deleted.");
+        return Ok(());
+    }
+    let confirm = [format!("Keep '{}'", original.task), format!("Delete '{}'", original.task)];
+    if prompt::choose("Really delete it?", &confirm) == Some(1) {
+        replace_entry(storage, original, None)?;
+    }
+    Ok(())
+}
+
+/// Adds an entry on `day`, suggesting tasks and codes from `entries`.
+fn review_add(storage: &mut dyn storage::Storage, day: chrono::NaiveDate, entries: &[log::Entry]) -> std::io::Result<()> {
+    let task = prompt::ask("Task", log::recent_values(entries, |e| &e.task));
+    let labels = config::labels();
+    let code = config::load().expand_code(&prompt::ask(&labels.code, log::recent_values(entries, |e| &e.code)));
+    if task.is_empty() || code.is_empty() {
+        println!("The task and {} can't be empty; nothing was added.", labels.code_lower());
+        return Ok(());
+    }
+    let start = ask_time("Started at (HH:MM)", day, None);
+    let end = ask_time("Stopped at (HH:MM)", day, None);
+    if end <= start {
+        println!("{}", STOPS_BEFORE_START);
+        return Ok(());
+    }
+    let entry = log::Entry {
+        date: day,
+        time: end.time(),
+        offset: log::local_offset(end),
+        code,
+        task,
+        duration_secs: (end - start).num_seconds() as u64,
+        invoice: None,
+        git: None,
+        id: log::new_id(),
+    };
+    storage.append(&entry)
+}
+
+/// The push target for `target`, set up from its section of the config file.
+#[cfg(feature = "integrations")]
+fn push_target(target: PushTargetArg, config: &config::Config) -> Result<Box<dyn push::PushTarget + '_>, String> {
+    match target {
+        PushTargetArg::Jira => push::jira::Jira::from_config(&config.jira).map(|t| Box::new(t) as _),
+        PushTargetArg::Harvest => push::harvest::Harvest::from_config(&config.harvest).map(|t| Box::new(t) as _),
+        PushTargetArg::Redmine => push::redmine::Redmine::from_config(&config.redmine).map(|t| Box::new(t) as _),
+        PushTargetArg::Youtrack => push::youtrack::YouTrack::from_config(&config.youtrack).map(|t| Box::new(t) as _),
+        PushTargetArg::Linear => push::linear::Linear::from_config(&config.linear).map(|t| Box::new(t) as _),
+        PushTargetArg::Asana => push::asana::Asana::from_config(&config.asana).map(|t| Box::new(t) as _),
+        PushTargetArg::Notion => push::notion::Notion::from_config(&config.notion).map(|t| Box::new(t) as _),
+        PushTargetArg::Gsheets => push::gsheets::GoogleSheets::from_config(&config.gsheets).map(|t| Box::new(t) as _),
+    }
+}
+
+/// Runs one of the `push` subcommands.
+#[cfg(feature = "integrations")]
+fn run_push(target: PushTargetArg, range: &DateRange, dry_run: bool) {
+    let config = config::load();
+    let entries = open_storage().query(&DateRange::default())
+        .map(log::to_local)
+        .context("Failed to read log file")
+        .or_exit();
+
+    let target = push_target(target, &config).map_err(Error::Invalid).or_exit();
+
+    if push::run(target.as_ref(), &entries, range, dry_run).or_exit() > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Runs one of the `import` subcommands.
+fn run_import(source: ImportSource, dry_run: bool, strict: bool) {
+    let imported = match source {
+        ImportSource::Timew { path } => {
+            let path = path.unwrap_or_else(|| {
+                std::env::var_os("TIMEWARRIORDB")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| exit(Error::Other("Could not find the home directory; pass the path instead.".into()))).join(".timewarrior"))
+                    .join("data")
+            });
+            import::timew::import(&path)
+        },
+        ImportSource::Watson { path } => {
+            let path = path.unwrap_or_else(|| {
+                std::env::var_os("WATSON_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| dirs::config_dir().unwrap_or_else(|| exit(Error::Other("Could not find the config directory; pass the path instead.".into()))).join("watson"))
+                    .join("frames")
+            });
+            import::watson::import(&path)
+        },
+        ImportSource::Csv { path } => import::csv_file::import(&path),
+    };
+    let imported = imported.context("Failed to read import source").or_exit();
+
+    if strict && let Some(error) = imported.errors.first() {
+        exit(Error::Other(format!("Could not read {}; nothing imported.", error)));
+    }
+    // Fuzzed or badly broken sources can fail on every row
+    const SHOWN: usize = 10;
+    for error in imported.errors.iter().take(SHOWN) {
+        eprintln!("Skipped {}", error);
+    }
+    if imported.errors.len() > SHOWN {
+        eprintln!("Skipped {} more rows that could not be read.", imported.errors.len() - SHOWN);
+    }
+
+    let (added, duplicates) = import::append_new(open_storage().as_mut(), imported.entries, dry_run)
+        .context("Failed to write to log file")
+        .or_exit();
+    println!(
+        "{} {} entries, skipped {} already in the log{}.",
+        if dry_run { "Would import" } else { "Imported" },
+        added,
+        duplicates,
+        match imported.errors.len() {
+            0 => String::new(),
+            n => format!(" and {} that could not be read", n),
+        }
+    );
+}
+
+/// The logged entries in `range` in the local time zone, from the current
+/// log only unless `include_archived` is set.
+fn logged(range: &DateRange, include_archived: bool) -> Vec<log::Entry> {
+    let storage = open_storage();
+    let entries = if include_archived { storage.history(range) } else { storage.query(range) };
+    log::to_local(entries.context("Failed to read log file").or_exit())
+}
+
+/// Runs one of the `export` subcommands.
+fn run_export(format: ExportFormat, mut filter: log::Filter, include_archived: bool) {
+    let config = config::load();
+    for code in &mut filter.code {
+        *code = config.expand_code(code);
+    }
+    let mut entries = logged(&DateRange::default(), include_archived);
+    entries.retain(|e| filter.includes(e));
+
+    match format {
+        ExportFormat::Clockify { range, email, out } => {
+            let Some(email) = email.or(config.clockify.email.clone()) else {
+                exit(Error::Invalid("Clockify needs a user email: pass --email or set clockify.email in the config file.".into()));
+            };
+            let selected = entries.iter().filter(|e| range.includes(e));
+            let content = export::clockify::export(selected, &config.clockify, &email);
+            export::write_output(out.as_deref(), &content)
+                .context("Failed to write export")
+                .or_exit();
+        },
+        ExportFormat::Timew { range, out } => {
+            let content = import::timew::export(entries.iter().filter(|e| range.includes(e)));
+            export::write_output(out.as_deref(), &content)
+                .context("Failed to write export")
+                .or_exit();
+        },
+        ExportFormat::Timeclock { range, out } => {
+            let content = export::timeclock::export(entries.iter().filter(|e| range.includes(e)));
+            export::write_output(out.as_deref(), &content)
+                .context("Failed to write export")
+                .or_exit();
+        },
+        ExportFormat::Org { range, out } => {
+            let content = export::org::export(entries.iter().filter(|e| range.includes(e)));
+            export::write_output(out.as_deref(), &content)
+                .context("Failed to write export")
+                .or_exit();
+        },
+        ExportFormat::Ics { range, out } => {
+            let content = export::ics::export(entries.iter().filter(|e| range.includes(e)));
+            export::write_output(out.as_deref(), &content)
+                .context("Failed to write export")
+                .or_exit();
+        },
+        #[cfg(feature = "integrations")]
+        ExportFormat::Wakatime { range, dry_run } => {
+            let target = push::wakatime::WakaTime::from_config(&config.wakatime).map_err(Error::Invalid).or_exit();
+            if push::run(&target, &entries, &range, dry_run).or_exit() > 0 {
+                std::process::exit(1);
+            }
+        },
+        ExportFormat::Site { range, out } => {
+            let [code] = filter.code.as_slice() else {
+                exit(Error::Invalid("A site is built for one code; pass it with --code.".to_string()));
+            };
+            let selected = entries.iter().filter(|e| range.includes(e));
+            export::site::export(selected, code, &out)
+                .context("Failed to write site")
+                .or_exit();
+            eprintln!("Site for '{}' written to '{}'.", code, out.display());
+        },
+    }
 }
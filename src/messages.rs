@@ -0,0 +1,246 @@
+//! Texts shown to people when starting and stopping sessions, in the
+//! language of `locale` (see [`crate::locale`]).
+//!
+//! Each text has an ID, such as `stopped`, and placeholders in braces, such
+//! as `{task}`. Any text can be reworded, or translated into a language
+//! that isn't built in, with `[messages]` in the config file:
+//!
+//! ```toml
+//! [messages]
+//! stopped = "Klaar! '{task}' ({label}: {code}): {duration}"
+//! ```
+
+use crate::config;
+use crate::locale::Language;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// The built-in texts by ID, in English, German, French, Spanish, Italian,
+/// Dutch and Portuguese, the order of [`Language`].
+const CATALOG: &[(&str, [&str; 7])] = &[
+    ("prompt-task", [
+        "Enter task name",
+        "Aufgabe eingeben",
+        "Nom de la tâche",
+        "Nombre de la tarea",
+        "Nome dell'attività",
+        "Naam van de taak",
+        "Nome da tarefa",
+    ]),
+    ("prompt-code", [
+        "Enter {label} for this task",
+        "{label} für diese Aufgabe eingeben",
+        "{label} pour cette tâche",
+        "{label} para esta tarea",
+        "{label} per questa attività",
+        "{label} voor deze taak",
+        "{label} para esta tarefa",
+    ]),
+    ("tracking", [
+        "Tracking task '{task}' with {label} '{code}'. Press Ctrl+C to stop{laps}.",
+        "Erfasse '{task}' mit {label} '{code}'. Strg+C beendet{laps}.",
+        "Suivi de '{task}' avec {label} '{code}'. Ctrl+C pour arrêter{laps}.",
+        "Registrando '{task}' con {label} '{code}'. Pulsa Ctrl+C para parar{laps}.",
+        "Registrazione di '{task}' con {label} '{code}'. Premi Ctrl+C per fermare{laps}.",
+        "'{task}' wordt bijgehouden met {label} '{code}'. Druk op Ctrl+C om te stoppen{laps}.",
+        "Registrando '{task}' com {label} '{code}'. Pressione Ctrl+C para parar{laps}.",
+    ]),
+    ("tracking-laps", [
+        ", l to record a lap",
+        ", l erfasst eine Runde",
+        ", l pour un tour",
+        ", l para marcar una vuelta",
+        ", l per segnare un giro",
+        ", l voor een ronde",
+        ", l para marcar uma volta",
+    ]),
+    ("tracking-detached", [
+        "Tracking task '{task}' with {label} '{code}'. Run `{stop}` to log it.",
+        "Erfasse '{task}' mit {label} '{code}'. `{stop}` trägt sie ins Log ein.",
+        "Suivi de '{task}' avec {label} '{code}'. Lancez `{stop}` pour l'enregistrer.",
+        "Registrando '{task}' con {label} '{code}'. Ejecuta `{stop}` para guardarla.",
+        "Registrazione di '{task}' con {label} '{code}'. Esegui `{stop}` per salvarla.",
+        "'{task}' wordt bijgehouden met {label} '{code}'. Voer `{stop}` uit om het te loggen.",
+        "Registrando '{task}' com {label} '{code}'. Execute `{stop}` para salvá-la.",
+    ]),
+    ("elapsed", [
+        "Elapsed: {time}",
+        "Verstrichen: {time}",
+        "Écoulé : {time}",
+        "Transcurrido: {time}",
+        "Trascorso: {time}",
+        "Verstreken: {time}",
+        "Decorrido: {time}",
+    ]),
+    ("stopped", [
+        "Stopped. Time spent on task '{task}' ({label}: {code}): {duration}",
+        "Beendet. Zeit für '{task}' ({label}: {code}): {duration}",
+        "Arrêté. Temps passé sur '{task}' ({label} : {code}) : {duration}",
+        "Detenido. Tiempo dedicado a '{task}' ({label}: {code}): {duration}",
+        "Fermato. Tempo dedicato a '{task}' ({label}: {code}): {duration}",
+        "Gestopt. Tijd besteed aan '{task}' ({label}: {code}): {duration}",
+        "Parado. Tempo gasto em '{task}' ({label}: {code}): {duration}",
+    ]),
+    ("stopped-previous", [
+        "Stopped and logged '{task}' ({duration}).",
+        "'{task}' beendet und eingetragen ({duration}).",
+        "'{task}' arrêté et enregistré ({duration}).",
+        "'{task}' detenido y guardado ({duration}).",
+        "'{task}' fermato e salvato ({duration}).",
+        "'{task}' gestopt en gelogd ({duration}).",
+        "'{task}' parado e registrado ({duration}).",
+    ]),
+    ("stopped-elsewhere", [
+        "Session stopped from another shell.",
+        "Die Sitzung wurde in einer anderen Shell beendet.",
+        "Session arrêtée depuis un autre terminal.",
+        "Sesión detenida desde otra terminal.",
+        "Sessione fermata da un altro terminale.",
+        "Sessie gestopt vanuit een andere shell.",
+        "Sessão parada a partir de outro terminal.",
+    ]),
+    ("already-stopped", [
+        "Session was already stopped.",
+        "Die Sitzung war schon beendet.",
+        "La session était déjà arrêtée.",
+        "La sesión ya estaba detenida.",
+        "La sessione era già fermata.",
+        "De sessie was al gestopt.",
+        "A sessão já estava parada.",
+    ]),
+    ("no-session", [
+        "No session is running.",
+        "Es läuft keine Sitzung.",
+        "Aucune session en cours.",
+        "No hay ninguna sesión en curso.",
+        "Nessuna sessione in corso.",
+        "Er loopt geen sessie.",
+        "Nenhuma sessão em andamento.",
+    ]),
+    ("day-total", [
+        "{day}: {total} in total",
+        "{day}: {total} insgesamt",
+        "{day} : {total} au total",
+        "{day}: {total} en total",
+        "{day}: {total} in totale",
+        "{day}: {total} in totaal",
+        "{day}: {total} no total",
+    ]),
+    ("today", ["Today", "Heute", "Aujourd'hui", "Hoy", "Oggi", "Vandaag", "Hoje"]),
+    ("running-terminal", [
+        "'{task}' ({code}) has been running in another terminal for {duration}.",
+        "'{task}' ({code}) läuft seit {duration} in einem anderen Terminal.",
+        "'{task}' ({code}) tourne depuis {duration} dans un autre terminal.",
+        "'{task}' ({code}) lleva {duration} en marcha en otra terminal.",
+        "'{task}' ({code}) è in corso da {duration} in un altro terminale.",
+        "'{task}' ({code}) loopt al {duration} in een andere terminal.",
+        "'{task}' ({code}) está em andamento há {duration} em outro terminal.",
+    ]),
+    ("running-background", [
+        "'{task}' ({code}) has been running in the background for {duration}.",
+        "'{task}' ({code}) läuft seit {duration} im Hintergrund.",
+        "'{task}' ({code}) tourne depuis {duration} en arrière-plan.",
+        "'{task}' ({code}) lleva {duration} en marcha en segundo plano.",
+        "'{task}' ({code}) è in corso da {duration} in background.",
+        "'{task}' ({code}) loopt al {duration} op de achtergrond.",
+        "'{task}' ({code}) está em andamento há {duration} em segundo plano.",
+    ]),
+    ("what-now", ["What now?", "Was nun?", "Et maintenant ?", "¿Y ahora?", "E adesso?", "Wat nu?", "E agora?"]),
+    ("choice-switch", [
+        "Switch: log '{task}' and start a new session",
+        "Wechseln: '{task}' eintragen und eine neue Sitzung starten",
+        "Changer : enregistrer '{task}' et démarrer une nouvelle session",
+        "Cambiar: guardar '{task}' y empezar una sesión nueva",
+        "Cambiare: salvare '{task}' e avviare una nuova sessione",
+        "Wisselen: '{task}' loggen en een nieuwe sessie starten",
+        "Trocar: registrar '{task}' e iniciar uma nova sessão",
+    ]),
+    ("choice-stop", [
+        "Stop: log '{task}' and start nothing",
+        "Beenden: '{task}' eintragen und nichts starten",
+        "Arrêter : enregistrer '{task}' sans rien démarrer",
+        "Parar: guardar '{task}' sin empezar nada",
+        "Fermare: salvare '{task}' senza avviare nulla",
+        "Stoppen: '{task}' loggen en niets starten",
+        "Parar: registrar '{task}' sem iniciar nada",
+    ]),
+    ("choice-abort", [
+        "Abort: keep '{task}' running (use --session to run another alongside it)",
+        "Abbrechen: '{task}' weiterlaufen lassen (mit --session läuft eine weitere daneben)",
+        "Annuler : laisser tourner '{task}' (--session pour en lancer une autre en parallèle)",
+        "Cancelar: dejar '{task}' en marcha (usa --session para otra en paralelo)",
+        "Annullare: lasciare '{task}' in corso (usa --session per un'altra in parallelo)",
+        "Afbreken: '{task}' laten lopen (gebruik --session voor een tweede ernaast)",
+        "Cancelar: manter '{task}' em andamento (use --session para outra em paralelo)",
+    ]),
+];
+
+/// The texts in the configured language, with the overrides from
+/// `[messages]`.
+#[derive(Debug, Clone)]
+pub struct Messages {
+    pub language: Language,
+    pub overrides: BTreeMap<String, String>,
+}
+
+impl Messages {
+    /// The texts for the config file, which works with a broken one, like
+    /// [`config::labels`].
+    pub fn load() -> Messages {
+        Messages { language: config::language(), overrides: config::messages() }
+    }
+
+    /// The text `id`, with each `{name}` replaced by its value in `args`.
+    /// An unknown ID is returned as it is.
+    pub fn text(&self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let template = match self.overrides.get(id) {
+            Some(text) => text.as_str(),
+            None => CATALOG.iter().find(|(known, _)| *known == id).map_or(id, |(_, texts)| texts[self.language as usize]),
+        };
+        let mut text = template.to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+}
+
+/// The text `id` in the configured language; see [`Messages::text`].
+pub fn text(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    Messages::load().text(id, args)
+}
+
+/// The IDs of the texts that can be set in `[messages]`.
+pub fn ids() -> impl Iterator<Item = &'static str> {
+    CATALOG.iter().map(|(id, _)| *id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `{name}` placeholders in `text`, sorted.
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut found: Vec<&str> = text.split('{').skip(1).filter_map(|part| part.split_once('}')).map(|(name, _)| name).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn translations_keep_the_placeholders() {
+        for (id, texts) in CATALOG {
+            for text in texts {
+                assert_eq!(placeholders(text), placeholders(texts[0]), "{} in '{}'", id, text);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_in_and_overrides() {
+        let mut messages = Messages { language: Language::German, overrides: BTreeMap::new() };
+        assert_eq!(messages.text("day-total", &[("day", &"Heute"), ("total", &"2h 05m")]), "Heute: 2h 05m insgesamt");
+        messages.overrides.insert("today".to_string(), "Idag".to_string());
+        assert_eq!(messages.text("today", &[]), "Idag");
+        assert_eq!(messages.text("unknown", &[]), "unknown");
+    }
+}
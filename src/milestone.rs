@@ -0,0 +1,119 @@
+//! Nudges at session milestones (`[alerts]` `every` and `at`), with today's
+//! total and the status of the session's budgets.
+//!
+//! A session running in a terminal alerts on its own. Detached sessions are
+//! alerted by the next command that runs, such as the `status` a status bar
+//! runs every few seconds; the alerts sent are remembered in
+//! `milestones.toml` in the data directory so each is sent once.
+
+use crate::budget::{self, Usage};
+use crate::config::{self, AlertsConfig};
+use crate::duration;
+use crate::recover;
+use crate::session::Session;
+use chrono::{DateTime, Local, SecondsFormat};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How long each detached session had run when milestones were last
+/// checked, by [`key`].
+pub type Sent = BTreeMap<String, u64>;
+
+/// Identifies a run of a session: its name and start.
+pub fn key(session: &Session) -> String {
+    let name = session.name.as_deref().unwrap_or("default");
+    format!("{}@{}", name, session.started.to_rfc3339_opts(SecondsFormat::Secs, false))
+}
+
+/// The milestone `session` reached since it was last checked, if any,
+/// recording that it has been checked up to `now`.
+pub fn due(alerts: &AlertsConfig, session: &Session, now: DateTime<Local>, sent: &mut Sent) -> Option<u64> {
+    let elapsed = (now - session.started).num_seconds().max(0) as u64;
+    let before = sent.insert(key(session), elapsed).unwrap_or(0);
+    alerts.crossed(&session.code, before, elapsed)
+}
+
+/// The nudge for `session` having run for `reached` seconds, with the time
+/// tracked today and the budgets that cover its code.
+pub fn message(session: &Session, reached: u64, today_secs: u64, budgets: &[Usage]) -> String {
+    let mut message = format!(
+        "'{}' ({}) has been running for {}. Today: {} in total.",
+        session.task,
+        session.code,
+        duration::format_hms(reached, false),
+        duration::format_hms(today_secs, false)
+    );
+    for usage in budgets.iter().filter(|u| budget::covers(&u.key, &session.code)) {
+        message.push_str(&format!(" {}.", usage));
+    }
+    message
+}
+
+/// Where the alerts sent for detached sessions are remembered.
+pub fn sent_path() -> PathBuf {
+    config::data_dir().join("milestones.toml")
+}
+
+/// Reads the alerts sent from `path`; a missing file has none, and a
+/// corrupt one is quarantined and has none either.
+pub fn read_sent(path: &Path) -> io::Result<Sent> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Sent::new()),
+        Err(e) => return Err(e),
+    };
+    if let Some(sent) = std::str::from_utf8(&content).ok().and_then(|c| toml::from_str(c).ok()) {
+        return Ok(sent);
+    }
+    // Losing it at worst repeats a nudge
+    let moved = recover::quarantine(path, false)?;
+    eprintln!("Warning: the milestone state was corrupt and has been moved to '{}'.", moved.display());
+    Ok(Sent::new())
+}
+
+/// Writes the alerts sent to `path`.
+pub fn write_sent(path: &Path, sent: &Sent) -> io::Result<()> {
+    let content = toml::to_string(sent).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn session(code: &str, started: DateTime<Local>) -> Session {
+        let mut session = Session::new(None, "Review".to_string(), code.to_string(), None, false);
+        session.started = started;
+        session
+    }
+
+    #[test]
+    fn nudges_once_per_milestone() {
+        let alerts: AlertsConfig = toml::from_str("at = ['1h', '2h', '4h']").unwrap();
+        let started = Local::now() - TimeDelta::hours(5);
+        let session = session("ACME", started);
+        let mut sent = Sent::new();
+        // First seen after several milestones: only the latest is sent
+        assert_eq!(due(&alerts, &session, started + TimeDelta::minutes(130), &mut sent), Some(7200));
+        assert_eq!(due(&alerts, &session, started + TimeDelta::minutes(131), &mut sent), None);
+        assert_eq!(due(&alerts, &session, started + TimeDelta::hours(4), &mut sent), Some(4 * 3600));
+        assert_eq!(sent.len(), 1);
+    }
+
+    #[test]
+    fn mentions_today_and_budgets() {
+        let session = session("ACME/web", Local::now());
+        let budgets = vec![
+            Usage { key: "ACME".to_string(), budget: "40h/week".parse().unwrap(), used_secs: 12 * 3600 },
+            Usage { key: "OPS".to_string(), budget: "10h/week".parse().unwrap(), used_secs: 3600 },
+        ];
+        let message = message(&session, 3600, 3 * 3600 + 600, &budgets);
+        assert!(message.starts_with("'Review' (ACME/web) has been running for 1h 00m. Today: 3h 10m in total. ACME: "), "{}", message);
+        assert!(!message.contains("OPS"));
+    }
+}
@@ -0,0 +1,120 @@
+//! Desktop notifications, e.g. when a session is stopped at its limit, and
+//! alerts sent through the channels in `[channels]`.
+//!
+//! Notifications are shown with the tool each platform already has:
+//! `notify-send` on Linux and BSD, `osascript` on macOS and PowerShell on
+//! Windows. Where none is available, nothing is shown; callers print the
+//! same message in the terminal as well.
+//!
+//! Slack, Telegram and webhook channels need the `integrations` feature.
+
+use crate::config::ChannelsConfig;
+use crate::secret;
+use std::process::{Command, Stdio};
+
+/// Shows a desktop notification, ignoring any failure to do so.
+pub fn send(title: &str, body: &str) {
+    let _ = command(title, body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Sends an alert through every configured channel. Webhooks receive
+/// `details` with the title and message added. Returns a message for each
+/// channel that failed.
+pub fn broadcast(channels: &ChannelsConfig, title: &str, body: &str, details: serde_json::Value) -> Vec<String> {
+    if channels.desktop {
+        send(title, body);
+    }
+    let mut failures = Vec::new();
+    let slack = secret::lookup("SLACK_WEBHOOK_URL", &channels.slack).unwrap_or_else(|e| {
+        failures.push(format!("Slack: {}", e));
+        None
+    });
+    if let Some(url) = slack {
+        let message = serde_json::json!({ "text": format!("*{}*\n{}", title, body) });
+        if let Err(e) = post(&url, &message) {
+            failures.push(format!("Slack: {}", e));
+        }
+    }
+    match (secret::lookup("TELEGRAM_BOT_TOKEN", &channels.telegram.token), &channels.telegram.chat_id) {
+        (Err(e), _) => failures.push(format!("Telegram: {}", e)),
+        (Ok(Some(token)), Some(chat_id)) => {
+            let message = serde_json::json!({ "chat_id": chat_id, "text": format!("{}\n{}", title, body) });
+            if let Err(e) = post(&format!("https://api.telegram.org/bot{}/sendMessage", token), &message) {
+                failures.push(format!("Telegram: {}", e.replace(&token, "<token>")));
+            }
+        },
+        (Ok(None), None) => {},
+        _ => failures.push("Telegram: both telegram.token and telegram.chat_id are needed".to_string()),
+    }
+    if let Some(url) = &channels.webhook {
+        let mut message = details;
+        if let Some(object) = message.as_object_mut() {
+            object.insert("title".to_string(), title.into());
+            object.insert("message".to_string(), body.into());
+        }
+        if let Err(e) = post(url, &message) {
+            failures.push(format!("Webhook: {}", e));
+        }
+    }
+    failures
+}
+
+/// Posts `body` as JSON to `url`.
+#[cfg(feature = "integrations")]
+pub(crate) fn post(url: &str, body: &serde_json::Value) -> Result<(), String> {
+    ureq::post(url)
+        .timeout(std::time::Duration::from_secs(10))
+        .send_json(body)
+        .map(|_| ())
+        .map_err(crate::push::http_error)
+}
+
+#[cfg(not(feature = "integrations"))]
+pub(crate) fn post(_url: &str, _body: &serde_json::Value) -> Result<(), String> {
+    Err("not available in this build (needs the integrations feature)".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command.args(["-e", &format!("display notification {} with title {}", quote(body), quote(title))]);
+    command
+}
+
+#[cfg(windows)]
+fn command(title: &str, body: &str) -> Command {
+    let script = format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+         $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent(1); \
+         $text = $xml.GetElementsByTagName('text'); \
+         $text.Item(0).AppendChild($xml.CreateTextNode({})) > $null; \
+         $text.Item(1).AppendChild($xml.CreateTextNode({})) > $null; \
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('simpletimer').Show([Windows.UI.Notifications.ToastNotification]::new($xml))",
+        quote(title),
+        quote(body)
+    );
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-Command", &script]);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name=simpletimer", title, body]);
+    command
+}
+
+/// Quotes text as a string literal for AppleScript or PowerShell.
+#[cfg(any(target_os = "macos", windows))]
+fn quote(text: &str) -> String {
+    if cfg!(windows) {
+        format!("'{}'", text.replace('\'', "''"))
+    } else {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
@@ -0,0 +1,176 @@
+//! Guided migration of a log kept by older versions (`onboard`).
+//!
+//! Older versions kept a single CSV log in the home directory, with fewer
+//! columns the older they are. Onboarding analyses such a log, backs it up,
+//! copies its entries into the storage of the active profile and checks
+//! that the totals per code match before and after.
+
+use crate::log::{self, Entry};
+use chrono::{Local, NaiveDate};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Columns added to the log over time, oldest first, with what they hold.
+const LATER_COLUMNS: [(&str, &str); 6] = [
+    ("Seconds", "seconds"),
+    ("Invoice", "invoices"),
+    ("Git", "git branches"),
+    ("Id", "entry IDs"),
+    ("DecimalHours", "decimal hours"),
+    ("Offset", "time zones"),
+];
+
+/// What a legacy log holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// The header row as written.
+    pub header: Vec<String>,
+    /// Columns of the current layout the log doesn't have yet.
+    pub missing: Vec<&'static str>,
+    pub entries: Vec<Entry>,
+    /// Rows that couldn't be parsed and won't be migrated.
+    pub skipped: usize,
+}
+
+impl Analysis {
+    /// A short name for the header layout, e.g. "written before invoices".
+    pub fn variant(&self) -> String {
+        match LATER_COLUMNS.iter().find(|(name, _)| self.missing.contains(name)) {
+            Some((_, what)) => format!("written before {}", what),
+            None => "current".to_string(),
+        }
+    }
+
+    /// The first and last day with entries.
+    pub fn span(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let first = self.entries.iter().map(|e| e.date).min()?;
+        Some((first, self.entries.iter().map(|e| e.date).max()?))
+    }
+}
+
+/// Analyses the content of a legacy log; `path` is only used in messages.
+pub fn analyze(content: &str, path: &Path) -> io::Result<Analysis> {
+    let mut rows = log::parse_csv(content).into_iter();
+    let header: Vec<String> = rows.next().unwrap_or_default();
+    let has = |name: &str| header.iter().any(|h| h.trim().eq_ignore_ascii_case(name));
+    let missing = LATER_COLUMNS.iter().map(|(name, _)| *name).filter(|name| !has(name)).collect();
+    let rows = rows.filter(|row| !row.iter().all(|field| field.trim().is_empty())).count();
+    let entries = log::parse_entries(content, path)?;
+    Ok(Analysis { header, missing, skipped: rows.saturating_sub(entries.len()), entries })
+}
+
+/// The number of entries and seconds per code, to check that nothing was
+/// lost or counted twice.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Totals(pub BTreeMap<String, (usize, u64)>);
+
+impl Totals {
+    pub fn of(entries: &[Entry]) -> Self {
+        let mut totals = Totals::default();
+        for entry in entries {
+            let (count, secs) = totals.0.entry(entry.code.clone()).or_default();
+            *count += 1;
+            *secs += entry.duration_secs;
+        }
+        totals
+    }
+
+    pub fn entries(&self) -> usize {
+        self.0.values().map(|(count, _)| count).sum()
+    }
+
+    pub fn secs(&self) -> u64 {
+        self.0.values().map(|(_, secs)| secs).sum()
+    }
+
+    /// Codes whose totals differ from `expected`.
+    pub fn mismatches(&self, expected: &Totals) -> Vec<String> {
+        let codes: std::collections::BTreeSet<&String> = self.0.keys().chain(expected.0.keys()).collect();
+        codes.into_iter().filter(|code| self.0.get(*code) != expected.0.get(*code)).cloned().collect()
+    }
+}
+
+/// The legacy entries not already in `existing`, e.g. when onboarding is
+/// run again after an interrupted attempt. Entries match by content, since
+/// legacy ones may not have IDs yet.
+pub fn new_entries(existing: &[Entry], legacy: &[Entry]) -> Vec<Entry> {
+    let key = |e: &Entry| (e.date, e.time, e.code.clone(), e.task.clone(), e.duration_secs);
+    let mut seen: HashSet<_> = existing.iter().map(key).collect();
+    legacy.iter().filter(|e| seen.insert(key(e))).cloned().collect()
+}
+
+/// Copies `path` to `<name>.<timestamp>.bak` next to it, returning the copy.
+pub fn backup(path: &Path) -> io::Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(Local::now().format(".%Y%m%d-%H%M%S.bak").to_string());
+    let copy = path.with_file_name(name);
+    std::fs::copy(path, &copy)?;
+    Ok(copy)
+}
+
+/// Legacy logs in the home directory besides the one of the active profile:
+/// `time_log.csv` and `time_log_<profile>.csv`, with the profile they
+/// belong to.
+pub fn other_legacy_logs() -> Vec<(Option<String>, PathBuf)> {
+    let Ok(current) = log::legacy_log_path() else { return Vec::new() };
+    let Some(home) = dirs::home_dir() else { return Vec::new() };
+    let Ok(dir) = std::fs::read_dir(home) else { return Vec::new() };
+    let mut found: Vec<(Option<String>, PathBuf)> = dir
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|path| *path != current)
+        .filter_map(|path| {
+            let stem = path.file_name()?.to_str()?.strip_suffix(".csv")?;
+            let profile = match stem.strip_prefix("time_log") {
+                Some("") => None,
+                Some(rest) => Some(rest.strip_prefix('_')?.to_string()),
+                None => return None,
+            };
+            Some((profile, path))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "Date,Time,Code,Task,Hours,Minutes\n\
+        2024-01-02,10:00:00,ACME,Design,1,30\n\
+        2024-01-03,11:00:00,OPS,Backups,0,15\n\
+        not a date,11:00:00,OPS,Broken,0,15\n\
+        \n";
+
+    #[test]
+    fn analyses_the_header_variant() {
+        let analysis = analyze(ORIGINAL, Path::new("time_log.csv")).unwrap();
+        assert_eq!(analysis.missing, vec!["Seconds", "Invoice", "Git", "Id", "DecimalHours", "Offset"]);
+        assert_eq!(analysis.variant(), "written before seconds");
+        assert_eq!(analysis.entries.len(), 2);
+        assert_eq!(analysis.skipped, 1);
+        assert_eq!(analysis.span(), Some(("2024-01-02".parse().unwrap(), "2024-01-03".parse().unwrap())));
+
+        let current = analyze(&format!("{}\n", log::HEADERS), Path::new("time_log.csv")).unwrap();
+        assert_eq!(current.variant(), "current");
+        assert!(current.entries.is_empty());
+    }
+
+    #[test]
+    fn skips_entries_already_migrated_and_compares_totals() {
+        let legacy = analyze(ORIGINAL, Path::new("time_log.csv")).unwrap().entries;
+        let mut existing = vec![legacy[0].clone()];
+        existing[0].id = "already-migrated".to_string();
+
+        let added = new_entries(&existing, &legacy);
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].task, "Backups");
+
+        let expected = Totals::of(&[existing.clone(), added].concat());
+        assert_eq!((expected.entries(), expected.secs()), (2, 105 * 60));
+        assert!(Totals::of(&legacy).mismatches(&expected).is_empty());
+        assert_eq!(Totals::of(&existing).mismatches(&expected), vec!["OPS"]);
+    }
+}
@@ -0,0 +1,59 @@
+//! Fuzzy picker for restarting a recent task.
+//!
+//! Shown instead of the task prompt when no task is given on an
+//! interactive terminal: typing filters the most recent distinct task/code
+//! pairs from the log, and Enter starts the highlighted one.
+
+use crate::log::{self, Entry};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::FuzzySelect;
+use std::collections::HashSet;
+use std::io::IsTerminal;
+
+/// How many recent task/code pairs are offered.
+const MAX_PAIRS: usize = 50;
+
+/// Rows of the list shown at once.
+const VISIBLE_ROWS: usize = 10;
+
+/// Distinct task/code pairs, most recently logged first.
+pub fn recent_pairs(entries: &[Entry], limit: usize) -> Vec<(String, String)> {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.end()));
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .map(|e| (e.task.clone(), e.code.clone()))
+        .filter(|pair| seen.insert(pair.clone()))
+        .take(limit)
+        .collect()
+}
+
+/// Lets the user pick a recent task, returning its task and code.
+///
+/// Returns `None` when there is nothing to pick from, when not running on a
+/// terminal, or when the user chooses to enter a new task (or presses Esc).
+pub fn pick_recent() -> Option<(String, String)> {
+    if !crate::prompt::interactive() || !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let entries = crate::storage::open().and_then(|storage| storage.query(&log::DateRange::default())).ok()?;
+    let pairs = recent_pairs(&entries, MAX_PAIRS);
+    if pairs.is_empty() {
+        return None;
+    }
+
+    let mut items = vec!["+ New task".to_string()];
+    items.extend(pairs.iter().map(|(task, code)| format!("{} ({})", task, code)));
+    let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Task (type to filter, Esc for a new task)")
+        .items(&items)
+        .default(if items.len() > 1 { 1 } else { 0 })
+        .max_length(VISIBLE_ROWS)
+        .interact_opt()
+        .ok()
+        .flatten()?;
+
+    // Index 0 is "New task"
+    selection.checked_sub(1).map(|i| pairs[i].clone())
+}
@@ -0,0 +1,102 @@
+//! Line prompts with editing, history and tab completion.
+//!
+//! On a terminal, prompts support cursor movement and editing, Up/Down to
+//! step through previously used values (most recent first) and Tab to
+//! complete from them. Otherwise, e.g. when input is piped in, a plain line
+//! is read. Nothing is asked in quiet mode, which is on with `--quiet` and
+//! whenever stdout isn't a terminal, e.g. under cron, `watch` or in CI.
+
+use dialoguer::{Completion, History, Input, Select};
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--quiet`.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Values offered by a prompt, most recently used first.
+struct Suggestions(Vec<String>);
+
+impl Completion for Suggestions {
+    /// Completes to the only match, or to the longest prefix shared by all
+    /// matches, like readline does.
+    fn get(&self, input: &str) -> Option<String> {
+        let lower = input.to_lowercase();
+        let mut matches = self.0.iter().filter(|s| s.to_lowercase().starts_with(&lower));
+        let first = matches.next()?;
+        let common = matches.fold(first.as_str(), |common, other| {
+            let length = common
+                .char_indices()
+                .zip(other.chars())
+                .take_while(|((_, a), b)| a.to_lowercase().eq(b.to_lowercase()))
+                .last()
+                .map_or(0, |((i, a), _)| i + a.len_utf8());
+            &common[..length]
+        });
+        (common.chars().count() > input.chars().count()).then(|| common.to_string())
+    }
+}
+
+impl History<String> for Suggestions {
+    fn read(&self, pos: usize) -> Option<String> {
+        self.0.get(pos).cloned()
+    }
+
+    // The log is the history; nothing needs to be stored
+    fn write(&mut self, _value: &String) {}
+}
+
+/// Asks for a line of text, offering `suggestions` for completion and
+/// history. Returns the trimmed answer, which may be empty.
+pub fn ask(prompt: &str, suggestions: Vec<String>) -> String {
+    if interactive() {
+        let mut suggestions = Suggestions(suggestions);
+        let completion = Suggestions(suggestions.0.clone());
+        return Input::<String>::new()
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .completion_with(&completion)
+            .history_with(&mut suggestions)
+            .interact_text()
+            .expect("Failed to read from the terminal")
+            .trim()
+            .to_string();
+    }
+
+    print!("{}: ", prompt);
+    stdout().flush().expect("Failed to flush stdout"); // Ensure prompt appears before input
+    let mut answer = String::new();
+    stdin().read_line(&mut answer).expect("Failed to read from stdin");
+    answer.trim().to_string()
+}
+
+/// Turns quiet mode on for the rest of the process (`--quiet`).
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--quiet` was given: nothing is asked and the live timer isn't
+/// shown.
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Whether anyone may be there to answer a prompt: not in quiet mode.
+pub fn can_ask() -> bool {
+    !quiet() && stdout().is_terminal()
+}
+
+/// Whether both input and output are a terminal, so the user can be asked.
+pub fn interactive() -> bool {
+    can_ask() && stdin().is_terminal()
+}
+
+/// Lets the user pick one of `items` with the arrow keys, returning its
+/// index, or `None` if they pressed Esc. Only call when [`interactive`].
+pub fn choose(prompt: &str, items: &[String]) -> Option<usize> {
+    Select::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(0)
+        .interact_opt()
+        .expect("Failed to read from the terminal")
+}
@@ -0,0 +1,86 @@
+//! Recording tracked time on Asana tasks.
+//!
+//! The entry's code is used as the Asana task ID, or mapped to one in
+//! `[asana.tasks]`. Time is added as a native time tracking entry by
+//! default; workspaces without time tracking can have it posted as a
+//! comment instead (`mode = "comment"`).
+
+use super::{http_error, json_id, PushTarget};
+use crate::config::{AsanaConfig, AsanaMode};
+use crate::duration;
+use crate::log::Entry;
+use crate::secret;
+use serde_json::json;
+
+const API_URL: &str = "https://app.asana.com/api/1.0";
+
+pub struct Asana<'a> {
+    config: &'a AsanaConfig,
+    token: String,
+    api_url: String,
+}
+
+impl<'a> Asana<'a> {
+    /// Builds the target from config, with `ASANA_TOKEN` taking precedence when set.
+    pub fn from_config(config: &'a AsanaConfig) -> Result<Self, String> {
+        let token = secret::lookup("ASANA_TOKEN", &config.token)?
+            .ok_or("Asana token missing: set asana.token in the config file or ASANA_TOKEN")?;
+        let api_url = config.api_url.clone().unwrap_or_else(|| API_URL.to_string());
+        Ok(Asana { config, token, api_url: api_url.trim_end_matches('/').to_string() })
+    }
+
+    /// The task an entry's time belongs to.
+    fn task<'e>(&'e self, entry: &'e Entry) -> &'e str {
+        self.config.tasks.get(&entry.code).map_or(&entry.code, |task| task)
+    }
+}
+
+impl PushTarget for Asana<'_> {
+    fn name(&self) -> &'static str {
+        "asana"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        let task = self.task(entry);
+        if task.is_empty() || !task.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("code '{}' is not an Asana task ID and has no [asana.tasks] mapping", entry.code));
+        }
+        if self.config.mode == AsanaMode::Time && entry.duration_secs < 60 {
+            return Err("Asana tracks time in whole minutes".to_string());
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let (path, data) = match self.config.mode {
+            AsanaMode::Time => (
+                "time_tracking_entries",
+                json!({
+                    "duration_minutes": (entry.duration_secs + 30) / 60,
+                    "entered_on": entry.start().date().to_string(),
+                }),
+            ),
+            AsanaMode::Comment => (
+                "stories",
+                json!({
+                    "text": format!(
+                        "Tracked {} on {}: {}",
+                        duration::format_hms(entry.duration_secs, false),
+                        entry.start().date(),
+                        entry.task
+                    ),
+                }),
+            ),
+        };
+
+        let response: serde_json::Value = ureq::post(&format!("{}/tasks/{}/{}", self.api_url, self.task(entry), path))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("Accept", "application/json")
+            .send_json(json!({ "data": data }))
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+
+        Ok(json_id(&response["data"]["gid"]))
+    }
+}
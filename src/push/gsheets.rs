@@ -0,0 +1,154 @@
+//! Appending entries as rows of a Google Sheets spreadsheet.
+//!
+//! Authenticates as a service account: a JWT signed with the account's key
+//! is exchanged for an access token, which is fetched once per run. Share
+//! the spreadsheet with the service account's email address (as an editor)
+//! so it can append rows.
+
+use super::{http_error, PushTarget};
+use crate::config::GoogleSheetsConfig;
+use crate::duration;
+use crate::log::Entry;
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cell::OnceCell;
+use std::path::PathBuf;
+
+const API_URL: &str = "https://sheets.googleapis.com/v4";
+const SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets";
+
+/// The fields of a service account key file used here.
+#[derive(Debug, Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+pub struct GoogleSheets<'a> {
+    config: &'a GoogleSheetsConfig,
+    key: ServiceAccountKey,
+    spreadsheet_id: String,
+    api_url: String,
+    access_token: OnceCell<String>,
+}
+
+impl<'a> GoogleSheets<'a> {
+    /// Builds the target from config. The key file defaults to
+    /// `GOOGLE_APPLICATION_CREDENTIALS` when `gsheets.credentials` is unset.
+    pub fn from_config(config: &'a GoogleSheetsConfig) -> Result<Self, String> {
+        let spreadsheet_id = config
+            .spreadsheet_id
+            .clone()
+            .ok_or("Spreadsheet missing: set gsheets.spreadsheet_id in the config file")?;
+        let credentials = config
+            .credentials
+            .clone()
+            .or_else(|| std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from))
+            .ok_or("Service account key missing: set gsheets.credentials in the config file or GOOGLE_APPLICATION_CREDENTIALS")?;
+        let content = std::fs::read_to_string(&credentials)
+            .map_err(|e| format!("Failed to read '{}': {}", credentials.display(), e))?;
+        let key: ServiceAccountKey = serde_json::from_str(&content)
+            .map_err(|e| format!("'{}' is not a service account key: {}", credentials.display(), e))?;
+        let api_url = config.api_url.clone().unwrap_or_else(|| API_URL.to_string());
+        Ok(GoogleSheets {
+            config,
+            key,
+            spreadsheet_id,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            access_token: OnceCell::new(),
+        })
+    }
+
+    /// An access token for the Sheets API, requested on first use.
+    fn access_token(&self) -> Result<&str, String> {
+        if let Some(token) = self.access_token.get() {
+            return Ok(token);
+        }
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: SCOPE,
+            aud: &self.key.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let signing_key = EncodingKey::from_rsa_pem(self.key.private_key.as_bytes()).map_err(|e| e.to_string())?;
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &signing_key)
+            .map_err(|e| e.to_string())?;
+
+        let response: serde_json::Value = ureq::post(&self.key.token_uri)
+            .send_form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        let token = response["access_token"]
+            .as_str()
+            .ok_or("token response has no access_token")?
+            .to_string();
+        Ok(self.access_token.get_or_init(|| token))
+    }
+}
+
+/// Percent-encodes a URL path segment.
+fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl PushTarget for GoogleSheets<'_> {
+    fn name(&self) -> &'static str {
+        "gsheets"
+    }
+
+    fn check(&self, _entry: &Entry) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let row = json!([
+            entry.start().date().to_string(),
+            entry.start().format("%H:%M:%S").to_string(),
+            entry.end().format("%H:%M:%S").to_string(),
+            entry.code,
+            entry.task,
+            duration::format_decimal_hours(entry.duration_secs),
+        ]);
+        // Sheet names are quoted in A1 notation so names with spaces work
+        let range = format!("'{}'", self.config.sheet.replace('\'', "''"));
+        let url = format!(
+            "{}/spreadsheets/{}/values/{}:append",
+            self.api_url, self.spreadsheet_id, percent_encode(&range)
+        );
+        let response: serde_json::Value = ureq::post(&url)
+            .query("valueInputOption", "USER_ENTERED")
+            .query("insertDataOption", "INSERT_ROWS")
+            .set("Authorization", &format!("Bearer {}", self.access_token()?))
+            .send_json(json!({ "values": [row] }))
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+
+        // The range the row was written to, e.g. `Time!A12:F12`
+        Ok(response["updates"]["updatedRange"].as_str().unwrap_or_default().to_string())
+    }
+}
@@ -0,0 +1,98 @@
+//! Creating Harvest time entries through the Harvest v2 API.
+//!
+//! Codes are mapped to Harvest project and task IDs in the
+//! `[harvest.projects]` table of the config file. Each entry is sent with
+//! an external reference, and Harvest is asked for an existing entry with
+//! that reference before creating one, so a push that was interrupted after
+//! Harvest accepted an entry but before the sync ledger was updated doesn't
+//! create a duplicate when re-run.
+
+use super::{entry_key, http_error, json_id, setting, PushTarget};
+use crate::config::HarvestConfig;
+use crate::log::Entry;
+use crate::secret;
+use serde_json::json;
+
+const API_URL: &str = "https://api.harvestapp.com/v2";
+
+pub struct Harvest<'a> {
+    config: &'a HarvestConfig,
+    account_id: String,
+    token: String,
+    api_url: String,
+}
+
+impl<'a> Harvest<'a> {
+    /// Builds the target from config, with `HARVEST_ACCOUNT_ID` and
+    /// `HARVEST_TOKEN` taking precedence when set.
+    pub fn from_config(config: &'a HarvestConfig) -> Result<Self, String> {
+        let account_id = setting("HARVEST_ACCOUNT_ID", &config.account_id)
+            .ok_or("Harvest account missing: set harvest.account_id in the config file or HARVEST_ACCOUNT_ID")?;
+        let token = secret::lookup("HARVEST_TOKEN", &config.token)?
+            .ok_or("Harvest token missing: set harvest.token in the config file or HARVEST_TOKEN")?;
+        let api_url = config.api_url.clone().unwrap_or_else(|| API_URL.to_string());
+        Ok(Harvest { config, account_id, token, api_url })
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        ureq::request(method, &format!("{}{}", self.api_url, path))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("Harvest-Account-Id", &self.account_id)
+            .set("User-Agent", concat!("SimpleTimer/", env!("CARGO_PKG_VERSION")))
+    }
+
+    /// Looks for a time entry previously created for this entry.
+    fn find_existing(&self, reference: &str) -> Result<Option<String>, String> {
+        let response: serde_json::Value = self
+            .request("GET", "/time_entries")
+            .query("external_reference_id", reference)
+            .call()
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        Ok(response["time_entries"]
+            .as_array()
+            .and_then(|entries| entries.first())
+            .map(|entry| json_id(&entry["id"])))
+    }
+}
+
+impl PushTarget for Harvest<'_> {
+    fn name(&self) -> &'static str {
+        "harvest"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        if !self.config.projects.contains_key(&entry.code) {
+            return Err(format!("code '{}' has no [harvest.projects] mapping", entry.code));
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let mapping = &self.config.projects[&entry.code];
+        let reference = entry_key(entry);
+        if let Some(id) = self.find_existing(&reference)? {
+            return Ok(id);
+        }
+
+        let body = json!({
+            "project_id": mapping.project_id,
+            "task_id": mapping.task_id,
+            "spent_date": entry.start().date().to_string(),
+            "hours": (entry.duration_secs as f64 / 36.0).round() / 100.0,
+            "notes": entry.task,
+            "external_reference": {
+                "id": reference,
+                "group_id": entry.code,
+            },
+        });
+        let response: serde_json::Value = self
+            .request("POST", "/time_entries")
+            .send_json(body)
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        Ok(json_id(&response["id"]))
+    }
+}
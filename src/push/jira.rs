@@ -0,0 +1,91 @@
+//! Posting entries as Jira worklogs.
+//!
+//! The entry's code is used as the Jira issue key (e.g. `PROJ-123`) and its
+//! task as the worklog comment.
+
+use super::{http_error, json_id, setting, PushTarget};
+use crate::config::JiraConfig;
+use crate::log::Entry;
+use crate::secret;
+use base64::Engine;
+use chrono::{Local, TimeZone};
+use serde_json::json;
+
+/// Jira rejects worklogs shorter than a minute.
+const MIN_WORKLOG_SECS: u64 = 60;
+
+pub struct Jira {
+    base_url: String,
+    authorization: String,
+}
+
+impl Jira {
+    /// Builds the target from config, with `JIRA_URL`, `JIRA_EMAIL` and
+    /// `JIRA_API_TOKEN` taking precedence when set.
+    ///
+    /// With an email, the token is sent as a Jira Cloud API token (basic
+    /// auth); without one it is sent as a personal access token (bearer).
+    pub fn from_config(config: &JiraConfig) -> Result<Self, String> {
+        let base_url = setting("JIRA_URL", &config.url)
+            .ok_or("Jira URL missing: set jira.url in the config file or JIRA_URL")?;
+        let token = secret::lookup("JIRA_API_TOKEN", &config.token)?
+            .ok_or("Jira token missing: set jira.token in the config file or JIRA_API_TOKEN")?;
+        let authorization = match setting("JIRA_EMAIL", &config.email) {
+            Some(email) => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", email, token))
+            ),
+            None => format!("Bearer {}", token),
+        };
+        Ok(Jira { base_url: base_url.trim_end_matches('/').to_string(), authorization })
+    }
+}
+
+/// Whether a code looks like a Jira issue key, e.g. `PROJ-123`.
+pub fn is_issue_key(code: &str) -> bool {
+    let Some((project, number)) = code.rsplit_once('-') else {
+        return false;
+    };
+    project.starts_with(|c: char| c.is_ascii_uppercase())
+        && project.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+}
+
+impl PushTarget for Jira {
+    fn name(&self) -> &'static str {
+        "jira"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        if !is_issue_key(&entry.code) {
+            return Err(format!("code '{}' is not a Jira issue key", entry.code));
+        }
+        if entry.duration_secs < MIN_WORKLOG_SECS {
+            return Err("Jira worklogs must be at least one minute long".to_string());
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let started = Local
+            .from_local_datetime(&entry.start())
+            .earliest()
+            .ok_or("start time does not exist in the local timezone")?;
+        let body = json!({
+            "started": started.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string(),
+            "timeSpentSeconds": entry.duration_secs,
+            "comment": entry.task,
+        });
+
+        let response: serde_json::Value = ureq::post(&format!("{}/rest/api/2/issue/{}/worklog", self.base_url, entry.code))
+            .set("Authorization", &self.authorization)
+            .set("Accept", "application/json")
+            .send_json(body)
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+
+        Ok(json_id(&response["id"]))
+    }
+}
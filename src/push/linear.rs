@@ -0,0 +1,78 @@
+//! Commenting tracked time on Linear issues.
+//!
+//! Linear has no native time tracking, so each entry is added as a comment
+//! on the issue named by its code (e.g. `ENG-123`), or on the issue the code
+//! is mapped to in `[linear.issues]`.
+
+use super::jira::is_issue_key;
+use super::{http_error, json_id, PushTarget};
+use crate::config::LinearConfig;
+use crate::duration;
+use crate::log::Entry;
+use crate::secret;
+use serde_json::json;
+
+const API_URL: &str = "https://api.linear.app/graphql";
+
+const CREATE_COMMENT: &str = "mutation($issueId: String!, $body: String!) {
+  commentCreate(input: { issueId: $issueId, body: $body }) { success comment { id } }
+}";
+
+pub struct Linear<'a> {
+    config: &'a LinearConfig,
+    api_key: String,
+    api_url: String,
+}
+
+impl<'a> Linear<'a> {
+    /// Builds the target from config, with `LINEAR_API_KEY` taking precedence when set.
+    pub fn from_config(config: &'a LinearConfig) -> Result<Self, String> {
+        let api_key = secret::lookup("LINEAR_API_KEY", &config.api_key)?
+            .ok_or("Linear API key missing: set linear.api_key in the config file or LINEAR_API_KEY")?;
+        let api_url = config.api_url.clone().unwrap_or_else(|| API_URL.to_string());
+        Ok(Linear { config, api_key, api_url })
+    }
+
+    /// The issue an entry's time belongs to.
+    fn issue<'e>(&'e self, entry: &'e Entry) -> &'e str {
+        self.config.issues.get(&entry.code).map_or(&entry.code, |issue| issue)
+    }
+}
+
+impl PushTarget for Linear<'_> {
+    fn name(&self) -> &'static str {
+        "linear"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        if !is_issue_key(self.issue(entry)) {
+            return Err(format!("code '{}' is not a Linear issue and has no [linear.issues] mapping", entry.code));
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let body = format!(
+            "Tracked **{}** on {}: {}",
+            duration::format_hms(entry.duration_secs, false),
+            entry.start().date(),
+            entry.task
+        );
+        let response: serde_json::Value = ureq::post(&self.api_url)
+            .set("Authorization", &self.api_key)
+            .send_json(json!({
+                "query": CREATE_COMMENT,
+                "variables": { "issueId": self.issue(entry), "body": body },
+            }))
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+
+        // GraphQL reports failures in the body with a 200 status
+        if let Some(errors) = response["errors"].as_array().filter(|errors| !errors.is_empty()) {
+            let messages: Vec<&str> = errors.iter().filter_map(|e| e["message"].as_str()).collect();
+            return Err(messages.join("; "));
+        }
+        Ok(json_id(&response["data"]["commentCreate"]["comment"]["id"]))
+    }
+}
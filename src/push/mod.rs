@@ -0,0 +1,174 @@
+//! Pushing logged entries to external time-tracking services.
+//!
+//! Each target keeps a sync ledger (`sync/<target>.csv` in the data
+//! directory) recording which entries have already been pushed, by entry
+//! ID, and the ID the remote service assigned, so re-running a push only
+//! sends new entries.
+
+pub mod asana;
+pub mod gsheets;
+pub mod harvest;
+pub mod jira;
+pub mod linear;
+pub mod notion;
+pub mod redmine;
+pub mod wakatime;
+pub mod youtrack;
+
+use crate::config;
+use crate::error::{Context, Error};
+use crate::log::{self, DateRange, Entry};
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// A service entries can be pushed to.
+pub trait PushTarget {
+    /// Short name, used for the sync ledger file and in messages.
+    fn name(&self) -> &'static str;
+
+    /// Checks whether an entry can be pushed, returning the reason if not.
+    fn check(&self, entry: &Entry) -> Result<(), String>;
+
+    /// Sends one entry, returning the ID the remote service assigned to it.
+    fn push(&self, entry: &Entry) -> Result<String, String>;
+}
+
+/// Identifier of an entry by its content, which ledgers used before
+/// entries had IDs.
+pub fn entry_key(entry: &Entry) -> String {
+    format!(
+        "{}T{}|{}|{}|{}",
+        entry.date,
+        entry.time.format("%H:%M:%S"),
+        entry.code,
+        entry.task,
+        entry.duration_secs
+    )
+}
+
+/// Whether a ledger read with [`read_synced`] lists the entry, by ID or by
+/// the content key older ledgers used.
+pub fn is_synced(synced: &BTreeMap<String, String>, entry: &Entry) -> bool {
+    (!entry.id.is_empty() && synced.contains_key(&entry.id)) || synced.contains_key(&entry_key(entry))
+}
+
+fn ledger_path(target: &str) -> PathBuf {
+    config::data_dir().join("sync").join(format!("{}.csv", target))
+}
+
+/// Reads the sync ledger of a target: entry ID (or key) -> remote ID.
+pub fn read_synced(target: &str) -> io::Result<BTreeMap<String, String>> {
+    let content = match log::read_text(&ledger_path(target)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(log::parse_csv(&content)
+        .into_iter()
+        .skip(1)
+        .filter_map(|row| Some((row.first()?.clone(), row.get(1)?.clone())))
+        .collect())
+}
+
+/// Records that an entry has been pushed to a target.
+pub fn record_synced(target: &str, entry: &Entry, remote_id: &str) -> io::Result<()> {
+    let path = ledger_path(target);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let needs_header = fs::metadata(&path).map(|m| m.len() == 0).unwrap_or(true);
+    let mut file = OpenOptions::new().append(true).create(true).open(&path)?;
+    if needs_header {
+        writeln!(file, "Key,RemoteId,SyncedAt")?;
+    }
+    let synced_at = Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+    // Entries without an ID (from an old log that hasn't been written since)
+    // are recorded by their content instead
+    let key = if entry.id.is_empty() { entry_key(entry) } else { entry.id.clone() };
+    file.write_all(log::csv_record([key.as_str(), remote_id, &synced_at]).as_bytes())
+}
+
+/// Pushes every entry in `range` that hasn't been pushed to `target` yet.
+///
+/// Entries are recorded in the sync ledger one by one as they succeed, so a
+/// failure part-way through never causes duplicates on the next run.
+/// Returns the number of entries that failed, or an error as soon as a
+/// pushed entry can't be recorded, so it isn't pushed twice.
+pub fn run(target: &dyn PushTarget, entries: &[Entry], range: &DateRange, dry_run: bool) -> Result<usize, Error> {
+    let synced = read_synced(target.name()).context("Failed to read the sync ledger")?;
+    let (mut pushed, mut skipped, mut failed) = (0, 0, 0);
+
+    for entry in entries.iter().filter(|e| range.includes(e)) {
+        if is_synced(&synced, entry) {
+            continue;
+        }
+        let label = format!("{} {} '{}' ({})", entry.date, entry.code, entry.task, crate::duration::format_hms(entry.duration_secs, true));
+        if let Err(reason) = target.check(entry) {
+            println!("Skipped {}: {}", label, reason);
+            skipped += 1;
+            continue;
+        }
+        if dry_run {
+            println!("Would push {}", label);
+            pushed += 1;
+            continue;
+        }
+        match target.push(entry) {
+            Ok(remote_id) => {
+                record_synced(target.name(), entry, &remote_id).map_err(|e| {
+                    let context = format!(
+                        "Pushed {} to {} as '{}', but failed to record it in '{}'; add it by hand or it is pushed again",
+                        label,
+                        target.name(),
+                        remote_id,
+                        ledger_path(target.name()).display()
+                    );
+                    Error::io(context, e)
+                })?;
+                println!("Pushed {} -> {}", label, remote_id);
+                pushed += 1;
+            },
+            Err(e) => {
+                eprintln!("Failed to push {}: {}", label, e);
+                failed += 1;
+            },
+        }
+    }
+
+    println!(
+        "{}: {} {}, {} skipped, {} failed.",
+        target.name(),
+        pushed,
+        if dry_run { "to push" } else { "pushed" },
+        skipped,
+        failed
+    );
+    Ok(failed)
+}
+
+/// Turns a ureq error into a readable message, including the response body.
+pub fn http_error(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            format!("HTTP {}: {}", code, body.trim())
+        },
+        other => other.to_string(),
+    }
+}
+
+/// Renders an ID from a JSON response, which services send as either a number or a string.
+pub fn json_id(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(id) => id.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Reads a setting from the environment, falling back to the config value.
+pub fn setting(env_var: &str, configured: &Option<String>) -> Option<String> {
+    std::env::var(env_var).ok().filter(|v| !v.is_empty()).or_else(|| configured.clone())
+}
@@ -0,0 +1,103 @@
+//! Appending entries as rows of a Notion database.
+//!
+//! Which database property each field goes to is configured in
+//! `[notion.columns]`; a column set to an empty string is left out. The
+//! task is the row's title, the date holds the session's start and end,
+//! the code is a select (or text) property and the hours a number.
+
+use super::{http_error, json_id, PushTarget};
+use crate::config::{NotionCodeType, NotionConfig};
+use crate::log::Entry;
+use crate::secret;
+use chrono::{Local, TimeZone};
+use serde_json::{json, Map, Value};
+
+const API_URL: &str = "https://api.notion.com/v1";
+
+/// API version the request bodies are written for.
+const NOTION_VERSION: &str = "2022-06-28";
+
+pub struct Notion<'a> {
+    config: &'a NotionConfig,
+    token: String,
+    database_id: String,
+    api_url: String,
+}
+
+impl<'a> Notion<'a> {
+    /// Builds the target from config, with `NOTION_TOKEN` taking precedence when set.
+    pub fn from_config(config: &'a NotionConfig) -> Result<Self, String> {
+        let token = secret::lookup("NOTION_TOKEN", &config.token)?
+            .ok_or("Notion token missing: set notion.token in the config file or NOTION_TOKEN")?;
+        let database_id = config
+            .database_id
+            .clone()
+            .ok_or("Notion database missing: set notion.database_id in the config file")?;
+        if config.columns.task.is_empty() {
+            return Err("notion.columns.task must name the database's title property".to_string());
+        }
+        let api_url = config.api_url.clone().unwrap_or_else(|| API_URL.to_string());
+        Ok(Notion { config, token, database_id, api_url: api_url.trim_end_matches('/').to_string() })
+    }
+
+    /// The row's property values, keyed by the configured column names.
+    fn properties(&self, entry: &Entry) -> Map<String, Value> {
+        let columns = &self.config.columns;
+        let text = |content: &str| json!([{ "text": { "content": content } }]);
+        let timestamp = |time| {
+            Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map_or_else(|| time.format("%Y-%m-%dT%H:%M:%S").to_string(), |t| t.to_rfc3339())
+        };
+
+        let mut properties = Map::new();
+        properties.insert(columns.task.clone(), json!({ "title": text(&entry.task) }));
+        if !columns.date.is_empty() {
+            properties.insert(
+                columns.date.clone(),
+                json!({ "date": { "start": timestamp(entry.start()), "end": timestamp(entry.end()) } }),
+            );
+        }
+        if !columns.code.is_empty() {
+            let value = match columns.code_type {
+                // Select options can't contain commas
+                NotionCodeType::Select => json!({ "select": { "name": entry.code.replace(',', " ") } }),
+                NotionCodeType::Text => json!({ "rich_text": text(&entry.code) }),
+            };
+            properties.insert(columns.code.clone(), value);
+        }
+        if !columns.hours.is_empty() {
+            properties.insert(
+                columns.hours.clone(),
+                json!({ "number": (entry.duration_secs as f64 / 36.0).round() / 100.0 }),
+            );
+        }
+        properties
+    }
+}
+
+impl PushTarget for Notion<'_> {
+    fn name(&self) -> &'static str {
+        "notion"
+    }
+
+    fn check(&self, _entry: &Entry) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let body = json!({
+            "parent": { "database_id": self.database_id },
+            "properties": self.properties(entry),
+        });
+        let response: Value = ureq::post(&format!("{}/pages", self.api_url))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("Notion-Version", NOTION_VERSION)
+            .send_json(body)
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        Ok(json_id(&response["id"]))
+    }
+}
@@ -0,0 +1,77 @@
+//! Creating Redmine time entries through the REST API.
+//!
+//! The entry's code is used as the Redmine issue ID (`1234` or `#1234`) and
+//! its task as the time entry comment.
+
+use super::{http_error, json_id, setting, PushTarget};
+use crate::config::RedmineConfig;
+use crate::log::Entry;
+use crate::secret;
+use serde_json::json;
+
+/// Redmine rejects longer comments.
+const MAX_COMMENT_CHARS: usize = 1024;
+
+/// Hours are stored with two decimals, so shorter entries would round to zero.
+const MIN_TIME_ENTRY_SECS: u64 = 36;
+
+pub struct Redmine<'a> {
+    config: &'a RedmineConfig,
+    base_url: String,
+    api_key: String,
+}
+
+impl<'a> Redmine<'a> {
+    /// Builds the target from config, with `REDMINE_URL` and
+    /// `REDMINE_API_KEY` taking precedence when set.
+    pub fn from_config(config: &'a RedmineConfig) -> Result<Self, String> {
+        let base_url = setting("REDMINE_URL", &config.url)
+            .ok_or("Redmine URL missing: set redmine.url in the config file or REDMINE_URL")?;
+        let api_key = secret::lookup("REDMINE_API_KEY", &config.api_key)?
+            .ok_or("Redmine API key missing: set redmine.api_key in the config file or REDMINE_API_KEY")?;
+        Ok(Redmine { config, base_url: base_url.trim_end_matches('/').to_string(), api_key })
+    }
+}
+
+/// The Redmine issue ID a code refers to, e.g. `1234` for `#1234`.
+fn issue_id(code: &str) -> Option<u64> {
+    code.trim().trim_start_matches('#').parse().ok()
+}
+
+impl PushTarget for Redmine<'_> {
+    fn name(&self) -> &'static str {
+        "redmine"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        if issue_id(&entry.code).is_none() {
+            return Err(format!("code '{}' is not a Redmine issue ID", entry.code));
+        }
+        if entry.duration_secs < MIN_TIME_ENTRY_SECS {
+            return Err("Redmine time entries must be at least 0.01 hours long".to_string());
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let mut time_entry = json!({
+            "issue_id": issue_id(&entry.code),
+            "spent_on": entry.start().date().to_string(),
+            "hours": (entry.duration_secs as f64 / 36.0).round() / 100.0,
+            "comments": entry.task.chars().take(MAX_COMMENT_CHARS).collect::<String>(),
+        });
+        if let Some(activity_id) = self.config.activity_id {
+            time_entry["activity_id"] = json!(activity_id);
+        }
+
+        let response: serde_json::Value = ureq::post(&format!("{}/time_entries.json", self.base_url))
+            .set("X-Redmine-API-Key", &self.api_key)
+            .set("Accept", "application/json")
+            .send_json(json!({ "time_entry": time_entry }))
+            .map_err(http_error)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+
+        Ok(json_id(&response["time_entry"]["id"]))
+    }
+}
@@ -0,0 +1,92 @@
+//! Sending entries to WakaTime-compatible servers as heartbeats.
+//!
+//! WakaTime only knows heartbeats, so each entry is expanded into a
+//! heartbeat every couple of minutes from its start to its end. Servers
+//! join heartbeats less than their keystroke timeout (15 minutes by
+//! default) apart into continuous time, so the dashboard shows each entry
+//! with the code as the project and the task as the entity. Wakapi and
+//! other servers implementing the WakaTime API work the same way.
+
+use super::{http_error, PushTarget};
+use crate::config::WakaTimeConfig;
+use crate::log::Entry;
+use crate::secret;
+use base64::Engine;
+use serde_json::json;
+
+const API_URL: &str = "https://api.wakatime.com/api/v1";
+
+/// Seconds between the generated heartbeats.
+const INTERVAL_SECS: u64 = 120;
+
+/// Most heartbeats the bulk endpoint accepts per request.
+const BATCH_SIZE: usize = 25;
+
+pub struct WakaTime<'a> {
+    config: &'a WakaTimeConfig,
+    authorization: String,
+    api_url: String,
+}
+
+impl<'a> WakaTime<'a> {
+    /// Builds the target from config, with `WAKATIME_API_KEY` taking
+    /// precedence when set.
+    pub fn from_config(config: &'a WakaTimeConfig) -> Result<Self, String> {
+        let api_key = secret::lookup("WAKATIME_API_KEY", &config.api_key)?
+            .ok_or("WakaTime API key missing: set wakatime.api_key in the config file or WAKATIME_API_KEY")?;
+        let authorization = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(api_key));
+        let api_url = config.api_url.clone().unwrap_or_else(|| API_URL.to_string());
+        Ok(WakaTime { config, authorization, api_url: api_url.trim_end_matches('/').to_string() })
+    }
+
+    /// The heartbeats representing an entry.
+    fn heartbeats(&self, entry: &Entry) -> Vec<serde_json::Value> {
+        let start = entry.start_utc().timestamp() as u64;
+        let end = entry.end_utc().timestamp() as u64;
+        let branch = entry.git.as_deref().and_then(|git| git.split_once('@')).map(|(_, branch)| branch);
+
+        let mut times: Vec<u64> = (start..end).step_by(INTERVAL_SECS as usize).collect();
+        times.push(end);
+        times
+            .into_iter()
+            .map(|time| {
+                json!({
+                    "time": time,
+                    "entity": entry.task,
+                    "type": "app",
+                    "category": self.config.category,
+                    "project": entry.code,
+                    "branch": branch,
+                    "is_write": false,
+                })
+            })
+            .collect()
+    }
+}
+
+impl PushTarget for WakaTime<'_> {
+    fn name(&self) -> &'static str {
+        "wakatime"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        if entry.duration_secs == 0 {
+            return Err("entry has no duration".to_string());
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let heartbeats = self.heartbeats(entry);
+        // Re-sending after a failed batch is harmless: servers de-duplicate
+        // heartbeats with the same time and entity.
+        for batch in heartbeats.chunks(BATCH_SIZE) {
+            ureq::post(&format!("{}/users/current/heartbeats.bulk", self.api_url))
+                .set("Authorization", &self.authorization)
+                .set("User-Agent", concat!("SimpleTimer/", env!("CARGO_PKG_VERSION")))
+                .send_json(batch)
+                .map_err(http_error)?;
+        }
+        Ok(format!("{} heartbeats", heartbeats.len()))
+    }
+}
@@ -0,0 +1,70 @@
+//! Adding YouTrack work items to issues.
+//!
+//! The entry's code is used as the YouTrack issue ID (e.g. `PROJ-123`) and
+//! its task as the work item description.
+
+use super::jira::is_issue_key;
+use super::{http_error, json_id, setting, PushTarget};
+use crate::config::YouTrackConfig;
+use crate::log::Entry;
+use crate::secret;
+use serde_json::json;
+
+/// YouTrack tracks work in whole minutes.
+const MIN_WORK_ITEM_SECS: u64 = 60;
+
+pub struct YouTrack {
+    base_url: String,
+    token: String,
+}
+
+impl YouTrack {
+    /// Builds the target from config, with `YOUTRACK_URL` and
+    /// `YOUTRACK_TOKEN` taking precedence when set.
+    pub fn from_config(config: &YouTrackConfig) -> Result<Self, String> {
+        let base_url = setting("YOUTRACK_URL", &config.url)
+            .ok_or("YouTrack URL missing: set youtrack.url in the config file or YOUTRACK_URL")?;
+        let token = secret::lookup("YOUTRACK_TOKEN", &config.token)?
+            .ok_or("YouTrack token missing: set youtrack.token in the config file or YOUTRACK_TOKEN")?;
+        Ok(YouTrack { base_url: base_url.trim_end_matches('/').to_string(), token })
+    }
+}
+
+impl PushTarget for YouTrack {
+    fn name(&self) -> &'static str {
+        "youtrack"
+    }
+
+    fn check(&self, entry: &Entry) -> Result<(), String> {
+        // YouTrack issue IDs have the same shape as Jira keys
+        if !is_issue_key(&entry.code) {
+            return Err(format!("code '{}' is not a YouTrack issue ID", entry.code));
+        }
+        if entry.duration_secs < MIN_WORK_ITEM_SECS {
+            return Err("YouTrack work items must be at least one minute long".to_string());
+        }
+        Ok(())
+    }
+
+    fn push(&self, entry: &Entry) -> Result<String, String> {
+        let body = json!({
+            "date": entry.start_utc().timestamp_millis(),
+            "duration": { "minutes": (entry.duration_secs + 30) / 60 },
+            "text": entry.task,
+        });
+
+        let response: serde_json::Value = ureq::post(&format!(
+            "{}/api/issues/{}/timeTracking/workItems",
+            self.base_url, entry.code
+        ))
+        .query("fields", "id")
+        .set("Authorization", &format!("Bearer {}", self.token))
+        .set("Accept", "application/json")
+        .send_json(body)
+        .map_err(http_error)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+
+        Ok(json_id(&response["id"]))
+    }
+}
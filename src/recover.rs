@@ -0,0 +1,254 @@
+//! Safe mode for corrupt state files, and salvaging them (`recover`).
+//!
+//! A state file that can't be parsed, such as a running session's, never
+//! stops a command. It is moved to `quarantine/` in the data directory,
+//! and the session's task, code and start are salvaged from it where
+//! possible, so tracking carries on. `recover` goes through what was
+//! quarantined, asks for what couldn't be salvaged and logs or resumes the
+//! session. CSV files (the log, laps and sync ledgers) are read past
+//! invalid bytes and malformed rows instead, which `recover` can clean up.
+
+use crate::config;
+use crate::log::{self, Entry};
+use crate::session::{Lap, Session};
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Suffix of quarantined files that were salvaged when they were moved,
+/// which are only kept as a backup.
+const RESTORED: &str = ".restored";
+
+/// Format of the time a file was quarantined, in its name.
+const STAMP: &str = "%Y%m%d-%H%M%S";
+
+/// Where corrupt files are moved.
+pub fn quarantine_dir() -> PathBuf {
+    config::data_dir().join("quarantine")
+}
+
+/// Moves the corrupt file at `path` into the quarantine, named after its
+/// place in the data directory and the time, e.g.
+/// `sessions_builds.toml.20240506-093000`. `restored` marks a file whose
+/// content was salvaged already.
+pub fn quarantine(path: &Path, restored: bool) -> io::Result<PathBuf> {
+    let target = target(path, restored)?;
+    std::fs::rename(path, &target)?;
+    Ok(target)
+}
+
+/// Copies the file at `path` into the quarantine before it is repaired,
+/// marked as only kept as a backup.
+pub fn back_up(path: &Path) -> io::Result<PathBuf> {
+    let target = target(path, true)?;
+    std::fs::copy(path, &target)?;
+    Ok(target)
+}
+
+/// Where [`quarantine`] puts the file at `path`.
+fn target(path: &Path, restored: bool) -> io::Result<PathBuf> {
+    let data_dir = config::data_dir();
+    let relative = path.strip_prefix(&data_dir).unwrap_or(path);
+    let name: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    let mut name = format!("{}.{}", name.join("_"), Local::now().format(STAMP));
+    if restored {
+        name.push_str(RESTORED);
+    }
+    let dir = quarantine_dir();
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+/// What could be read from a corrupt session file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Salvaged {
+    pub task: Option<String>,
+    pub code: Option<String>,
+    pub started: Option<DateTime<Local>>,
+    pub git: Option<String>,
+    pub max_secs: Option<u64>,
+    pub laps: Vec<Lap>,
+}
+
+impl Salvaged {
+    /// The session, if at least its start could be read. A missing task or
+    /// code falls back to `Unnamed Task` and `NA`, as for `toggle`.
+    pub fn session(&self, name: Option<&str>) -> Option<Session> {
+        let task = self.task.clone().unwrap_or_else(|| "Unnamed Task".to_string());
+        let code = self.code.clone().unwrap_or_else(|| "NA".to_string());
+        let mut session = Session::new(name.map(str::to_string), task, code, self.git.clone(), false);
+        session.started = self.started?;
+        session.max_secs = self.max_secs;
+        session.laps = self.laps.clone();
+        Some(session)
+    }
+}
+
+/// Reads what it can from the content of a corrupt session file: invalid
+/// bytes are replaced, and if it still doesn't parse, each top-level line
+/// that does is used on its own.
+pub fn salvage(bytes: &[u8]) -> Salvaged {
+    let content = String::from_utf8_lossy(bytes);
+    if let Ok(session) = toml::from_str::<Session>(&content) {
+        return Salvaged {
+            task: Some(session.task),
+            code: Some(session.code),
+            started: Some(session.started),
+            git: session.git,
+            max_secs: session.max_secs,
+            laps: session.laps,
+        };
+    }
+    let mut table = toml::Table::new();
+    // Laps follow the top-level keys as `[[laps]]` tables
+    for line in content.lines().take_while(|line| !line.trim_start().starts_with('[')) {
+        if let Ok(parsed) = line.parse::<toml::Table>() {
+            table.extend(parsed);
+        }
+    }
+    let text = |key: &str| table.get(key).and_then(|v| v.as_str()).map(str::to_string).filter(|v| !v.trim().is_empty());
+    Salvaged {
+        task: text("task"),
+        code: text("code"),
+        started: text("started").and_then(|v| DateTime::parse_from_rfc3339(&v).ok()).map(|t| t.with_timezone(&Local)),
+        git: text("git"),
+        max_secs: table.get("max_secs").and_then(|v| v.as_integer()).and_then(|v| u64::try_from(v).ok()),
+        laps: Vec::new(),
+    }
+}
+
+/// What is wrong with the content of a CSV log, and the entries that can
+/// still be read from it.
+#[derive(Debug, Default)]
+pub struct LogDamage {
+    /// Whether it has bytes that aren't valid UTF-8.
+    pub invalid_utf8: bool,
+    /// Rows that can't be read as entries.
+    pub malformed: usize,
+    pub entries: Vec<Entry>,
+}
+
+impl LogDamage {
+    pub fn is_damaged(&self) -> bool {
+        self.invalid_utf8 || self.malformed > 0
+    }
+}
+
+/// Checks the content of the CSV log at `path`.
+pub fn check_log(content: &[u8], path: &Path) -> io::Result<LogDamage> {
+    let text = String::from_utf8_lossy(content);
+    let (entries, unreadable) = log::parse_log(&text, path)?;
+    Ok(LogDamage { invalid_utf8: std::str::from_utf8(content).is_err(), malformed: unreadable.len(), entries })
+}
+
+/// A file in the quarantine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarantined {
+    pub path: PathBuf,
+    /// Where the file was, relative to the data directory, with `_` for
+    /// directory separators, e.g. `sessions_builds.toml`.
+    pub original: String,
+    pub at: NaiveDateTime,
+    /// Whether its content was salvaged when it was moved.
+    pub restored: bool,
+}
+
+impl Quarantined {
+    /// The session the file was the state of: `Some(None)` for the default
+    /// session, `Some(Some(name))` for a named one, `None` if it wasn't a
+    /// session's.
+    pub fn session(&self) -> Option<Option<&str>> {
+        match self.original.as_str() {
+            "session.toml" => Some(None),
+            other => other.strip_prefix("sessions_")?.strip_suffix(".toml").map(Some),
+        }
+    }
+}
+
+/// Parses the name of a quarantined file.
+fn parse_name(path: &Path) -> Option<Quarantined> {
+    let name = path.file_name()?.to_str()?;
+    let (name, restored) = match name.strip_suffix(RESTORED) {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let (original, stamp) = name.rsplit_once('.')?;
+    let at = NaiveDateTime::parse_from_str(stamp, STAMP).ok()?;
+    Some(Quarantined { path: path.to_path_buf(), original: original.to_string(), at, restored })
+}
+
+/// The quarantined files, oldest first.
+pub fn list() -> io::Result<Vec<Quarantined>> {
+    let files = match std::fs::read_dir(quarantine_dir()) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut quarantined: Vec<Quarantined> = files.filter_map(|file| parse_name(&file.ok()?.path())).collect();
+    quarantined.sort_by_key(|q| q.at);
+    Ok(quarantined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn salvages_what_it_can() {
+        let broken = b"task = \"Review \xff\"\ncode = \"ACME\"\nstarted = \"2024-05-06T09:30:00+02:00\"\n";
+        let salvaged = salvage(broken);
+        assert_eq!(salvaged.task.as_deref(), Some("Review \u{FFFD}"));
+        let session = salvaged.session(Some("builds")).unwrap();
+        assert_eq!(session.code, "ACME");
+        assert_eq!(session.started, DateTime::parse_from_rfc3339("2024-05-06T09:30:00+02:00").unwrap());
+        assert_eq!(session.name.as_deref(), Some("builds"));
+
+        // A truncated write: the task line is cut off
+        let truncated = b"code = \"ACME\"\nstarted = \"2024-05-06T09:30:00+02:00\"\ntask = \"Rev";
+        let salvaged = salvage(truncated);
+        assert_eq!(salvaged.task, None);
+        assert_eq!(salvaged.session(None).unwrap().task, "Unnamed Task");
+
+        let hopeless = salvage(b"\0\0\0garbage");
+        assert_eq!(hopeless, Salvaged::default());
+        assert!(hopeless.session(None).is_none());
+    }
+
+    #[test]
+    fn finds_damage_in_the_log() {
+        let path = Path::new("time_log.csv");
+        let entry = Entry {
+            date: "2024-05-06".parse().unwrap(),
+            time: "09:30:00".parse().unwrap(),
+            offset: None,
+            code: "ACME".to_string(),
+            task: "Review!".to_string(),
+            duration_secs: 1800,
+            invoice: None,
+            git: None,
+            id: "a1".to_string(),
+        };
+        let healthy = log::to_csv(&[entry]);
+        assert!(!check_log(healthy.as_bytes(), path).unwrap().is_damaged());
+
+        let mut broken = format!("{}not a row\n", healthy).into_bytes();
+        let at = broken.iter().position(|b| *b == b'!').unwrap();
+        broken[at] = 0xff;
+        let damage = check_log(&broken, path).unwrap();
+        assert!(damage.invalid_utf8);
+        assert_eq!(damage.malformed, 1);
+        assert_eq!(damage.entries.len(), 1);
+    }
+
+    #[test]
+    fn parses_quarantined_names() {
+        let parsed = parse_name(Path::new("/q/sessions_client_a.toml.20240506-093000.restored")).unwrap();
+        assert_eq!(parsed.original, "sessions_client_a.toml");
+        assert_eq!(parsed.at.to_string(), "2024-05-06 09:30:00");
+        assert!(parsed.restored);
+        assert_eq!(parsed.session(), Some(Some("client_a")));
+        assert_eq!(parse_name(Path::new("session.toml.20240506-093000")).unwrap().session(), Some(None));
+        assert_eq!(parse_name(Path::new("budget_alerts.toml.20240506-093000")).unwrap().session(), None);
+        assert!(parse_name(Path::new("notes.txt")).is_none());
+    }
+}
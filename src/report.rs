@@ -0,0 +1,472 @@
+//! Summaries of the time log, grouped by code and task.
+//!
+//! Built-in layouts (see [`Layout`]) give each audience the right shape:
+//! a one-line-per-code overview, the full breakdown, daily hours for
+//! payroll, time per client as it is billed, or time per client, project
+//! and code from the `[projects]` in the config file.
+
+use crate::billing;
+use crate::config::Config;
+use crate::duration::{self, RoundMode};
+use crate::log::{DateRange, Entry};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The shape of a printed report: how time is grouped and rounded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// One line per code, without tasks.
+    Compact,
+    /// Time per code, broken down by task.
+    #[default]
+    Detailed,
+    /// Hours per day, rounded to the nearest quarter hour.
+    Payroll,
+    /// Time per client and code, rounded up to six minutes as it is billed.
+    Client,
+    /// Time per client, project and code, with a subtotal at each level.
+    Tree,
+}
+
+impl Layout {
+    /// Display rounding the layout uses unless `--round-display` is given:
+    /// the increment in minutes and how to round to it.
+    pub fn rounding(self) -> Option<(u64, RoundMode)> {
+        match self {
+            Layout::Compact | Layout::Detailed | Layout::Tree => None,
+            Layout::Payroll => Some((15, RoundMode::Nearest)),
+            Layout::Client => Some((6, RoundMode::Up)),
+        }
+    }
+
+    /// Uses the layout's [`rounding`](Layout::rounding) in `options` unless
+    /// they already round.
+    pub fn apply_rounding(self, options: &mut ReportOptions) {
+        if options.round_display.is_none()
+            && let Some((increment, mode)) = self.rounding()
+        {
+            options.round_display = Some(increment);
+            options.round_mode = mode;
+        }
+    }
+}
+
+/// Options controlling which entries a report covers and how it is printed.
+#[derive(Debug, Default)]
+pub struct ReportOptions {
+    pub range: DateRange,
+    /// Round every printed duration to this many minutes. Totals are still
+    /// summed from the precise logged values; only the display is rounded.
+    pub round_display: Option<u64>,
+    pub round_mode: RoundMode,
+}
+
+impl ReportOptions {
+    /// Applies display rounding to a duration, if enabled.
+    pub fn rounded(&self, secs: u64) -> u64 {
+        match self.round_display {
+            Some(increment) => duration::round_secs(secs, increment, self.round_mode),
+            None => secs,
+        }
+    }
+
+    /// Formats a duration for display, applying display rounding if enabled.
+    fn display(&self, secs: u64) -> String {
+        match self.round_display {
+            Some(increment) => {
+                let rounded = duration::round_secs(secs, increment, self.round_mode);
+                format!("{:>10} {:>7}", duration::format_hms(rounded, false), duration::format_decimal_hours(rounded))
+            },
+            None => format!("{:>10} {:>7}", duration::format_hms(secs, true), duration::format_decimal_hours(secs)),
+        }
+    }
+}
+
+/// Label used for codes that are not in a project.
+pub const NO_PROJECT: &str = "(no project)";
+
+/// The level codes are reported at.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Level {
+    /// Each code on its own.
+    #[default]
+    Engagement,
+    /// Codes rolled up to their project from `[projects]`; codes in no
+    /// project stay as they are.
+    Project,
+    /// Codes rolled up to their client (see [`Config::client`]), e.g.
+    /// `ACME/ONBOARDING` into `ACME`; codes without one into
+    /// [`billing::NO_CLIENT`].
+    Client,
+}
+
+/// The entries with their codes replaced by the project or client they
+/// belong to at `level`.
+pub fn roll_up(entries: &[Entry], level: Level, config: &Config) -> Vec<Entry> {
+    let rolled = |code: &str| match level {
+        Level::Engagement => code.to_string(),
+        Level::Project => config.project(code).unwrap_or(code).to_string(),
+        Level::Client => config.client(code).unwrap_or(billing::NO_CLIENT).to_string(),
+    };
+    entries.iter().map(|entry| Entry { code: rolled(&entry.code), ..entry.clone() }).collect()
+}
+
+/// Sums the logged seconds in `range` per client, project and code.
+pub fn tree<'a>(entries: &'a [Entry], range: &DateRange, config: &'a Config) -> BTreeMap<&'a str, BTreeMap<&'a str, BTreeMap<&'a str, u64>>> {
+    let mut tree: BTreeMap<&str, BTreeMap<&str, BTreeMap<&str, u64>>> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| range.includes(e)) {
+        let client = config.client(&entry.code).unwrap_or(billing::NO_CLIENT);
+        let project = config.project(&entry.code).unwrap_or(NO_PROJECT);
+        *tree.entry(client).or_default().entry(project).or_default().entry(entry.code.as_str()).or_default() += entry.duration_secs;
+    }
+    tree
+}
+
+/// Sums the logged seconds in `range` per code and task, sorted by both.
+pub fn totals<'a>(entries: &'a [Entry], range: &DateRange) -> BTreeMap<&'a str, BTreeMap<&'a str, u64>> {
+    totals_by(entries, range, |e| e.code.as_str(), |e| e.task.as_str())
+}
+
+/// Sums the logged seconds in `range` per group and subgroup.
+fn totals_by<'a, K: Ord>(
+    entries: &'a [Entry],
+    range: &DateRange,
+    group: impl Fn(&'a Entry) -> K,
+    subgroup: impl Fn(&'a Entry) -> &'a str,
+) -> BTreeMap<K, BTreeMap<&'a str, u64>> {
+    let mut totals: BTreeMap<K, BTreeMap<&str, u64>> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| range.includes(e)) {
+        *totals.entry(group(entry)).or_default().entry(subgroup(entry)).or_default() += entry.duration_secs;
+    }
+    totals
+}
+
+/// Prints the report in `layout` under a heading row, followed by a grand
+/// total. Clients come from `[projects]` and the `[billing]` section, and
+/// codes are called by `labels.code`.
+pub fn print_report<'a>(entries: &'a [Entry], options: &ReportOptions, layout: Layout, config: &'a Config) {
+    if !entries.iter().any(|e| options.range.includes(e)) {
+        println!("No entries found.");
+        return;
+    }
+    let label = &config.labels.code;
+    let heading = match layout {
+        Layout::Compact => label.clone(),
+        Layout::Detailed => format!("{} / Task", label),
+        Layout::Payroll => "Day".to_string(),
+        Layout::Client => format!("Client / {}", label),
+        Layout::Tree => format!("Client / Project / {}", label),
+    };
+    println!("{:<40} {:>10} {:>7}", heading, "Time", "Hours");
+    let grand_total = match layout {
+        Layout::Compact => print_compact(&totals(entries, &options.range), options),
+        Layout::Detailed => print_groups(&totals(entries, &options.range), options),
+        Layout::Payroll => print_days(entries, options),
+        Layout::Client => {
+            let client = |e: &'a Entry| config.client(&e.code).unwrap_or(billing::NO_CLIENT);
+            print_groups(&totals_by(entries, &options.range, client, |e| e.code.as_str()), options)
+        },
+        Layout::Tree => print_tree(&tree(entries, &options.range, config), options),
+    };
+    println!("{:<40} {}", "Total", options.display(grand_total));
+    if let Some(increment) = options.round_display {
+        println!(
+            "\nDurations shown rounded ({}) to {} minute(s); logged values are unchanged.",
+            format!("{:?}", options.round_mode).to_lowercase(),
+            increment
+        );
+    }
+}
+
+/// Raw and rounded time for one code in one month.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundingLine {
+    /// The month, as `YYYY-MM`.
+    pub period: String,
+    pub code: String,
+    pub raw_secs: u64,
+    pub rounded_secs: u64,
+}
+
+impl RoundingLine {
+    /// Seconds added (positive) or removed (negative) by rounding.
+    pub fn difference(&self) -> i64 {
+        self.rounded_secs as i64 - self.raw_secs as i64
+    }
+}
+
+/// The effect of the rounding in `options` per month and code, rounding
+/// each code's monthly total as billing does.
+pub fn rounding_impact(entries: &[Entry], options: &ReportOptions) -> Vec<RoundingLine> {
+    let months = totals_by(entries, &options.range, |e| e.local_date().format("%Y-%m").to_string(), |e| e.code.as_str());
+    months
+        .into_iter()
+        .flat_map(|(period, codes)| {
+            codes.into_iter().map(move |(code, secs)| RoundingLine {
+                period: period.clone(),
+                code: code.to_string(),
+                raw_secs: secs,
+                rounded_secs: options.rounded(secs),
+            })
+        })
+        .collect()
+}
+
+/// Prints the rounding impact per month and code, followed by totals.
+pub fn print_rounding_impact(entries: &[Entry], options: &ReportOptions, config: &Config) {
+    let lines = rounding_impact(entries, options);
+    if lines.is_empty() {
+        println!("No entries found.");
+        return;
+    }
+    let signed = |secs: i64| format!("{}{}", if secs < 0 { "-" } else { "+" }, duration::format_hms(secs.unsigned_abs(), true));
+    println!("{:<8} {:<30} {:>12} {:>12} {:>13}", "Month", config.labels.code, "Raw", "Rounded", "Difference");
+    for line in &lines {
+        println!(
+            "{:<8} {:<30} {:>12} {:>12} {:>13}",
+            line.period,
+            line.code,
+            duration::format_hms(line.raw_secs, true),
+            duration::format_hms(line.rounded_secs, true),
+            signed(line.difference())
+        );
+    }
+    let raw: u64 = lines.iter().map(|l| l.raw_secs).sum();
+    let rounded: u64 = lines.iter().map(|l| l.rounded_secs).sum();
+    println!(
+        "{:<39} {:>12} {:>12} {:>13}",
+        "Total",
+        duration::format_hms(raw, true),
+        duration::format_hms(rounded, true),
+        signed(rounded as i64 - raw as i64)
+    );
+    let up = lines.iter().filter(|l| l.difference() > 0).count();
+    let down = lines.iter().filter(|l| l.difference() < 0).count();
+    println!(
+        "\nRounded {} to {} minute(s): {} total(s) up, {} down, {} unchanged.",
+        format!("{:?}", options.round_mode).to_lowercase(),
+        options.round_display.unwrap_or_default(),
+        up,
+        down,
+        lines.len() - up - down
+    );
+}
+
+/// Prints each group's total followed by its subgroups, returning the
+/// grand total.
+fn print_groups<K: std::fmt::Display>(totals: &BTreeMap<K, BTreeMap<&str, u64>>, options: &ReportOptions) -> u64 {
+    let mut grand_total = 0;
+    for (group, subgroups) in totals {
+        let group_total: u64 = subgroups.values().sum();
+        grand_total += group_total;
+        println!("{:<40} {}", group.to_string(), options.display(group_total));
+        for (name, secs) in subgroups {
+            println!("  {:<38} {}", name, options.display(*secs));
+        }
+    }
+    grand_total
+}
+
+/// The time per client in `range`, broken down by project, with codes in no
+/// project on their own lines, each rounded as in `options`.
+pub fn by_client<'a>(entries: &'a [Entry], options: &ReportOptions, config: &'a Config) -> BTreeMap<&'a str, Vec<(&'a str, u64)>> {
+    tree(entries, &options.range, config)
+        .into_iter()
+        .map(|(client, projects)| {
+            let lines = projects
+                .into_iter()
+                .flat_map(|(project, codes)| match project {
+                    NO_PROJECT => codes.into_iter().collect::<Vec<_>>(),
+                    _ => vec![(project, codes.values().sum())],
+                })
+                .map(|(name, secs)| (name, options.rounded(secs)))
+                .collect();
+            (client, lines)
+        })
+        .collect()
+}
+
+/// Prints the time per client followed by its projects, as plain text to
+/// paste into monthly emails to clients.
+pub fn print_by_client(entries: &[Entry], options: &ReportOptions, config: &Config) {
+    let clients = by_client(entries, options, config);
+    let days: Vec<NaiveDate> = entries.iter().filter(|e| options.range.includes(e)).map(|e| e.date).collect();
+    let (Some(first), Some(last)) = (days.iter().min(), days.iter().max()) else {
+        println!("No entries found.");
+        return;
+    };
+    let (first, last) = (options.range.since.unwrap_or(*first), options.range.until.unwrap_or(*last));
+    let language = crate::config::language();
+    let time = |secs| config.display.duration(secs, false);
+    println!("Time from {} to {}", language.format_date(first, language.date_format()), language.format_date(last, language.date_format()));
+    for (client, lines) in clients {
+        println!("\n{}: {}", client, time(lines.iter().map(|(_, secs)| secs).sum()));
+        for (name, secs) in lines {
+            println!("- {}: {}", name, time(secs));
+        }
+    }
+}
+
+/// Prints each client's total, then each of its projects' totals followed
+/// by their codes, see [`print_groups`].
+fn print_tree(tree: &BTreeMap<&str, BTreeMap<&str, BTreeMap<&str, u64>>>, options: &ReportOptions) -> u64 {
+    let mut grand_total = 0;
+    for (client, projects) in tree {
+        let client_total: u64 = projects.values().flat_map(BTreeMap::values).sum();
+        grand_total += client_total;
+        println!("{:<40} {}", client, options.display(client_total));
+        for (project, codes) in projects {
+            println!("  {:<38} {}", project, options.display(codes.values().sum()));
+            for (code, secs) in codes {
+                println!("    {:<36} {}", code, options.display(*secs));
+            }
+        }
+    }
+    grand_total
+}
+
+/// Prints one line per code, see [`print_groups`].
+fn print_compact(totals: &BTreeMap<&str, BTreeMap<&str, u64>>, options: &ReportOptions) -> u64 {
+    let mut grand_total = 0;
+    for (code, tasks) in totals {
+        let code_total: u64 = tasks.values().sum();
+        grand_total += code_total;
+        println!("{:<40} {}", code, options.display(code_total));
+    }
+    grand_total
+}
+
+/// Prints the time logged on each day, see [`print_groups`].
+fn print_days(entries: &[Entry], options: &ReportOptions) -> u64 {
+    let days = daily_totals(entries, &options.range);
+    let language = crate::config::language();
+    let format = format!("{} %a", language.date_format());
+    for (day, secs) in &days {
+        println!("{:<40} {}", language.format_date(*day, &format), options.display(*secs));
+    }
+    days.values().sum()
+}
+
+/// Sums the logged seconds in `range` per day.
+pub fn daily_totals(entries: &[Entry], range: &DateRange) -> BTreeMap<NaiveDate, u64> {
+    let mut days = BTreeMap::new();
+    for entry in entries.iter().filter(|e| range.includes(e)) {
+        *days.entry(entry.date).or_default() += entry.duration_secs;
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    fn entry(date: &str, code: &str, task: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: date.parse().unwrap(),
+            time: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: task.to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn totals_group_by_code_and_task() {
+        let entries = vec![
+            entry("2024-03-01", "ACME", "Design", 600),
+            entry("2024-03-02", "ACME", "Design", 300),
+            entry("2024-03-02", "ACME", "Review", 120),
+            entry("2024-03-03", "Internal", "Email", 60),
+        ];
+        let totals = totals(&entries, &DateRange::default());
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["ACME"]["Design"], 900);
+        assert_eq!(totals["ACME"]["Review"], 120);
+        assert_eq!(totals["Internal"]["Email"], 60);
+    }
+
+    #[test]
+    fn totals_respect_the_date_range() {
+        let entries = vec![entry("2024-03-01", "ACME", "Design", 600), entry("2024-03-05", "ACME", "Design", 300)];
+        let range = DateRange { since: NaiveDate::from_ymd_opt(2024, 3, 2), until: None };
+        assert_eq!(totals(&entries, &range)["ACME"]["Design"], 300);
+    }
+
+    #[test]
+    fn rounding_only_applies_when_enabled() {
+        let options = ReportOptions::default();
+        assert_eq!(options.rounded(401), 401);
+        let options = ReportOptions { round_display: Some(15), round_mode: RoundMode::Up, ..Default::default() };
+        assert_eq!(options.rounded(401), 900);
+    }
+
+    #[test]
+    fn daily_totals_sum_each_day() {
+        let entries = vec![
+            entry("2024-03-01", "ACME", "Design", 600),
+            entry("2024-03-01", "Internal", "Email", 60),
+            entry("2024-03-02", "ACME", "Design", 300),
+        ];
+        let days = daily_totals(&entries, &DateRange::default());
+        assert_eq!(days.values().copied().collect::<Vec<_>>(), vec![660, 300]);
+    }
+
+    #[test]
+    fn rolls_up_to_the_client_part() {
+        let entries = vec![entry("2024-03-01", "ACME/ONBOARDING", "Kickoff", 600), entry("2024-03-01", "ACME/SUPPORT", "Kickoff", 300)];
+        assert_eq!(totals(&entries, &DateRange::default()).len(), 2);
+        let rolled = roll_up(&entries, Level::Client, &Config::default());
+        assert_eq!(totals(&rolled, &DateRange::default())["ACME"]["Kickoff"], 900);
+    }
+
+    #[test]
+    fn rolls_up_to_projects_and_clients() {
+        let config: Config = toml::from_str("[projects.Website]\nclient = 'ACME'\ncodes = ['FEAT-42', 'FEAT-43']").unwrap();
+        let entries = vec![
+            entry("2024-03-01", "FEAT-42", "Design", 600),
+            entry("2024-03-01", "FEAT-43", "Build", 300),
+            entry("2024-03-01", "OPS", "Deploy", 60),
+        ];
+        let projects = roll_up(&entries, Level::Project, &config);
+        assert_eq!(projects.iter().map(|e| e.code.as_str()).collect::<Vec<_>>(), vec!["Website", "Website", "OPS"]);
+        let clients = roll_up(&entries, Level::Client, &config);
+        assert_eq!(totals(&clients, &DateRange::default())["ACME"].values().sum::<u64>(), 900);
+
+        let tree = tree(&entries, &DateRange::default(), &config);
+        assert_eq!(tree["ACME"]["Website"]["FEAT-43"], 300);
+        assert_eq!(tree[billing::NO_CLIENT][NO_PROJECT]["OPS"], 60);
+
+        let options = ReportOptions { round_display: Some(15), round_mode: RoundMode::Up, ..Default::default() };
+        let clients = by_client(&entries, &options, &config);
+        assert_eq!(clients["ACME"], vec![("Website", 15 * 60)]);
+        assert_eq!(clients[billing::NO_CLIENT], vec![("OPS", 15 * 60)]);
+    }
+
+    #[test]
+    fn rounding_impact_per_month_and_code() {
+        let entries = vec![
+            entry("2024-03-01", "ACME", "Design", 20 * 60),
+            entry("2024-03-20", "ACME", "Design", 20 * 60),
+            entry("2024-04-01", "ACME", "Design", 5 * 60),
+        ];
+        let options = ReportOptions { round_display: Some(15), ..Default::default() };
+        let lines = rounding_impact(&entries, &options);
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].period.as_str(), lines[0].raw_secs, lines[0].rounded_secs), ("2024-03", 40 * 60, 45 * 60));
+        assert_eq!(lines[0].difference(), 5 * 60);
+        assert_eq!(lines[1].difference(), -5 * 60);
+
+        // Logged just after midnight on April 1st at +14:00, which is still
+        // March here
+        let abroad = Entry { time: NaiveTime::from_hms_opt(0, 30, 0).unwrap(), offset: Some("+14:00".parse().unwrap()), ..entries[2].clone() };
+        assert_eq!(rounding_impact(&[abroad], &options)[0].period, "2024-03");
+    }
+}
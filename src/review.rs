@@ -0,0 +1,170 @@
+//! Checks for the weekly timesheet review (`review`).
+//!
+//! The review steps through the days of a week and flags entries worth a
+//! second look before the week is submitted: weekdays without any time,
+//! long gaps or overlaps between sessions, days over twelve hours and codes
+//! that have never been used before.
+
+use crate::config::{self, Config};
+use crate::duration;
+use crate::log::{DateRange, Entry};
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, Weekday};
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A gap between two sessions on the same day longer than this is flagged.
+pub const GAP_SECS: u64 = 3600;
+
+/// A day with more time logged than this is flagged.
+pub const LONG_DAY_SECS: u64 = 12 * 3600;
+
+/// Which week to review.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Week {
+    #[default]
+    This,
+    Last,
+}
+
+impl Week {
+    /// The Monday to Sunday range of the week, counted from `today`.
+    pub fn range(self, today: NaiveDate) -> DateRange {
+        let monday = today.week(Weekday::Mon).first_day();
+        let monday = match self {
+            Week::This => monday,
+            Week::Last => monday - Days::new(7),
+        };
+        DateRange { since: Some(monday), until: Some(monday + Days::new(6)) }
+    }
+}
+
+/// Something about a day that may need fixing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anomaly {
+    /// A weekday without any logged time.
+    NoTime,
+    /// Nothing was tracked between two sessions for more than [`GAP_SECS`].
+    Gap { from: NaiveDateTime, to: NaiveDateTime },
+    /// A session started before the previous one ended.
+    Overlap { first: String, second: String },
+    /// More than [`LONG_DAY_SECS`] were logged.
+    LongDay { secs: u64 },
+    /// A code used neither before this week nor in the config file.
+    UnknownCode { code: String },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::NoTime => write!(f, "no time logged on a weekday"),
+            Anomaly::Gap { from, to } => write!(
+                f,
+                "gap of {} between {} and {}",
+                duration::format_hms((*to - *from).num_seconds().max(0) as u64, false),
+                from.format("%H:%M"),
+                to.format("%H:%M")
+            ),
+            Anomaly::Overlap { first, second } => write!(f, "'{}' starts before '{}' ends", second, first),
+            Anomaly::LongDay { secs } => write!(f, "{} logged in one day", duration::format_hms(*secs, false)),
+            Anomaly::UnknownCode { code } => {
+                write!(f, "{} '{}' hasn't been used before", config::labels().code_lower(), code)
+            },
+        }
+    }
+}
+
+/// Codes that are known not to be typos: those used in `earlier` entries
+/// and those mentioned in the config file.
+pub fn known_codes(earlier: &[Entry], config: &Config) -> BTreeSet<String> {
+    let mut codes: BTreeSet<String> = earlier.iter().map(|e| e.code.clone()).collect();
+    codes.extend(config.billing.rates.keys().cloned());
+    codes.extend(config.billing.clients.keys().cloned());
+    codes.extend(config.aliases.values().cloned());
+    codes.extend(config.presets.values().filter_map(|p| p.code.clone()));
+    codes.extend(config.git.codes.values().cloned());
+    codes.extend(config.activity.projects.values().cloned());
+    codes.extend(config.projects.values().flat_map(|p| p.codes.iter().cloned()));
+    codes
+}
+
+/// The entries logged on `day`, ordered by when they started.
+pub fn day_entries(entries: &[Entry], day: NaiveDate) -> Vec<Entry> {
+    let mut selected: Vec<Entry> = entries.iter().filter(|e| e.date == day).cloned().collect();
+    selected.sort_by_key(Entry::start);
+    selected
+}
+
+/// Checks the entries of one day, as returned by [`day_entries`].
+pub fn check_day(day: NaiveDate, entries: &[Entry], known: &BTreeSet<String>) -> Vec<Anomaly> {
+    let mut anomalies = Vec::new();
+    if entries.is_empty() && day.weekday().num_days_from_monday() < 5 {
+        anomalies.push(Anomaly::NoTime);
+    }
+    for pair in entries.windows(2) {
+        let (first, second) = (&pair[0], &pair[1]);
+        if second.start() < first.end() {
+            anomalies.push(Anomaly::Overlap { first: first.task.clone(), second: second.task.clone() });
+        } else if (second.start() - first.end()).num_seconds() > GAP_SECS as i64 {
+            anomalies.push(Anomaly::Gap { from: first.end(), to: second.start() });
+        }
+    }
+    let secs: u64 = entries.iter().map(|e| e.duration_secs).sum();
+    if secs > LONG_DAY_SECS {
+        anomalies.push(Anomaly::LongDay { secs });
+    }
+    let unknown: BTreeSet<&str> = entries.iter().map(|e| e.code.as_str()).filter(|c| !known.contains(*c)).collect();
+    anomalies.extend(unknown.into_iter().map(|code| Anomaly::UnknownCode { code: code.to_string() }));
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time: &str, code: &str, task: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: "2024-05-06".parse().unwrap(),
+            time: time.parse().unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: task.to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let wednesday = "2024-05-08".parse().unwrap();
+        let range = Week::Last.range(wednesday);
+        assert_eq!(range.since, "2024-04-29".parse().ok());
+        assert_eq!(range.until, "2024-05-05".parse().ok());
+        assert_eq!(Week::This.range(wednesday).since, "2024-05-06".parse().ok());
+    }
+
+    #[test]
+    fn flags_gaps_overlaps_long_days_and_unknown_codes() {
+        let monday: NaiveDate = "2024-05-06".parse().unwrap();
+        let known = BTreeSet::from(["X".to_string()]);
+        let entries = vec![
+            entry("10:00:00", "X", "Morning", 3600),
+            // Two hours after the morning session
+            entry("13:00:00", "X", "Lunch call", 3600),
+            entry("12:50:00", "Y", "Overlapping", 600),
+        ];
+        let entries = day_entries(&entries, monday);
+        let anomalies = check_day(monday, &entries, &known);
+        assert_eq!(anomalies.len(), 3, "{:?}", anomalies);
+        assert!(matches!(anomalies[0], Anomaly::Gap { .. }));
+        assert!(matches!(anomalies[1], Anomaly::Overlap { .. }));
+        assert_eq!(anomalies[2], Anomaly::UnknownCode { code: "Y".to_string() });
+
+        assert_eq!(check_day(monday, &[], &known), vec![Anomaly::NoTime]);
+        assert!(check_day(monday + Days::new(5), &[], &known).is_empty());
+        let long = [entry("23:00:00", "X", "Marathon", 13 * 3600)];
+        assert_eq!(check_day(monday, &long, &known), vec![Anomaly::LongDay { secs: 13 * 3600 }]);
+    }
+}
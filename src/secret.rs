@@ -0,0 +1,191 @@
+//! Integration tokens kept in the OS keyring (`secret`) rather than in the
+//! config file, which refers to them as `keyring:<name>`.
+//!
+//! Secrets are stored with the keyring tool of the system: `secret-tool`
+//! (libsecret, which GNOME Keyring and KWallet provide) on Linux and BSD,
+//! and `security` (the login keychain) on macOS. Each profile has its own
+//! secrets, so the `jira-token` of the `work` profile is not the default
+//! profile's.
+
+use crate::config;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// How a config value refers to a secret in the keyring.
+pub const PREFIX: &str = "keyring:";
+
+/// Service the secrets are stored under.
+const SERVICE: &str = "simpletimer";
+
+/// Checks a secret name given on the command line.
+pub fn parse_name(name: &str) -> Result<String, String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        Ok(name.to_string())
+    } else {
+        Err("secret names may only contain letters, digits, '-', '_' and '.'".to_string())
+    }
+}
+
+/// The account a secret is stored under: its name, within the profile.
+fn account(name: &str) -> String {
+    match config::profile() {
+        Some(profile) => format!("{}/{}", profile, name),
+        None => name.to_string(),
+    }
+}
+
+/// Runs a keyring tool, feeding it `input`, and returns its output if it
+/// succeeded or `None` if it failed with `missing`, the status for an
+/// unknown secret.
+fn run(program: &str, args: &[&str], input: Option<&str>, missing: i32) -> io::Result<Option<String>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(e.kind(), format!("'{}' is not installed", program)),
+            _ => e,
+        })?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    match output.status.code() {
+        Some(0) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+        Some(code) if code == missing => Ok(None),
+        _ => Err(io::Error::other(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))),
+    }
+}
+
+/// Stores the secret `name`, replacing any stored before.
+pub fn set(name: &str, value: &str) -> io::Result<()> {
+    let account = account(name);
+    if cfg!(target_os = "macos") {
+        // `security` only takes the password as an argument
+        run("security", &["add-generic-password", "-U", "-s", SERVICE, "-a", &account, "-w", value], None, -1)?;
+    } else if cfg!(unix) {
+        let label = format!("{} {}", SERVICE, account);
+        run("secret-tool", &["store", "--label", &label, "service", SERVICE, "account", &account], Some(value), -1)?;
+    } else {
+        return Err(unsupported());
+    }
+    Ok(())
+}
+
+/// The secret `name`, or `None` if it isn't stored.
+pub fn get(name: &str) -> io::Result<Option<String>> {
+    let account = account(name);
+    let found = if cfg!(target_os = "macos") {
+        run("security", &["find-generic-password", "-s", SERVICE, "-a", &account, "-w"], None, 44)?
+    } else if cfg!(unix) {
+        run("secret-tool", &["lookup", "service", SERVICE, "account", &account], None, 1)?
+    } else {
+        return Err(unsupported());
+    };
+    // `security` ends the password with a newline
+    Ok(found.map(|value| value.strip_suffix('\n').map(str::to_string).unwrap_or(value)))
+}
+
+/// Removes the secret `name`, returning whether it was stored.
+pub fn delete(name: &str) -> io::Result<bool> {
+    let found = get(name)?.is_some();
+    let account = account(name);
+    if cfg!(target_os = "macos") {
+        run("security", &["delete-generic-password", "-s", SERVICE, "-a", &account], None, 44)?;
+    } else {
+        run("secret-tool", &["clear", "service", SERVICE, "account", &account], None, 1)?;
+    }
+    Ok(found)
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "no keyring is supported on this system; use the environment variables instead")
+}
+
+/// The value of a config setting: the secret it refers to if it is a
+/// `keyring:` reference, else the value itself.
+pub fn resolve(value: &str) -> Result<String, String> {
+    let Some(name) = value.strip_prefix(PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let name = name.trim();
+    match get(name) {
+        Ok(Some(secret)) => Ok(secret),
+        Ok(None) => Err(format!("secret '{}' is not in the keyring: store it with `timer secret set {}`", name, name)),
+        Err(e) => Err(format!("secret '{}' can't be read from the keyring: {}", name, e)),
+    }
+}
+
+/// Reads a secret setting from the environment, falling back to the config
+/// value, which may be a `keyring:` reference.
+pub fn lookup(env_var: &str, configured: &Option<String>) -> Result<Option<String>, String> {
+    match std::env::var(env_var).ok().filter(|v| !v.is_empty()) {
+        Some(value) => Ok(Some(value)),
+        None => configured.as_deref().map(resolve).transpose(),
+    }
+}
+
+/// The settings in a config table that refer to the keyring, by dotted key,
+/// with the name of the secret each refers to.
+pub fn references(table: &toml::Table) -> Vec<(String, String)> {
+    fn walk(table: &toml::Table, prefix: &str, found: &mut Vec<(String, String)>) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            match value {
+                toml::Value::String(text) => {
+                    if let Some(name) = text.strip_prefix(PREFIX) {
+                        found.push((key, name.trim().to_string()));
+                    }
+                },
+                toml::Value::Table(table) => walk(table, &key, found),
+                _ => {},
+            }
+        }
+    }
+    let mut found = Vec::new();
+    walk(table, "", &mut found);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_values_are_kept() {
+        assert_eq!(resolve("abc123"), Ok("abc123".to_string()));
+        assert_eq!(lookup("SIMPLETIMER_TEST_UNSET_TOKEN", &None), Ok(None));
+        assert_eq!(lookup("SIMPLETIMER_TEST_UNSET_TOKEN", &Some("abc".to_string())), Ok(Some("abc".to_string())));
+    }
+
+    #[test]
+    fn finds_references() {
+        let table: toml::Table = r#"
+            [jira]
+            url = "https://example.atlassian.net"
+            token = "keyring:jira-token"
+
+            [profiles.work.harvest]
+            token = "keyring: harvest"
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            references(&table),
+            vec![
+                ("jira.token".to_string(), "jira-token".to_string()),
+                ("profiles.work.harvest.token".to_string(), "harvest".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_names() {
+        assert!(parse_name("jira-token").is_ok());
+        assert!(parse_name("smtp.password").is_ok());
+        assert!(parse_name("").is_err());
+        assert!(parse_name("a b").is_err());
+    }
+}
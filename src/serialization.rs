@@ -0,0 +1,414 @@
+//! Round-trip checks for every way entries are written: the storage
+//! backends, archives and the export formats.
+//!
+//! Each check writes entries and reads them back with an independent
+//! reader, comparing the result with what the format promises to keep.
+//! Lossless formats must return the entries unchanged; line-based formats
+//! flatten line breaks but must never split or merge rows. The checks run
+//! as property tests and from `doctor --self-test`, with names full of
+//! emoji, line breaks, quotes and right-to-left text.
+
+use crate::archive;
+use crate::config::ClockifyConfig;
+use crate::export;
+use crate::import::timew;
+use crate::log::{self, Entry};
+use crate::storage::{log_file::LogFile, memory::Memory, Storage};
+use chrono::{NaiveDate, NaiveTime};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Names that have broken, or could break, a row.
+const TRICKY: &[&str] = &[
+    "Plain task",
+    "Comma, separated",
+    "\"Quoted\" and \"\"doubled\"\"",
+    "Line one\nline two",
+    "Windows\r\nline break",
+    "Lone\rcarriage return",
+    "Tab\tseparated",
+    "Semicolon; backslash \\ hash # star *",
+    " leading and trailing spaces ",
+    "Two  spaces",
+    "Emoji ⏱️🚀👩‍💻",
+    "עברית ومعها العربية",
+    "Mixed \u{202E}override\u{202C} and \u{200B}zero width",
+    "Combining e\u{301} and \u{FEFF}BOM",
+    "Line\u{2028}separator",
+    "Null \u{0} and bell \u{7}",
+    "# looks like a comment",
+    "* looks like a heading",
+    "inc 20240101T000000Z - 20240101T010000Z # x",
+    "",
+];
+
+/// Entries using every tricky name as task and code.
+pub fn samples() -> Vec<Entry> {
+    TRICKY
+        .iter()
+        .enumerate()
+        .map(|(i, text)| sample_entry(i as u64, text, TRICKY[TRICKY.len() - 1 - i]))
+        .collect()
+}
+
+/// `count` entries with names mixed from tricky characters, reproducible
+/// for a given `seed`.
+pub fn random_entries(seed: u64, count: usize) -> Vec<Entry> {
+    const PIECES: &[&str] = &[
+        "a", "Z", "7", " ", "  ", ",", ";", "\"", "'", "\\", "#", "*", "-", "\n", "\r", "\r\n", "\t", "⏱️", "👩‍💻", "é",
+        "e\u{301}", "ש", "ع", "\u{202E}", "\u{200B}", "\u{FEFF}", "\u{2028}", "\u{0}", "\u{1B}",
+    ];
+    let mut state = seed | 1;
+    let mut next = move || {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let text = |next: &mut dyn FnMut() -> u64| {
+        let length = next() % 12;
+        (0..length).map(|_| PIECES[(next() % PIECES.len() as u64) as usize]).collect::<String>()
+    };
+    (0..count)
+        .map(|i| {
+            let task = text(&mut next);
+            let code = text(&mut next);
+            sample_entry(next() % 100_000 + i as u64, &task, &code)
+        })
+        .collect()
+}
+
+fn sample_entry(n: u64, task: &str, code: &str) -> Entry {
+    Entry {
+        date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap() + chrono::Days::new(n % 2000),
+        time: NaiveTime::from_num_seconds_from_midnight_opt((n * 7919 % 86_400) as u32, 0).unwrap(),
+        offset: None,
+        code: code.to_string(),
+        task: task.to_string(),
+        duration_secs: n * 104_729 % 36_000,
+        invoice: n.is_multiple_of(3).then(|| format!("2024-{:03}", n % 1000)),
+        git: n.is_multiple_of(4).then(|| "repo@feature/x".to_string()),
+        id: log::new_id(),
+    }
+}
+
+/// Runs every check, returning each one's name and result.
+pub fn check_all(entries: &[Entry]) -> Vec<(&'static str, Result<(), String>)> {
+    let mut results = vec![
+        ("csv", check_csv(entries)),
+        ("memory", check_storage(&mut Memory::default(), entries)),
+        ("log file", with_scratch(|dir| check_storage(&mut LogFile::new(dir.join("time_log.csv")), entries))),
+    ];
+    #[cfg(feature = "sqlite")]
+    results.push((
+        "sqlite",
+        with_scratch(|dir| {
+            let mut storage = crate::storage::sqlite::Sqlite::open(&dir.join("time_log.sqlite")).map_err(|e| e.to_string())?;
+            check_storage(&mut storage, entries)
+        }),
+    ));
+    results.extend([
+        ("archive", with_scratch(|dir| check_archive(dir, entries))),
+        ("timewarrior", check_timew(entries)),
+        ("icalendar", check_ics(entries)),
+        ("clockify", check_clockify(entries)),
+        ("timeclock", check_timeclock(entries)),
+        ("org-mode", check_org(entries)),
+    ]);
+    results
+}
+
+/// Compares what a format promises to keep with what was read back.
+fn expect<T: Debug + PartialEq>(what: &str, index: usize, expected: T, actual: T) -> Result<(), String> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!("entry {}: {} should be {:?}, got {:?}", index + 1, what, expected, actual))
+    }
+}
+
+fn expect_count(expected: usize, actual: usize) -> Result<(), String> {
+    expect("the number of entries", 0, expected, actual)
+}
+
+/// Runs `check` in an empty directory that is removed afterwards.
+fn with_scratch(check: impl FnOnce(&Path) -> Result<(), String>) -> Result<(), String> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir: PathBuf = std::env::temp_dir().join(format!(
+        "simple_timer_self_test_{}_{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let result = check(&dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+fn check_entries(expected: &[Entry], actual: &[Entry]) -> Result<(), String> {
+    expect_count(expected.len(), actual.len())?;
+    expected.iter().zip(actual).enumerate().try_for_each(|(i, (e, a))| expect("the entry", i, e, a))
+}
+
+/// The CSV log must keep every field exactly.
+pub fn check_csv(entries: &[Entry]) -> Result<(), String> {
+    let read = log::parse_entries(&log::to_csv(entries), Path::new("self-test")).map_err(|e| e.to_string())?;
+    check_entries(entries, &read)
+}
+
+/// A storage backend must keep every field exactly, whether entries are
+/// appended or rewritten.
+pub fn check_storage(storage: &mut dyn Storage, entries: &[Entry]) -> Result<(), String> {
+    let all = crate::log::DateRange::default();
+    storage.rewrite(entries).map_err(|e| e.to_string())?;
+    check_entries(entries, &storage.query(&all).map_err(|e| e.to_string())?)?;
+    storage.rewrite(&[]).map_err(|e| e.to_string())?;
+    storage.append_all(entries).map_err(|e| e.to_string())?;
+    check_entries(entries, &storage.query(&all).map_err(|e| e.to_string())?)
+}
+
+/// Archived entries must read back exactly, in date order.
+fn check_archive(dir: &Path, entries: &[Entry]) -> Result<(), String> {
+    let path = dir.join("time_log.csv");
+    log::write_entries(&path, entries).map_err(|e| e.to_string())?;
+    let before = NaiveDate::MAX;
+    archive::archive(&path, before).map_err(|e| e.to_string())?;
+    let read = archive::read_all(&path).map_err(|e| e.to_string())?;
+
+    let mut expected = entries.to_vec();
+    expected.sort_by_key(|e| (e.date.format("%Y").to_string(), e.end()));
+    check_entries(&expected, &read)
+}
+
+/// Line breaks become spaces in line-based formats.
+fn one_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/// Timewarrior keeps code and task on one line, and the times exactly.
+pub fn check_timew(entries: &[Entry]) -> Result<(), String> {
+    let imported = timew::parse(&timew::export(entries));
+    if let Some(error) = imported.errors.first() {
+        return Err(error.to_string());
+    }
+    let read = imported.entries;
+    expect_count(entries.len(), read.len())?;
+    for (i, (entry, read)) in entries.iter().zip(&read).enumerate() {
+        expect("the code", i, one_line(&entry.code), read.code.clone())?;
+        expect("the task", i, one_line(&entry.task), read.task.clone())?;
+        expect("the start", i, entry.start_utc(), read.start_utc())?;
+        expect("the duration", i, entry.duration_secs, read.duration_secs)?;
+    }
+    Ok(())
+}
+
+/// iCalendar keeps text with line breaks as `\n`, dropping other control
+/// characters, which it doesn't allow.
+pub fn check_ics(entries: &[Entry]) -> Result<(), String> {
+    let content = export::ics::export(entries);
+    if content.split("\r\n").any(|line| line.len() > 75) {
+        return Err("a line is longer than 75 octets".to_string());
+    }
+    let unfolded = content.replace("\r\n ", "");
+    let mut events = Vec::new();
+    for line in unfolded.split("\r\n") {
+        if line == "BEGIN:VEVENT" {
+            events.push((String::new(), String::new()));
+        } else if let (Some(value), Some(event)) = (line.strip_prefix("SUMMARY:"), events.last_mut()) {
+            event.0 = unescape_ics(value);
+        } else if let (Some(value), Some(event)) = (line.strip_prefix("CATEGORIES:"), events.last_mut()) {
+            event.1 = unescape_ics(value);
+        }
+    }
+    let text = |s: &str| {
+        s.replace("\r\n", "\n").replace('\r', "\n").chars().filter(|c| !c.is_control() || *c == '\n' || *c == '\t').collect::<String>()
+    };
+    expect_count(entries.len(), events.len())?;
+    for (i, (entry, (summary, categories))) in entries.iter().zip(events).enumerate() {
+        expect("the summary", i, text(&entry.task), summary)?;
+        expect("the category", i, text(&entry.code), categories)?;
+    }
+    Ok(())
+}
+
+fn unescape_ics(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => {},
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Clockify's CSV keeps description and tags exactly.
+pub fn check_clockify(entries: &[Entry]) -> Result<(), String> {
+    let rows = log::parse_csv(&export::clockify::export(entries, &ClockifyConfig::default(), "me@example.com"));
+    expect_count(entries.len(), rows.len().saturating_sub(1))?;
+    for (i, (entry, row)) in entries.iter().zip(rows.iter().skip(1)).enumerate() {
+        expect("the columns", i, 10, row.len())?;
+        expect("the description", i, &entry.task, &row[2])?;
+        expect("the tags", i, &entry.code, &row[5])?;
+    }
+    Ok(())
+}
+
+/// Timeclock keeps one check-in/check-out pair per entry, with the code
+/// as a valid account name.
+pub fn check_timeclock(entries: &[Entry]) -> Result<(), String> {
+    let content = export::timeclock::export(entries);
+    let mut sorted: Vec<&Entry> = entries.iter().collect();
+    sorted.sort_by_key(|e| e.start());
+
+    let lines: Vec<&str> = content.lines().collect();
+    expect_count(sorted.len() * 2, lines.len())?;
+    for (i, (entry, pair)) in sorted.iter().zip(lines.chunks(2)).enumerate() {
+        let Some(check_in) = pair[0].strip_prefix("i ") else {
+            return Err(format!("entry {}: expected a check-in line, got {:?}", i + 1, pair[0]));
+        };
+        if !pair[1].starts_with("o ") {
+            return Err(format!("entry {}: expected a check-out line, got {:?}", i + 1, pair[1]));
+        }
+        let (account, description) = check_in.get(20..).and_then(|rest| rest.split_once("  ")).unwrap_or_default();
+        expect("the account", i, export::timeclock::account(&entry.code).as_str(), account)?;
+        expect("the description", i, one_line(&entry.task).as_str(), description)?;
+    }
+    Ok(())
+}
+
+/// Org-mode keeps a heading per code and task with one clock line per entry.
+pub fn check_org(entries: &[Entry]) -> Result<(), String> {
+    let content = export::org::export(entries);
+    let (mut code, mut task) = (String::new(), String::new());
+    let mut clocks = Vec::new();
+    for line in content.lines() {
+        if let Some(heading) = line.strip_prefix("** ") {
+            task = heading.to_string();
+        } else if let Some(heading) = line.strip_prefix("* ") {
+            code = heading.to_string();
+        } else if line.trim_start().starts_with("CLOCK: ") {
+            clocks.push((code.clone(), task.clone()));
+        }
+    }
+    let mut expected: Vec<(String, String)> = entries.iter().map(|e| (one_line(&e.code), one_line(&e.task))).collect();
+    expected.sort();
+    clocks.sort();
+    expect_count(expected.len(), clocks.len())?;
+    expected.into_iter().zip(clocks).enumerate().try_for_each(|(i, (e, a))| expect("the headings", i, e, a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Any text, or text made of the characters most likely to break a row.
+    fn text() -> impl Strategy<Value = String> {
+        prop_oneof![any::<String>(), "[a-zA-Z0-9 ,;\"'\\\\#*\r\n\t⏱🚀éשع\u{202E}\u{200B}\u{FEFF}\u{2028}\u{0}-]{0,16}"]
+    }
+
+    fn entry() -> impl Strategy<Value = Entry> {
+        (
+            0..3650u64,
+            0..86_400u32,
+            text(),
+            text(),
+            0..200_000u64,
+            proptest::option::of("[A-Za-z0-9-]{1,12}"),
+            proptest::option::of("[a-z]{1,8}@[a-z/]{1,12}"),
+            "[0-9a-f-]{1,36}",
+        )
+            .prop_map(|(day, secs, code, task, duration_secs, invoice, git, id)| Entry {
+                date: NaiveDate::from_ymd_opt(2015, 1, 1).unwrap() + chrono::Days::new(day),
+                time: NaiveTime::from_num_seconds_from_midnight_opt(secs, 0).unwrap(),
+                offset: None,
+                code,
+                task,
+                duration_secs,
+                invoice,
+                git,
+                id,
+            })
+    }
+
+    #[test]
+    fn samples_pass_every_check() {
+        for (name, result) in check_all(&samples()) {
+            assert_eq!(result, Ok(()), "{}", name);
+        }
+        for (name, result) in check_all(&random_entries(42, 200)) {
+            assert_eq!(result, Ok(()), "{}", name);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn csv_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_csv(&entries), Ok(()));
+        }
+
+        #[test]
+        fn memory_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_storage(&mut Memory::default(), &entries), Ok(()));
+        }
+
+        #[test]
+        fn timew_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_timew(&entries), Ok(()));
+        }
+
+        #[test]
+        fn ics_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_ics(&entries), Ok(()));
+        }
+
+        #[test]
+        fn clockify_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_clockify(&entries), Ok(()));
+        }
+
+        #[test]
+        fn timeclock_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_timeclock(&entries), Ok(()));
+        }
+
+        #[test]
+        fn org_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(check_org(&entries), Ok(()));
+        }
+    }
+
+    proptest! {
+        // These touch the file system, so fewer cases
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn log_file_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(with_scratch(|dir| check_storage(&mut LogFile::new(dir.join("time_log.csv")), &entries)), Ok(()));
+        }
+
+        #[test]
+        fn archive_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            prop_assert_eq!(with_scratch(|dir| check_archive(dir, &entries)), Ok(()));
+        }
+
+        #[cfg(feature = "sqlite")]
+        #[test]
+        fn sqlite_round_trips(entries in proptest::collection::vec(entry(), 0..20)) {
+            let result = with_scratch(|dir| {
+                let mut storage = crate::storage::sqlite::Sqlite::open(&dir.join("time_log.sqlite")).map_err(|e| e.to_string())?;
+                check_storage(&mut storage, &entries)
+            });
+            prop_assert_eq!(result, Ok(()));
+        }
+    }
+}
@@ -0,0 +1,427 @@
+//! The running sessions, shared between processes through state files.
+//!
+//! Starting the timer records the task, code and start time in
+//! `session.toml` in the data directory; stopping it (with Ctrl+C, `stop`
+//! or `toggle`, from any shell) removes the file and logs the entry. A
+//! foreground timer watches the file and exits when its session has been
+//! stopped elsewhere.
+//!
+//! Named sessions (`start --session <NAME>`) run alongside the default one,
+//! each with its own file in `sessions/`, for parallel work such as a build
+//! running for one client while reviewing for another.
+//!
+//! A state file that can't be parsed is quarantined rather than stopping
+//! the command, and the session is restored from what could be salvaged
+//! (see [`crate::recover`]).
+
+use crate::config;
+use crate::hooks::{self, Event};
+use crate::laps;
+use crate::log::{self, Entry};
+use crate::recover;
+use crate::slack;
+use crate::storage;
+use chrono::{DateTime, Local, Offset, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A session that is currently being tracked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Name of a session running alongside the default one, which has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub task: String,
+    pub code: String,
+    pub started: DateTime<Local>,
+    /// Repository and branch the task was taken from, see [`Entry::git`].
+    pub git: Option<String>,
+    /// Process showing the live timer, if the session runs in the foreground.
+    pub pid: Option<u32>,
+    /// Longest the session may run, in seconds; it is logged as having
+    /// stopped when it reached this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_secs: Option<u64>,
+    /// Laps recorded so far, oldest first; see [`crate::laps`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub laps: Vec<Lap>,
+}
+
+/// The end of a named segment of a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lap {
+    pub name: String,
+    pub at: DateTime<Local>,
+}
+
+impl Session {
+    pub fn new(name: Option<String>, task: String, code: String, git: Option<String>, foreground: bool) -> Self {
+        Session {
+            name,
+            task,
+            code,
+            started: Local::now(),
+            git,
+            pid: foreground.then(std::process::id),
+            max_secs: None,
+            laps: Vec::new(),
+        }
+    }
+
+    /// Whether `other` is the same run of a session as this one, perhaps
+    /// with more laps.
+    pub fn same_run(&self, other: &Session) -> bool {
+        self.name == other.name && self.started == other.started
+    }
+
+    /// Records a lap ending at `at`, named `Lap <n>` if no name is given,
+    /// returning the length of its segment in seconds.
+    pub fn add_lap(&mut self, name: Option<String>, at: DateTime<Local>) -> u64 {
+        let previous = self.laps.last().map_or(self.started, |lap| lap.at);
+        let name = name.filter(|n| !n.trim().is_empty()).unwrap_or_else(|| format!("Lap {}", self.laps.len() + 1));
+        self.laps.push(Lap { name: name.trim().to_string(), at });
+        (at - previous).num_seconds().max(0) as u64
+    }
+
+    /// The segments ended by the laps, if the session were stopped at `end`;
+    /// laps after `end` are left out.
+    pub fn segments(&self, end: DateTime<Local>) -> Vec<laps::Segment> {
+        let mut previous = self.started;
+        self.laps
+            .iter()
+            .filter(|lap| lap.at <= end)
+            .map(|lap| {
+                let secs = (lap.at - previous).num_seconds().max(0) as u64;
+                previous = lap.at;
+                laps::Segment { name: lap.name.clone(), secs }
+            })
+            .collect()
+    }
+
+    /// When the session reaches its maximum duration, if it has one.
+    pub fn deadline(&self) -> Option<DateTime<Local>> {
+        let max = TimeDelta::try_seconds(i64::try_from(self.max_secs?).ok()?)?;
+        self.started.checked_add_signed(max)
+    }
+
+    /// Seconds elapsed since the session started.
+    pub fn elapsed_secs(&self) -> u64 {
+        (Local::now() - self.started).num_seconds().max(0) as u64
+    }
+
+    /// The log entry for this session if it were stopped at `end`.
+    pub fn to_entry(&self, end: DateTime<Local>) -> Entry {
+        Entry {
+            date: end.date_naive(),
+            time: end.time(),
+            offset: Some(end.offset().fix()),
+            code: self.code.clone(),
+            task: self.task.clone(),
+            duration_secs: (end - self.started).num_seconds().max(0) as u64,
+            invoice: None,
+            git: self.git.clone(),
+            id: log::new_id(),
+        }
+    }
+}
+
+/// The state file of the default session (`None`) or a named one.
+pub fn state_path(name: Option<&str>) -> PathBuf {
+    match name {
+        None => config::data_dir().join("session.toml"),
+        Some(name) => sessions_dir().join(format!("{}.toml", name)),
+    }
+}
+
+fn sessions_dir() -> PathBuf {
+    config::data_dir().join("sessions")
+}
+
+/// Checks a session name given on the command line. Names become file
+/// names, so only letters, digits, `-` and `_` are allowed.
+pub fn parse_name(name: &str) -> Result<String, String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        Ok(name.to_string())
+    } else {
+        Err("session names may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+/// The running default session (`None`) or named session, if any.
+pub fn current(name: Option<&str>) -> io::Result<Option<Session>> {
+    read_state(name, &state_path(name))
+}
+
+fn read_state(name: Option<&str>, path: &Path) -> io::Result<Option<Session>> {
+    let Some(content) = read_file(path)? else {
+        return Ok(None);
+    };
+    match parse_state(name, &content) {
+        Some(session) => Ok(Some(session)),
+        None => restore(name, path),
+    }
+}
+
+/// The content of a state file, or `None` if there is none.
+fn read_file(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+    }
+}
+
+fn parse_state(name: Option<&str>, content: &[u8]) -> Option<Session> {
+    let mut session = std::str::from_utf8(content).ok().and_then(|c| toml::from_str::<Session>(c).ok())?;
+    // The file decides which session this is
+    session.name = name.map(str::to_string);
+    Some(session)
+}
+
+/// Safe mode for a corrupt state file: quarantines it and restores the
+/// session from what can be salvaged, or leaves it to `recover` if its
+/// start is lost, so tracking carries on either way.
+///
+/// The file is read again under the lock [`save`] takes first, so only a
+/// file that is corrupt on its own is quarantined, never one read while
+/// another process was saving it. An empty file is left alone.
+fn restore(name: Option<&str>, path: &Path) -> io::Result<Option<Session>> {
+    let _lock = log::lock(path)?;
+    let Some(content) = read_file(path)? else {
+        return Ok(None);
+    };
+    if let Some(session) = parse_state(name, &content) {
+        return Ok(Some(session));
+    }
+    if content.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    let salvaged = recover::salvage(&content).session(name);
+    let moved = match recover::quarantine(path, salvaged.is_some()) {
+        Ok(moved) => moved,
+        // Stopped meanwhile
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+    };
+    let which = name.map_or("the running session".to_string(), |name| format!("session '{}'", name));
+    match salvaged {
+        Some(session) => {
+            write_state(path, &session)?;
+            eprintln!(
+                "Warning: the state of {} was corrupt; '{}' ({}) was restored from it and keeps running. The original is in '{}'.",
+                which,
+                session.task,
+                session.code,
+                moved.display()
+            );
+            Ok(Some(session))
+        },
+        None => {
+            eprintln!(
+                "Warning: the state of {} was corrupt and has been moved to '{}'. Tracking carries on; run `timer recover` to log what can be salvaged.",
+                which,
+                moved.display()
+            );
+            Ok(None)
+        },
+    }
+}
+
+/// Every running session: the default one first, then named ones by name.
+pub fn all() -> io::Result<Vec<Session>> {
+    let mut names: Vec<String> = match fs::read_dir(sessions_dir()) {
+        Ok(files) => files
+            .filter_map(|file| {
+                let path = file.ok()?.path();
+                (path.extension()? == "toml").then(|| path.file_stem()?.to_str().map(str::to_string))?
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    names.sort();
+
+    let mut sessions: Vec<Session> = current(None)?.into_iter().collect();
+    for name in names {
+        sessions.extend(current(Some(&name))?);
+    }
+    Ok(sessions)
+}
+
+/// Records `session` as the running session of its name. The state file
+/// is replaced in one step under a lock, so other commands never read a
+/// half-written one.
+pub fn save(session: &Session) -> io::Result<()> {
+    let path = state_path(session.name.as_deref());
+    let _lock = log::lock(&path)?;
+    write_state(&path, session)
+}
+
+fn write_state(path: &Path, session: &Session) -> io::Result<()> {
+    let content = toml::to_string(session).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    log::replace_file(path, content.as_bytes())
+}
+
+/// Records a lap of the running session `name` ending at `at`, e.g. from
+/// another shell than the one showing its timer, which picks it up. Returns
+/// the session with the lap and the length of its segment, or `None` if no
+/// session of that name runs.
+pub fn record_lap(name: Option<&str>, lap: Option<String>, at: DateTime<Local>) -> io::Result<Option<(Session, u64)>> {
+    let Some(mut session) = current(name)? else {
+        return Ok(None);
+    };
+    let secs = session.add_lap(lap, at);
+    save(&session)?;
+    Ok(Some((session, secs)))
+}
+
+/// Records `session` as newly started, runs the start hooks and shows it
+/// as your Slack status.
+pub fn start(session: &Session) -> io::Result<()> {
+    save(session)?;
+    let config = config::load();
+    hooks::fire(&config.hooks, Event::Start(session));
+    if slack::enabled(&config.slack) {
+        slack::show(&config.slack, Some(session));
+    }
+    Ok(())
+}
+
+/// Stops the running default (`None`) or named session and logs it,
+/// returning the logged entry.
+///
+/// The state file is removed before logging, so when two processes try to
+/// stop the same session at once only one of them logs it. If logging
+/// fails, the session is saved back and keeps running.
+pub fn stop(name: Option<&str>) -> io::Result<Option<Entry>> {
+    stop_at(name, Local::now())
+}
+
+/// Like [`stop`], but logs the session as having ended at `end`, e.g. when
+/// the next session was backdated. A session started after `end` is logged
+/// with no time, and one that reached its maximum duration before `end` as
+/// having ended then.
+pub fn stop_at(name: Option<&str>, end: DateTime<Local>) -> io::Result<Option<Entry>> {
+    let Some(session) = current(name)? else {
+        return Ok(None);
+    };
+    let end = session.deadline().map_or(end, |deadline| deadline.min(end));
+    match fs::remove_file(state_path(name)) {
+        Ok(()) => {},
+        // Someone else stopped it first
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let config = config::load();
+    let mut entry = session.to_entry(end);
+    entry.duration_secs = config.precision.apply(entry.duration_secs);
+    if let Err(e) = storage::open().and_then(|mut storage| storage.append(&entry)) {
+        // Keep it running rather than lose it, or at least say what it was
+        if let Err(saved) = save(&session) {
+            let lost = format!(
+                "{}, and the session couldn't be kept running either ({}). Add it with `review`: '{}' ({}) from {} to {}",
+                e,
+                saved,
+                session.task,
+                session.code,
+                entry.start().format("%Y-%m-%d %H:%M:%S"),
+                entry.end().format("%H:%M:%S")
+            );
+            return Err(io::Error::new(e.kind(), lost));
+        }
+        return Err(e);
+    }
+    // The entry is logged; losing its laps only loses detail
+    if let Err(e) = laps::record(&laps::laps_path(), &entry.id, &session.segments(end)) {
+        eprintln!("Warning: the laps of '{}' ({}) couldn't be saved: {}", entry.task, entry.code, e);
+    }
+    hooks::fire(&config.hooks, Event::Stop(&session, &entry));
+    if slack::enabled(&config.slack) {
+        // Show a session that still runs, if any
+        slack::show(&config.slack, all().unwrap_or_default().first());
+    }
+    Ok(Some(entry))
+}
+
+/// Stops and logs every session that has reached its maximum duration,
+/// returning them with their entries.
+///
+/// A detached session has no process to stop it on time, so this runs at
+/// the start of every command; its entry still ends at the limit.
+pub fn stop_expired() -> io::Result<Vec<(Session, Entry)>> {
+    let now = Local::now();
+    let mut stopped = Vec::new();
+    for session in all()? {
+        if session.deadline().is_some_and(|deadline| deadline <= now)
+            && let Some(entry) = stop_at(session.name.as_deref(), now)?
+        {
+            stopped.push((session, entry));
+        }
+    }
+    Ok(stopped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_names_are_safe_file_names() {
+        assert_eq!(parse_name("builds"), Ok("builds".to_string()));
+        assert_eq!(parse_name("client-a_2"), Ok("client-a_2".to_string()));
+        for bad in ["", "../x", "a/b", "a b", ".hidden"] {
+            assert!(parse_name(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn deadline_follows_the_maximum_duration() {
+        let mut session = Session::new(None, "Task".to_string(), "X".to_string(), None, false);
+        assert_eq!(session.deadline(), None);
+        session.max_secs = Some(3600);
+        assert_eq!(session.deadline(), Some(session.started + TimeDelta::hours(1)));
+        session.max_secs = Some(u64::MAX);
+        assert_eq!(session.deadline(), None);
+    }
+
+    #[test]
+    fn reads_never_see_a_session_being_saved() {
+        let path = std::env::temp_dir().join(format!("simple_timer_session_{}.toml", std::process::id()));
+        let mut session = Session::new(None, "Debugging".to_string(), "X".to_string(), None, false);
+        write_state(&path, &session).unwrap();
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                for _ in 0..300 {
+                    session.add_lap(None, Local::now());
+                    let _lock = log::lock(&path).unwrap();
+                    write_state(&path, &session).unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let read = read_state(None, &path).unwrap().expect("The session keeps running");
+            assert_eq!(read.task, "Debugging");
+        }
+        writer.join().unwrap();
+        assert_eq!(read_state(None, &path).unwrap().unwrap().laps.len(), 300);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn laps_split_the_session_into_segments() {
+        let mut session = Session::new(None, "Debugging".to_string(), "X".to_string(), None, false);
+        let before = session.clone();
+        let started = session.started;
+        assert_eq!(session.add_lap(Some("Test run".to_string()), started + TimeDelta::minutes(5)), 300);
+        assert!(session.same_run(&before));
+        assert_eq!(session.add_lap(None, started + TimeDelta::minutes(7)), 120);
+        assert_eq!(session.laps[1].name, "Lap 2");
+
+        let segments = session.segments(started + TimeDelta::minutes(6));
+        assert_eq!(segments, vec![laps::Segment { name: "Test run".to_string(), secs: 300 }]);
+        assert_eq!(session.segments(started + TimeDelta::hours(1)).len(), 2);
+    }
+}
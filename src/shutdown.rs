@@ -0,0 +1,55 @@
+//! Finishing up when the process is told to end: Ctrl+C, `kill` (SIGTERM),
+//! a closed terminal (SIGHUP), and on Windows a closed console window, a
+//! logoff or a shutdown.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+/// Runs `handler` when the process is told to end, then exits. `handler`
+/// may exit itself, e.g. with an error status.
+pub fn on_exit(handler: impl Fn() + Send + Sync + 'static) {
+    let handler: Arc<dyn Fn() + Send + Sync> = Arc::new(move || {
+        // Printing fails once the terminal is gone (SIGHUP); what matters, such
+        // as logging the session, is done before that
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(&handler));
+        std::process::exit(0);
+    });
+    let signalled = handler.clone();
+    ctrlc::set_handler(move || signalled()).expect("Error setting the signal handler");
+    #[cfg(windows)]
+    windows::on_close(handler);
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::sync::{Arc, OnceLock};
+    use windows_sys::Win32::Foundation::{BOOL, FALSE, TRUE};
+    use windows_sys::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT};
+
+    static HANDLER: OnceLock<Arc<dyn Fn() + Send + Sync>> = OnceLock::new();
+
+    /// Runs `handler` when the console window is closed, the user logs off or
+    /// the system shuts down.
+    pub fn on_close(handler: Arc<dyn Fn() + Send + Sync>) {
+        if HANDLER.set(handler).is_ok() {
+            // Called before the Ctrl+C handler, which was added first
+            // SAFETY: `handle` is a valid handler routine for the whole process
+            unsafe { SetConsoleCtrlHandler(Some(handle), TRUE) };
+        }
+    }
+
+    unsafe extern "system" fn handle(event: u32) -> BOOL {
+        match event {
+            // Windows ends the process as soon as this returns, before the
+            // Ctrl+C handler's thread would get to run, so it runs here
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+                if let Some(handler) = HANDLER.get() {
+                    handler();
+                }
+                TRUE
+            },
+            // Ctrl+C and Ctrl+Break go on to the Ctrl+C handler
+            _ => FALSE,
+        }
+    }
+}
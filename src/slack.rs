@@ -0,0 +1,90 @@
+//! Your Slack status while a session runs (`[slack]`).
+//!
+//! Starting a session sets the status, e.g. 🎯 "Focusing: Review", until the
+//! session's maximum duration if it has one. Stopping it clears the status,
+//! or shows another session that still runs. Needs a user token with the
+//! `users.profile:write` scope; failures are reported on stderr and never
+//! stop the timer.
+
+use crate::config::SlackConfig;
+use crate::secret;
+use crate::session::Session;
+use serde_json::{Value, json};
+
+/// Slack only shows this many characters of a status.
+const MAX_TEXT: usize = 100;
+
+/// Whether a token is configured.
+pub fn enabled(config: &SlackConfig) -> bool {
+    config.token.is_some() || std::env::var("SLACK_TOKEN").is_ok_and(|token| !token.is_empty())
+}
+
+/// The profile Slack's `users.profile.set` takes to show `session`, or to
+/// clear the status if there is none.
+pub fn profile(config: &SlackConfig, session: Option<&Session>) -> Value {
+    let Some(session) = session else {
+        return json!({ "status_text": "", "status_emoji": "", "status_expiration": 0 });
+    };
+    let text = config.text.replace("{task}", &session.task).replace("{code}", &session.code);
+    json!({
+        "status_text": text.chars().take(MAX_TEXT).collect::<String>(),
+        "status_emoji": config.emoji,
+        "status_expiration": session.deadline().map_or(0, |deadline| deadline.timestamp()),
+    })
+}
+
+/// Shows `session` as your status, or clears it.
+pub fn show(config: &SlackConfig, session: Option<&Session>) {
+    let token = match secret::lookup("SLACK_TOKEN", &config.token) {
+        Ok(Some(token)) => token,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Failed to set the Slack status: {}", e);
+            return;
+        },
+    };
+    if let Err(e) = set_profile(&token, &profile(config, session)) {
+        eprintln!("Failed to set the Slack status: {}", e);
+    }
+}
+
+#[cfg(feature = "integrations")]
+fn set_profile(token: &str, profile: &Value) -> Result<(), String> {
+    let response: Value = ureq::post("https://slack.com/api/users.profile.set")
+        .timeout(std::time::Duration::from_secs(10))
+        .set("Authorization", &format!("Bearer {}", token))
+        .send_json(json!({ "profile": profile }))
+        .map_err(crate::push::http_error)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    // Slack answers errors with HTTP 200 too
+    match response["ok"].as_bool() {
+        Some(true) => Ok(()),
+        _ => Err(response["error"].as_str().unwrap_or("unexpected response").to_string()),
+    }
+}
+
+#[cfg(not(feature = "integrations"))]
+fn set_profile(_token: &str, _profile: &Value) -> Result<(), String> {
+    Err("not available in this build (needs the integrations feature)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_the_session_until_its_limit() {
+        let config = SlackConfig { text: "{task} ({code})".to_string(), ..Default::default() };
+        let mut session = Session::new(None, "Review".to_string(), "ACME".to_string(), None, false);
+        assert_eq!(profile(&config, Some(&session)), json!({ "status_text": "Review (ACME)", "status_emoji": ":dart:", "status_expiration": 0 }));
+
+        session.max_secs = Some(3600);
+        session.task = "x".repeat(150);
+        let shown = profile(&config, Some(&session));
+        assert_eq!(shown["status_expiration"], session.started.timestamp() + 3600);
+        assert_eq!(shown["status_text"].as_str().unwrap().chars().count(), MAX_TEXT);
+
+        assert_eq!(profile(&config, None)["status_text"], "");
+    }
+}
@@ -0,0 +1,186 @@
+//! Weekly snapshots of the log, to see what changed since.
+//!
+//! The first time the log is opened in a week (weeks start on Monday), a
+//! copy of every entry, archived ones included, is kept next to the log in
+//! `snapshots/<log>-<date>.csv.zst`, in the archive format. `diff` compares
+//! the log with a snapshot to list added, edited and deleted entries, e.g.
+//! as a check before submitting a timesheet. The last [`KEEP`] snapshots
+//! are kept.
+
+use crate::archive;
+use crate::log::{self, DateRange, Entry};
+use crate::storage::Storage;
+use chrono::{Local, NaiveDate, Weekday};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Number of snapshots kept; older ones are removed.
+pub const KEEP: usize = 12;
+
+/// Snapshots are written on an ordinary command, so favour speed.
+const LEVEL: i32 = 3;
+
+/// Directory holding the snapshots of the log at `log_path`.
+pub fn snapshot_dir(log_path: &Path) -> PathBuf {
+    log_path.with_file_name("snapshots")
+}
+
+fn snapshot_path(log_path: &Path, date: NaiveDate) -> PathBuf {
+    let stem = log_path.file_stem().unwrap_or_default().to_string_lossy();
+    snapshot_dir(log_path).join(format!("{}-{}.csv.zst", stem, date.format("%Y-%m-%d")))
+}
+
+/// Existing snapshots of the log and the days they were taken, oldest first.
+pub fn list(log_path: &Path) -> io::Result<Vec<(NaiveDate, PathBuf)>> {
+    let stem = log_path.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}-", stem);
+    let mut snapshots: Vec<(NaiveDate, PathBuf)> = match std::fs::read_dir(snapshot_dir(log_path)) {
+        Ok(dir) => dir
+            .filter_map(Result::ok)
+            .filter_map(|e| {
+                let path = e.path();
+                let name = path.file_name()?.to_str()?;
+                let date = name.strip_prefix(&prefix)?.strip_suffix(".csv.zst")?;
+                Some((NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?, path))
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Reads the entries of a snapshot.
+pub fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    archive::read_shard(path)
+}
+
+/// Takes a snapshot of `storage` unless one was taken this week, returning
+/// its path, and removes all but the last [`KEEP`] snapshots.
+pub fn take_weekly(log_path: &Path, storage: &dyn Storage) -> io::Result<Option<PathBuf>> {
+    let today = Local::now().date_naive();
+    let week = today.week(Weekday::Mon).first_day();
+    let snapshots = list(log_path)?;
+    if snapshots.last().is_some_and(|(date, _)| *date >= week) {
+        return Ok(None);
+    }
+
+    let path = snapshot_path(log_path, today);
+    std::fs::create_dir_all(snapshot_dir(log_path))?;
+    let entries = storage.history(&DateRange::default())?;
+    let compressed = zstd::encode_all(log::to_csv(&entries).as_bytes(), LEVEL)?;
+    log::replace_file(&path, &compressed)?;
+
+    for (_, old) in snapshots.iter().rev().skip(KEEP - 1) {
+        std::fs::remove_file(old)?;
+    }
+    Ok(Some(path))
+}
+
+/// The snapshot to compare with for changes since `since`: the newest one
+/// taken on or before that day, or else the oldest there is.
+pub fn find(log_path: &Path, since: NaiveDate) -> io::Result<Option<(NaiveDate, PathBuf)>> {
+    let mut snapshots = list(log_path)?;
+    let index = snapshots.iter().rposition(|(date, _)| *date <= since).unwrap_or(0);
+    Ok((index < snapshots.len()).then(|| snapshots.swap_remove(index)))
+}
+
+/// How entries differ between a snapshot and the log.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub added: Vec<Entry>,
+    /// Entries as they were and as they are now.
+    pub edited: Vec<(Entry, Entry)>,
+    pub deleted: Vec<Entry>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.edited.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Compares entries by ID. Entries without one (from logs written before
+/// IDs existed) only match an entry with the same content.
+pub fn diff(before: &[Entry], after: &[Entry]) -> Changes {
+    let mut by_id: HashMap<&str, &Entry> = HashMap::new();
+    let mut by_content: HashMap<Entry, usize> = HashMap::new();
+    for entry in before {
+        if entry.id.is_empty() {
+            *by_content.entry(without_id(entry)).or_default() += 1;
+        } else {
+            by_id.insert(&entry.id, entry);
+        }
+    }
+
+    let mut changes = Changes::default();
+    for entry in after {
+        if let Some(old) = by_id.remove(entry.id.as_str()) {
+            if old != entry {
+                changes.edited.push((old.clone(), entry.clone()));
+            }
+            continue;
+        }
+        // An entry given its ID since the snapshot
+        if let Some(count) = by_content.get_mut(&without_id(entry)).filter(|count| **count > 0) {
+            *count -= 1;
+            continue;
+        }
+        changes.added.push(entry.clone());
+    }
+
+    for entry in before {
+        let gone = if entry.id.is_empty() {
+            by_content.get_mut(&without_id(entry)).filter(|count| **count > 0).map(|count| *count -= 1).is_some()
+        } else {
+            by_id.contains_key(entry.id.as_str())
+        };
+        if gone {
+            changes.deleted.push(entry.clone());
+        }
+    }
+    changes
+}
+
+fn without_id(entry: &Entry) -> Entry {
+    Entry { id: String::new(), ..entry.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, task: &str) -> Entry {
+        Entry {
+            date: "2024-05-06".parse().unwrap(),
+            time: "10:00:00".parse().unwrap(),
+            offset: None,
+            code: "X".to_string(),
+            task: task.to_string(),
+            duration_secs: 600,
+            invoice: None,
+            git: None,
+            id: id.to_string(),
+        }
+    }
+
+    fn tasks(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.task.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_added_edited_and_deleted_entries() {
+        let before = vec![entry("1", "kept"), entry("2", "typo"), entry("3", "removed"), entry("", "legacy"), entry("", "legacy gone")];
+        // The legacy entry was given an ID since
+        let after = vec![entry("1", "kept"), entry("2", "fixed"), entry("4", "legacy"), entry("5", "new")];
+
+        let changes = diff(&before, &after);
+        assert_eq!(tasks(&changes.added), vec!["new"]);
+        assert_eq!(changes.edited.len(), 1);
+        assert_eq!((changes.edited[0].0.task.as_str(), changes.edited[0].1.task.as_str()), ("typo", "fixed"));
+        assert_eq!(tasks(&changes.deleted), vec!["removed", "legacy gone"]);
+        assert!(diff(&after, &after).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! Totals, averages and records over the log (`stats`).
+//!
+//! A streak counts consecutive days with time logged. Weekends without time
+//! don't break a streak (nor extend it), and a streak that reached yesterday
+//! is still current until today is over.
+
+use crate::log::Entry;
+use chrono::{Datelike, Days, NaiveDate};
+use std::collections::BTreeMap;
+
+/// Statistics over a set of entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub total_secs: u64,
+    pub entries: usize,
+    /// Days with any time logged.
+    pub days: usize,
+    /// The longest single session.
+    pub longest: Option<Entry>,
+    /// Consecutive days with time logged, up to today.
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    /// The day with the most time logged, and that time.
+    pub busiest_day: Option<(NaiveDate, u64)>,
+}
+
+impl Stats {
+    /// Computes the statistics of `entries` as of `today`.
+    pub fn new(entries: &[Entry], today: NaiveDate) -> Self {
+        let mut per_day: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+        for entry in entries {
+            *per_day.entry(entry.date).or_default() += entry.duration_secs;
+        }
+        let days: Vec<NaiveDate> = per_day.keys().copied().collect();
+        Stats {
+            total_secs: per_day.values().sum(),
+            entries: entries.len(),
+            days: days.len(),
+            longest: entries.iter().max_by_key(|e| e.duration_secs).cloned(),
+            current_streak: current_streak(&days, today),
+            longest_streak: longest_streak(&days),
+            // The earliest of equally busy days
+            busiest_day: per_day.iter().rev().max_by_key(|(_, secs)| **secs).map(|(day, secs)| (*day, *secs)),
+        }
+    }
+
+    /// The average time per day with time logged.
+    pub fn average_secs(&self) -> u64 {
+        self.total_secs.checked_div(self.days as u64).unwrap_or(0)
+    }
+}
+
+fn is_weekend(day: NaiveDate) -> bool {
+    day.weekday().num_days_from_monday() >= 5
+}
+
+/// Whether `later` continues a streak that reached `earlier`: the next day,
+/// or the next after a weekend without time.
+fn continues(earlier: NaiveDate, later: NaiveDate) -> bool {
+    let mut day = earlier + Days::new(1);
+    while day < later && is_weekend(day) {
+        day = day + Days::new(1);
+    }
+    day == later
+}
+
+/// The streak ending today, or yesterday (skipping a weekend) if nothing
+/// was logged today yet. `days` are sorted and distinct.
+fn current_streak(days: &[NaiveDate], today: NaiveDate) -> usize {
+    let Some(last) = days.iter().rposition(|day| *day <= today) else { return 0 };
+    if days[last] != today && !continues(days[last], today) {
+        return 0;
+    }
+    1 + days[..=last].windows(2).rev().take_while(|pair| continues(pair[0], pair[1])).count()
+}
+
+fn longest_streak(days: &[NaiveDate]) -> usize {
+    let mut longest = 0;
+    let mut streak = 0;
+    for (i, day) in days.iter().enumerate() {
+        streak = if i > 0 && continues(days[i - 1], *day) { streak + 1 } else { 1 };
+        longest = longest.max(streak);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    fn entry(date: &str, task: &str, duration_secs: u64) -> Entry {
+        Entry {
+            date: date.parse().unwrap(),
+            time: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            offset: None,
+            code: "X".to_string(),
+            task: task.to_string(),
+            duration_secs,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn computes_totals_records_and_streaks() {
+        let entries = vec![
+            // A streak of two, broken on Wednesday 2024-05-01
+            entry("2024-04-29", "Planning", 3600),
+            entry("2024-04-30", "Design", 4 * 3600),
+            // Thursday to Tuesday, over a weekend without time
+            entry("2024-05-02", "Build", 3600),
+            entry("2024-05-03", "Build", 3600),
+            entry("2024-05-03", "Review", 2 * 3600),
+            entry("2024-05-06", "Build", 3600),
+            entry("2024-05-07", "Build", 3600),
+        ];
+        let wednesday = "2024-05-08".parse().unwrap();
+        let stats = Stats::new(&entries, wednesday);
+        assert_eq!(stats.total_secs, 11 * 3600);
+        assert_eq!((stats.entries, stats.days), (7, 6));
+        assert_eq!(stats.average_secs(), 11 * 3600 / 6);
+        assert_eq!(stats.longest.unwrap().task, "Design");
+        assert_eq!(stats.busiest_day, Some(("2024-04-30".parse().unwrap(), 4 * 3600)));
+        assert_eq!(stats.current_streak, 4);
+        assert_eq!(stats.longest_streak, 4);
+
+        // Nothing logged yesterday
+        assert_eq!(Stats::new(&entries, "2024-05-09".parse().unwrap()).current_streak, 0);
+        assert_eq!(Stats::new(&[], wednesday), Stats {
+            total_secs: 0,
+            entries: 0,
+            days: 0,
+            longest: None,
+            current_streak: 0,
+            longest_streak: 0,
+            busiest_day: None,
+        });
+    }
+}
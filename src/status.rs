@@ -0,0 +1,219 @@
+//! Machine-readable status of the running sessions (`status --format`),
+//! for status bars such as waybar, polybar and i3blocks.
+//!
+//! The status is read from the session state files, so it works from any
+//! process, whether the timer runs in the foreground elsewhere or detached.
+
+use crate::session::Session;
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde_json::json;
+
+/// How `status` prints the running sessions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// An object with `running` and a `sessions` array.
+    Json,
+    /// A waybar custom module object with `text`, `tooltip`, `class` and
+    /// `alt`; use with `return-type = "json"`.
+    Waybar,
+    /// The i3blocks lines `full_text` and `short_text`; nothing when idle,
+    /// which hides the block.
+    I3blocks,
+    /// A single line, e.g. for a polybar script module.
+    Text,
+    /// A short colored segment for the tmux status line, e.g.
+    /// `set -g status-right '#(simple_timer status --format tmux)'`.
+    Tmux,
+    /// A minimal uncolored string for a shell prompt (PS1, starship), such
+    /// as `⏱ Review 1:05`; nothing when idle.
+    Prompt,
+}
+
+/// Longest task name shown in the prompt, in characters.
+pub const PROMPT_TASK_CHARS: usize = 20;
+
+/// Longest task name shown in the tmux segment, in characters.
+pub const TMUX_TASK_CHARS: usize = 24;
+
+/// Elapsed time as on the live timer, e.g. `01:05:09`.
+pub fn clock(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+fn elapsed(session: &Session, now: DateTime<Local>) -> u64 {
+    (now - session.started).num_seconds().max(0) as u64
+}
+
+/// One line per session: task, code and elapsed time.
+fn describe(session: &Session, now: DateTime<Local>) -> String {
+    let name = session.name.as_ref().map(|name| format!("[{}] ", name)).unwrap_or_default();
+    format!("{}{} ({}) {}", name, session.task, session.code, clock(elapsed(session, now)))
+}
+
+/// The first session, with a count of the others, e.g. `Review (ACME) 00:12:03 +1`.
+fn summary(sessions: &[Session], now: DateTime<Local>) -> Option<String> {
+    let first = sessions.first()?;
+    let more = match sessions.len() - 1 {
+        0 => String::new(),
+        others => format!(" +{}", others),
+    };
+    Some(format!("{} ({}) {}{}", first.task, first.code, clock(elapsed(first, now)), more))
+}
+
+/// The `json` format: an object with `running` and a `sessions` array.
+pub fn to_json(sessions: &[Session], now: DateTime<Local>) -> serde_json::Value {
+    let sessions: Vec<_> = sessions
+        .iter()
+        .map(|s| {
+            json!({
+                "name": s.name,
+                "task": s.task,
+                "code": s.code,
+                "started": s.started.to_rfc3339(),
+                "elapsed_secs": elapsed(s, now),
+                "elapsed": clock(elapsed(s, now)),
+                "foreground": s.pid.is_some(),
+            })
+        })
+        .collect();
+    json!({ "running": !sessions.is_empty(), "sessions": sessions })
+}
+
+/// Renders `sessions`, as returned by [`crate::session::all`], in `format`.
+/// The result has no trailing newline and is empty for i3blocks and text
+/// when nothing is running.
+pub fn render(sessions: &[Session], format: Format, now: DateTime<Local>) -> String {
+    match format {
+        Format::Json => to_json(sessions, now).to_string(),
+        Format::Waybar => {
+            let tooltip: Vec<String> = sessions.iter().map(|s| describe(s, now)).collect();
+            let (class, tooltip) = match sessions.is_empty() {
+                true => ("idle", "No session is running".to_string()),
+                false => ("running", tooltip.join("\n")),
+            };
+            json!({
+                "text": summary(sessions, now).unwrap_or_default(),
+                "tooltip": tooltip,
+                "class": class,
+                "alt": class,
+            })
+            .to_string()
+        },
+        Format::I3blocks => match sessions.first() {
+            Some(first) => format!("{}\n{}", summary(sessions, now).unwrap_or_default(), clock(elapsed(first, now))),
+            None => String::new(),
+        },
+        Format::Text => summary(sessions, now).unwrap_or_default(),
+        Format::Tmux => match sessions.first() {
+            Some(first) => {
+                let secs = elapsed(first, now);
+                let more = if sessions.len() > 1 { format!(" +{}", sessions.len() - 1) } else { String::new() };
+                format!(
+                    "#[fg=green]● {} ({}) {}:{:02}{}#[default]",
+                    tmux_escape(&truncate(&first.task, TMUX_TASK_CHARS)),
+                    tmux_escape(&first.code),
+                    secs / 3600,
+                    secs % 3600 / 60,
+                    more
+                )
+            },
+            None => String::new(),
+        },
+        Format::Prompt => match sessions.first() {
+            Some(first) => {
+                let secs = elapsed(first, now);
+                let more = if sessions.len() > 1 { format!(" +{}", sessions.len() - 1) } else { String::new() };
+                format!("⏱ {} {}:{:02}{}", truncate(&first.task, PROMPT_TASK_CHARS), secs / 3600, secs % 3600 / 60, more)
+            },
+            None => String::new(),
+        },
+    }
+}
+
+/// Shortens `text` to at most `max` characters, ending in an ellipsis if it
+/// was cut, and preferably at a word boundary.
+pub fn truncate(text: &str, max: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    // Break at the last space unless that would lose most of the room
+    let cut = match cut.rfind(' ') {
+        Some(space) if cut[..space].chars().count() >= max / 2 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Escapes `#`, which starts a format in the tmux status line.
+fn tmux_escape(text: &str) -> String {
+    text.replace('#', "##")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn session(name: Option<&str>, task: &str, started: DateTime<Local>) -> Session {
+        Session {
+            name: name.map(str::to_string),
+            task: task.to_string(),
+            code: "ACME".to_string(),
+            started,
+            git: None,
+            pid: None,
+            max_secs: None,
+            laps: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn renders_every_format() {
+        let now = Local::now();
+        let sessions = vec![
+            session(None, "Review", now - TimeDelta::seconds(3909)),
+            session(Some("builds"), "Release \"build\"", now - TimeDelta::seconds(60)),
+        ];
+
+        let json: serde_json::Value = serde_json::from_str(&render(&sessions, Format::Json, now)).unwrap();
+        assert_eq!(json["running"], true);
+        assert_eq!(json["sessions"][0]["elapsed_secs"], 3909);
+        assert_eq!(json["sessions"][1]["name"], "builds");
+
+        let waybar: serde_json::Value = serde_json::from_str(&render(&sessions, Format::Waybar, now)).unwrap();
+        assert_eq!(waybar["text"], "Review (ACME) 01:05:09 +1");
+        assert_eq!(waybar["tooltip"], "Review (ACME) 01:05:09\n[builds] Release \"build\" (ACME) 00:01:00");
+        assert_eq!(waybar["class"], "running");
+
+        assert_eq!(render(&sessions[..1], Format::I3blocks, now), "Review (ACME) 01:05:09\n01:05:09");
+        assert_eq!(render(&sessions[..1], Format::Text, now), "Review (ACME) 01:05:09");
+    }
+
+    #[test]
+    fn renders_idle() {
+        let now = Local::now();
+        assert_eq!(render(&[], Format::Json, now), r#"{"running":false,"sessions":[]}"#);
+        let waybar: serde_json::Value = serde_json::from_str(&render(&[], Format::Waybar, now)).unwrap();
+        assert_eq!((waybar["text"].as_str(), waybar["class"].as_str()), (Some(""), Some("idle")));
+        assert_eq!(render(&[], Format::I3blocks, now), "");
+        assert_eq!(render(&[], Format::Text, now), "");
+        assert_eq!(render(&[], Format::Tmux, now), "");
+        assert_eq!(render(&[], Format::Prompt, now), "");
+    }
+
+    #[test]
+    fn tmux_segment_is_short_and_escaped() {
+        let now = Local::now();
+        let sessions = vec![
+            session(None, "Fix #42 in the very long-winded importer rewrite", now - TimeDelta::seconds(3909)),
+            session(Some("builds"), "Build", now),
+        ];
+        assert_eq!(render(&sessions, Format::Tmux, now), "#[fg=green]● Fix ##42 in the very… (ACME) 1:05 +1#[default]");
+        assert_eq!(render(&sessions[1..], Format::Prompt, now), "⏱ Build 0:00");
+        assert_eq!(truncate("Short", 24), "Short");
+        assert_eq!(truncate("Supercalifragilisticexpialidocious", 10), "Supercali…");
+    }
+}
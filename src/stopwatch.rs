@@ -0,0 +1,76 @@
+//! A stopwatch that never touches the log (`stopwatch`).
+//!
+//! Shows the same live display as the timer, to a tenth of a second. On a
+//! terminal, Space records a lap and q, Esc, Enter or Ctrl+C stops; the laps
+//! and the total are printed at the end and then forgotten.
+
+use console::{Key, Term};
+use std::io::{stdout, IsTerminal, Write};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How often the display is refreshed.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Elapsed time as `HH:MM:SS.t`.
+fn format(elapsed: Duration) -> String {
+    let tenths = elapsed.as_millis() / 100;
+    let secs = tenths / 10;
+    format!("{:02}:{:02}:{:02}.{}", secs / 3600, secs % 3600 / 60, secs % 60, tenths % 10)
+}
+
+/// Runs the stopwatch until stopped.
+pub fn run() {
+    let started = Instant::now();
+    let term = Term::stdout();
+    if !std::io::stdin().is_terminal() || !term.is_term() {
+        // Without a terminal there are no keys to read, so only Ctrl+C stops it
+        crate::shutdown::on_exit(move || println!("\nStopped after {}. Nothing was logged.", format(started.elapsed())));
+        println!("Stopwatch running; press Ctrl+C to stop. Nothing is logged.");
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    // Keys are read on their own thread so the display keeps ticking
+    let (keys, pressed) = mpsc::channel();
+    let reader = term.clone();
+    std::thread::spawn(move || {
+        while let Ok(key) = reader.read_key() {
+            if keys.send(key).is_err() {
+                break;
+            }
+        }
+    });
+
+    println!("Stopwatch: Space records a lap, q or Enter stops. Nothing is logged.");
+    let mut laps: Vec<Duration> = Vec::new();
+    loop {
+        match pressed.recv_timeout(TICK) {
+            Ok(Key::Char(' ')) => {
+                let total = started.elapsed();
+                let lap = total - laps.iter().sum::<Duration>();
+                laps.push(lap);
+                let _ = term.clear_line();
+                println!("Lap {:<3} {}  (total {})", laps.len(), format(lap), format(total));
+            },
+            Ok(Key::Char('q') | Key::Escape | Key::Enter | Key::CtrlC) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            _ => {},
+        }
+        // Green where the console takes ANSI codes; legacy Windows consoles don't
+        print!("\r{}", console::style(format!("Elapsed: {}", format(started.elapsed()))).green());
+        stdout().flush().expect("Failed to flush stdout");
+    }
+
+    let total = started.elapsed();
+    let _ = term.clear_line();
+    if !laps.is_empty() {
+        let last = total - laps.iter().sum::<Duration>();
+        println!("Lap {:<3} {}  (total {})", laps.len() + 1, format(last), format(total));
+        laps.push(last);
+        let fastest = laps.iter().min().copied().unwrap_or_default();
+        let slowest = laps.iter().max().copied().unwrap_or_default();
+        println!("Fastest lap {}, slowest {}.", format(fastest), format(slowest));
+    }
+    println!("Stopped after {}. Nothing was logged.", format(total));
+}
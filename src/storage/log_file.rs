@@ -0,0 +1,54 @@
+//! The CSV log (see [`crate::log`]), with its archives.
+
+use super::Storage;
+use crate::archive;
+use crate::log::{self, DateRange, Entry};
+use std::io;
+use std::path::PathBuf;
+
+pub struct LogFile {
+    path: PathBuf,
+}
+
+impl LogFile {
+    pub fn new(path: PathBuf) -> Self {
+        LogFile { path }
+    }
+}
+
+impl Storage for LogFile {
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        log::append_entry(&self.path, entry)
+    }
+
+    fn append_all(&mut self, entries: &[Entry]) -> io::Result<()> {
+        log::lock(&self.path)?.append_entries(entries)
+    }
+
+    fn query(&self, range: &DateRange) -> io::Result<Vec<Entry>> {
+        let mut entries = log::read_entries(&self.path)?;
+        entries.retain(|e| range.includes(e));
+        Ok(entries)
+    }
+
+    fn rewrite(&mut self, entries: &[Entry]) -> io::Result<()> {
+        log::write_entries(&self.path, entries)
+    }
+
+    fn history(&self, range: &DateRange) -> io::Result<Vec<Entry>> {
+        let mut entries = archive::read_all(&self.path)?;
+        entries.retain(|e| range.includes(e));
+        Ok(entries)
+    }
+
+    fn update(&mut self, change: &mut dyn FnMut(&mut Vec<Entry>) -> io::Result<()>) -> io::Result<()> {
+        let lock = log::lock(&self.path)?;
+        let mut entries = log::read_entries(&self.path)?;
+        change(&mut entries)?;
+        lock.write_entries(&entries)
+    }
+}
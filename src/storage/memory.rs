@@ -0,0 +1,39 @@
+//! Entries kept in memory only, e.g. for tests or previews.
+
+use super::Storage;
+use crate::log::{DateRange, Entry};
+use std::io;
+
+#[derive(Debug, Default, Clone)]
+pub struct Memory {
+    pub entries: Vec<Entry>,
+}
+
+impl Memory {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Memory { entries }
+    }
+}
+
+impl Storage for Memory {
+    fn location(&self) -> String {
+        "memory".to_string()
+    }
+
+    fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut entry = entry.clone();
+        entry.ensure_id();
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn query(&self, range: &DateRange) -> io::Result<Vec<Entry>> {
+        Ok(self.entries.iter().filter(|e| range.includes(e)).cloned().collect())
+    }
+
+    fn rewrite(&mut self, entries: &[Entry]) -> io::Result<()> {
+        self.entries = entries.to_vec();
+        self.entries.iter_mut().for_each(Entry::ensure_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+//! Where entries are kept: the CSV log by default, or an SQLite database.
+//!
+//! Reports, invoicing, imports and pushes work on a [`Storage`], so they
+//! don't depend on the backend, and tests can run them against
+//! [`memory::Memory`] without touching the file system.
+
+pub mod log_file;
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::config::{self, StorageBackend};
+use crate::log::{self, DateRange, Entry};
+use crate::snapshot;
+use std::io;
+
+/// A store of logged entries.
+pub trait Storage {
+    /// Where the entries are kept, for messages.
+    fn location(&self) -> String;
+
+    /// Adds one entry.
+    fn append(&mut self, entry: &Entry) -> io::Result<()>;
+
+    /// The entries in `range`, in the order they were added.
+    fn query(&self, range: &DateRange) -> io::Result<Vec<Entry>>;
+
+    /// Replaces all entries.
+    fn rewrite(&mut self, entries: &[Entry]) -> io::Result<()>;
+
+    /// Adds several entries at once.
+    fn append_all(&mut self, entries: &[Entry]) -> io::Result<()> {
+        entries.iter().try_for_each(|entry| self.append(entry))
+    }
+
+    /// Like [`query`](Storage::query), but including archived entries for
+    /// backends that archive old entries.
+    fn history(&self, range: &DateRange) -> io::Result<Vec<Entry>> {
+        self.query(range)
+    }
+
+    /// Reads all entries, lets `change` modify them and writes them back.
+    /// Backends that can be shared between processes hold a lock
+    /// throughout, so entries added meanwhile aren't lost. Nothing is
+    /// written if `change` fails.
+    fn update(&mut self, change: &mut dyn FnMut(&mut Vec<Entry>) -> io::Result<()>) -> io::Result<()> {
+        let mut entries = self.query(&DateRange::default())?;
+        change(&mut entries)?;
+        self.rewrite(&entries)
+    }
+}
+
+/// Opens the storage selected by `storage` in the config file, taking this
+/// week's snapshot (see [`snapshot`]) if there isn't one yet.
+///
+/// Fails with [`io::ErrorKind::Unsupported`] if the storage needs a feature
+/// this build lacks.
+pub fn open() -> io::Result<Box<dyn Storage>> {
+    let log_path = log::log_path()?;
+    let storage = open_backend(log_path.clone())?;
+    if let Err(e) = snapshot::take_weekly(&log_path, storage.as_ref()) {
+        eprintln!("Failed to take this week's snapshot of the log: {}", e);
+    }
+    Ok(storage)
+}
+
+fn open_backend(csv: std::path::PathBuf) -> io::Result<Box<dyn Storage>> {
+    match config::load().storage {
+        StorageBackend::Csv => Ok(Box::new(log_file::LogFile::new(csv))),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => {
+            let path = csv.with_extension("sqlite");
+            let fresh = !path.exists();
+            let opened = sqlite::Sqlite::open(&path).and_then(|mut storage| {
+                // Carry the history over when switching from the CSV log
+                if fresh && csv.exists() {
+                    let entries = log::read_entries(&csv)?;
+                    storage.append_all(&entries)?;
+                    eprintln!("Copied {} entries from '{}' into '{}'.", entries.len(), csv.display(), path.display());
+                }
+                Ok(storage)
+            });
+            match opened {
+                Ok(storage) => Ok(Box::new(storage)),
+                Err(e) => Err(io::Error::new(e.kind(), format!("failed to open '{}': {}", path.display(), e))),
+            }
+        },
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err(io::Error::new(io::ErrorKind::Unsupported, "`storage = \"sqlite\"` needs a build with the `sqlite` feature")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn entry(date: &str, task: &str) -> Entry {
+        Entry {
+            date: date.parse().unwrap(),
+            time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            offset: None,
+            code: "X".to_string(),
+            task: task.to_string(),
+            duration_secs: 600,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    fn tasks(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.task.as_str()).collect()
+    }
+
+    /// The behaviour every backend must share.
+    fn check_backend(storage: &mut dyn Storage) {
+        assert!(storage.query(&DateRange::default()).unwrap().is_empty());
+
+        storage.append(&entry("2024-03-01", "a")).unwrap();
+        storage.append_all(&[entry("2024-03-02", "b, \"quoted\""), entry("2024-03-03", "c")]).unwrap();
+        let added = storage.query(&DateRange::default()).unwrap();
+        assert_eq!(tasks(&added), vec!["a", "b, \"quoted\"", "c"]);
+        // Entries get IDs when they are stored
+        assert!(added.iter().all(|e| !e.id.is_empty()));
+        assert_ne!(added[1].id, added[2].id);
+
+        let range = DateRange { since: NaiveDate::from_ymd_opt(2024, 3, 2), until: NaiveDate::from_ymd_opt(2024, 3, 2) };
+        assert_eq!(tasks(&storage.query(&range).unwrap()), vec!["b, \"quoted\""]);
+
+        storage
+            .update(&mut |entries| {
+                entries.retain(|e| e.task != "a");
+                entries[0].invoice = Some("2024-001".to_string());
+                Ok(())
+            })
+            .unwrap();
+        let entries = storage.query(&DateRange::default()).unwrap();
+        assert_eq!(tasks(&entries), vec!["b, \"quoted\"", "c"]);
+        assert_eq!(entries[0].invoice.as_deref(), Some("2024-001"));
+        assert_eq!(entries[0].duration_secs, 600);
+        assert_eq!(entries[0].id, added[1].id);
+
+        // A failed update changes nothing
+        let failed = storage.update(&mut |entries| {
+            entries.clear();
+            Err(io::Error::other("nope"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(storage.query(&DateRange::default()).unwrap().len(), 2);
+
+        storage.rewrite(&[entry("2024-04-01", "d")]).unwrap();
+        assert_eq!(tasks(&storage.history(&DateRange::default()).unwrap()), vec!["d"]);
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("simple_timer_storage_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn log_file_updates_keep_unreadable_rows() {
+        let path = temp_path("unreadable.csv");
+        std::fs::write(&path, format!("{}\n2024-03-01,12:00:00,X,a,0,10,0,0.17,,a1,\nnot,a,row\n", log::headers())).unwrap();
+        let mut storage = log_file::LogFile::new(path.clone());
+        storage
+            .update(&mut |entries| {
+                entries[0].task = "b".to_string();
+                Ok(())
+            })
+            .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with("\nnot,a,row,,,,,,,,,\n"), "{}", content);
+        assert_eq!(tasks(&storage.query(&DateRange::default()).unwrap()), vec!["b"]);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("csv.lock"));
+    }
+
+    #[test]
+    fn memory_backend() {
+        check_backend(&mut memory::Memory::default());
+    }
+
+    #[test]
+    fn log_file_backend() {
+        let path = temp_path("log.csv");
+        check_backend(&mut log_file::LogFile::new(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("csv.lock"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_backend() {
+        let path = temp_path("log.sqlite");
+        check_backend(&mut sqlite::Sqlite::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
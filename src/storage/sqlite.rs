@@ -0,0 +1,180 @@
+//! Entries in an SQLite database, for logs too large to re-read as CSV on
+//! every command.
+
+use super::Storage;
+use crate::log::{self, DateRange, Entry};
+use chrono::{Days, NaiveDate, NaiveTime};
+use rusqlite::{params, Connection, TransactionBehavior};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS entries (
+    id INTEGER PRIMARY KEY,
+    date TEXT NOT NULL,
+    time TEXT NOT NULL,
+    code TEXT NOT NULL,
+    task TEXT NOT NULL,
+    duration_secs INTEGER NOT NULL,
+    invoice TEXT,
+    git TEXT,
+    uuid TEXT,
+    offset TEXT
+);
+CREATE INDEX IF NOT EXISTS entries_date ON entries (date);";
+
+const INSERT: &str = "INSERT INTO entries (date, time, code, task, duration_secs, invoice, git, uuid, offset)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+
+pub struct Sqlite {
+    connection: Connection,
+    location: String,
+}
+
+fn to_io(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl Sqlite {
+    /// Opens the database, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path).map_err(to_io)?;
+        // Wait for other processes instead of failing straight away
+        connection.busy_timeout(Duration::from_secs(5)).map_err(to_io)?;
+        connection.execute_batch(SCHEMA).map_err(to_io)?;
+        Self::add_ids(&connection).map_err(to_io)?;
+        Self::add_column(&connection, "offset").map_err(to_io)?;
+        Ok(Sqlite { connection, location: path.display().to_string() })
+    }
+
+    /// Adds the text column `name` to databases created before it existed.
+    fn add_column(connection: &Connection, name: &str) -> rusqlite::Result<()> {
+        let has_column = connection
+            .prepare("SELECT 1 FROM pragma_table_info('entries') WHERE name = ?1")?
+            .exists([name])?;
+        if !has_column {
+            connection.execute(&format!("ALTER TABLE entries ADD COLUMN {} TEXT", name), [])?;
+        }
+        Ok(())
+    }
+
+    /// Adds the `uuid` column to databases created before entries had IDs,
+    /// and gives every entry without one an ID.
+    fn add_ids(connection: &Connection) -> rusqlite::Result<()> {
+        Self::add_column(connection, "uuid")?;
+        let missing: Vec<i64> = connection
+            .prepare("SELECT id FROM entries WHERE uuid IS NULL OR uuid = ''")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for id in missing {
+            connection.execute("UPDATE entries SET uuid = ?1 WHERE id = ?2", params![log::new_id(), id])?;
+        }
+        Ok(())
+    }
+
+    fn insert(connection: &Connection, entry: &Entry) -> rusqlite::Result<()> {
+        connection.execute(
+            INSERT,
+            params![
+                entry.date.format("%Y-%m-%d").to_string(),
+                entry.time.format("%H:%M:%S").to_string(),
+                entry.code,
+                entry.task,
+                entry.duration_secs as i64,
+                entry.invoice,
+                entry.git,
+                if entry.id.is_empty() { log::new_id() } else { entry.id.clone() },
+                entry.offset.map(|offset| offset.to_string()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn select(connection: &Connection, location: &str, range: &DateRange) -> io::Result<Vec<Entry>> {
+        let mut statement = connection
+            .prepare(
+                "SELECT date, time, code, task, duration_secs, invoice, git, uuid, offset FROM entries
+                 WHERE (?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date <= ?2) ORDER BY id",
+            )
+            .map_err(to_io)?;
+        // Entries logged in another time zone may move to the next or previous
+        // day, so look a day further and keep those that are in range locally
+        let day = |date: Option<NaiveDate>, shift: fn(NaiveDate, Days) -> Option<NaiveDate>| {
+            date.map(|d| shift(d, Days::new(1)).unwrap_or(d).format("%Y-%m-%d").to_string())
+        };
+        let rows = statement
+            .query_map(params![day(range.since, NaiveDate::checked_sub_days), day(range.until, NaiveDate::checked_add_days)], |row| {
+                let date: String = row.get(0)?;
+                let time: String = row.get(1)?;
+                let duration_secs: i64 = row.get(4)?;
+                let uuid: Option<String> = row.get(7)?;
+                let offset: Option<String> = row.get(8)?;
+                Ok((date, time, row.get(2)?, row.get(3)?, duration_secs, row.get(5)?, row.get(6)?, uuid.unwrap_or_default(), offset))
+            })
+            .map_err(to_io)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (date, time, code, task, duration_secs, invoice, git, id, offset) = row.map_err(to_io)?;
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Malformed entry in '{}'", location));
+            let entry = Entry {
+                date: NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| invalid())?,
+                time: NaiveTime::parse_from_str(&time, "%H:%M:%S").map_err(|_| invalid())?,
+                offset: offset.map(|offset| offset.parse().map_err(|_| invalid())).transpose()?,
+                code,
+                task,
+                duration_secs: u64::try_from(duration_secs).map_err(|_| invalid())?,
+                invoice,
+                git,
+                id,
+            };
+            if range.includes(&entry) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn replace_all(connection: &Connection, entries: &[Entry]) -> rusqlite::Result<()> {
+        connection.execute("DELETE FROM entries", [])?;
+        entries.iter().try_for_each(|entry| Self::insert(connection, entry))
+    }
+}
+
+impl Storage for Sqlite {
+    fn location(&self) -> String {
+        self.location.clone()
+    }
+
+    fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        Self::insert(&self.connection, entry).map_err(to_io)
+    }
+
+    fn append_all(&mut self, entries: &[Entry]) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(to_io)?;
+        entries.iter().try_for_each(|entry| Self::insert(&transaction, entry)).map_err(to_io)?;
+        transaction.commit().map_err(to_io)
+    }
+
+    fn query(&self, range: &DateRange) -> io::Result<Vec<Entry>> {
+        Self::select(&self.connection, &self.location, range)
+    }
+
+    fn rewrite(&mut self, entries: &[Entry]) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(to_io)?;
+        Self::replace_all(&transaction, entries).map_err(to_io)?;
+        transaction.commit().map_err(to_io)
+    }
+
+    fn update(&mut self, change: &mut dyn FnMut(&mut Vec<Entry>) -> io::Result<()>) -> io::Result<()> {
+        // Take the write lock up front so nobody writes between reading and rewriting
+        let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate).map_err(to_io)?;
+        let mut entries = Self::select(&transaction, &self.location, &DateRange::default())?;
+        change(&mut entries)?;
+        Self::replace_all(&transaction, &entries).map_err(to_io)?;
+        transaction.commit().map_err(to_io)
+    }
+}
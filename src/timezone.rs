@@ -0,0 +1,71 @@
+//! The time zone the timer works in (`timezone` in the config file, or
+//! `--timezone`): sessions are logged, and days in reports start, in it.
+//!
+//! It is applied by setting `TZ` for the process, which `chrono` reads for
+//! the local time zone. Every entry is logged with its UTC offset, so
+//! entries logged elsewhere are shown in the current zone.
+
+use chrono::FixedOffset;
+
+/// Folders the time zone database is looked up in, as by `chrono`.
+#[cfg(unix)]
+const ZONE_INFO: [&str; 4] = ["/usr/share/zoneinfo", "/share/zoneinfo", "/etc/zoneinfo", "/usr/share/lib/zoneinfo"];
+
+/// The value of `TZ` that makes `zone` the local time zone: `UTC`, an
+/// offset such as `+05:30`, or on Unix a name such as `Europe/Berlin`.
+/// `None` for `local`, which keeps the system's zone.
+pub fn tz_value(zone: &str) -> Result<Option<String>, String> {
+    let zone = zone.trim();
+    if zone.is_empty() || zone.eq_ignore_ascii_case("local") {
+        return Ok(None);
+    }
+    if cfg!(windows) {
+        return Err(format!("time zone '{}' can't be used on Windows, which only supports the system's zone (`local`)", zone));
+    }
+    if zone.eq_ignore_ascii_case("utc") || zone == "Z" {
+        return Ok(Some("UTC0".to_string()));
+    }
+    if let Ok(offset) = zone.parse::<FixedOffset>() {
+        return Ok(Some(posix_offset(offset)));
+    }
+    if is_named_zone(zone) {
+        return Ok(Some(zone.to_string()));
+    }
+    Err(format!("unknown time zone '{}': expected local, UTC, an offset such as +05:30 or a name such as Europe/Berlin", zone))
+}
+
+/// A POSIX `TZ` for a fixed offset. POSIX counts west of Greenwich as
+/// positive, so `+05:30` is `<+0530>-05:30`.
+fn posix_offset(offset: FixedOffset) -> String {
+    let secs = offset.local_minus_utc();
+    let (sign, west) = if secs < 0 { ('-', '+') } else { ('+', '-') };
+    let (hours, minutes) = (secs.abs() / 3600, secs.abs() % 3600 / 60);
+    format!("<{}{:02}{:02}>{}{:02}:{:02}", sign, hours, minutes, west, hours, minutes)
+}
+
+/// Whether `name` is in the time zone database, e.g. `America/New_York`.
+#[cfg(unix)]
+fn is_named_zone(name: &str) -> bool {
+    let valid = |part: &str| !part.is_empty() && part != "." && part != ".." && part.chars().all(|c| c.is_ascii_alphanumeric() || "_-+".contains(c));
+    name.split('/').all(valid) && ZONE_INFO.iter().any(|dir| std::path::Path::new(dir).join(name).is_file())
+}
+
+#[cfg(not(unix))]
+fn is_named_zone(_name: &str) -> bool {
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_zones_into_tz_values() {
+        assert_eq!(tz_value("local"), Ok(None));
+        assert_eq!(tz_value("UTC"), Ok(Some("UTC0".to_string())));
+        assert_eq!(tz_value("+05:30"), Ok(Some("<+0530>-05:30".to_string())));
+        assert_eq!(tz_value("-03:00"), Ok(Some("<-0300>+03:00".to_string())));
+        assert!(tz_value("Mars/Olympus_Mons").is_err());
+        assert!(tz_value("../../etc/passwd").is_err());
+    }
+}
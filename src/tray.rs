@@ -0,0 +1,200 @@
+//! A system tray icon with quick controls (`tray`).
+//!
+//! The icon is shown by `yad --notification`, which most Linux and BSD
+//! desktops can install from their package manager. Its tooltip shows the
+//! running session and elapsed time. Its menu stops the running session,
+//! resumes the last one, or switches to a recent task, all by running this
+//! program's `stop`, `again` and `switch` commands. Clicking the icon stops
+//! the running session or resumes the last one.
+
+use crate::log::Entry;
+use crate::session::Session;
+use crate::status;
+use chrono::{DateTime, Local};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// Recent tasks offered in the menu.
+pub const RECENT_TASKS: usize = 8;
+
+/// Distinct task and code pairs of `entries`, most recently ended first.
+pub fn recent_tasks(entries: &[Entry], limit: usize) -> Vec<(String, String)> {
+    let mut sorted: Vec<&Entry> = entries.iter().collect();
+    sorted.sort_by_key(|e| std::cmp::Reverse(e.end()));
+    let mut seen = HashSet::new();
+    sorted
+        .into_iter()
+        .filter(|e| seen.insert((&e.task, &e.code)))
+        .take(limit)
+        .map(|e| (e.task.clone(), e.code.clone()))
+        .collect()
+}
+
+/// Quotes an argument for the command line yad runs a menu action with.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// The yad commands (`icon:`, `tooltip:`, `action:` and `menu:`) that show
+/// `sessions` and offer `recent` tasks. `program` is the command line that
+/// runs this program, with any global options.
+pub fn commands(program: &[String], sessions: &[Session], recent: &[(String, String)], now: DateTime<Local>) -> Vec<String> {
+    let program: Vec<String> = program.iter().map(|arg| quote(arg)).collect();
+    let run = |args: &[&str]| {
+        let args = args.iter().map(|arg| quote(arg));
+        program.iter().cloned().chain(args).collect::<Vec<_>>().join(" ")
+    };
+    // yad separates menu items with `|` and their fields with `!`
+    let fits = |text: &str| !text.contains(['|', '!', '\n']);
+
+    let running = sessions.iter().find(|s| s.name.is_none());
+    let tooltip = match status::render(sessions, status::Format::Text, now) {
+        text if text.is_empty() => "No session is running".to_string(),
+        text => text,
+    };
+    let mut menu = Vec::new();
+    let click = match (running, recent.first()) {
+        (Some(session), _) => {
+            if fits(&session.task) {
+                menu.push(format!("Stop '{}'!{}", session.task, run(&["stop"])));
+            }
+            run(&["stop"])
+        },
+        (None, Some((task, code))) => {
+            if fits(task) && fits(code) {
+                menu.push(format!("Resume '{}' ({})!{}", task, code, run(&["again", "--detach"])));
+            }
+            run(&["again", "--detach"])
+        },
+        (None, None) => String::new(),
+    };
+    let verb = if running.is_some() { "Switch to" } else { "Start" };
+    // Leave out the task already offered above
+    let (current, others) = match running {
+        Some(session) => (Some((&session.task, &session.code)), recent),
+        None => (None, recent.get(1..).unwrap_or_default()),
+    };
+    for (task, code) in others.iter().filter(|(task, code)| fits(task) && fits(code)) {
+        if current == Some((task, code)) {
+            continue;
+        }
+        menu.push(format!("{} '{}' ({})!{}", verb, task, code, run(&["switch", "--task", task, "--code", code])));
+    }
+    menu.push("Quit!quit".to_string());
+
+    let icon = if running.is_some() { "media-record" } else { "media-playback-pause" };
+    vec![
+        format!("icon:{}", icon),
+        format!("tooltip:{}", tooltip.replace('\n', " ")),
+        format!("action:{}", click),
+        format!("menu:{}", menu.join("|")),
+    ]
+}
+
+/// The tray icon, shown by a yad process.
+pub struct Tray {
+    child: Child,
+    stdin: ChildStdin,
+    /// Last value sent for each command, so unchanged ones aren't resent.
+    sent: HashMap<String, String>,
+}
+
+impl Tray {
+    /// Shows the icon. Fails with [`io::ErrorKind::NotFound`] if yad isn't
+    /// installed.
+    pub fn spawn() -> io::Result<Tray> {
+        let mut child = Command::new("yad")
+            .args(["--notification", "--listen", "--no-middle", "--image=media-playback-pause", "--text=simpletimer"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        Ok(Tray { child, stdin, sent: HashMap::new() })
+    }
+
+    /// Sends the commands that changed since they were last sent.
+    pub fn show(&mut self, commands: &[String]) -> io::Result<()> {
+        for command in commands {
+            let (kind, value) = command.split_once(':').unwrap_or((command, ""));
+            if self.sent.get(kind).map(String::as_str) != Some(value) {
+                writeln!(self.stdin, "{}", command)?;
+                self.sent.insert(kind.to_string(), value.to_string());
+            }
+        }
+        self.stdin.flush()
+    }
+
+    /// Whether the icon is still shown, i.e. Quit wasn't chosen.
+    pub fn is_open(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Tray {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeZone};
+
+    fn entry(task: &str, code: &str, hour: u32) -> Entry {
+        Entry {
+            date: "2024-05-06".parse().unwrap(),
+            time: NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            offset: None,
+            code: code.to_string(),
+            task: task.to_string(),
+            duration_secs: 1800,
+            invoice: None,
+            git: None,
+            id: String::new(),
+        }
+    }
+
+    fn pair(task: &str, code: &str) -> (String, String) {
+        (task.to_string(), code.to_string())
+    }
+
+    #[test]
+    fn lists_recent_tasks_once() {
+        let entries = vec![entry("Review", "ACME", 9), entry("Standup", "TEAM", 10), entry("Review", "ACME", 11), entry("Review", "OPS", 8)];
+        assert_eq!(recent_tasks(&entries, 2), vec![pair("Review", "ACME"), pair("Standup", "TEAM")]);
+        assert_eq!(recent_tasks(&entries, 8).len(), 3);
+    }
+
+    #[test]
+    fn builds_the_menu() {
+        let program = vec!["/usr/bin/timer".to_string(), "--profile".to_string(), "work".to_string()];
+        let recent = vec![pair("Review", "ACME"), pair("Pay the bills | taxes", "HOME"), pair("Bob's PR", "ACME")];
+        let now = Local.with_ymd_and_hms(2024, 5, 6, 10, 30, 0).unwrap();
+
+        let idle = commands(&program, &[], &recent, now);
+        assert_eq!(idle[0], "icon:media-playback-pause");
+        assert_eq!(idle[1], "tooltip:No session is running");
+        assert_eq!(idle[2], "action:'/usr/bin/timer' '--profile' 'work' 'again' '--detach'");
+        let menu: Vec<&str> = idle[3].strip_prefix("menu:").unwrap().split('|').collect();
+        assert_eq!(menu.len(), 3);
+        assert!(menu[0].starts_with("Resume 'Review' (ACME)!"));
+        assert_eq!(
+            menu[1],
+            r"Start 'Bob's PR' (ACME)!'/usr/bin/timer' '--profile' 'work' 'switch' '--task' 'Bob'\''s PR' '--code' 'ACME'"
+        );
+        assert_eq!(menu[2], "Quit!quit");
+
+        let mut session = Session::new(None, "Review".to_string(), "ACME".to_string(), None, false);
+        session.started = now - chrono::Duration::minutes(5);
+        let running = commands(&program, &[session], &recent, now);
+        assert_eq!(running[1], "tooltip:Review (ACME) 00:05:00");
+        assert!(running[2].ends_with("'stop'"));
+        let menu: Vec<&str> = running[3].strip_prefix("menu:").unwrap().split('|').collect();
+        assert!(menu[0].starts_with("Stop 'Review'!"));
+        assert!(menu[1].starts_with("Switch to 'Bob's PR' (ACME)!"));
+        assert_eq!(menu.len(), 3);
+    }
+}